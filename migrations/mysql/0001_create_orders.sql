@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS orders (
+    order_id VARCHAR(255),
+    menu_item VARCHAR(255),
+    cooking_time VARCHAR(255),
+    table_id INT
+);
@@ -0,0 +1,9 @@
+CREATE TABLE menu_items (
+    menu_item_id CHAR(36) NOT NULL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    category VARCHAR(255) NOT NULL,
+    price BIGINT UNSIGNED NOT NULL,
+    prep_time_minutes INT UNSIGNED NOT NULL,
+    available BOOLEAN NOT NULL DEFAULT TRUE,
+    UNIQUE KEY menu_items_name (name)
+);
@@ -0,0 +1,6 @@
+ALTER TABLE orders
+    ADD COLUMN status VARCHAR(16) NOT NULL DEFAULT 'placed',
+    ADD COLUMN started_at DATETIME(6) NULL,
+    ADD COLUMN finished_at DATETIME(6) NULL,
+    ADD COLUMN served_at DATETIME(6) NULL,
+    ADD COLUMN cancelled_at DATETIME(6) NULL;
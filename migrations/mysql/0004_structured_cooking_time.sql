@@ -0,0 +1,12 @@
+ALTER TABLE orders
+    ADD COLUMN created_at DATETIME(6) NULL,
+    ADD COLUMN cooking_duration_secs INT UNSIGNED NULL;
+
+UPDATE orders
+SET created_at = UTC_TIMESTAMP(6),
+    cooking_duration_secs = COALESCE(CAST(SUBSTRING_INDEX(cooking_time, ' ', 1) AS UNSIGNED), 0) * 60;
+
+ALTER TABLE orders
+    MODIFY COLUMN created_at DATETIME(6) NOT NULL,
+    MODIFY COLUMN cooking_duration_secs INT UNSIGNED NOT NULL,
+    DROP COLUMN cooking_time;
@@ -0,0 +1,46 @@
+-- MySQL DDL is not transactional, so clear out what a failed earlier run left behind.
+DROP TABLE IF EXISTS orders_new;
+DROP TABLE IF EXISTS orders_with_bad_rows;
+
+-- Stop before touching anything if a row cannot be carried over as it is. Without
+-- this, UNHEX would zero-pad a malformed id into BINARY(16), and outside strict SQL
+-- mode missing values would be stored as zeroes or empty strings. The check table
+-- starts with one row, so any bad row fails the second insert with a duplicate
+-- entry for `orders_with_bad_rows`, and the old table stays in place to be fixed
+-- by hand.
+CREATE TABLE orders_with_bad_rows (found TINYINT NOT NULL PRIMARY KEY);
+INSERT INTO orders_with_bad_rows (found) VALUES (1);
+INSERT INTO orders_with_bad_rows (found)
+SELECT 1 FROM orders
+WHERE order_id IS NULL
+    OR REPLACE(order_id, '-', '') NOT REGEXP '^[0-9A-Fa-f]{32}$'
+    OR table_id IS NULL
+    OR table_id < 0
+    OR menu_item IS NULL
+LIMIT 1;
+DROP TABLE orders_with_bad_rows;
+
+CREATE TABLE orders_new (
+    order_id BINARY(16) NOT NULL,
+    table_id BIGINT UNSIGNED NOT NULL,
+    menu_item VARCHAR(255) NOT NULL,
+    created_at DATETIME(6) NOT NULL,
+    cooking_duration_secs INT UNSIGNED NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'placed',
+    started_at DATETIME(6) NULL,
+    finished_at DATETIME(6) NULL,
+    served_at DATETIME(6) NULL,
+    cancelled_at DATETIME(6) NULL,
+    PRIMARY KEY (order_id),
+    INDEX orders_table_id_created_at (table_id, created_at)
+);
+
+-- A duplicated id fails this insert on the primary key in any SQL mode, leaving the
+-- old table in place; the next run drops the partial `orders_new` first.
+INSERT INTO orders_new (order_id, table_id, menu_item, created_at, cooking_duration_secs, status, started_at, finished_at, served_at, cancelled_at)
+SELECT UNHEX(REPLACE(order_id, '-', '')), table_id, menu_item, created_at, cooking_duration_secs, status, started_at, finished_at, served_at, cancelled_at
+FROM orders;
+
+RENAME TABLE orders TO orders_old, orders_new TO orders;
+
+DROP TABLE orders_old;
@@ -0,0 +1,6 @@
+CREATE TABLE dining_tables (
+    table_number BIGINT UNSIGNED NOT NULL PRIMARY KEY,
+    seats INT UNSIGNED NOT NULL CHECK (seats > 0),
+    section VARCHAR(255) NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'closed'
+);
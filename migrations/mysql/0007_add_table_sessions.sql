@@ -0,0 +1,27 @@
+CREATE TABLE table_sessions (
+    session_id BINARY(16) NOT NULL PRIMARY KEY,
+    table_number BIGINT UNSIGNED NOT NULL,
+    opened_at DATETIME(6) NOT NULL,
+    closed_at DATETIME(6) NULL,
+    INDEX table_sessions_table_number_opened_at (table_number, opened_at)
+);
+
+ALTER TABLE dining_tables ADD COLUMN current_session_id BINARY(16) NULL;
+
+ALTER TABLE orders
+    ADD COLUMN session_id BINARY(16) NULL,
+    ADD INDEX orders_session_id_created_at (session_id, created_at);
+
+-- Tables that are open already get a session, which takes over the orders still
+-- being cooked or served there.
+UPDATE dining_tables SET current_session_id = UNHEX(REPLACE(UUID(), '-', '')) WHERE status = 'open';
+
+INSERT INTO table_sessions (session_id, table_number, opened_at)
+SELECT current_session_id, table_number, UTC_TIMESTAMP(6)
+FROM dining_tables
+WHERE status = 'open';
+
+UPDATE orders
+JOIN dining_tables ON dining_tables.table_number = orders.table_id
+SET orders.session_id = dining_tables.current_session_id
+WHERE dining_tables.status = 'open' AND orders.status IN ('placed', 'cooking', 'ready');
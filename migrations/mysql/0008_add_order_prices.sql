@@ -0,0 +1,7 @@
+ALTER TABLE orders ADD COLUMN price BIGINT UNSIGNED NOT NULL DEFAULT 0;
+
+-- Existing orders are charged the current menu price; orders for items no longer on
+-- the menu stay at zero.
+UPDATE orders
+JOIN menu_items ON menu_items.name = orders.menu_item
+SET orders.price = menu_items.price;
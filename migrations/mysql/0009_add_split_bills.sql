@@ -0,0 +1,20 @@
+ALTER TABLE orders ADD COLUMN seat INT UNSIGNED NULL CHECK (seat > 0);
+
+CREATE TABLE split_bills (
+    bill_id BINARY(16) NOT NULL PRIMARY KEY,
+    session_id BINARY(16) NOT NULL,
+    position INT UNSIGNED NOT NULL,
+    label VARCHAR(255) NOT NULL,
+    subtotal BIGINT UNSIGNED NOT NULL,
+    service_charge BIGINT UNSIGNED NOT NULL,
+    tax BIGINT UNSIGNED NOT NULL,
+    total BIGINT UNSIGNED NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'open',
+    UNIQUE INDEX split_bills_session_id_position (session_id, position)
+);
+
+CREATE TABLE split_bill_orders (
+    bill_id BINARY(16) NOT NULL,
+    order_id BINARY(16) NOT NULL,
+    PRIMARY KEY (bill_id, order_id)
+);
@@ -0,0 +1,12 @@
+CREATE TABLE payments (
+    payment_id BINARY(16) NOT NULL PRIMARY KEY,
+    session_id BINARY(16) NOT NULL,
+    bill_id BINARY(16) NULL,
+    tender VARCHAR(16) NOT NULL,
+    reference VARCHAR(255) NULL,
+    amount BIGINT UNSIGNED NOT NULL,
+    tip BIGINT UNSIGNED NOT NULL,
+    change_due BIGINT UNSIGNED NOT NULL,
+    paid_at DATETIME(6) NOT NULL,
+    INDEX payments_session_id_paid_at (session_id, paid_at)
+);
@@ -0,0 +1,19 @@
+-- events holds a comma-separated list of event names; empty means every event.
+CREATE TABLE webhooks (
+    webhook_id BINARY(16) NOT NULL PRIMARY KEY,
+    url VARCHAR(2048) NOT NULL,
+    events VARCHAR(255) NOT NULL DEFAULT '',
+    created_at DATETIME(6) NOT NULL
+);
+
+CREATE TABLE webhook_dead_letters (
+    dead_letter_id BINARY(16) NOT NULL PRIMARY KEY,
+    webhook_id BINARY(16) NOT NULL,
+    event_id BIGINT UNSIGNED NOT NULL,
+    event VARCHAR(64) NOT NULL,
+    payload MEDIUMTEXT NOT NULL,
+    attempts INT UNSIGNED NOT NULL,
+    last_error TEXT NOT NULL,
+    failed_at DATETIME(6) NOT NULL,
+    INDEX webhook_dead_letters_failed_at (failed_at)
+);
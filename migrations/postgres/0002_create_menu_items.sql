@@ -0,0 +1,11 @@
+CREATE TABLE menu_items (
+    menu_item_id UUID NOT NULL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    category VARCHAR(255) NOT NULL,
+    price BIGINT NOT NULL CHECK (price >= 0),
+    prep_time_minutes INT NOT NULL CHECK (prep_time_minutes >= 0),
+    available BOOLEAN NOT NULL DEFAULT TRUE
+);
+
+-- Names are unique ignoring case, as they are with MySQL's default collation.
+CREATE UNIQUE INDEX menu_items_name ON menu_items (lower(name));
@@ -0,0 +1,6 @@
+ALTER TABLE orders
+    ADD COLUMN status VARCHAR(16) NOT NULL DEFAULT 'placed',
+    ADD COLUMN started_at TIMESTAMPTZ NULL,
+    ADD COLUMN finished_at TIMESTAMPTZ NULL,
+    ADD COLUMN served_at TIMESTAMPTZ NULL,
+    ADD COLUMN cancelled_at TIMESTAMPTZ NULL;
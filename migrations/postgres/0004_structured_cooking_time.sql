@@ -0,0 +1,16 @@
+ALTER TABLE orders
+    ADD COLUMN created_at TIMESTAMPTZ NULL,
+    ADD COLUMN cooking_duration_secs BIGINT NULL;
+
+UPDATE orders
+SET created_at = now(),
+    cooking_duration_secs = CASE
+        WHEN split_part(cooking_time, ' ', 1) ~ '^[0-9]+$' THEN split_part(cooking_time, ' ', 1)::BIGINT * 60
+        ELSE 0
+    END;
+
+ALTER TABLE orders
+    ALTER COLUMN created_at SET NOT NULL,
+    ALTER COLUMN cooking_duration_secs SET NOT NULL,
+    ADD CONSTRAINT orders_cooking_duration_secs CHECK (cooking_duration_secs >= 0),
+    DROP COLUMN cooking_time;
@@ -0,0 +1,10 @@
+-- Rows with a missing or malformed id, a missing table or item, or a duplicated id
+-- make this migration fail, and it rolls back as a whole.
+ALTER TABLE orders
+    ALTER COLUMN order_id TYPE UUID USING order_id::UUID,
+    ALTER COLUMN table_id TYPE BIGINT,
+    ALTER COLUMN table_id SET NOT NULL,
+    ALTER COLUMN menu_item SET NOT NULL,
+    ADD PRIMARY KEY (order_id);
+
+CREATE INDEX orders_table_id_created_at ON orders (table_id, created_at);
@@ -0,0 +1,6 @@
+CREATE TABLE dining_tables (
+    table_number BIGINT NOT NULL PRIMARY KEY CHECK (table_number >= 0),
+    seats INT NOT NULL CHECK (seats > 0),
+    section VARCHAR(255) NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'closed'
+);
@@ -0,0 +1,30 @@
+CREATE TABLE table_sessions (
+    session_id UUID NOT NULL PRIMARY KEY,
+    table_number BIGINT NOT NULL,
+    opened_at TIMESTAMPTZ NOT NULL,
+    closed_at TIMESTAMPTZ NULL
+);
+
+CREATE INDEX table_sessions_table_number_opened_at ON table_sessions (table_number, opened_at);
+
+ALTER TABLE dining_tables ADD COLUMN current_session_id UUID NULL;
+
+ALTER TABLE orders ADD COLUMN session_id UUID NULL;
+
+CREATE INDEX orders_session_id_created_at ON orders (session_id, created_at);
+
+-- Tables that are open already get a session, which takes over the orders still
+-- being cooked or served there.
+UPDATE dining_tables SET current_session_id = gen_random_uuid() WHERE status = 'open';
+
+INSERT INTO table_sessions (session_id, table_number, opened_at)
+SELECT current_session_id, table_number, now()
+FROM dining_tables
+WHERE status = 'open';
+
+UPDATE orders
+SET session_id = dining_tables.current_session_id
+FROM dining_tables
+WHERE dining_tables.table_number = orders.table_id
+    AND dining_tables.status = 'open'
+    AND orders.status IN ('placed', 'cooking', 'ready');
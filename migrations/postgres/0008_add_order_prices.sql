@@ -0,0 +1,8 @@
+ALTER TABLE orders ADD COLUMN price BIGINT NOT NULL DEFAULT 0 CHECK (price >= 0);
+
+-- Existing orders are charged the current menu price; orders for items no longer on
+-- the menu stay at zero.
+UPDATE orders
+SET price = menu_items.price
+FROM menu_items
+WHERE lower(menu_items.name) = lower(orders.menu_item);
@@ -0,0 +1,20 @@
+ALTER TABLE orders ADD COLUMN seat INT NULL CHECK (seat > 0);
+
+CREATE TABLE split_bills (
+    bill_id UUID NOT NULL PRIMARY KEY,
+    session_id UUID NOT NULL,
+    position INT NOT NULL CHECK (position >= 0),
+    label VARCHAR(255) NOT NULL,
+    subtotal BIGINT NOT NULL CHECK (subtotal >= 0),
+    service_charge BIGINT NOT NULL CHECK (service_charge >= 0),
+    tax BIGINT NOT NULL CHECK (tax >= 0),
+    total BIGINT NOT NULL CHECK (total >= 0),
+    status VARCHAR(16) NOT NULL DEFAULT 'open',
+    UNIQUE (session_id, position)
+);
+
+CREATE TABLE split_bill_orders (
+    bill_id UUID NOT NULL,
+    order_id UUID NOT NULL,
+    PRIMARY KEY (bill_id, order_id)
+);
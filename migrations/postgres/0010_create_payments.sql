@@ -0,0 +1,13 @@
+CREATE TABLE payments (
+    payment_id UUID NOT NULL PRIMARY KEY,
+    session_id UUID NOT NULL,
+    bill_id UUID NULL,
+    tender VARCHAR(16) NOT NULL,
+    reference VARCHAR(255) NULL,
+    amount BIGINT NOT NULL CHECK (amount >= 0),
+    tip BIGINT NOT NULL CHECK (tip >= 0),
+    change_due BIGINT NOT NULL CHECK (change_due >= 0),
+    paid_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX payments_session_id_paid_at ON payments (session_id, paid_at);
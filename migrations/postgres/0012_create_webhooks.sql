@@ -0,0 +1,20 @@
+-- events holds a comma-separated list of event names; empty means every event.
+CREATE TABLE webhooks (
+    webhook_id UUID NOT NULL PRIMARY KEY,
+    url TEXT NOT NULL,
+    events TEXT NOT NULL DEFAULT '',
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE webhook_dead_letters (
+    dead_letter_id UUID NOT NULL PRIMARY KEY,
+    webhook_id UUID NOT NULL,
+    event_id BIGINT NOT NULL CHECK (event_id >= 0),
+    event VARCHAR(64) NOT NULL,
+    payload TEXT NOT NULL,
+    attempts BIGINT NOT NULL CHECK (attempts >= 0),
+    last_error TEXT NOT NULL,
+    failed_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX webhook_dead_letters_failed_at ON webhook_dead_letters (failed_at);
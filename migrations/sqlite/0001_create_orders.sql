@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS orders (
+    order_id TEXT,
+    menu_item TEXT,
+    cooking_time TEXT,
+    table_id INTEGER
+);
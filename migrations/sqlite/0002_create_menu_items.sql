@@ -0,0 +1,8 @@
+CREATE TABLE menu_items (
+    menu_item_id TEXT NOT NULL PRIMARY KEY,
+    name TEXT NOT NULL COLLATE NOCASE UNIQUE,
+    category TEXT NOT NULL COLLATE NOCASE,
+    price INTEGER NOT NULL CHECK (price >= 0),
+    prep_time_minutes INTEGER NOT NULL CHECK (prep_time_minutes >= 0),
+    available INTEGER NOT NULL DEFAULT 1
+);
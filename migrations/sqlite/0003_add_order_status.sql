@@ -0,0 +1,5 @@
+ALTER TABLE orders ADD COLUMN status TEXT NOT NULL DEFAULT 'placed';
+ALTER TABLE orders ADD COLUMN started_at TEXT NULL;
+ALTER TABLE orders ADD COLUMN finished_at TEXT NULL;
+ALTER TABLE orders ADD COLUMN served_at TEXT NULL;
+ALTER TABLE orders ADD COLUMN cancelled_at TEXT NULL;
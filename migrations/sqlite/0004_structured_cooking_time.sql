@@ -0,0 +1,10 @@
+-- SQLite cannot add NOT NULL to an existing column; migration 5 rebuilds the table
+-- with the constraints.
+ALTER TABLE orders ADD COLUMN created_at TEXT NULL;
+ALTER TABLE orders ADD COLUMN cooking_duration_secs INTEGER NULL;
+
+UPDATE orders
+SET created_at = strftime('%Y-%m-%d %H:%M:%f', 'now'),
+    cooking_duration_secs = COALESCE(CAST(substr(cooking_time, 1, instr(cooking_time || ' ', ' ') - 1) AS INTEGER), 0) * 60;
+
+ALTER TABLE orders DROP COLUMN cooking_time;
@@ -0,0 +1,24 @@
+CREATE TABLE orders_new (
+    order_id BLOB NOT NULL PRIMARY KEY CHECK (length(order_id) = 16),
+    table_id INTEGER NOT NULL,
+    menu_item TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    cooking_duration_secs INTEGER NOT NULL,
+    status TEXT NOT NULL DEFAULT 'placed',
+    started_at TEXT NULL,
+    finished_at TEXT NULL,
+    served_at TEXT NULL,
+    cancelled_at TEXT NULL
+);
+
+-- Rows with a missing or malformed id, a missing table or item, or a duplicated id
+-- make this insert fail, and the whole migration rolls back.
+INSERT INTO orders_new (order_id, table_id, menu_item, created_at, cooking_duration_secs, status, started_at, finished_at, served_at, cancelled_at)
+SELECT unhex(replace(order_id, '-', '')), table_id, menu_item, created_at, cooking_duration_secs, status, started_at, finished_at, served_at, cancelled_at
+FROM orders;
+
+DROP TABLE orders;
+
+ALTER TABLE orders_new RENAME TO orders;
+
+CREATE INDEX orders_table_id_created_at ON orders (table_id, created_at);
@@ -0,0 +1,6 @@
+CREATE TABLE dining_tables (
+    table_number INTEGER NOT NULL PRIMARY KEY CHECK (table_number >= 0),
+    seats INTEGER NOT NULL CHECK (seats > 0),
+    section TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'closed'
+);
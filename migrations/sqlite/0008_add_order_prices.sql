@@ -0,0 +1,7 @@
+ALTER TABLE orders ADD COLUMN price INTEGER NOT NULL DEFAULT 0 CHECK (price >= 0);
+
+-- Existing orders are charged the current menu price; orders for items no longer on
+-- the menu stay at zero.
+UPDATE orders
+SET price = (SELECT menu_items.price FROM menu_items WHERE menu_items.name = orders.menu_item)
+WHERE EXISTS (SELECT 1 FROM menu_items WHERE menu_items.name = orders.menu_item);
@@ -0,0 +1,20 @@
+ALTER TABLE orders ADD COLUMN seat INTEGER NULL CHECK (seat > 0);
+
+CREATE TABLE split_bills (
+    bill_id BLOB NOT NULL PRIMARY KEY CHECK (length(bill_id) = 16),
+    session_id BLOB NOT NULL,
+    position INTEGER NOT NULL CHECK (position >= 0),
+    label TEXT NOT NULL,
+    subtotal INTEGER NOT NULL CHECK (subtotal >= 0),
+    service_charge INTEGER NOT NULL CHECK (service_charge >= 0),
+    tax INTEGER NOT NULL CHECK (tax >= 0),
+    total INTEGER NOT NULL CHECK (total >= 0),
+    status TEXT NOT NULL DEFAULT 'open',
+    UNIQUE (session_id, position)
+);
+
+CREATE TABLE split_bill_orders (
+    bill_id BLOB NOT NULL,
+    order_id BLOB NOT NULL,
+    PRIMARY KEY (bill_id, order_id)
+);
@@ -0,0 +1,13 @@
+CREATE TABLE payments (
+    payment_id BLOB NOT NULL PRIMARY KEY CHECK (length(payment_id) = 16),
+    session_id BLOB NOT NULL,
+    bill_id BLOB NULL,
+    tender TEXT NOT NULL,
+    reference TEXT NULL,
+    amount INTEGER NOT NULL CHECK (amount >= 0),
+    tip INTEGER NOT NULL CHECK (tip >= 0),
+    change_due INTEGER NOT NULL CHECK (change_due >= 0),
+    paid_at TEXT NOT NULL
+);
+
+CREATE INDEX payments_session_id_paid_at ON payments (session_id, paid_at);
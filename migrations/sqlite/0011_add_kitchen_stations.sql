@@ -0,0 +1,11 @@
+ALTER TABLE menu_items ADD COLUMN station TEXT NOT NULL DEFAULT 'grill';
+
+-- Existing orders all go to the grill, like the menu items they were placed from.
+ALTER TABLE orders ADD COLUMN station TEXT NOT NULL DEFAULT 'grill';
+
+-- Orders placed before tickets existed each make up a ticket of their own.
+ALTER TABLE orders ADD COLUMN ticket_id BLOB NULL CHECK (ticket_id IS NULL OR length(ticket_id) = 16);
+
+UPDATE orders SET ticket_id = order_id;
+
+CREATE INDEX orders_status_created_at ON orders (status, created_at);
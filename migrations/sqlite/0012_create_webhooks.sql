@@ -0,0 +1,20 @@
+-- events holds a comma-separated list of event names; empty means every event.
+CREATE TABLE webhooks (
+    webhook_id BLOB NOT NULL PRIMARY KEY CHECK (length(webhook_id) = 16),
+    url TEXT NOT NULL,
+    events TEXT NOT NULL DEFAULT '',
+    created_at TEXT NOT NULL
+);
+
+CREATE TABLE webhook_dead_letters (
+    dead_letter_id BLOB NOT NULL PRIMARY KEY CHECK (length(dead_letter_id) = 16),
+    webhook_id BLOB NOT NULL,
+    event_id INTEGER NOT NULL CHECK (event_id >= 0),
+    event TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    attempts INTEGER NOT NULL CHECK (attempts >= 0),
+    last_error TEXT NOT NULL,
+    failed_at TEXT NOT NULL
+);
+
+CREATE INDEX webhook_dead_letters_failed_at ON webhook_dead_letters (failed_at);
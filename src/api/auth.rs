@@ -0,0 +1,194 @@
+use crate::api::error::ApiError;
+use crate::domain::auth::{Principal, Role};
+use crate::protocol::protocol::{PrincipalResponse, TokenInput, TokenResponse};
+use crate::ServerState;
+use chrono::Utc;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::{get, post, serde::json::{self, Json}, State};
+
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+pub const AUTHORIZATION_HEADER: &str = "Authorization";
+
+/// Every role, for routes any member of staff may use.
+pub const STAFF: &[Role] = &Role::ALL;
+
+fn unauthorized(message: impl Into<String>) -> ApiError {
+    ApiError::new(Status::Unauthorized, "unauthorized", message)
+}
+
+/// Authenticates the caller from an `X-Api-Key` header or an
+/// `Authorization: Bearer <token>` header. Handlers take it as
+/// `Result<Principal, ApiError>` and pass it to `authorize`, so a missing or bad
+/// credential comes back as a 401 error body.
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Principal {
+    type Error = ApiError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(state) = request.rocket().state::<Box<ServerState>>() else {
+            rocket::error!("Cannot authenticate {}: ServerState is not managed", request.uri());
+            let error = ApiError::new(Status::InternalServerError, "internal_error", "Internal server error");
+            return Outcome::Error((Status::InternalServerError, error));
+        };
+        let headers = request.headers();
+        let principal = if let Some(api_key) = headers.get_one(API_KEY_HEADER) {
+            state.auth.check_api_key(api_key.trim())
+        } else if let Some(authorization) = headers.get_one(AUTHORIZATION_HEADER) {
+            match authorization.trim().strip_prefix("Bearer ") {
+                Some(token) => state.auth.check_token(token.trim(), Utc::now()),
+                None => Err("Authorization must be a Bearer token".to_string()),
+            }
+        } else {
+            Err(format!("Send an API key in {} or a Bearer token in {}", API_KEY_HEADER, AUTHORIZATION_HEADER))
+        };
+        match principal {
+            Ok(principal) => Outcome::Success(principal),
+            Err(message) => Outcome::Error((Status::Unauthorized, unauthorized(message))),
+        }
+    }
+}
+
+/// Lets the caller through if they authenticated and their role covers one of
+/// `roles`.
+pub fn authorize(caller: Result<Principal, ApiError>, roles: &[Role]) -> Result<Principal, ApiError> {
+    let caller = caller?;
+    caller.require(roles)?;
+    Ok(caller)
+}
+
+fn principal_response(principal: Principal) -> PrincipalResponse {
+    PrincipalResponse { name: principal.name, role: principal.role.to_string() }
+}
+
+#[get("/auth/me")]
+pub fn get_caller(caller: Result<Principal, ApiError>) -> Result<Json<PrincipalResponse>, ApiError> {
+    Ok(Json(principal_response(authorize(caller, STAFF)?)))
+}
+
+#[post("/auth/tokens", data = "<token_data>")]
+pub fn issue_token(
+    caller: Result<Principal, ApiError>,
+    token_data: Result<Json<TokenInput>, json::Error<'_>>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<TokenResponse>, ApiError> {
+    let caller = authorize(caller, &[Role::Manager])?;
+    let issued = state.auth.issue(&caller, token_data?.into_inner(), Utc::now())?;
+    Ok(Json(TokenResponse {
+        token: issued.token,
+        name: issued.principal.name,
+        role: issued.principal.role.to_string(),
+        expires_at: issued.expires_at,
+    }))
+}
+
+/// An `Authorization` header with a fresh token for `role`, for tests that call
+/// guarded routes.
+#[cfg(test)]
+pub(crate) fn bearer(state: &ServerState, role: Role) -> rocket::http::Header<'static> {
+    let principal = Principal { name: format!("test-{}", role), role };
+    let token = state.auth.sign(&principal, Utc::now() + chrono::Duration::hours(1));
+    rocket::http::Header::new(AUTHORIZATION_HEADER, format!("Bearer {}", token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::error::RequestIdFairing;
+    use crate::db::memory::InMemoryStorage;
+    use crate::domain::auth::Authenticator;
+    use rocket::http::{ContentType, Header};
+    use rocket::local::blocking::Client;
+    use rocket::routes;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    fn client() -> Client {
+        let mut state = ServerState::new(Arc::new(InMemoryStorage::new()));
+        state.auth = Authenticator::new(
+            "server key",
+            vec![(Principal { name: "office".to_string(), role: Role::Manager }, "0123456789abcdef".to_string())],
+        );
+        let rocket = rocket::build()
+            .attach(RequestIdFairing)
+            .register("/", crate::api::catchers())
+            .manage(Box::new(state))
+            .mount("/", routes![get_caller, issue_token]);
+        Client::tracked(rocket).expect("valid rocket instance")
+    }
+
+    fn api_key() -> Header<'static> {
+        Header::new(API_KEY_HEADER, "0123456789abcdef")
+    }
+
+    #[test]
+    fn test_api_key_issues_tokens() {
+        let client = client();
+        let response = client
+            .post("/auth/tokens")
+            .header(api_key())
+            .header(ContentType::JSON)
+            .body(json!({ "name": "till-1", "role": "server", "ttl_minutes": 30 }).to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let issued: Value = response.into_json().unwrap();
+        assert_eq!(issued["role"], "server");
+
+        let token = issued["token"].as_str().unwrap();
+        let response = client
+            .get("/auth/me")
+            .header(Header::new(AUTHORIZATION_HEADER, format!("Bearer {}", token)))
+            .dispatch();
+        let caller: Value = response.into_json().unwrap();
+        assert_eq!(caller, json!({ "name": "till-1", "role": "server" }));
+
+        // A server cannot hand out tokens, and a manager cannot make admins.
+        let response = client
+            .post("/auth/tokens")
+            .header(Header::new(AUTHORIZATION_HEADER, format!("Bearer {}", token)))
+            .header(ContentType::JSON)
+            .body(json!({ "name": "till-2", "role": "server" }).to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+        let response = client
+            .post("/auth/tokens")
+            .header(api_key())
+            .header(ContentType::JSON)
+            .body(json!({ "name": "root", "role": "admin" }).to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["code"], "forbidden");
+    }
+
+    #[test]
+    fn test_missing_or_bad_credentials_are_unauthorized() {
+        let client = client();
+        let requests = [
+            client.get("/auth/me"),
+            client.get("/auth/me").header(Header::new(API_KEY_HEADER, "not-a-key")),
+            client.get("/auth/me").header(Header::new(AUTHORIZATION_HEADER, "Basic b2ZmaWNl")),
+            client.get("/auth/me").header(Header::new(AUTHORIZATION_HEADER, "Bearer office.admin.9999999999.00")),
+        ];
+        for request in requests {
+            let response = request.dispatch();
+            assert_eq!(response.status(), Status::Unauthorized);
+            let body: Value = response.into_json().unwrap();
+            assert_eq!(body["code"], "unauthorized");
+            assert!(body["request_id"].is_string());
+        }
+    }
+
+    #[test]
+    fn test_missing_state_is_an_internal_error() {
+        let rocket = rocket::build()
+            .attach(RequestIdFairing)
+            .register("/", crate::api::catchers())
+            .mount("/", routes![get_caller]);
+        let client = Client::tracked(rocket).expect("valid rocket instance");
+        let response = client.get("/auth/me").header(api_key()).dispatch();
+        assert_eq!(response.status(), Status::InternalServerError);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["code"], "internal_error");
+    }
+}
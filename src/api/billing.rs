@@ -0,0 +1,644 @@
+use crate::api::auth::{authorize, STAFF};
+use crate::api::error::ApiError;
+use crate::domain::auth::{Principal, Role};
+use crate::domain::billing::{self, Bill, BillLine, Payment, SplitBill};
+use crate::protocol::protocol::{
+    BillLineResponse, BillResponse, PaymentInput, PaymentResponse, SplitBillResponse, SplitInput,
+};
+use crate::ServerState;
+use rocket::{delete, get, post, serde::json::{self, Json}, State};
+use uuid::Uuid;
+
+fn line_response(line: BillLine) -> BillLineResponse {
+    BillLineResponse {
+        menu_item: line.menu_item,
+        unit_price: line.unit_price,
+        quantity: line.quantity,
+        line_total: line.line_total,
+    }
+}
+
+pub(crate) fn to_response(bill: Bill) -> BillResponse {
+    BillResponse {
+        table_number: bill.table_number,
+        session_id: bill.session_id,
+        currency: bill.currency,
+        lines: bill.lines.into_iter().map(line_response).collect(),
+        subtotal: bill.subtotal,
+        service_charge: bill.service_charge,
+        tax: bill.tax,
+        total: bill.total,
+        paid: bill.paid,
+        balance: bill.balance,
+        status: bill.status.to_string(),
+    }
+}
+
+pub(crate) fn split_response(bill: SplitBill) -> SplitBillResponse {
+    SplitBillResponse {
+        id: bill.id,
+        table_number: bill.table_number,
+        session_id: bill.session_id,
+        position: bill.position,
+        label: bill.label,
+        currency: bill.currency,
+        order_ids: bill.order_ids,
+        lines: bill.lines.into_iter().map(line_response).collect(),
+        subtotal: bill.subtotal,
+        service_charge: bill.service_charge,
+        tax: bill.tax,
+        total: bill.total,
+        paid: bill.paid,
+        balance: bill.balance,
+        status: bill.status.to_string(),
+    }
+}
+
+pub(crate) fn payment_response(payment: Payment) -> PaymentResponse {
+    PaymentResponse {
+        id: payment.id,
+        session_id: payment.session_id,
+        bill_id: payment.bill_id,
+        tender: payment.tender.to_string(),
+        reference: payment.reference,
+        amount: payment.amount,
+        tip: payment.tip,
+        change: payment.change,
+        paid_at: payment.paid_at,
+    }
+}
+
+fn parse_bill_id(bill_id: &str) -> Result<Uuid, ApiError> {
+    Uuid::parse_str(bill_id).map_err(|e| ApiError::invalid_id("bill_id", e))
+}
+
+#[get("/tables/<table_id>/bill")]
+pub async fn get_table_bill(
+    table_id: u64,
+    caller: Result<Principal, ApiError>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<BillResponse>, ApiError> {
+    authorize(caller, STAFF)?;
+    Ok(Json(to_response(billing::get_bill(table_id, state).await?)))
+}
+
+#[get("/tables/<table_id>/sessions/<session_id>/bill")]
+pub async fn get_session_bill(
+    table_id: u64,
+    session_id: &str,
+    caller: Result<Principal, ApiError>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<BillResponse>, ApiError> {
+    authorize(caller, STAFF)?;
+    let session_id = Uuid::parse_str(session_id).map_err(|e| ApiError::invalid_id("session_id", e))?;
+    Ok(Json(to_response(billing::get_session_bill(table_id, session_id, state).await?)))
+}
+
+#[post("/tables/<table_id>/bill/split", data = "<split_data>")]
+pub async fn split_table_bill(
+    table_id: u64,
+    caller: Result<Principal, ApiError>,
+    split_data: Result<Json<SplitInput>, json::Error<'_>>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<Vec<SplitBillResponse>>, ApiError> {
+    authorize(caller, &[Role::Server])?;
+    let bills = billing::split_bill(table_id, split_data?.into_inner(), state).await?;
+    Ok(Json(bills.into_iter().map(split_response).collect()))
+}
+
+#[delete("/tables/<table_id>/bill/split")]
+pub async fn remove_table_bill_split(
+    table_id: u64,
+    caller: Result<Principal, ApiError>,
+    state: &State<Box<ServerState>>,
+) -> Result<(), ApiError> {
+    authorize(caller, &[Role::Server])?;
+    Ok(billing::remove_split(table_id, state).await?)
+}
+
+#[get("/tables/<table_id>/bills")]
+pub async fn get_split_bills(
+    table_id: u64,
+    caller: Result<Principal, ApiError>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<Vec<SplitBillResponse>>, ApiError> {
+    authorize(caller, STAFF)?;
+    let bills = billing::get_split_bills(table_id, state).await?;
+    Ok(Json(bills.into_iter().map(split_response).collect()))
+}
+
+#[get("/tables/<table_id>/bills/<bill_id>")]
+pub async fn get_split_bill(
+    table_id: u64,
+    bill_id: &str,
+    caller: Result<Principal, ApiError>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<SplitBillResponse>, ApiError> {
+    authorize(caller, STAFF)?;
+    let bill_id = parse_bill_id(bill_id)?;
+    Ok(Json(split_response(billing::get_split_bill(table_id, bill_id, state).await?)))
+}
+
+#[post("/tables/<table_id>/bills/<bill_id>/close")]
+pub async fn close_split_bill(
+    table_id: u64,
+    bill_id: &str,
+    caller: Result<Principal, ApiError>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<SplitBillResponse>, ApiError> {
+    authorize(caller, &[Role::Server])?;
+    let bill_id = parse_bill_id(bill_id)?;
+    Ok(Json(split_response(billing::close_split_bill(table_id, bill_id, state).await?)))
+}
+
+#[post("/tables/<table_id>/bill/payments", data = "<payment_data>")]
+pub async fn pay_table_bill(
+    table_id: u64,
+    caller: Result<Principal, ApiError>,
+    payment_data: Result<Json<PaymentInput>, json::Error<'_>>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<PaymentResponse>, ApiError> {
+    authorize(caller, &[Role::Server])?;
+    let payment = billing::record_payment(table_id, None, payment_data?.into_inner(), state).await?;
+    Ok(Json(payment_response(payment)))
+}
+
+#[post("/tables/<table_id>/bills/<bill_id>/payments", data = "<payment_data>")]
+pub async fn pay_split_bill(
+    table_id: u64,
+    bill_id: &str,
+    caller: Result<Principal, ApiError>,
+    payment_data: Result<Json<PaymentInput>, json::Error<'_>>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<PaymentResponse>, ApiError> {
+    authorize(caller, &[Role::Server])?;
+    let bill_id = parse_bill_id(bill_id)?;
+    let payment = billing::record_payment(table_id, Some(bill_id), payment_data?.into_inner(), state).await?;
+    Ok(Json(payment_response(payment)))
+}
+
+#[get("/tables/<table_id>/bill/payments")]
+pub async fn get_table_payments(
+    table_id: u64,
+    caller: Result<Principal, ApiError>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<Vec<PaymentResponse>>, ApiError> {
+    authorize(caller, STAFF)?;
+    let payments = billing::get_payments(table_id, state).await?;
+    Ok(Json(payments.into_iter().map(payment_response).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::auth::bearer;
+    use crate::api::error::RequestIdFairing;
+    use crate::api::{floor, tables};
+    use crate::db::{memory::InMemoryStorage, MenuItem, Station, Storage};
+    use crate::domain::auth::Role;
+    use crate::domain::billing::Pricing;
+    use rocket::http::{ContentType, Header, Status};
+    use rocket::local::blocking::Client;
+    use rocket::routes;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    fn client() -> Client {
+        let db = Arc::new(InMemoryStorage::new());
+        for (name, price) in [("Pizza", 1250), ("Salad", 749)] {
+            db.add_menu_item(MenuItem {
+                id: Uuid::new_v4(),
+                name: name.to_string(),
+                category: "Mains".to_string(),
+                price,
+                prep_time_minutes: 12,
+                available: true,
+                station: Station::Grill,
+            })
+            .unwrap();
+        }
+        let mut state = ServerState::new(db);
+        state.pricing = Pricing {
+            currency: "EUR".to_string(),
+            tax_rate_bps: 825,
+            service_charge_bps: 1000,
+        };
+        let rocket = rocket::build()
+            .attach(RequestIdFairing)
+            .register("/", crate::api::catchers())
+            .manage(Box::new(state))
+            .mount("/", routes![
+                get_table_bill,
+                get_session_bill,
+                split_table_bill,
+                remove_table_bill_split,
+                get_split_bills,
+                get_split_bill,
+                close_split_bill,
+                pay_table_bill,
+                pay_split_bill,
+                get_table_payments,
+                floor::add_table,
+                floor::open_table,
+                floor::close_table,
+                tables::add_table_orders,
+                tables::update_table_order_status,
+                tables::delete_table_order,
+            ]);
+        Client::tracked(rocket).expect("valid rocket instance")
+    }
+
+    fn seat_table(client: &Client, number: u64) -> String {
+        client
+            .post("/tables")
+            .header(manager(client))
+            .header(ContentType::JSON)
+            .body(json!({ "number": number, "seats": 4, "section": "Main" }).to_string())
+            .dispatch();
+        let table: Value = client
+            .post(format!("/tables/{}/open", number))
+            .header(manager(client))
+            .dispatch()
+            .into_json()
+            .unwrap();
+        table["session_id"].as_str().unwrap().to_string()
+    }
+
+    fn order(client: &Client, number: u64, items: &[&str]) -> Vec<String> {
+        let orders: Vec<Value> = items.iter().map(|item| json!({ "menu_item": item })).collect();
+        place(client, number, orders)
+    }
+
+    fn order_for_seats(client: &Client, number: u64, items: &[(&str, u32)]) -> Vec<String> {
+        let orders: Vec<Value> = items.iter().map(|(item, seat)| json!({ "menu_item": item, "seat": seat })).collect();
+        place(client, number, orders)
+    }
+
+    fn place(client: &Client, number: u64, orders: Vec<Value>) -> Vec<String> {
+        client
+            .post(format!("/tables/{}/orders", number))
+            .header(server(client))
+            .header(ContentType::JSON)
+            .body(json!({ "orders": orders }).to_string())
+            .dispatch()
+            .into_json()
+            .unwrap()
+    }
+
+    fn server(client: &Client) -> Header<'static> {
+        bearer(client.rocket().state::<Box<ServerState>>().unwrap(), Role::Server)
+    }
+
+    fn manager(client: &Client) -> Header<'static> {
+        bearer(client.rocket().state::<Box<ServerState>>().unwrap(), Role::Manager)
+    }
+
+    #[test]
+    fn test_get_table_bill() {
+        let client = client();
+        let session_id = seat_table(&client, 1);
+        let ids = order(&client, 1, &["Pizza", "Salad", "Pizza"]);
+        client
+            .patch(format!("/tables/1/orders/{}/status", ids[2]))
+            .header(server(&client))
+            .header(ContentType::JSON)
+            .body(r#"{"status": "cancelled"}"#)
+            .dispatch();
+
+        let response = client.get("/tables/1/bill").header(manager(&client)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "table_number": 1,
+                "session_id": session_id,
+                "currency": "EUR",
+                "lines": [
+                    { "menu_item": "Pizza", "unit_price": 1250, "quantity": 1, "line_total": 1250 },
+                    { "menu_item": "Salad", "unit_price": 749, "quantity": 1, "line_total": 749 },
+                ],
+                "subtotal": 1999,
+                "service_charge": 200,
+                "tax": 181,
+                "total": 2380,
+                "paid": 0,
+                "balance": 2380,
+                "status": "open",
+            })
+        );
+    }
+
+    #[test]
+    fn test_bills_of_closed_tables() {
+        let client = client();
+        let session_id = seat_table(&client, 1);
+        order(&client, 1, &["Salad"]);
+        pay(&client, "/tables/1/bill/payments", json!({ "tender": "cash", "amount": 892 }));
+        assert_eq!(client.post("/tables/1/close").header(manager(&client)).dispatch().status(), Status::Ok);
+
+        let response = client.get("/tables/1/bill").header(manager(&client)).dispatch();
+        assert_eq!(response.status(), Status::Conflict);
+        assert_eq!(client.get("/tables/9/bill").header(manager(&client)).dispatch().status(), Status::NotFound);
+
+        let body: Value = client
+            .get(format!("/tables/1/sessions/{}/bill", session_id))
+            .header(manager(&client))
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!((&body["subtotal"], &body["balance"], &body["status"]), (&json!(749), &json!(0), &json!("paid")));
+        let response = client
+            .get(format!("/tables/2/sessions/{}/bill", session_id))
+            .header(manager(&client))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    fn split(client: &Client, number: u64, body: Value) -> (Status, Value) {
+        let response = client
+            .post(format!("/tables/{}/bill/split", number))
+            .header(manager(client))
+            .header(ContentType::JSON)
+            .body(body.to_string())
+            .dispatch();
+        (response.status(), response.into_json().unwrap())
+    }
+
+    fn totals(bills: &Value) -> Vec<(String, u64)> {
+        bills
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|bill| (bill["label"].as_str().unwrap().to_string(), bill["total"].as_u64().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_split_bill_by_seat_and_evenly() {
+        let client = client();
+        seat_table(&client, 1);
+        let ids = order_for_seats(&client, 1, &[("Pizza", 2), ("Salad", 1), ("Pizza", 1)]);
+
+        let (status, bills) = split(&client, 1, json!({ "mode": "seat" }));
+        assert_eq!(status, Status::Ok);
+        assert_eq!(totals(&bills), vec![("Seat 1".to_string(), 2381), ("Seat 2".to_string(), 1488)]);
+        assert_eq!(bills[0]["order_ids"].as_array().unwrap().len(), 2);
+        assert_eq!(bills[1]["order_ids"], json!([ids[0]]));
+        assert_eq!(
+            bills[1]["lines"],
+            json!([{ "menu_item": "Pizza", "unit_price": 1250, "quantity": 1, "line_total": 1250 }])
+        );
+        assert_eq!(bills[0]["status"], "open");
+        assert_eq!(bills[0]["currency"], "EUR");
+
+        // Splitting again replaces the earlier shares.
+        let (status, bills) = split(&client, 1, json!({ "mode": "even", "ways": 3 }));
+        assert_eq!(status, Status::Ok);
+        assert_eq!(
+            totals(&bills),
+            vec![("1 of 3".to_string(), 1290), ("2 of 3".to_string(), 1290), ("3 of 3".to_string(), 1289)]
+        );
+        let whole: Value = client.get("/tables/1/bill").header(manager(&client)).dispatch().into_json().unwrap();
+        assert_eq!(whole["total"], 3869);
+        let listed: Value = client.get("/tables/1/bills").header(manager(&client)).dispatch().into_json().unwrap();
+        assert_eq!(listed, bills);
+    }
+
+    #[test]
+    fn test_split_bill_by_items() {
+        let client = client();
+        seat_table(&client, 1);
+        let ids = order(&client, 1, &["Pizza", "Salad"]);
+
+        let (status, bills) = split(&client, 1, json!({ "mode": "items", "parts": [[ids[1]], [ids[0]]] }));
+        assert_eq!(status, Status::Ok);
+        assert_eq!(totals(&bills), vec![("Part 1".to_string(), 892), ("Part 2".to_string(), 1488)]);
+
+        let (status, _) = split(&client, 1, json!({ "mode": "items", "parts": [[ids[0]]] }));
+        assert_eq!(status, Status::UnprocessableEntity);
+        let (status, _) = split(&client, 1, json!({ "mode": "seat" }));
+        assert_eq!(status, Status::UnprocessableEntity);
+        let (status, _) = split(&client, 1, json!({ "mode": "even", "ways": 1 }));
+        assert_eq!(status, Status::UnprocessableEntity);
+        let (status, _) = split(&client, 2, json!({ "mode": "even", "ways": 2 }));
+        assert_eq!(status, Status::NotFound);
+    }
+
+    #[test]
+    fn test_split_bills_are_paid_and_closed_independently() {
+        let client = client();
+        seat_table(&client, 1);
+        order(&client, 1, &["Pizza", "Salad"]);
+        let (_, bills) = split(&client, 1, json!({ "mode": "even", "ways": 2 }));
+        let first = bills[0]["id"].as_str().unwrap();
+        let second = bills[1]["id"].as_str().unwrap();
+        assert_eq!((&bills[0]["total"], &bills[0]["balance"]), (&json!(1190), &json!(1190)));
+
+        let response = client.post(format!("/tables/1/bills/{}/close", first)).header(manager(&client)).dispatch();
+        assert_eq!(response.status(), Status::Conflict);
+        let (status, payment) = pay(
+            &client,
+            &format!("/tables/1/bills/{}/payments", first),
+            json!({ "tender": "card", "amount": 1390, "tip": 200, "reference": "VISA-4242" }),
+        );
+        assert_eq!(status, Status::Ok);
+        assert_eq!((&payment["amount"], &payment["tip"], &payment["bill_id"]), (&json!(1190), &json!(200), &json!(first)));
+
+        let response = client.post(format!("/tables/1/bills/{}/close", first)).header(manager(&client)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let closed: Value = response.into_json().unwrap();
+        assert_eq!((&closed["status"], &closed["paid"], &closed["balance"]), (&json!("closed"), &json!(1190), &json!(0)));
+        let other: Value = client
+            .get(format!("/tables/1/bills/{}", second))
+            .header(manager(&client))
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(other["status"], "open");
+
+        let response = client.post(format!("/tables/1/bills/{}/close", first)).header(manager(&client)).dispatch();
+        assert_eq!(response.status(), Status::Conflict);
+        let (status, _) = pay(
+            &client,
+            &format!("/tables/1/bills/{}/payments", first),
+            json!({ "tender": "cash", "amount": 100 }),
+        );
+        assert_eq!(status, Status::Conflict);
+        let (status, _) = split(&client, 1, json!({ "mode": "even", "ways": 3 }));
+        assert_eq!(status, Status::Conflict);
+        let response = client.delete("/tables/1/bill/split").header(manager(&client)).dispatch();
+        assert_eq!(response.status(), Status::Conflict);
+
+        // The table stays open until the other share is paid too.
+        assert_eq!(client.post("/tables/1/close").header(manager(&client)).dispatch().status(), Status::Conflict);
+        pay(&client, &format!("/tables/1/bills/{}/payments", second), json!({ "tender": "cash", "amount": 1200 }));
+        let other: Value = client
+            .get(format!("/tables/1/bills/{}", second))
+            .header(manager(&client))
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(other["status"], "paid");
+        assert_eq!(client.post("/tables/1/close").header(manager(&client)).dispatch().status(), Status::Ok);
+
+        let missing = client
+            .post(format!("/tables/2/bills/{}/close", Uuid::new_v4()))
+            .header(manager(&client))
+            .dispatch();
+        assert_eq!(missing.status(), Status::NotFound);
+        assert_eq!(client.get("/tables/1/bills/nope").header(manager(&client)).dispatch().status(), Status::BadRequest);
+    }
+
+    fn pay(client: &Client, path: &str, body: Value) -> (Status, Value) {
+        let response = client
+            .post(path.to_string())
+            .header(manager(client))
+            .header(ContentType::JSON)
+            .body(body.to_string())
+            .dispatch();
+        (response.status(), response.into_json().unwrap())
+    }
+
+    #[test]
+    fn test_partial_payments_tips_and_change() {
+        let client = client();
+        seat_table(&client, 1);
+        order(&client, 1, &["Pizza", "Salad"]);
+
+        let (status, card) = pay(
+            &client,
+            "/tables/1/bill/payments",
+            json!({ "tender": "card", "amount": 1000, "reference": "AMEX-1005" }),
+        );
+        assert_eq!(status, Status::Ok);
+        assert_eq!((&card["amount"], &card["change"], &card["bill_id"]), (&json!(1000), &json!(0), &Value::Null));
+        let bill: Value = client.get("/tables/1/bill").header(manager(&client)).dispatch().into_json().unwrap();
+        assert_eq!((&bill["paid"], &bill["balance"], &bill["status"]), (&json!(1000), &json!(1380), &json!("open")));
+        assert_eq!(client.post("/tables/1/close").header(manager(&client)).dispatch().status(), Status::Conflict);
+
+        for bad in [
+            json!({ "tender": "card", "amount": 2000, "reference": "VISA-4242" }),
+            json!({ "tender": "gift_card", "amount": 500 }),
+            json!({ "tender": "cash", "amount": 100, "tip": 200 }),
+            json!({ "tender": "cheque", "amount": 100 }),
+        ] {
+            assert_eq!(pay(&client, "/tables/1/bill/payments", bad).0, Status::UnprocessableEntity);
+        }
+
+        let (status, cash) = pay(&client, "/tables/1/bill/payments", json!({ "tender": "cash", "amount": 2000, "tip": 300 }));
+        assert_eq!(status, Status::Ok);
+        assert_eq!((&cash["amount"], &cash["tip"], &cash["change"]), (&json!(1380), &json!(300), &json!(320)));
+        let bill: Value = client.get("/tables/1/bill").header(manager(&client)).dispatch().into_json().unwrap();
+        assert_eq!((&bill["paid"], &bill["balance"], &bill["status"]), (&json!(2380), &json!(0), &json!("paid")));
+        let (status, _) = pay(&client, "/tables/1/bill/payments", json!({ "tender": "cash", "amount": 100 }));
+        assert_eq!(status, Status::Conflict);
+
+        let payments: Value = client
+            .get("/tables/1/bill/payments")
+            .header(manager(&client))
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(payments.as_array().unwrap().len(), 2);
+        assert_eq!(payments[0]["reference"], "AMEX-1005");
+        assert_eq!(client.post("/tables/1/close").header(manager(&client)).dispatch().status(), Status::Ok);
+    }
+
+    #[test]
+    fn test_remove_split() {
+        let client = client();
+        seat_table(&client, 1);
+        order(&client, 1, &["Pizza"]);
+        split(&client, 1, json!({ "mode": "even", "ways": 2 }));
+
+        assert_eq!(client.delete("/tables/1/bill/split").header(manager(&client)).dispatch().status(), Status::Ok);
+        let bills: Value = client.get("/tables/1/bills").header(manager(&client)).dispatch().into_json().unwrap();
+        assert_eq!(bills, json!([]));
+    }
+
+    #[test]
+    fn test_order_changes_drop_an_unpaid_split() {
+        let client = client();
+        seat_table(&client, 1);
+        let ids = order(&client, 1, &["Pizza", "Salad"]);
+        split(&client, 1, json!({ "mode": "items", "parts": [[ids[0]], [ids[1]]] }));
+
+        // A later order would be on no share, so the split goes.
+        let salad = order(&client, 1, &["Salad"]);
+        let bills: Value = client.get("/tables/1/bills").header(manager(&client)).dispatch().into_json().unwrap();
+        assert_eq!(bills, json!([]));
+
+        split(&client, 1, json!({ "mode": "even", "ways": 2 }));
+        let response = client
+            .patch(format!("/tables/1/orders/{}/status", salad[0]))
+            .header(server(&client))
+            .header(ContentType::JSON)
+            .body(r#"{"status": "cancelled"}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let after: Value = client.get("/tables/1/bills").header(manager(&client)).dispatch().into_json().unwrap();
+        assert_eq!(after, json!([]));
+
+        // Once a share has been paid towards, the orders are fixed.
+        let (_, bills) = split(&client, 1, json!({ "mode": "even", "ways": 2 }));
+        assert_eq!(bills[0]["total"], 1190);
+        let first = bills[0]["id"].as_str().unwrap();
+        pay(&client, &format!("/tables/1/bills/{}/payments", first), json!({ "tender": "cash", "amount": 500 }));
+        let response = client
+            .post("/tables/1/orders")
+            .header(server(&client))
+            .header(ContentType::JSON)
+            .body(json!({ "orders": [{ "menu_item": "Pizza" }] }).to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::Conflict);
+        let response = client.delete(format!("/tables/1/orders/{}", ids[1])).header(server(&client)).dispatch();
+        assert_eq!(response.status(), Status::Conflict);
+        let bill: Value = client.get("/tables/1/bill").header(manager(&client)).dispatch().into_json().unwrap();
+        assert_eq!(bill["total"], 2380);
+    }
+
+    #[test]
+    fn test_orders_are_fixed_once_the_whole_bill_is_paid_towards() {
+        let client = client();
+        seat_table(&client, 1);
+        let ids = order(&client, 1, &["Pizza", "Salad"]);
+        pay(&client, "/tables/1/bill/payments", json!({ "tender": "cash", "amount": 2000 }));
+
+        let response = client
+            .patch(format!("/tables/1/orders/{}/status", ids[0]))
+            .header(server(&client))
+            .header(ContentType::JSON)
+            .body(r#"{"status": "cancelled"}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Conflict);
+        let response = client.delete(format!("/tables/1/orders/{}", ids[1])).header(server(&client)).dispatch();
+        assert_eq!(response.status(), Status::Conflict);
+
+        let bill: Value = client.get("/tables/1/bill").header(manager(&client)).dispatch().into_json().unwrap();
+        assert_eq!((&bill["total"], &bill["paid"], &bill["balance"]), (&json!(2380), &json!(2000), &json!(380)));
+    }
+
+    #[test]
+    fn test_split_bill_is_paid_through_its_shares() {
+        let client = client();
+        seat_table(&client, 1);
+        order(&client, 1, &["Pizza", "Salad"]);
+        let (_, bills) = split(&client, 1, json!({ "mode": "even", "ways": 2 }));
+
+        let (status, _) = pay(&client, "/tables/1/bill/payments", json!({ "tender": "cash", "amount": 2380 }));
+        assert_eq!(status, Status::Conflict);
+        let shares: Value = client.get("/tables/1/bills").header(manager(&client)).dispatch().into_json().unwrap();
+        assert_eq!((&shares[0]["balance"], &shares[1]["balance"]), (&json!(1190), &json!(1190)));
+
+        for share in bills.as_array().unwrap() {
+            let path = format!("/tables/1/bills/{}/payments", share["id"].as_str().unwrap());
+            assert_eq!(pay(&client, &path, json!({ "tender": "cash", "amount": 1190 })).0, Status::Ok);
+            assert_eq!(pay(&client, &path, json!({ "tender": "cash", "amount": 100 })).0, Status::Conflict);
+        }
+        let bill: Value = client.get("/tables/1/bill").header(manager(&client)).dispatch().into_json().unwrap();
+        assert_eq!((&bill["paid"], &bill["balance"]), (&json!(2380), &json!(0)));
+        let (status, _) = pay(&client, "/tables/1/bill/payments", json!({ "tender": "cash", "amount": 100 }));
+        assert_eq!(status, Status::Conflict);
+    }
+}
@@ -0,0 +1,293 @@
+use crate::db::StorageError;
+use crate::protocol::protocol::{ErrorResponse, FieldError};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{Header, Status};
+use rocket::request::Request;
+use rocket::response::{self, Responder, Response};
+use rocket::serde::json::{self, Json};
+use rocket::{catch, Data};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Identifier attached to every request, echoed back in the `X-Request-Id` response
+/// header and in error bodies so a client report can be matched to server logs.
+pub struct RequestId(pub Uuid);
+
+impl RequestId {
+    /// Returns the id for `request`, reusing a well-formed `X-Request-Id` sent by the
+    /// client and generating a fresh one otherwise.
+    pub fn of(request: &Request<'_>) -> Uuid {
+        request
+            .local_cache(|| {
+                let id = request
+                    .headers()
+                    .get_one(REQUEST_ID_HEADER)
+                    .and_then(|value| Uuid::parse_str(value).ok())
+                    .unwrap_or_else(Uuid::new_v4);
+                RequestId(id)
+            })
+            .0
+    }
+}
+
+/// Fairing that assigns a request id on arrival and returns it on every response.
+pub struct RequestIdFairing;
+
+#[rocket::async_trait]
+impl Fairing for RequestIdFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request ID",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        RequestId::of(request);
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        response.set_header(Header::new(REQUEST_ID_HEADER, RequestId::of(request).to_string()));
+    }
+}
+
+/// Error returned by every API handler and catcher, rendered as an `ErrorResponse` body.
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: Status,
+    pub code: &'static str,
+    pub message: String,
+    pub details: Vec<FieldError>,
+    /// What went wrong underneath, logged with the request id but never sent to the
+    /// client, which only gets the generic `message`.
+    pub cause: Option<String>,
+}
+
+impl ApiError {
+    pub fn new(status: Status, code: &'static str, message: impl Into<String>) -> Self {
+        ApiError {
+            status,
+            code,
+            message: message.into(),
+            details: Vec::new(),
+            cause: None,
+        }
+    }
+
+    pub fn with_detail(mut self, field: impl Into<String>, message: impl Into<String>) -> Self {
+        self.details.push(FieldError {
+            field: field.into(),
+            message: message.into(),
+        });
+        self
+    }
+
+    /// Builds the error for a path segment that is not a valid UUID.
+    pub fn invalid_id(field: &str, error: uuid::Error) -> Self {
+        ApiError::new(Status::BadRequest, "invalid_id", format!("{} is not a valid UUID", field))
+            .with_detail(field, error.to_string())
+    }
+}
+
+/// Maps a storage failure to the HTTP status a client should see.
+pub fn error_status(error: &StorageError) -> Status {
+    match error {
+        StorageError::NotFound(_) => Status::NotFound,
+        StorageError::Conflict(_) => Status::Conflict,
+        StorageError::Validation(_) => Status::UnprocessableEntity,
+        StorageError::Forbidden(_) => Status::Forbidden,
+        StorageError::Unavailable(_) => Status::ServiceUnavailable,
+        StorageError::Internal(_) | StorageError::Corrupt(_) => Status::InternalServerError,
+    }
+}
+
+impl From<StorageError> for ApiError {
+    fn from(error: StorageError) -> Self {
+        let code = match error {
+            StorageError::NotFound(_) => "not_found",
+            StorageError::Conflict(_) => "conflict",
+            StorageError::Validation(_) => "validation_failed",
+            StorageError::Forbidden(_) => "forbidden",
+            StorageError::Unavailable(_) => "unavailable",
+            StorageError::Internal(_) => "internal_error",
+            StorageError::Corrupt(_) => "corrupt_data",
+        };
+        match error {
+            // Driver and connection errors can describe the database; keep them in the logs.
+            StorageError::Internal(cause) => ApiError {
+                cause: Some(cause),
+                ..ApiError::new(Status::InternalServerError, code, "Internal server error")
+            },
+            StorageError::Unavailable(cause) => ApiError {
+                cause: Some(cause),
+                ..ApiError::new(Status::ServiceUnavailable, code, "The service is temporarily unavailable")
+            },
+            error => ApiError::new(error_status(&error), code, error.to_string()),
+        }
+    }
+}
+
+impl<'a> From<json::Error<'a>> for ApiError {
+    fn from(error: json::Error<'a>) -> Self {
+        match error {
+            json::Error::Io(e) => ApiError::new(Status::BadRequest, "bad_request", e.to_string()),
+            json::Error::Parse(_, e) => {
+                ApiError::new(Status::UnprocessableEntity, "invalid_body", "Request body is not valid")
+                    .with_detail("body", e.to_string())
+            }
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let request_id = RequestId::of(request);
+        if let Some(cause) = &self.cause {
+            rocket::error!("Request {} ({} {}) failed: {}", request_id, request.method(), request.uri(), cause);
+        }
+        let body = ErrorResponse {
+            code: self.code.to_string(),
+            message: self.message,
+            request_id,
+            details: self.details,
+        };
+        Response::build_from(Json(body).respond_to(request)?)
+            .status(self.status)
+            .ok()
+    }
+}
+
+#[catch(404)]
+pub fn not_found(request: &Request<'_>) -> ApiError {
+    ApiError::new(
+        Status::NotFound,
+        "not_found",
+        format!("No route matches {} {}", request.method(), request.uri().path()),
+    )
+}
+
+#[catch(422)]
+pub fn unprocessable_entity(_: &Request<'_>) -> ApiError {
+    ApiError::new(Status::UnprocessableEntity, "invalid_body", "Request body could not be processed")
+}
+
+#[catch(500)]
+pub fn internal_error(_: &Request<'_>) -> ApiError {
+    ApiError::new(Status::InternalServerError, "internal_error", "Internal server error")
+}
+
+#[catch(default)]
+pub fn default_catcher(status: Status, _: &Request<'_>) -> ApiError {
+    let code = match status.code {
+        400 => "bad_request",
+        401 => "unauthorized",
+        403 => "forbidden",
+        409 => "conflict",
+        415 => "unsupported_media_type",
+        503 => "unavailable",
+        _ => "http_error",
+    };
+    ApiError::new(status, code, status.reason_lossy())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::decode::CorruptRow;
+    use rocket::local::blocking::Client;
+    use rocket::{catchers, get, routes};
+    use serde_json::Value;
+
+    #[test]
+    fn test_error_status() {
+        let cases = [
+            (StorageError::NotFound("missing".into()), Status::NotFound),
+            (StorageError::Conflict("duplicate".into()), Status::Conflict),
+            (StorageError::Validation("bad".into()), Status::UnprocessableEntity),
+            (StorageError::Forbidden("managers only".into()), Status::Forbidden),
+            (StorageError::Unavailable("down".into()), Status::ServiceUnavailable),
+            (StorageError::Internal("boom".into()), Status::InternalServerError),
+            (
+                StorageError::Corrupt(CorruptRow::new("orders", "abc", "bad status")),
+                Status::InternalServerError,
+            ),
+        ];
+        for (error, status) in cases {
+            assert_eq!(error_status(&error), status);
+        }
+    }
+
+    #[get("/fail")]
+    fn fail() -> Result<(), ApiError> {
+        Err(StorageError::Conflict("Order already exists".into()).into())
+    }
+
+    #[get("/broken")]
+    fn broken() -> Result<(), ApiError> {
+        Err(StorageError::Internal("Access denied for user 'restaurant'@'10.0.0.7'".into()).into())
+    }
+
+    fn client() -> Client {
+        let rocket = rocket::build()
+            .attach(RequestIdFairing)
+            .register("/", catchers![not_found, unprocessable_entity, internal_error, default_catcher])
+            .mount("/", routes![fail, broken]);
+        Client::tracked(rocket).expect("valid rocket instance")
+    }
+
+    #[test]
+    fn test_error_body_carries_request_id() {
+        let client = client();
+        let response = client.get("/fail").dispatch();
+        assert_eq!(response.status(), Status::Conflict);
+
+        let header = response.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["code"], "conflict");
+        assert_eq!(body["message"], "Order already exists");
+        assert_eq!(body["request_id"], header);
+        assert!(body.get("details").is_none());
+    }
+
+    #[test]
+    fn test_client_request_id_is_reused() {
+        let client = client();
+        let request_id = Uuid::new_v4().to_string();
+        let response = client
+            .get("/fail")
+            .header(Header::new(REQUEST_ID_HEADER, request_id.clone()))
+            .dispatch();
+
+        assert_eq!(response.headers().get_one(REQUEST_ID_HEADER), Some(request_id.as_str()));
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["request_id"], request_id);
+    }
+
+    #[test]
+    fn test_unknown_route_uses_catcher() {
+        let client = client();
+        let response = client.get("/nowhere").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["code"], "not_found");
+        assert_eq!(body["message"], "No route matches GET /nowhere");
+    }
+
+    #[test]
+    fn test_internal_errors_hide_their_cause() {
+        let client = client();
+        let response = client.get("/broken").dispatch();
+        assert_eq!(response.status(), Status::InternalServerError);
+
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["code"], "internal_error");
+        assert_eq!(body["message"], "Internal server error");
+
+        let error = ApiError::from(StorageError::Unavailable("pool timed out".into()));
+        assert_eq!((error.status, error.code), (Status::ServiceUnavailable, "unavailable"));
+        assert_eq!(error.cause.as_deref(), Some("pool timed out"));
+        assert!(!error.message.contains("pool"));
+    }
+}
@@ -0,0 +1,240 @@
+use crate::api::auth::{authorize, STAFF};
+use crate::api::error::ApiError;
+use crate::api::tables;
+use crate::domain::auth::Principal;
+use crate::domain::events::{self, Event, OrderEvent, Subscription};
+use crate::protocol::protocol::{EventResponse, OrderEventResponse};
+use crate::ServerState;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::stream::{self, EventStream};
+use rocket::tokio::select;
+use rocket::{get, Shutdown, State};
+
+pub const LAST_EVENT_ID_HEADER: &str = "Last-Event-ID";
+
+/// The id of the last event a reconnecting client saw, from the `Last-Event-ID`
+/// header. A missing or malformed header means the client starts fresh.
+pub struct LastEventId(pub Option<u64>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LastEventId {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let id = request
+            .headers()
+            .get_one(LAST_EVENT_ID_HEADER)
+            .and_then(|value| value.trim().parse().ok());
+        Outcome::Success(LastEventId(id))
+    }
+}
+
+pub(crate) fn to_response(event: Event) -> EventResponse {
+    let table_number = event.table_number;
+    EventResponse {
+        id: event.id,
+        at: event.at,
+        event: match event.kind {
+            OrderEvent::Created(order) => OrderEventResponse::OrderCreated {
+                table_number,
+                order: tables::to_response(order),
+            },
+            OrderEvent::Deleted(order) => OrderEventResponse::OrderDeleted { table_number, order_id: order.id },
+            OrderEvent::StatusChanged { order_id, from, to } => OrderEventResponse::OrderStatusChanged {
+                table_number,
+                order_id,
+                from: from.to_string(),
+                to: to.to_string(),
+            },
+        },
+    }
+}
+
+/// Sends events until the client goes away or the server shuts down.
+fn event_stream(mut subscription: Subscription, mut shutdown: Shutdown) -> EventStream![] {
+    EventStream! {
+        loop {
+            let event = select! {
+                // Replayed events are ready at once and go out before a pending shutdown.
+                biased;
+                event = subscription.next() => match event {
+                    Some(event) => event,
+                    None => break,
+                },
+                _ = &mut shutdown => break,
+            };
+            let name = event.kind.name();
+            let id = event.id.to_string();
+            yield stream::Event::json(&to_response(event)).event(name).id(id);
+        }
+    }
+}
+
+#[get("/events")]
+pub fn stream_events(
+    caller: Result<Principal, ApiError>,
+    last_event_id: LastEventId,
+    state: &State<Box<ServerState>>,
+    shutdown: Shutdown,
+) -> Result<EventStream![], ApiError> {
+    authorize(caller, STAFF)?;
+    Ok(event_stream(events::subscribe(last_event_id.0, state), shutdown))
+}
+
+#[get("/tables/<table_id>/events")]
+pub async fn stream_table_events(
+    table_id: u64,
+    caller: Result<Principal, ApiError>,
+    last_event_id: LastEventId,
+    state: &State<Box<ServerState>>,
+    shutdown: Shutdown,
+) -> Result<EventStream![], ApiError> {
+    authorize(caller, STAFF)?;
+    let subscription = events::subscribe_table(table_id, last_event_id.0, state).await?;
+    Ok(event_stream(subscription, shutdown))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::auth::bearer;
+    use crate::api::error::RequestIdFairing;
+    use crate::api::floor;
+    use crate::db::{memory::InMemoryStorage, MenuItem, Station, Storage};
+    use crate::domain::auth::Role;
+    use rocket::http::{ContentType, Header, Status};
+    use rocket::local::asynchronous::Client;
+    use rocket::routes;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    async fn client() -> Client {
+        let db = Arc::new(InMemoryStorage::new());
+        db.add_menu_item(MenuItem {
+            id: Uuid::new_v4(),
+            name: "Pizza".to_string(),
+            category: "Mains".to_string(),
+            price: 1250,
+            prep_time_minutes: 12,
+            available: true,
+            station: Station::Grill,
+        })
+        .unwrap();
+        let rocket = rocket::build()
+            .attach(RequestIdFairing)
+            .register("/", crate::api::catchers())
+            .manage(Box::new(ServerState::new(db)))
+            .mount("/", routes![
+                stream_events,
+                stream_table_events,
+                floor::add_table,
+                floor::open_table,
+                tables::add_table_orders,
+                tables::update_table_order_status,
+                tables::delete_table_order,
+            ]);
+        Client::tracked(rocket).await.expect("valid rocket instance")
+    }
+
+    async fn order(client: &Client, number: u64) -> String {
+        client
+            .post("/tables")
+            .header(manager(client))
+            .header(ContentType::JSON)
+            .body(json!({ "number": number, "seats": 4, "section": "Main" }).to_string())
+            .dispatch()
+            .await;
+        client.post(format!("/tables/{}/open", number)).header(manager(client)).dispatch().await;
+        let ids: Vec<String> = client
+            .post(format!("/tables/{}/orders", number))
+            .header(server(client))
+            .header(ContentType::JSON)
+            .body(json!({ "orders": [{ "menu_item": "Pizza" }] }).to_string())
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        ids[0].clone()
+    }
+
+    /// Reads what a stream sent before the server shut down, as (id, event, data).
+    async fn read_stream(client: &Client, uri: &str, last_event_id: Option<&str>) -> Vec<(String, String, Value)> {
+        let mut request = client.get(uri.to_string()).header(manager(client));
+        if let Some(id) = last_event_id {
+            request = request.header(Header::new(LAST_EVENT_ID_HEADER, id.to_string()));
+        }
+        client.rocket().shutdown().notify();
+        let response = request.dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let body = response.into_string().await.unwrap();
+        body.split("\n\n")
+            .filter(|message| message.contains("data:"))
+            .map(|message| {
+                let field = |name: &str| {
+                    message
+                        .lines()
+                        .find_map(|line| line.strip_prefix(name))
+                        .unwrap()
+                        .trim()
+                        .to_string()
+                };
+                (field("id:"), field("event:"), serde_json::from_str(&field("data:")).unwrap())
+            })
+            .collect()
+    }
+
+    fn server(client: &Client) -> Header<'static> {
+        bearer(client.rocket().state::<Box<ServerState>>().unwrap(), Role::Server)
+    }
+
+    fn manager(client: &Client) -> Header<'static> {
+        bearer(client.rocket().state::<Box<ServerState>>().unwrap(), Role::Manager)
+    }
+
+    #[rocket::async_test]
+    async fn test_events_resume_after_last_event_id() {
+        let client = client().await;
+        let pizza = order(&client, 1).await;
+        let salad = order(&client, 2).await;
+        client
+            .patch(format!("/tables/1/orders/{}/status", pizza))
+            .header(server(&client))
+            .header(ContentType::JSON)
+            .body(r#"{"status": "cooking"}"#)
+            .dispatch()
+            .await;
+        client.delete(format!("/tables/2/orders/{}", salad)).header(server(&client)).dispatch().await;
+
+        let events = read_stream(&client, "/events", Some("0")).await;
+        let summary: Vec<(&str, &str)> = events.iter().map(|(id, name, _)| (id.as_str(), name.as_str())).collect();
+        assert_eq!(
+            summary,
+            vec![("1", "order_created"), ("2", "order_created"), ("3", "order_status_changed"), ("4", "order_deleted")]
+        );
+        assert_eq!(events[0].2["order"]["id"], pizza.as_str());
+        assert_eq!(events[0].2["type"], "order_created");
+        assert_eq!((events[2].2["from"].as_str(), events[2].2["to"].as_str()), (Some("placed"), Some("cooking")));
+        assert_eq!(events[3].2["table_number"], 2);
+
+        let resumed = read_stream(&client, "/events", Some("2")).await;
+        let ids: Vec<&str> = resumed.iter().map(|(id, _, _)| id.as_str()).collect();
+        assert_eq!(ids, vec!["3", "4"]);
+        assert!(read_stream(&client, "/events", None).await.is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn test_table_events_only_cover_that_table() {
+        let client = client().await;
+        order(&client, 1).await;
+        let salad = order(&client, 2).await;
+
+        let events = read_stream(&client, "/tables/2/events", Some("0")).await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].2["order"]["id"], salad.as_str());
+
+        let response = client.get("/tables/9/events").header(manager(&client)).dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+    }
+}
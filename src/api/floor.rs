@@ -0,0 +1,236 @@
+use crate::api::auth::{authorize, STAFF};
+use crate::api::error::ApiError;
+use crate::api::tables;
+use crate::domain::auth::{Principal, Role};
+use crate::domain::floor::{self, Session, Table};
+use crate::domain::tables::{get_session_orders, Decoding};
+use crate::protocol::protocol::{SessionOrdersResponse, SessionResponse, TableInput, TableResponse};
+use crate::ServerState;
+use rocket::{get, post, serde::json::{self, Json}, State};
+use uuid::Uuid;
+
+fn to_response(table: Table) -> TableResponse {
+    TableResponse {
+        number: table.number,
+        seats: table.seats,
+        section: table.section,
+        status: table.status.to_string(),
+        session_id: table.session_id,
+    }
+}
+
+fn session_response(session: Session) -> SessionResponse {
+    SessionResponse {
+        id: session.id,
+        table_number: session.table_number,
+        opened_at: session.opened_at,
+        closed_at: session.closed_at,
+    }
+}
+
+#[get("/tables")]
+pub async fn get_tables(
+    caller: Result<Principal, ApiError>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<Vec<TableResponse>>, ApiError> {
+    authorize(caller, STAFF)?;
+    let tables = floor::get_floor(state).await?;
+    Ok(Json(tables.into_iter().map(to_response).collect()))
+}
+
+#[get("/tables/<number>")]
+pub async fn get_table(
+    number: u64,
+    caller: Result<Principal, ApiError>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<TableResponse>, ApiError> {
+    authorize(caller, STAFF)?;
+    Ok(Json(to_response(floor::get_table(number, state).await?)))
+}
+
+#[post("/tables", data = "<table_data>")]
+pub async fn add_table(
+    caller: Result<Principal, ApiError>,
+    table_data: Result<Json<TableInput>, json::Error<'_>>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<TableResponse>, ApiError> {
+    authorize(caller, &[Role::Server])?;
+    let table = floor::register_table(table_data?.into_inner(), state).await?;
+    Ok(Json(to_response(table)))
+}
+
+#[post("/tables/<number>/open")]
+pub async fn open_table(
+    number: u64,
+    caller: Result<Principal, ApiError>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<TableResponse>, ApiError> {
+    authorize(caller, &[Role::Server])?;
+    Ok(Json(to_response(floor::open_table(number, state).await?)))
+}
+
+#[post("/tables/<number>/close")]
+pub async fn close_table(
+    number: u64,
+    caller: Result<Principal, ApiError>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<TableResponse>, ApiError> {
+    authorize(caller, &[Role::Server])?;
+    Ok(Json(to_response(floor::close_table(number, state).await?)))
+}
+
+#[get("/tables/<number>/sessions")]
+pub async fn get_table_sessions(
+    number: u64,
+    caller: Result<Principal, ApiError>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<Vec<SessionResponse>>, ApiError> {
+    authorize(caller, STAFF)?;
+    let sessions = floor::get_sessions(number, state).await?;
+    Ok(Json(sessions.into_iter().map(session_response).collect()))
+}
+
+#[get("/tables/<number>/sessions/<session_id>")]
+pub async fn get_table_session(
+    number: u64,
+    session_id: &str,
+    caller: Result<Principal, ApiError>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<SessionOrdersResponse>, ApiError> {
+    authorize(caller, STAFF)?;
+    let session_id = Uuid::parse_str(session_id).map_err(|e| ApiError::invalid_id("session_id", e))?;
+    let session = floor::get_session(number, session_id, state).await?;
+    let orders = get_session_orders(number, session_id, Decoding::SkipCorrupt, state).await?;
+    Ok(Json(SessionOrdersResponse {
+        session: session_response(session),
+        orders: orders.into_iter().map(tables::to_response).collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::auth::bearer;
+    use crate::api::error::RequestIdFairing;
+    use crate::db::memory::InMemoryStorage;
+    use rocket::http::{ContentType, Header, Status};
+    use rocket::local::blocking::Client;
+    use rocket::routes;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    fn client() -> Client {
+        let rocket = rocket::build()
+            .attach(RequestIdFairing)
+            .register("/", crate::api::catchers())
+            .manage(Box::new(ServerState::new(Arc::new(InMemoryStorage::new()))))
+            .mount("/", routes![
+                get_tables,
+                get_table,
+                add_table,
+                open_table,
+                close_table,
+                get_table_sessions,
+                get_table_session,
+            ]);
+        Client::tracked(rocket).expect("valid rocket instance")
+    }
+
+    fn register(client: &Client, number: u64, section: &str) -> Status {
+        client
+            .post("/tables")
+            .header(manager(client))
+            .header(ContentType::JSON)
+            .body(json!({ "number": number, "seats": 4, "section": section }).to_string())
+            .dispatch()
+            .status()
+    }
+
+    fn manager(client: &Client) -> Header<'static> {
+        bearer(client.rocket().state::<Box<ServerState>>().unwrap(), Role::Manager)
+    }
+
+    #[test]
+    fn test_floor_plan() {
+        let client = client();
+        assert_eq!(register(&client, 2, "Patio"), Status::Ok);
+        assert_eq!(register(&client, 1, "Bar"), Status::Ok);
+        assert_eq!(register(&client, 1, "Bar"), Status::Conflict);
+
+        let response = client.post("/tables/2/open").header(manager(&client)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["status"], "open");
+        let session_id = body["session_id"].clone();
+
+        let body: Value = client.get("/tables").header(manager(&client)).dispatch().into_json().unwrap();
+        assert_eq!(
+            body,
+            json!([
+                { "number": 1, "seats": 4, "section": "Bar", "status": "closed", "session_id": null },
+                { "number": 2, "seats": 4, "section": "Patio", "status": "open", "session_id": session_id },
+            ])
+        );
+
+        assert_eq!(client.post("/tables/2/open").header(manager(&client)).dispatch().status(), Status::Conflict);
+        assert_eq!(client.post("/tables/2/close").header(manager(&client)).dispatch().status(), Status::Ok);
+        let body: Value = client.get("/tables/2").header(manager(&client)).dispatch().into_json().unwrap();
+        assert_eq!(body["status"], "closed");
+    }
+
+    #[test]
+    fn test_session_history() {
+        let client = client();
+        register(&client, 1, "Bar");
+        let opened: Value = client.post("/tables/1/open").header(manager(&client)).dispatch().into_json().unwrap();
+        let session_id = opened["session_id"].as_str().unwrap().to_string();
+        client.post("/tables/1/close").header(manager(&client)).dispatch();
+        client.post("/tables/1/open").header(manager(&client)).dispatch();
+
+        let body: Value = client.get("/tables/1/sessions").header(manager(&client)).dispatch().into_json().unwrap();
+        assert_eq!(body.as_array().unwrap().len(), 2);
+        assert_eq!(body[0]["id"], session_id.as_str());
+        assert!(body[0]["closed_at"].is_string());
+        assert!(body[1]["closed_at"].is_null());
+
+        let response = client.get(format!("/tables/1/sessions/{}", session_id)).header(manager(&client)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["table_number"], 1);
+        assert_eq!(body["orders"], json!([]));
+
+        let response = client.get(format!("/tables/2/sessions/{}", session_id)).header(manager(&client)).dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        let response = client.get("/tables/1/sessions/not-a-uuid").header(manager(&client)).dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn test_unknown_and_invalid_tables() {
+        let client = client();
+        let response = client.post("/tables/7/open").header(manager(&client)).dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["message"], "Table 7 is not registered");
+        assert_eq!(client.get("/tables/7").header(manager(&client)).dispatch().status(), Status::NotFound);
+
+        assert_eq!(register(&client, 3, " "), Status::UnprocessableEntity);
+        let response = client
+            .post("/tables")
+            .header(manager(&client))
+            .header(ContentType::JSON)
+            .body(r#"{"number": 3}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+    }
+
+    #[test]
+    fn test_floor_needs_staff_credentials() {
+        let client = client();
+        assert_eq!(client.get("/tables").dispatch().status(), Status::Unauthorized);
+        register(&client, 1, "Bar");
+        let kitchen = bearer(client.rocket().state::<Box<ServerState>>().unwrap(), Role::Kitchen);
+        assert_eq!(client.post("/tables/1/open").header(kitchen.clone()).dispatch().status(), Status::Forbidden);
+        assert_eq!(client.get("/tables/1").header(kitchen).dispatch().status(), Status::Ok);
+    }
+}
@@ -0,0 +1,510 @@
+use crate::api::auth::{authorize, STAFF};
+use crate::api::error::ApiError;
+use crate::api::tables;
+use crate::db::StorageError;
+use crate::domain::auth::{Principal, Role};
+use crate::domain::events::{self, Event};
+use crate::domain::kitchen::{self, Ticket};
+use crate::domain::tables::{change_order_status, Station};
+use crate::protocol::protocol::{KitchenCommand, KitchenMessage, OrderStatusInput, TicketResponse};
+use crate::ServerState;
+use chrono::Utc;
+use rocket::futures::{SinkExt, StreamExt};
+use rocket::http::Status;
+use rocket::tokio::select;
+use rocket::tokio::time::{interval_at, Duration, Instant};
+use rocket::{get, post, serde::json::Json, Shutdown, State};
+use rocket_ws::{stream::DuplexStream, Channel, Message, WebSocket};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// How often the kitchen socket sends a heartbeat and pings the display.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// A display that sends nothing, not even a pong, for this many heartbeats in a row
+/// is disconnected.
+const MISSED_HEARTBEATS: u32 = 3;
+
+fn to_response(ticket: Ticket) -> TicketResponse {
+    TicketResponse {
+        id: ticket.id,
+        table_number: ticket.table_number,
+        fired_at: ticket.fired_at,
+        orders: ticket.orders.into_iter().map(tables::to_response).collect(),
+    }
+}
+
+#[get("/kitchen/queue?<station>")]
+pub async fn get_kitchen_queue(
+    station: Option<&str>,
+    caller: Result<Principal, ApiError>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<Vec<TicketResponse>>, ApiError> {
+    authorize(caller, STAFF)?;
+    let station = station.map(str::parse).transpose()?;
+    let tickets = kitchen::get_queue(station.as_slice(), state).await?;
+    Ok(Json(tickets.into_iter().map(to_response).collect()))
+}
+
+#[post("/kitchen/stations/<station>/tickets/<ticket_id>/bump")]
+pub async fn bump_ticket(
+    station: &str,
+    ticket_id: &str,
+    caller: Result<Principal, ApiError>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<TicketResponse>, ApiError> {
+    authorize(caller, &[Role::Kitchen])?;
+    let ticket_id = Uuid::parse_str(ticket_id).map_err(|e| ApiError::invalid_id("ticket_id", e))?;
+    let ticket = kitchen::bump_ticket(station.parse()?, ticket_id, state).await?;
+    Ok(Json(to_response(ticket)))
+}
+
+fn error_message(error: ApiError) -> KitchenMessage {
+    if let Some(cause) = &error.cause {
+        rocket::error!("Kitchen socket command failed: {}", cause);
+    }
+    KitchenMessage::Error {
+        code: error.code.to_string(),
+        message: error.message,
+    }
+}
+
+async fn run_command(
+    command: KitchenCommand,
+    caller: &Principal,
+    stations: &mut Option<Vec<Station>>,
+    state: &State<Box<ServerState>>,
+) -> Result<KitchenMessage, StorageError> {
+    match command {
+        KitchenCommand::Subscribe { stations: names } => {
+            let subscribed = names.iter().map(|name| name.parse()).collect::<Result<Vec<Station>, _>>()?;
+            let tickets = kitchen::get_queue(&subscribed, state).await?;
+            let covered = Station::ALL
+                .into_iter()
+                .filter(|station| kitchen::covers(&subscribed, *station))
+                .map(|station| station.to_string())
+                .collect();
+            *stations = Some(subscribed);
+            Ok(KitchenMessage::Queue {
+                stations: covered,
+                tickets: tickets.into_iter().map(to_response).collect(),
+            })
+        }
+        KitchenCommand::Bump { station, ticket_id } => {
+            caller.require(&[Role::Kitchen])?;
+            let ticket = kitchen::bump_ticket(station.parse()?, ticket_id, state).await?;
+            Ok(KitchenMessage::Bumped { ticket: to_response(ticket) })
+        }
+        KitchenCommand::SetStatus { table_number, order_id, status } => {
+            caller.require(&[Role::Server, Role::Kitchen])?;
+            let order = change_order_status(table_number, order_id, OrderStatusInput { status }, state).await?;
+            Ok(KitchenMessage::Order { order: tables::to_response(order) })
+        }
+        KitchenCommand::Ping => Ok(KitchenMessage::Pong),
+    }
+}
+
+async fn handle_text(
+    text: &str,
+    caller: &Principal,
+    stations: &mut Option<Vec<Station>>,
+    state: &State<Box<ServerState>>,
+) -> KitchenMessage {
+    let command = match serde_json::from_str(text) {
+        Ok(command) => command,
+        Err(e) => {
+            return error_message(ApiError::new(Status::BadRequest, "invalid_message", e.to_string()));
+        }
+    };
+    run_command(command, caller, stations, state)
+        .await
+        .unwrap_or_else(|e| error_message(e.into()))
+}
+
+/// The update a subscribed display needs after `event`, if it touches its stations.
+async fn ticket_update(event: Event, stations: &[Station], state: &State<Box<ServerState>>) -> Option<KitchenMessage> {
+    let update = async {
+        let Some(ticket_id) = kitchen::affected_ticket(&event, stations, state).await? else {
+            return Ok(None);
+        };
+        Ok(Some(match kitchen::get_ticket(ticket_id, stations, state).await? {
+            Some(ticket) => KitchenMessage::Ticket { ticket: to_response(ticket) },
+            None => KitchenMessage::TicketCleared { ticket_id },
+        }))
+    };
+    update.await.unwrap_or_else(|e: StorageError| Some(error_message(e.into())))
+}
+
+async fn run_kitchen_socket(
+    mut stream: DuplexStream,
+    caller: Principal,
+    state: &State<Box<ServerState>>,
+    mut shutdown: Shutdown,
+) -> rocket_ws::result::Result<()> {
+    let mut events = events::subscribe(None, state);
+    let mut stations: Option<Vec<Station>> = None;
+    let mut heartbeat = interval_at(Instant::now() + HEARTBEAT_INTERVAL, HEARTBEAT_INTERVAL);
+    let mut missed = 0;
+    // Tickets last reported cleared, so the events that finish one ticket report it once.
+    let mut cleared = HashSet::new();
+    loop {
+        let reply = select! {
+            message = stream.next() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    missed = 0;
+                    Some(handle_text(&text, &caller, &mut stations, state).await)
+                }
+                Some(Ok(Message::Binary(_))) => {
+                    missed = 0;
+                    Some(error_message(ApiError::new(
+                        Status::BadRequest,
+                        "invalid_message",
+                        "Messages must be JSON text",
+                    )))
+                }
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(_)) => {
+                    missed = 0;
+                    None
+                }
+                Some(Err(e)) => return Err(e),
+            },
+            Some(event) = events.next() => match &stations {
+                Some(stations) => match ticket_update(event, stations, state).await {
+                    Some(KitchenMessage::TicketCleared { ticket_id }) if !cleared.insert(ticket_id) => None,
+                    Some(KitchenMessage::Ticket { ticket }) => {
+                        cleared.remove(&ticket.id);
+                        Some(KitchenMessage::Ticket { ticket })
+                    }
+                    update => update,
+                },
+                None => None,
+            },
+            _ = heartbeat.tick() => {
+                missed += 1;
+                if missed > MISSED_HEARTBEATS {
+                    break;
+                }
+                stream.send(Message::Ping(Vec::new())).await?;
+                Some(KitchenMessage::Heartbeat { at: Utc::now() })
+            }
+            _ = &mut shutdown => break,
+        };
+        if let Some(reply) = reply {
+            let text = serde_json::to_string(&reply).expect("kitchen messages serialize");
+            stream.send(Message::Text(text)).await?;
+        }
+    }
+    stream.close(None).await
+}
+
+/// Two-way channel for kitchen displays, speaking `KitchenCommand` and
+/// `KitchenMessage` as JSON text. Nothing but heartbeats is sent until the display
+/// subscribes. Any member of staff may connect; each command checks the caller's
+/// role like the matching HTTP route.
+#[get("/kitchen/ws")]
+pub fn kitchen_socket<'r>(
+    ws: WebSocket,
+    caller: Result<Principal, ApiError>,
+    state: &'r State<Box<ServerState>>,
+    shutdown: Shutdown,
+) -> Result<Channel<'r>, ApiError> {
+    let caller = authorize(caller, STAFF)?;
+    Ok(ws.channel(move |stream| Box::pin(run_kitchen_socket(stream, caller, state, shutdown))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::auth::{bearer, AUTHORIZATION_HEADER};
+    use crate::api::error::RequestIdFairing;
+    use crate::api::floor;
+    use crate::db::{memory::InMemoryStorage, MenuItem, Station, Storage};
+    use crate::domain::auth::Role;
+    use rocket::http::{ContentType, Header, Status};
+    use rocket::local::blocking::Client;
+    use rocket::fairing::AdHoc;
+    use rocket::tokio::{self, sync::oneshot, time::timeout};
+    use rocket::{routes, Build, Rocket};
+    use tokio_tungstenite::tungstenite::{self, client::IntoClientRequest};
+    use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    fn rocket() -> Rocket<Build> {
+        let db = Arc::new(InMemoryStorage::new());
+        for (name, station) in [("Steak", Station::Grill), ("Fries", Station::Fryer), ("Lemonade", Station::Bar)] {
+            db.add_menu_item(MenuItem {
+                id: Uuid::new_v4(),
+                name: name.to_string(),
+                category: "Mains".to_string(),
+                price: 1000,
+                prep_time_minutes: 12,
+                available: true,
+                station,
+            })
+            .unwrap();
+        }
+        rocket::build()
+            .attach(RequestIdFairing)
+            .register("/", crate::api::catchers())
+            .manage(Box::new(ServerState::new(db)))
+            .mount("/", routes![
+                get_kitchen_queue,
+                bump_ticket,
+                kitchen_socket,
+                floor::add_table,
+                floor::open_table,
+                tables::add_table_orders,
+                tables::get_table_orders,
+                tables::update_table_order_status,
+            ])
+    }
+
+    fn client() -> Client {
+        Client::tracked(rocket()).expect("valid rocket instance")
+    }
+
+    fn order(client: &Client, number: u64, items: &[&str]) -> Vec<String> {
+        client
+            .post("/tables")
+            .header(manager(client))
+            .header(ContentType::JSON)
+            .body(json!({ "number": number, "seats": 4, "section": "Main" }).to_string())
+            .dispatch();
+        client.post(format!("/tables/{}/open", number)).header(manager(client)).dispatch();
+        let orders: Vec<Value> = items.iter().map(|item| json!({ "menu_item": item })).collect();
+        client
+            .post(format!("/tables/{}/orders", number))
+            .header(server(client))
+            .header(ContentType::JSON)
+            .body(json!({ "orders": orders }).to_string())
+            .dispatch()
+            .into_json()
+            .unwrap()
+    }
+
+    fn queue(client: &Client, uri: &str) -> Vec<Value> {
+        let response = client.get(uri).header(manager(client)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        response.into_json().unwrap()
+    }
+
+    fn items(ticket: &Value) -> Vec<(&str, &str)> {
+        ticket["orders"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|order| (order["menu_item"].as_str().unwrap(), order["station"].as_str().unwrap()))
+            .collect()
+    }
+
+    fn server(client: &Client) -> Header<'static> {
+        bearer(client.rocket().state::<Box<ServerState>>().unwrap(), Role::Server)
+    }
+
+    fn manager(client: &Client) -> Header<'static> {
+        bearer(client.rocket().state::<Box<ServerState>>().unwrap(), Role::Manager)
+    }
+
+    #[test]
+    fn test_queue_lists_tickets_in_fire_order() {
+        let client = client();
+        order(&client, 4, &["Steak", "Fries", "Lemonade"]);
+        let later = order(&client, 2, &["Steak"]);
+        let cancelled = order(&client, 7, &["Fries"]);
+        client
+            .patch(format!("/tables/7/orders/{}/status", cancelled[0]))
+            .header(server(&client))
+            .header(ContentType::JSON)
+            .body(r#"{"status": "cancelled"}"#)
+            .dispatch();
+
+        let tickets = queue(&client, "/kitchen/queue");
+        let tables: Vec<u64> = tickets.iter().map(|ticket| ticket["table_number"].as_u64().unwrap()).collect();
+        assert_eq!(tables, vec![4, 2]);
+        assert_eq!(items(&tickets[0]), vec![("Steak", "grill"), ("Fries", "fryer"), ("Lemonade", "bar")]);
+        assert_eq!(tickets[1]["orders"][0]["id"], later[0].as_str());
+        assert_eq!(tickets[0]["orders"][0]["ticket_id"], tickets[0]["id"]);
+
+        let grill = queue(&client, "/kitchen/queue?station=grill");
+        assert_eq!(grill.len(), 2);
+        assert_eq!(items(&grill[0]), vec![("Steak", "grill")]);
+        assert!(queue(&client, "/kitchen/queue?station=cold").is_empty());
+
+        let response = client.get("/kitchen/queue?station=pastry").header(manager(&client)).dispatch();
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+    }
+
+    #[test]
+    fn test_bump_completes_one_station() {
+        let client = client();
+        order(&client, 4, &["Steak", "Fries", "Lemonade"]);
+        let ticket_id = queue(&client, "/kitchen/queue")[0]["id"].as_str().unwrap().to_string();
+
+        let response = client
+            .post(format!("/kitchen/stations/bar/tickets/{}/bump", ticket_id))
+            .header(manager(&client))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let bumped: Value = response.into_json().unwrap();
+        assert_eq!(items(&bumped), vec![("Lemonade", "bar")]);
+        assert_eq!(bumped["orders"][0]["status"], "ready");
+
+        assert!(queue(&client, "/kitchen/queue?station=bar").is_empty());
+        let tickets = queue(&client, "/kitchen/queue");
+        assert_eq!(items(&tickets[0]), vec![("Steak", "grill"), ("Fries", "fryer")]);
+
+        let response = client
+            .post(format!("/kitchen/stations/bar/tickets/{}/bump", ticket_id))
+            .header(manager(&client))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+
+        client.post(format!("/kitchen/stations/grill/tickets/{}/bump", ticket_id)).header(manager(&client)).dispatch();
+        client.post(format!("/kitchen/stations/fryer/tickets/{}/bump", ticket_id)).header(manager(&client)).dispatch();
+        assert!(queue(&client, "/kitchen/queue").is_empty());
+        let orders: Vec<Value> = client.get("/tables/4/orders").header(server(&client)).dispatch().into_json().unwrap();
+        assert!(orders.iter().all(|order| order["status"] == "ready"));
+    }
+
+    #[test]
+    fn test_bump_rejects_bad_ids() {
+        let client = client();
+        let response = client
+            .post("/kitchen/stations/grill/tickets/not-a-uuid/bump")
+            .header(manager(&client))
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        let response = client
+            .post(format!("/kitchen/stations/grill/tickets/{}/bump", Uuid::new_v4()))
+            .header(manager(&client))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    type Socket = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+    /// Serves `rocket()` on a free local port, returning its base URL and the
+    /// `Authorization` header values of a server and a cook.
+    async fn serve() -> (String, String, String) {
+        let (port_sender, port) = oneshot::channel();
+        let rocket = rocket()
+            .configure(rocket::Config {
+                port: 0,
+                log_level: rocket::config::LogLevel::Off,
+                ..rocket::Config::debug_default()
+            })
+            .attach(AdHoc::on_liftoff("Port", |rocket| {
+                Box::pin(async move {
+                    let _ = port_sender.send(rocket.config().port);
+                })
+            }));
+        let state = rocket.state::<Box<ServerState>>().unwrap();
+        let [server, kitchen] = [Role::Server, Role::Kitchen].map(|role| bearer(state, role).value().to_string());
+        tokio::spawn(rocket.launch());
+        (format!("127.0.0.1:{}", port.await.unwrap()), server, kitchen)
+    }
+
+    async fn place(address: &str, authorization: &str, number: u64, items: &[&str]) {
+        let http = reqwest::Client::new();
+        http.post(format!("http://{}/tables", address))
+            .header(AUTHORIZATION_HEADER, authorization)
+            .json(&json!({ "number": number, "seats": 4, "section": "Main" }))
+            .send()
+            .await
+            .unwrap();
+        http.post(format!("http://{}/tables/{}/open", address, number))
+            .header(AUTHORIZATION_HEADER, authorization)
+            .send()
+            .await
+            .unwrap();
+        let orders: Vec<Value> = items.iter().map(|item| json!({ "menu_item": item })).collect();
+        let response = http
+            .post(format!("http://{}/tables/{}/orders", address, number))
+            .header(AUTHORIZATION_HEADER, authorization)
+            .json(&json!({ "orders": orders }))
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+    }
+
+    async fn connect(address: &str, authorization: Option<&str>) -> Result<Socket, tungstenite::Error> {
+        let mut request = format!("ws://{}/kitchen/ws", address).into_client_request()?;
+        if let Some(authorization) = authorization {
+            request.headers_mut().insert(AUTHORIZATION_HEADER, authorization.parse().unwrap());
+        }
+        connect_async(request).await.map(|(socket, _)| socket)
+    }
+
+    async fn send(socket: &mut Socket, command: Value) {
+        socket.send(Message::Text(command.to_string())).await.unwrap();
+    }
+
+    /// The next JSON message from the server, skipping pings and heartbeats.
+    async fn receive(socket: &mut Socket) -> Value {
+        loop {
+            let message = timeout(Duration::from_secs(5), socket.next()).await.expect("a message in time");
+            if let Message::Text(text) = message.unwrap().unwrap() {
+                let message: Value = serde_json::from_str(&text).unwrap();
+                if message["type"] != "heartbeat" {
+                    return message;
+                }
+            }
+        }
+    }
+
+    #[rocket::async_test]
+    async fn test_kitchen_socket() {
+        let (address, authorization, kitchen) = serve().await;
+        let mut socket = connect(&address, Some(&kitchen)).await.unwrap();
+        send(&mut socket, json!({ "type": "ping" })).await;
+        assert_eq!(receive(&mut socket).await["type"], "pong");
+
+        place(&address, &authorization, 4, &["Steak", "Lemonade"]).await;
+        send(&mut socket, json!({ "type": "subscribe", "stations": ["bar"] })).await;
+        let queue = receive(&mut socket).await;
+        assert_eq!((queue["type"].as_str(), queue["stations"].clone()), (Some("queue"), json!(["bar"])));
+        assert_eq!(items(&queue["tickets"][0]), vec![("Lemonade", "bar")]);
+        let first = queue["tickets"][0]["id"].clone();
+
+        // Only tickets with something for the bar are pushed.
+        place(&address, &authorization, 5, &["Steak"]).await;
+        place(&address, &authorization, 6, &["Lemonade"]).await;
+        let update = receive(&mut socket).await;
+        assert_eq!((update["type"].as_str(), update["ticket"]["table_number"].as_u64()), (Some("ticket"), Some(6)));
+        let order_id = update["ticket"]["orders"][0]["id"].clone();
+
+        send(&mut socket, json!({ "type": "bump", "station": "bar", "ticket_id": first })).await;
+        let bumped = receive(&mut socket).await;
+        assert_eq!((bumped["type"].as_str(), bumped["ticket"]["orders"][0]["status"].as_str()), (Some("bumped"), Some("ready")));
+        assert_eq!(receive(&mut socket).await, json!({ "type": "ticket_cleared", "ticket_id": first }));
+
+        send(&mut socket, json!({ "type": "set_status", "table_number": 6, "order_id": order_id, "status": "cooking" })).await;
+        assert_eq!(receive(&mut socket).await["order"]["status"], "cooking");
+        let update = receive(&mut socket).await;
+        assert_eq!(update["ticket"]["orders"][0]["status"], "cooking");
+
+        send(&mut socket, json!({ "type": "subscribe", "stations": ["pastry"] })).await;
+        assert_eq!(receive(&mut socket).await["code"], "validation_failed");
+        send(&mut socket, json!({ "type": "dance" })).await;
+        assert_eq!(receive(&mut socket).await["code"], "invalid_message");
+    }
+
+    #[rocket::async_test]
+    async fn test_kitchen_socket_checks_roles() {
+        let (address, server, _) = serve().await;
+        match connect(&address, None).await {
+            Err(tungstenite::Error::Http(response)) => assert_eq!(response.status(), 401),
+            other => panic!("expected a 401, got {:?}", other.map(|_| ())),
+        }
+
+        place(&address, &server, 4, &["Lemonade"]).await;
+        let mut socket = connect(&address, Some(&server)).await.unwrap();
+        send(&mut socket, json!({ "type": "subscribe", "stations": [] })).await;
+        let ticket_id = receive(&mut socket).await["tickets"][0]["id"].clone();
+        send(&mut socket, json!({ "type": "bump", "station": "bar", "ticket_id": ticket_id })).await;
+        assert_eq!(receive(&mut socket).await["code"], "forbidden");
+    }
+}
@@ -0,0 +1,195 @@
+use crate::api::auth::{authorize, STAFF};
+use crate::api::error::ApiError;
+use crate::domain::auth::{Principal, Role};
+use crate::domain::menu::{self, MenuItem};
+use crate::protocol::protocol::{MenuItemInput, MenuItemResponse};
+use crate::ServerState;
+use rocket::{delete, get, post, put, serde::json::{self, Json}, State};
+use uuid::Uuid;
+
+fn to_response(item: MenuItem) -> MenuItemResponse {
+    MenuItemResponse {
+        id: item.id,
+        name: item.name,
+        category: item.category,
+        price: item.price,
+        prep_time_minutes: item.prep_time_minutes,
+        available: item.available,
+        station: item.station.to_string(),
+    }
+}
+
+fn parse_menu_item_id(menu_item_id: &str) -> Result<Uuid, ApiError> {
+    Uuid::parse_str(menu_item_id).map_err(|e| ApiError::invalid_id("menu_item_id", e))
+}
+
+#[get("/menu")]
+pub async fn get_menu_items(
+    caller: Result<Principal, ApiError>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<Vec<MenuItemResponse>>, ApiError> {
+    authorize(caller, STAFF)?;
+    let items = menu::get_menu(state).await?;
+    Ok(Json(items.into_iter().map(to_response).collect()))
+}
+
+#[get("/menu/<menu_item_id>")]
+pub async fn get_menu_item(
+    menu_item_id: &str,
+    caller: Result<Principal, ApiError>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<MenuItemResponse>, ApiError> {
+    authorize(caller, STAFF)?;
+    let menu_item_id = parse_menu_item_id(menu_item_id)?;
+    Ok(Json(to_response(menu::get_menu_item(menu_item_id, state).await?)))
+}
+
+#[post("/menu", data = "<item_data>")]
+pub async fn add_menu_item(
+    caller: Result<Principal, ApiError>,
+    item_data: Result<Json<MenuItemInput>, json::Error<'_>>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<MenuItemResponse>, ApiError> {
+    authorize(caller, &[Role::Manager])?;
+    let item = menu::add_menu_item(item_data?.into_inner(), state).await?;
+    Ok(Json(to_response(item)))
+}
+
+#[put("/menu/<menu_item_id>", data = "<item_data>")]
+pub async fn update_menu_item(
+    menu_item_id: &str,
+    caller: Result<Principal, ApiError>,
+    item_data: Result<Json<MenuItemInput>, json::Error<'_>>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<MenuItemResponse>, ApiError> {
+    authorize(caller, &[Role::Manager])?;
+    let menu_item_id = parse_menu_item_id(menu_item_id)?;
+    let item = menu::update_menu_item(menu_item_id, item_data?.into_inner(), state).await?;
+    Ok(Json(to_response(item)))
+}
+
+#[delete("/menu/<menu_item_id>")]
+pub async fn delete_menu_item(
+    menu_item_id: &str,
+    caller: Result<Principal, ApiError>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<()>, ApiError> {
+    authorize(caller, &[Role::Manager])?;
+    let menu_item_id = parse_menu_item_id(menu_item_id)?;
+    menu::remove_menu_item(menu_item_id, state).await?;
+    Ok(Json(()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::auth::bearer;
+    use crate::api::error::RequestIdFairing;
+    use crate::db::memory::InMemoryStorage;
+    use rocket::http::{ContentType, Header, Status};
+    use rocket::local::blocking::Client;
+    use rocket::routes;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    fn client() -> Client {
+        let rocket = rocket::build()
+            .attach(RequestIdFairing)
+            .register("/", crate::api::catchers())
+            .manage(Box::new(ServerState::new(Arc::new(InMemoryStorage::new()))))
+            .mount("/", routes![
+                get_menu_items,
+                get_menu_item,
+                add_menu_item,
+                update_menu_item,
+                delete_menu_item,
+            ]);
+        Client::tracked(rocket).expect("valid rocket instance")
+    }
+
+    fn pizza() -> Value {
+        json!({
+            "name": "Pizza",
+            "category": "Mains",
+            "price": 1250,
+            "prep_time_minutes": 12
+        })
+    }
+
+    fn manager(client: &Client) -> Header<'static> {
+        bearer(client.rocket().state::<Box<ServerState>>().unwrap(), Role::Manager)
+    }
+
+    #[test]
+    fn test_menu_crud() {
+        let client = client();
+        let response = client
+            .post("/menu")
+            .header(manager(&client))
+            .header(ContentType::JSON)
+            .body(pizza().to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let created: Value = response.into_json().unwrap();
+        assert_eq!(created["available"], true);
+        let id = created["id"].as_str().unwrap().to_string();
+
+        let mut update = pizza();
+        update["price"] = json!(1400);
+        update["available"] = json!(false);
+        let response = client
+            .put(format!("/menu/{}", id))
+            .header(manager(&client))
+            .header(ContentType::JSON)
+            .body(update.to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let item: Value = client.get(format!("/menu/{}", id)).header(manager(&client)).dispatch().into_json().unwrap();
+        assert_eq!(item["price"], 1400);
+        assert_eq!(item["available"], false);
+
+        let items: Value = client.get("/menu").header(manager(&client)).dispatch().into_json().unwrap();
+        assert_eq!(items.as_array().unwrap().len(), 1);
+
+        let response = client.delete(format!("/menu/{}", id)).header(manager(&client)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let response = client.get(format!("/menu/{}", id)).header(manager(&client)).dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn test_duplicate_menu_item_conflicts() {
+        let client = client();
+        client.post("/menu").header(manager(&client)).header(ContentType::JSON).body(pizza().to_string()).dispatch();
+
+        let mut duplicate = pizza();
+        duplicate["name"] = json!("PIZZA");
+        let response = client
+            .post("/menu")
+            .header(manager(&client))
+            .header(ContentType::JSON)
+            .body(duplicate.to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::Conflict);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["code"], "conflict");
+    }
+
+    #[test]
+    fn test_invalid_menu_item_is_rejected() {
+        let client = client();
+        let mut blank = pizza();
+        blank["name"] = json!("");
+        let response = client
+            .post("/menu")
+            .header(manager(&client))
+            .header(ContentType::JSON)
+            .body(blank.to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+
+        let response = client.get("/menu/not-a-uuid").header(manager(&client)).dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+}
@@ -1 +1,21 @@
+pub mod auth;
+pub mod billing;
+pub mod error;
+pub mod events;
+pub mod floor;
+pub mod kitchen;
+pub mod menu;
 pub mod tables;
+pub mod webhooks;
+
+use rocket::{catchers, Catcher};
+
+/// Catchers that render every unhandled error status as a JSON `ErrorResponse`.
+pub fn catchers() -> Vec<Catcher> {
+    catchers![
+        error::not_found,
+        error::unprocessable_entity,
+        error::internal_error,
+        error::default_catcher,
+    ]
+}
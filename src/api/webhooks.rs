@@ -0,0 +1,305 @@
+use crate::api::auth::authorize;
+use crate::api::error::ApiError;
+use crate::api::events::to_response as event_response;
+use crate::domain::auth::{Principal, Role};
+use crate::domain::events::Event;
+use crate::domain::webhooks::{self, DeadLetter, Webhook};
+use crate::protocol::protocol::{DeadLetterResponse, WebhookInput, WebhookResponse};
+use crate::ServerState;
+use rocket::fairing::AdHoc;
+use rocket::tokio;
+use rocket::{delete, get, post, serde::json::{self, Json}, State};
+use uuid::Uuid;
+
+fn to_response(webhook: Webhook) -> WebhookResponse {
+    WebhookResponse {
+        id: webhook.id,
+        secret: None,
+        url: webhook.url,
+        events: webhook.events,
+        created_at: webhook.created_at,
+    }
+}
+
+fn dead_letter_response(dead_letter: DeadLetter) -> DeadLetterResponse {
+    DeadLetterResponse {
+        id: dead_letter.id,
+        webhook_id: dead_letter.webhook_id,
+        event_id: dead_letter.event_id,
+        event: dead_letter.event,
+        payload: dead_letter.payload,
+        attempts: dead_letter.attempts,
+        last_error: dead_letter.last_error,
+        failed_at: dead_letter.failed_at,
+    }
+}
+
+/// The body posted to webhooks: the event as the event streams send it.
+pub fn payload(event: Event) -> String {
+    json::to_string(&event_response(event)).expect("events always serialize")
+}
+
+/// Fairing that starts delivering order events to webhooks once the server is up,
+/// and stops when it shuts down.
+pub fn delivery() -> AdHoc {
+    AdHoc::on_liftoff("Webhook delivery", |rocket| {
+        Box::pin(async move {
+            let state = rocket.state::<Box<ServerState>>().expect("ServerState is managed");
+            let subscription = state.events.subscribe(None, None);
+            tokio::spawn(state.webhooks.clone().run(subscription, rocket.shutdown(), payload));
+        })
+    })
+}
+
+fn parse_webhook_id(webhook_id: &str) -> Result<Uuid, ApiError> {
+    Uuid::parse_str(webhook_id).map_err(|e| ApiError::invalid_id("webhook_id", e))
+}
+
+#[get("/webhooks")]
+pub async fn get_webhooks(
+    caller: Result<Principal, ApiError>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<Vec<WebhookResponse>>, ApiError> {
+    authorize(caller, &[Role::Manager])?;
+    let webhooks = webhooks::get_webhooks(state).await?;
+    Ok(Json(webhooks.into_iter().map(to_response).collect()))
+}
+
+#[get("/webhooks/<webhook_id>")]
+pub async fn get_webhook(
+    webhook_id: &str,
+    caller: Result<Principal, ApiError>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<WebhookResponse>, ApiError> {
+    authorize(caller, &[Role::Manager])?;
+    let webhook_id = parse_webhook_id(webhook_id)?;
+    Ok(Json(to_response(webhooks::get_webhook(webhook_id, state).await?)))
+}
+
+#[post("/webhooks", data = "<webhook_data>")]
+pub async fn add_webhook(
+    caller: Result<Principal, ApiError>,
+    webhook_data: Result<Json<WebhookInput>, json::Error<'_>>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<WebhookResponse>, ApiError> {
+    authorize(caller, &[Role::Manager])?;
+    let webhook = webhooks::add_webhook(webhook_data?.into_inner(), state).await?;
+    let secret = state.webhooks.signer().secret(webhook.id);
+    Ok(Json(WebhookResponse { secret: Some(secret), ..to_response(webhook) }))
+}
+
+#[delete("/webhooks/<webhook_id>")]
+pub async fn delete_webhook(
+    webhook_id: &str,
+    caller: Result<Principal, ApiError>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<()>, ApiError> {
+    authorize(caller, &[Role::Manager])?;
+    let webhook_id = parse_webhook_id(webhook_id)?;
+    webhooks::remove_webhook(webhook_id, state).await?;
+    Ok(Json(()))
+}
+
+#[get("/webhooks/dead-letters")]
+pub async fn get_dead_letters(
+    caller: Result<Principal, ApiError>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<Vec<DeadLetterResponse>>, ApiError> {
+    authorize(caller, &[Role::Manager])?;
+    let dead_letters = webhooks::get_dead_letters(state).await?;
+    Ok(Json(dead_letters.into_iter().map(dead_letter_response).collect()))
+}
+
+#[delete("/webhooks/dead-letters/<dead_letter_id>")]
+pub async fn delete_dead_letter(
+    dead_letter_id: &str,
+    caller: Result<Principal, ApiError>,
+    state: &State<Box<ServerState>>,
+) -> Result<Json<()>, ApiError> {
+    authorize(caller, &[Role::Manager])?;
+    let dead_letter_id = Uuid::parse_str(dead_letter_id).map_err(|e| ApiError::invalid_id("dead_letter_id", e))?;
+    webhooks::remove_dead_letter(dead_letter_id, state).await?;
+    Ok(Json(()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::auth::bearer;
+    use crate::api::error::RequestIdFairing;
+    use crate::api::{floor, tables};
+    use crate::db::{memory::InMemoryStorage, DeadLetter, MenuItem, Station, Storage};
+    use crate::domain::auth::Role;
+    use crate::domain::webhooks::tests::StubReceiver;
+    use chrono::Utc;
+    use rocket::http::{ContentType, Header, Status};
+    use rocket::local::asynchronous::Client;
+    use rocket::routes;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    async fn client(db: Arc<InMemoryStorage>) -> Client {
+        let rocket = rocket::build()
+            .attach(RequestIdFairing)
+            .attach(delivery())
+            .register("/", crate::api::catchers())
+            .manage(Box::new(ServerState::new(db)))
+            .mount("/", routes![
+                get_webhooks,
+                get_webhook,
+                add_webhook,
+                delete_webhook,
+                get_dead_letters,
+                delete_dead_letter,
+                floor::add_table,
+                floor::open_table,
+                tables::add_table_orders,
+            ]);
+        Client::tracked(rocket).await.expect("valid rocket instance")
+    }
+
+    async fn add(client: &Client, body: Value) -> (Status, Value) {
+        let response = client
+            .post("/webhooks")
+            .header(manager(client))
+            .header(ContentType::JSON)
+            .body(body.to_string())
+            .dispatch()
+            .await;
+        (response.status(), response.into_json().await.unwrap())
+    }
+
+    fn server(client: &Client) -> Header<'static> {
+        bearer(client.rocket().state::<Box<ServerState>>().unwrap(), Role::Server)
+    }
+
+    fn manager(client: &Client) -> Header<'static> {
+        bearer(client.rocket().state::<Box<ServerState>>().unwrap(), Role::Manager)
+    }
+
+    #[rocket::async_test]
+    async fn test_webhook_crud() {
+        let client = client(Arc::new(InMemoryStorage::new())).await;
+        let (status, created) =
+            add(&client, json!({ "url": "https://inventory.example.com/hook", "events": ["order_created"] })).await;
+        assert_eq!(status, Status::Ok);
+        assert_eq!(created["events"], json!(["order_created"]));
+        assert_eq!(created["secret"].as_str().unwrap().len(), 64);
+        let id = created["id"].as_str().unwrap().to_string();
+
+        let mut listed = created.clone();
+        listed.as_object_mut().unwrap().remove("secret");
+        let fetched = client.get(format!("/webhooks/{}", id)).header(manager(&client)).dispatch().await;
+        assert_eq!(fetched.into_json::<Value>().await.unwrap(), listed);
+        let all = client.get("/webhooks").header(manager(&client)).dispatch().await;
+        assert_eq!(all.into_json::<Value>().await.unwrap(), json!([listed]));
+
+        let server = bearer(client.rocket().state::<Box<ServerState>>().unwrap(), Role::Server);
+        assert_eq!(client.get("/webhooks").header(server).dispatch().await.status(), Status::Forbidden);
+
+        let uri = format!("/webhooks/{}", id);
+        assert_eq!(client.delete(uri.clone()).header(manager(&client)).dispatch().await.status(), Status::Ok);
+        assert_eq!(client.get(uri).header(manager(&client)).dispatch().await.status(), Status::NotFound);
+        let response = client.get("/webhooks/not-a-uuid").header(manager(&client)).dispatch().await;
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[rocket::async_test]
+    async fn test_invalid_webhook_is_rejected() {
+        let client = client(Arc::new(InMemoryStorage::new())).await;
+        let (status, _) = add(&client, json!({ "url": "ftp://inventory.example.com/" })).await;
+        assert_eq!(status, Status::UnprocessableEntity);
+        let (status, body) = add(&client, json!({ "url": "https://example.com/", "events": ["order_eaten"] })).await;
+        assert_eq!(status, Status::UnprocessableEntity);
+        assert!(body["message"].as_str().unwrap().contains("order_eaten"));
+    }
+
+    #[rocket::async_test]
+    async fn test_dead_letters_are_listed_and_removed() {
+        let db = Arc::new(InMemoryStorage::new());
+        let dead_letter = DeadLetter {
+            id: Uuid::new_v4(),
+            webhook_id: Uuid::new_v4(),
+            event_id: 3,
+            event: "order_deleted".to_string(),
+            payload: "{}".to_string(),
+            attempts: 6,
+            last_error: "HTTP 502 Bad Gateway".to_string(),
+            failed_at: Utc::now(),
+        };
+        db.add_dead_letter(dead_letter.clone()).unwrap();
+        let client = client(db).await;
+
+        let listed: Value = client
+            .get("/webhooks/dead-letters")
+            .header(manager(&client))
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        assert_eq!(listed[0]["id"], dead_letter.id.to_string());
+        assert_eq!((listed[0]["attempts"].as_u64(), listed[0]["event_id"].as_u64()), (Some(6), Some(3)));
+
+        let uri = format!("/webhooks/dead-letters/{}", dead_letter.id);
+        assert_eq!(client.delete(uri.clone()).header(manager(&client)).dispatch().await.status(), Status::Ok);
+        assert_eq!(client.delete(uri).header(manager(&client)).dispatch().await.status(), Status::NotFound);
+    }
+
+    #[rocket::async_test]
+    async fn test_new_orders_reach_webhooks_signed() {
+        let db = Arc::new(InMemoryStorage::new());
+        db.add_menu_item(MenuItem {
+            id: Uuid::new_v4(),
+            name: "Pizza".to_string(),
+            category: "Mains".to_string(),
+            price: 1250,
+            prep_time_minutes: 12,
+            available: true,
+            station: Station::Grill,
+        })
+        .unwrap();
+        let client = client(db).await;
+        let receiver = StubReceiver::start(vec![]).await;
+        let (_, webhook) = add(&client, json!({ "url": receiver.url, "events": ["order_created"] })).await;
+
+        client
+            .post("/tables")
+            .header(manager(&client))
+            .header(ContentType::JSON)
+            .body(json!({ "number": 4, "seats": 2, "section": "Bar" }).to_string())
+            .dispatch()
+            .await;
+        client.post("/tables/4/open").header(manager(&client)).dispatch().await;
+        let response = client
+            .post("/tables/4/orders")
+            .header(server(&client))
+            .header(ContentType::JSON)
+            .body(json!({ "orders": [{ "menu_item": "Pizza" }] }).to_string())
+            .dispatch()
+            .await;
+        let ids: Vec<String> = response.into_json().await.unwrap();
+
+        for _ in 0..200 {
+            if receiver.count() > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let received = receiver.received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        let request = &received[0];
+        let body: Value = serde_json::from_str(&request.body).unwrap();
+        assert_eq!((body["type"].as_str(), body["table_number"].as_u64()), (Some("order_created"), Some(4)));
+        assert_eq!(body["order"]["id"], ids[0].as_str());
+
+        let state = client.rocket().state::<Box<ServerState>>().unwrap();
+        let webhook_id = Uuid::parse_str(webhook["id"].as_str().unwrap()).unwrap();
+        let timestamp: i64 = request.headers["x-restaurant-timestamp"].parse().unwrap();
+        assert_eq!(
+            request.headers["x-restaurant-signature"],
+            state.webhooks.signer().sign(webhook_id, timestamp, &request.body)
+        );
+    }
+}
@@ -1,14 +1,16 @@
-use tokio;
-use reqwest;
 use futures::future::join_all;
+use reqwest::header::{HeaderMap, HeaderValue};
 use serde_json::json;
 use uuid::Uuid;
+use std::env;
 use std::time::Duration;
 
 #[tokio::main]
 async fn main() {
     wait_for_server().await;
 
+    setup_menu().await;
+    setup_tables().await;
     simulate_clients().await;
 }
 
@@ -33,8 +35,52 @@ async fn wait_for_server() {
     panic!("Server failed to start after 60 seconds");
 }
 
+async fn setup_menu() {
+    let client = staff_client();
+    let base_url = "http://localhost:8000";
+
+    let items = [
+        json!({ "name": "Pizza", "category": "Mains", "price": 1250, "prep_time_minutes": 12 }),
+        json!({ "name": "Salad", "category": "Starters", "price": 750, "prep_time_minutes": 5 }),
+    ];
+    for item in items {
+        // A 409 means the item is already on the menu from an earlier run.
+        match client.post(format!("{}/menu", base_url)).json(&item).send().await {
+            Ok(response) => println!("Add menu item {} status: {}", item["name"], response.status()),
+            Err(e) => println!("Error adding menu item {}: {}", item["name"], e),
+        }
+    }
+}
+
+async fn setup_tables() {
+    let client = staff_client();
+    let base_url = "http://localhost:8000";
+
+    for number in 1..=5 {
+        let table = json!({ "number": number, "seats": 4, "section": "Main" });
+        // A 409 means the table is already registered, or already open, from an earlier run.
+        match client.post(format!("{}/tables", base_url)).json(&table).send().await {
+            Ok(response) => println!("Register table {} status: {}", number, response.status()),
+            Err(e) => println!("Error registering table {}: {}", number, e),
+        }
+        match client.post(format!("{}/tables/{}/open", base_url, number)).send().await {
+            Ok(response) => println!("Open table {} status: {}", number, response.status()),
+            Err(e) => println!("Error opening table {}: {}", number, e),
+        }
+    }
+}
+
+/// A client that sends `RESTAURANT_API_KEY` with every request. Setting up the menu
+/// needs a manager's key.
+fn staff_client() -> reqwest::Client {
+    let api_key = env::var("RESTAURANT_API_KEY").expect("RESTAURANT_API_KEY must be declared");
+    let mut headers = HeaderMap::new();
+    headers.insert("X-Api-Key", HeaderValue::from_str(&api_key).expect("RESTAURANT_API_KEY must be a valid header value"));
+    reqwest::Client::builder().default_headers(headers).build().expect("valid HTTP client")
+}
+
 async fn simulate_clients() {
-    let client = reqwest::Client::new();
+    let client = staff_client();
     let base_url = "http://localhost:8000";
 
     let mut tasks = vec![];
@@ -46,7 +92,7 @@ async fn simulate_clients() {
         let task = tokio::spawn(async move {
             println!("Client {} started", i);
 
-            let add_order_response = client.post(&format!("{}/tables/{}/orders", base_url, i))
+            let add_order_response = client.post(format!("{}/tables/{}/orders", base_url, i))
                 .json(&json!({
                     "orders": [
                         { "menu_item": "Pizza" },
@@ -60,7 +106,7 @@ async fn simulate_clients() {
                 Ok(response) => {
                     println!("Client {} - Add orders status: {}", i, response.status());
                     if let Ok(order_ids) = response.json::<Vec<Uuid>>().await {
-                        if let Ok(get_orders_response) = client.get(&format!("{}/tables/{}/orders", base_url, i))
+                        if let Ok(get_orders_response) = client.get(format!("{}/tables/{}/orders", base_url, i))
                             .send()
                             .await
                         {
@@ -68,7 +114,7 @@ async fn simulate_clients() {
                         }
 
                         if let Some(order_id) = order_ids.first() {
-                            if let Ok(get_order_response) = client.get(&format!("{}/tables/{}/orders/{}", base_url, i, order_id))
+                            if let Ok(get_order_response) = client.get(format!("{}/tables/{}/orders/{}", base_url, i, order_id))
                                 .send()
                                 .await
                             {
@@ -77,7 +123,7 @@ async fn simulate_clients() {
                         }
 
                         if let Some(order_id) = order_ids.last() {
-                            if let Ok(delete_order_response) = client.delete(&format!("{}/tables/{}/orders/{}", base_url, i, order_id))
+                            if let Ok(delete_order_response) = client.delete(format!("{}/tables/{}/orders/{}", base_url, i, order_id))
                                 .send()
                                 .await
                             {
@@ -0,0 +1,56 @@
+use dotenv::dotenv;
+use ryans_restaurant_app::db::migrations::{self, Migration, Migrator};
+use ryans_restaurant_app::db::mysql::{self, MySqlDb};
+use ryans_restaurant_app::db::postgres::{self, PostgresDb};
+use ryans_restaurant_app::db::sqlite::{self, SqliteDb};
+use ryans_restaurant_app::db::StorageError;
+use std::env;
+use std::process;
+
+fn main() {
+    dotenv().ok();
+
+    let database_url = env::var("RESTAURANT_DATABASE_URL").expect("RESTAURANT_DATABASE_URL must be declared");
+    let command = env::args().nth(1).unwrap_or_else(|| "up".to_string());
+
+    let (db, known): (Box<dyn Migrator>, &[Migration]) = if SqliteDb::handles(&database_url) {
+        let db = SqliteDb::connect(&database_url).unwrap_or_else(|e| exit_with(e));
+        (Box::new(db), sqlite::MIGRATIONS)
+    } else if PostgresDb::handles(&database_url) {
+        let db = PostgresDb::connect(&database_url).unwrap_or_else(|e| exit_with(e));
+        (Box::new(db), postgres::MIGRATIONS)
+    } else {
+        let db = MySqlDb::connect(&database_url).unwrap_or_else(|e| exit_with(e));
+        (Box::new(db), mysql::MIGRATIONS)
+    };
+    match command.as_str() {
+        "up" => {
+            let applied = migrations::run(db.as_ref(), known).unwrap_or_else(|e| exit_with(e));
+            if applied.is_empty() {
+                println!("Database is already up to date");
+            }
+            for version in applied {
+                println!("Applied migration {}", version);
+            }
+        }
+        "status" => {
+            let applied = db.applied_versions().unwrap_or_else(|e| exit_with(e));
+            for migration in known {
+                let state = if applied.contains(&migration.version) { "applied" } else { "pending" };
+                println!("{:04} {:<30} {}", migration.version, migration.name, state);
+            }
+            if let Err(e) = migrations::verify(db.as_ref(), known) {
+                println!("{}", e);
+            }
+        }
+        other => {
+            eprintln!("Unknown command '{}'. Usage: migrate [up|status]", other);
+            process::exit(2);
+        }
+    }
+}
+
+fn exit_with(error: StorageError) -> ! {
+    eprintln!("{}", error);
+    process::exit(1);
+}
@@ -0,0 +1,203 @@
+use crate::db::{
+    AsyncStorage, BillStatus, DeadLetter, Decoding, MenuItem, Order, OrderStatus, Payment, Session, SplitBill, Storage,
+    StorageError, Table, Webhook,
+};
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Adapts a synchronous `Storage` to `AsyncStorage` by running every call on Tokio's
+/// blocking thread pool, so a slow query never stalls the async workers.
+pub struct BlockingStorage {
+    inner: Arc<dyn Storage>,
+}
+
+impl BlockingStorage {
+    pub fn new(inner: Arc<dyn Storage>) -> Self {
+        BlockingStorage { inner }
+    }
+
+    async fn run<T, F>(&self, call: F) -> Result<T, StorageError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn Storage) -> Result<T, StorageError> + Send + 'static,
+    {
+        let inner = Arc::clone(&self.inner);
+        rocket::tokio::task::spawn_blocking(move || call(inner.as_ref()))
+            .await
+            .map_err(|e| StorageError::Internal(format!("Storage call failed: {}", e)))?
+    }
+}
+
+#[rocket::async_trait]
+impl AsyncStorage for BlockingStorage {
+    async fn get_table_orders(&self, table_id: u64) -> Result<Vec<Order>, StorageError> {
+        self.run(move |db| db.get_table_orders(table_id)).await
+    }
+
+    async fn get_table_order(&self, table_id: u64, order_id: Uuid) -> Result<Order, StorageError> {
+        self.run(move |db| db.get_table_order(table_id, order_id)).await
+    }
+
+    async fn add_table_orders(&self, table_id: u64, orders: Vec<Order>) -> Result<Vec<Uuid>, StorageError> {
+        self.run(move |db| db.add_table_orders(table_id, orders)).await
+    }
+
+    async fn delete_table_order(&self, table_id: u64, order_id: Uuid) -> Result<(), StorageError> {
+        self.run(move |db| db.delete_table_order(table_id, order_id)).await
+    }
+
+    async fn update_order_status(
+        &self,
+        table_id: u64,
+        order_id: Uuid,
+        from: OrderStatus,
+        to: OrderStatus,
+        at: DateTime<Utc>,
+    ) -> Result<(), StorageError> {
+        self.run(move |db| db.update_order_status(table_id, order_id, from, to, at))
+            .await
+    }
+
+    async fn count_open_orders(&self) -> Result<usize, StorageError> {
+        self.run(|db| db.count_open_orders()).await
+    }
+
+    async fn get_open_orders(&self) -> Result<Vec<(u64, Order)>, StorageError> {
+        self.run(|db| db.get_open_orders()).await
+    }
+
+    async fn get_menu_items(&self) -> Result<Vec<MenuItem>, StorageError> {
+        self.run(|db| db.get_menu_items()).await
+    }
+
+    async fn get_menu_item(&self, menu_item_id: Uuid) -> Result<MenuItem, StorageError> {
+        self.run(move |db| db.get_menu_item(menu_item_id)).await
+    }
+
+    async fn add_menu_item(&self, item: MenuItem) -> Result<Uuid, StorageError> {
+        self.run(move |db| db.add_menu_item(item)).await
+    }
+
+    async fn update_menu_item(&self, item: MenuItem) -> Result<(), StorageError> {
+        self.run(move |db| db.update_menu_item(item)).await
+    }
+
+    async fn delete_menu_item(&self, menu_item_id: Uuid) -> Result<(), StorageError> {
+        self.run(move |db| db.delete_menu_item(menu_item_id)).await
+    }
+
+    async fn get_tables(&self) -> Result<Vec<Table>, StorageError> {
+        self.run(|db| db.get_tables()).await
+    }
+
+    async fn get_table(&self, number: u64) -> Result<Table, StorageError> {
+        self.run(move |db| db.get_table(number)).await
+    }
+
+    async fn add_table(&self, table: Table) -> Result<u64, StorageError> {
+        self.run(move |db| db.add_table(table)).await
+    }
+
+    async fn open_table_session(&self, number: u64, session: Session) -> Result<(), StorageError> {
+        self.run(move |db| db.open_table_session(number, session)).await
+    }
+
+    async fn close_table_session(&self, number: u64, session_id: Uuid, closed_at: DateTime<Utc>) -> Result<(), StorageError> {
+        self.run(move |db| db.close_table_session(number, session_id, closed_at)).await
+    }
+
+    async fn get_table_sessions(&self, number: u64) -> Result<Vec<Session>, StorageError> {
+        self.run(move |db| db.get_table_sessions(number)).await
+    }
+
+    async fn get_table_session(&self, number: u64, session_id: Uuid) -> Result<Session, StorageError> {
+        self.run(move |db| db.get_table_session(number, session_id)).await
+    }
+
+    async fn get_session_orders(&self, session_id: Uuid, decoding: Decoding) -> Result<Vec<Order>, StorageError> {
+        self.run(move |db| db.get_session_orders(session_id, decoding)).await
+    }
+
+    async fn replace_split_bills(&self, session_id: Uuid, bills: Vec<SplitBill>) -> Result<(), StorageError> {
+        self.run(move |db| db.replace_split_bills(session_id, bills)).await
+    }
+
+    async fn get_split_bills(&self, session_id: Uuid, decoding: Decoding) -> Result<Vec<SplitBill>, StorageError> {
+        self.run(move |db| db.get_split_bills(session_id, decoding)).await
+    }
+
+    async fn get_split_bill(&self, session_id: Uuid, bill_id: Uuid) -> Result<SplitBill, StorageError> {
+        self.run(move |db| db.get_split_bill(session_id, bill_id)).await
+    }
+
+    async fn update_split_bill_status(
+        &self,
+        session_id: Uuid,
+        bill_id: Uuid,
+        from: BillStatus,
+        to: BillStatus,
+    ) -> Result<(), StorageError> {
+        self.run(move |db| db.update_split_bill_status(session_id, bill_id, from, to))
+            .await
+    }
+
+    async fn add_payment(&self, payment: Payment) -> Result<Uuid, StorageError> {
+        self.run(move |db| db.add_payment(payment)).await
+    }
+
+    async fn get_session_payments(&self, session_id: Uuid, decoding: Decoding) -> Result<Vec<Payment>, StorageError> {
+        self.run(move |db| db.get_session_payments(session_id, decoding)).await
+    }
+
+    async fn add_webhook(&self, webhook: Webhook) -> Result<Uuid, StorageError> {
+        self.run(move |db| db.add_webhook(webhook)).await
+    }
+
+    async fn get_webhooks(&self) -> Result<Vec<Webhook>, StorageError> {
+        self.run(|db| db.get_webhooks()).await
+    }
+
+    async fn get_webhook(&self, webhook_id: Uuid) -> Result<Webhook, StorageError> {
+        self.run(move |db| db.get_webhook(webhook_id)).await
+    }
+
+    async fn delete_webhook(&self, webhook_id: Uuid) -> Result<(), StorageError> {
+        self.run(move |db| db.delete_webhook(webhook_id)).await
+    }
+
+    async fn add_dead_letter(&self, dead_letter: DeadLetter) -> Result<Uuid, StorageError> {
+        self.run(move |db| db.add_dead_letter(dead_letter)).await
+    }
+
+    async fn get_dead_letters(&self) -> Result<Vec<DeadLetter>, StorageError> {
+        self.run(|db| db.get_dead_letters()).await
+    }
+
+    async fn delete_dead_letter(&self, dead_letter_id: Uuid) -> Result<(), StorageError> {
+        self.run(move |db| db.delete_dead_letter(dead_letter_id)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::memory::InMemoryStorage;
+
+    #[rocket::async_test]
+    async fn test_calls_reach_the_wrapped_storage() {
+        let inner = Arc::new(InMemoryStorage::new());
+        let db = BlockingStorage::new(inner.clone());
+
+        let order = Order::placed(Uuid::new_v4(), "Pizza".to_string(), Utc::now(), 600);
+        db.add_table_orders(1, vec![order.clone()]).await.unwrap();
+
+        assert_eq!(inner.get_table_orders(1).unwrap().len(), 1);
+        assert_eq!(db.get_table_order(1, order.id).await.unwrap().menu_item, "Pizza");
+        assert_eq!(db.count_open_orders().await, Ok(1));
+        assert!(matches!(
+            db.get_table_order(2, order.id).await,
+            Err(StorageError::NotFound(_))
+        ));
+    }
+}
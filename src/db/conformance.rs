@@ -0,0 +1,471 @@
+//! Behaviour every `Storage` backend must share, written once and run against each.
+//!
+//! Each check takes any `&dyn Storage` and uses fresh random table ids, so it can run
+//! against a shared database that already holds other data. Backends opt in with
+//! `storage_conformance_tests!` inside their test module.
+
+use crate::db::{
+    BillStatus, DeadLetter, Decoding, MenuItem, Order, OrderStatus, Payment, Session, SplitBill, Station, Storage,
+    StorageError, Table, TableStatus, Tender, Webhook,
+};
+use chrono::{Duration, Utc};
+use std::thread;
+use uuid::Uuid;
+
+fn random_table_id() -> u64 {
+    1_000_000 + rand::random::<u32>() as u64
+}
+
+fn order(menu_item: &str) -> Order {
+    Order::placed(Uuid::new_v4(), menu_item.to_string(), Utc::now(), 600)
+}
+
+fn ids(orders: &[Order]) -> Vec<Uuid> {
+    orders.iter().map(|order| order.id).collect()
+}
+
+fn sorted(mut ids: Vec<Uuid>) -> Vec<Uuid> {
+    ids.sort();
+    ids
+}
+
+pub fn orders_round_trip(db: &dyn Storage) {
+    let table_id = random_table_id();
+    let ticket_id = Uuid::new_v4();
+    let pizza = Order { price: 1250, seat: Some(3), ticket_id, ..order("Pizza") };
+    let salad = Order { station: Station::Cold, ticket_id, ..order("Salad") };
+
+    let created = db.add_table_orders(table_id, vec![pizza.clone(), salad.clone()]).unwrap();
+    assert_eq!(created, vec![pizza.id, salad.id]);
+
+    let stored = db.get_table_orders(table_id).unwrap();
+    assert_eq!(sorted(ids(&stored)), sorted(vec![pizza.id, salad.id]));
+
+    let fetched = db.get_table_order(table_id, pizza.id).unwrap();
+    assert_eq!(fetched.id, pizza.id);
+    assert_eq!(fetched.menu_item, "Pizza");
+    assert_eq!(fetched.created_at.timestamp_micros(), pizza.created_at.timestamp_micros());
+    assert_eq!(fetched.cooking_duration_secs, 600);
+    assert_eq!(fetched.status, OrderStatus::Placed);
+    assert_eq!(fetched.started_at, None);
+    assert_eq!(fetched.price, 1250);
+    assert_eq!(fetched.seat, Some(3));
+    assert_eq!((fetched.station, fetched.ticket_id), (Station::Grill, ticket_id));
+    let fetched = db.get_table_order(table_id, salad.id).unwrap();
+    assert_eq!((fetched.seat, fetched.station, fetched.ticket_id), (None, Station::Cold, ticket_id));
+    assert_eq!(db.add_table_orders(table_id, vec![]), Ok(vec![]));
+}
+
+pub fn tables_are_isolated(db: &dyn Storage) {
+    let first = random_table_id();
+    let second = first + 1;
+    let pizza = order("Pizza");
+    let salad = order("Salad");
+    db.add_table_orders(first, vec![pizza.clone()]).unwrap();
+    db.add_table_orders(second, vec![salad.clone()]).unwrap();
+
+    assert_eq!(ids(&db.get_table_orders(first).unwrap()), vec![pizza.id]);
+    assert_eq!(ids(&db.get_table_orders(second).unwrap()), vec![salad.id]);
+    assert!(matches!(db.get_table_order(second, pizza.id), Err(StorageError::NotFound(_))));
+    assert!(matches!(db.delete_table_order(second, pizza.id), Err(StorageError::NotFound(_))));
+    assert!(db.get_table_order(first, pizza.id).is_ok());
+}
+
+pub fn delete_removes_only_that_order(db: &dyn Storage) {
+    let table_id = random_table_id();
+    let pizza = order("Pizza");
+    let salad = order("Salad");
+    db.add_table_orders(table_id, vec![pizza.clone(), salad.clone()]).unwrap();
+
+    assert_eq!(db.delete_table_order(table_id, pizza.id), Ok(()));
+    assert_eq!(ids(&db.get_table_orders(table_id).unwrap()), vec![salad.id]);
+    assert!(matches!(db.get_table_order(table_id, pizza.id), Err(StorageError::NotFound(_))));
+    assert!(matches!(db.delete_table_order(table_id, pizza.id), Err(StorageError::NotFound(_))));
+}
+
+pub fn missing_records_are_not_found(db: &dyn Storage) {
+    let table_id = random_table_id();
+    let missing = Uuid::new_v4();
+    assert_eq!(db.get_table_orders(table_id).map(|orders| orders.len()), Ok(0));
+    assert!(matches!(db.get_table_order(table_id, missing), Err(StorageError::NotFound(_))));
+    assert!(matches!(db.delete_table_order(table_id, missing), Err(StorageError::NotFound(_))));
+    assert!(matches!(
+        db.update_order_status(table_id, missing, OrderStatus::Placed, OrderStatus::Cooking, Utc::now()),
+        Err(StorageError::NotFound(_))
+    ));
+    assert!(matches!(db.get_menu_item(missing), Err(StorageError::NotFound(_))));
+    assert!(matches!(db.delete_menu_item(missing), Err(StorageError::NotFound(_))));
+}
+
+pub fn batch_insert_is_all_or_nothing(db: &dyn Storage) {
+    let table_id = random_table_id();
+    let pizza = order("Pizza");
+    db.add_table_orders(table_id, vec![pizza.clone()]).unwrap();
+
+    let result = db.add_table_orders(table_id, vec![order("Salad"), pizza.clone()]);
+    assert!(matches!(result, Err(StorageError::Conflict(_))), "got {:?}", result);
+    assert_eq!(ids(&db.get_table_orders(table_id).unwrap()), vec![pizza.id]);
+}
+
+pub fn status_updates_compare_and_set(db: &dyn Storage) {
+    let table_id = random_table_id();
+    let pizza = order("Pizza");
+    db.add_table_orders(table_id, vec![pizza.clone()]).unwrap();
+
+    let at = Utc::now();
+    db.update_order_status(table_id, pizza.id, OrderStatus::Placed, OrderStatus::Cooking, at).unwrap();
+    let fetched = db.get_table_order(table_id, pizza.id).unwrap();
+    assert_eq!(fetched.status, OrderStatus::Cooking);
+    assert_eq!(fetched.started_at.map(|at| at.timestamp_micros()), Some(at.timestamp_micros()));
+
+    let stale = db.update_order_status(table_id, pizza.id, OrderStatus::Placed, OrderStatus::Cancelled, at);
+    assert!(matches!(stale, Err(StorageError::Conflict(_))));
+    assert_eq!(db.get_table_order(table_id, pizza.id).unwrap().status, OrderStatus::Cooking);
+}
+
+pub fn open_orders_span_tables_in_fire_order(db: &dyn Storage) {
+    let (first, second) = (random_table_id(), random_table_id());
+    let fired_at = Utc::now() - Duration::minutes(30);
+    let soup = Order { created_at: fired_at, ..order("Soup") };
+    let pizza = Order { created_at: fired_at + Duration::minutes(1), ..order("Pizza") };
+    let salad = Order { created_at: fired_at + Duration::minutes(2), ..order("Salad") };
+    let cake = Order { created_at: fired_at + Duration::minutes(3), ..order("Cake") };
+    db.add_table_orders(first, vec![pizza.clone(), cake.clone()]).unwrap();
+    db.add_table_orders(second, vec![salad.clone(), soup.clone()]).unwrap();
+    let at = Utc::now();
+    db.update_order_status(first, pizza.id, OrderStatus::Placed, OrderStatus::Cooking, at).unwrap();
+    db.update_order_status(first, cake.id, OrderStatus::Placed, OrderStatus::Cancelled, at).unwrap();
+
+    let open: Vec<(u64, Uuid)> = db
+        .get_open_orders()
+        .unwrap()
+        .into_iter()
+        .filter(|(table_id, _)| *table_id == first || *table_id == second)
+        .map(|(table_id, order)| (table_id, order.id))
+        .collect();
+    assert_eq!(open, vec![(second, soup.id), (first, pizza.id), (second, salad.id)]);
+}
+
+pub fn concurrent_inserts_are_all_kept(db: &dyn Storage) {
+    let table_id = random_table_id();
+    let created: Vec<Uuid> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..8)
+            .map(|_| {
+                scope.spawn(move || {
+                    let mut created = Vec::new();
+                    for _ in 0..5 {
+                        created.extend(db.add_table_orders(table_id, vec![order("Pizza"), order("Salad")]).unwrap());
+                    }
+                    created
+                })
+            })
+            .collect();
+        workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+    });
+
+    assert_eq!(created.len(), 80);
+    assert_eq!(sorted(ids(&db.get_table_orders(table_id).unwrap())), sorted(created));
+}
+
+pub fn menu_items_round_trip(db: &dyn Storage) {
+    let name = format!("Pizza {}", Uuid::new_v4());
+    let pizza = MenuItem {
+        id: Uuid::new_v4(),
+        name: name.clone(),
+        category: "Mains".to_string(),
+        price: 1250,
+        prep_time_minutes: 12,
+        available: true,
+        station: Station::Grill,
+    };
+    assert_eq!(db.add_menu_item(pizza.clone()), Ok(pizza.id));
+
+    let mut clash = pizza.clone();
+    clash.id = Uuid::new_v4();
+    clash.name = name.to_uppercase();
+    assert!(matches!(db.add_menu_item(clash), Err(StorageError::Conflict(_))));
+
+    let mut updated = pizza.clone();
+    updated.price = 1400;
+    updated.available = false;
+    updated.station = Station::Fryer;
+    db.update_menu_item(updated).unwrap();
+    let fetched = db.get_menu_item(pizza.id).unwrap();
+    assert_eq!((fetched.name.as_str(), fetched.price, fetched.available), (name.as_str(), 1400, false));
+    assert_eq!(fetched.station, Station::Fryer);
+    assert!(db.get_menu_items().unwrap().iter().any(|item| item.id == pizza.id));
+
+    db.delete_menu_item(pizza.id).unwrap();
+    assert!(matches!(db.get_menu_item(pizza.id), Err(StorageError::NotFound(_))));
+}
+
+fn session(table_number: u64) -> Session {
+    Session {
+        id: Uuid::new_v4(),
+        table_number,
+        opened_at: Utc::now(),
+        closed_at: None,
+    }
+}
+
+pub fn tables_round_trip(db: &dyn Storage) {
+    let number = random_table_id();
+    let table = Table {
+        number,
+        seats: 4,
+        section: "Patio".to_string(),
+        status: TableStatus::Closed,
+        session_id: None,
+    };
+    assert_eq!(db.add_table(table.clone()), Ok(number));
+    assert!(matches!(db.add_table(table.clone()), Err(StorageError::Conflict(_))));
+    assert_eq!(db.get_table(number), Ok(table));
+    assert!(db.get_tables().unwrap().iter().any(|table| table.number == number));
+
+    let seating = session(number);
+    db.open_table_session(number, seating.clone()).unwrap();
+    let opened = db.get_table(number).unwrap();
+    assert_eq!((opened.status, opened.session_id), (TableStatus::Open, Some(seating.id)));
+    let stale = db.open_table_session(number, session(number));
+    assert!(matches!(stale, Err(StorageError::Conflict(_))));
+
+    let stale = db.close_table_session(number, Uuid::new_v4(), Utc::now());
+    assert!(matches!(stale, Err(StorageError::Conflict(_))));
+    db.close_table_session(number, seating.id, Utc::now()).unwrap();
+    let closed = db.get_table(number).unwrap();
+    assert_eq!((closed.status, closed.session_id), (TableStatus::Closed, None));
+
+    let missing = number + 1;
+    assert!(matches!(db.get_table(missing), Err(StorageError::NotFound(_))));
+    assert!(matches!(
+        db.open_table_session(missing, session(missing)),
+        Err(StorageError::NotFound(_))
+    ));
+}
+
+pub fn sessions_keep_their_orders(db: &dyn Storage) {
+    let number = random_table_id();
+    db.add_table(Table {
+        number,
+        seats: 2,
+        section: "Bar".to_string(),
+        status: TableStatus::Closed,
+        session_id: None,
+    })
+    .unwrap();
+
+    let first = session(number);
+    db.open_table_session(number, first.clone()).unwrap();
+    let pizza = Order { session_id: Some(first.id), ..order("Pizza") };
+    db.add_table_orders(number, vec![pizza.clone()]).unwrap();
+    let closed_at = Utc::now();
+    db.close_table_session(number, first.id, closed_at).unwrap();
+
+    let second = Session { opened_at: closed_at, ..session(number) };
+    db.open_table_session(number, second.clone()).unwrap();
+    let salad = Order { session_id: Some(second.id), ..order("Salad") };
+    db.add_table_orders(number, vec![salad.clone()]).unwrap();
+
+    assert_eq!(ids(&db.get_session_orders(first.id, Decoding::Strict).unwrap()), vec![pizza.id]);
+    assert_eq!(ids(&db.get_session_orders(second.id, Decoding::Strict).unwrap()), vec![salad.id]);
+    assert_eq!(db.get_table_order(number, salad.id).unwrap().session_id, Some(second.id));
+
+    let held = db.get_table_sessions(number).unwrap();
+    assert_eq!(held.iter().map(|session| session.id).collect::<Vec<_>>(), vec![first.id, second.id]);
+    assert_eq!(held[0].closed_at.map(|at| at.timestamp_micros()), Some(closed_at.timestamp_micros()));
+    assert_eq!(held[1].closed_at, None);
+    assert_eq!(db.get_table_session(number, first.id).unwrap().id, first.id);
+    assert!(matches!(db.get_table_session(number + 1, first.id), Err(StorageError::NotFound(_))));
+}
+
+fn split_bill(session_id: Uuid, position: u32, order_ids: Vec<Uuid>, subtotal: u64) -> SplitBill {
+    SplitBill {
+        id: Uuid::new_v4(),
+        session_id,
+        position,
+        label: format!("Part {}", position + 1),
+        order_ids,
+        subtotal,
+        service_charge: subtotal / 10,
+        tax: 0,
+        total: subtotal + subtotal / 10,
+        status: BillStatus::Open,
+    }
+}
+
+pub fn split_bills_replace_and_close(db: &dyn Storage) {
+    let session_id = Uuid::new_v4();
+    let orders = sorted(vec![Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()]);
+    assert_eq!(db.get_split_bills(session_id, Decoding::Strict), Ok(vec![]));
+
+    let first = split_bill(session_id, 0, vec![orders[2], orders[0]], 2000);
+    let second = split_bill(session_id, 1, vec![orders[1]], 750);
+    db.replace_split_bills(session_id, vec![second.clone(), first.clone()]).unwrap();
+    let stored = db.get_split_bills(session_id, Decoding::Strict).unwrap();
+    assert_eq!(stored, vec![SplitBill { order_ids: vec![orders[0], orders[2]], ..first.clone() }, second.clone()]);
+    assert_eq!(db.get_split_bill(session_id, second.id), Ok(second.clone()));
+    assert!(matches!(db.get_split_bill(Uuid::new_v4(), second.id), Err(StorageError::NotFound(_))));
+
+    // Splitting again throws the old shares away.
+    let even = vec![split_bill(session_id, 0, vec![], 1375), split_bill(session_id, 1, vec![], 1375)];
+    db.replace_split_bills(session_id, even.clone()).unwrap();
+    assert_eq!(db.get_split_bills(session_id, Decoding::Strict), Ok(even.clone()));
+    assert!(matches!(db.get_split_bill(session_id, first.id), Err(StorageError::NotFound(_))));
+
+    db.update_split_bill_status(session_id, even[0].id, BillStatus::Open, BillStatus::Closed)
+        .unwrap();
+    assert_eq!(db.get_split_bill(session_id, even[0].id).unwrap().status, BillStatus::Closed);
+    assert!(matches!(
+        db.update_split_bill_status(session_id, even[0].id, BillStatus::Open, BillStatus::Closed),
+        Err(StorageError::Conflict(_))
+    ));
+    assert!(matches!(
+        db.update_split_bill_status(session_id, first.id, BillStatus::Open, BillStatus::Closed),
+        Err(StorageError::NotFound(_))
+    ));
+
+    // Once a share is closed the split is settled and cannot be redone.
+    assert!(matches!(
+        db.replace_split_bills(session_id, vec![]),
+        Err(StorageError::Conflict(_))
+    ));
+    assert_eq!(db.get_split_bills(session_id, Decoding::Strict).unwrap().len(), 2);
+}
+
+pub fn payments_round_trip(db: &dyn Storage) {
+    let session_id = Uuid::new_v4();
+    let share = split_bill(session_id, 0, vec![], 1100);
+    db.replace_split_bills(session_id, vec![share.clone()]).unwrap();
+    let cash = Payment {
+        id: Uuid::new_v4(),
+        session_id,
+        bill_id: None,
+        tender: Tender::Cash,
+        reference: None,
+        amount: 1500,
+        tip: 200,
+        change: 300,
+        paid_at: Utc::now() - Duration::minutes(1),
+    };
+    let card = Payment {
+        id: Uuid::new_v4(),
+        bill_id: Some(share.id),
+        tender: Tender::Card,
+        reference: Some("VISA-4242".to_string()),
+        amount: 1100,
+        tip: 0,
+        change: 0,
+        paid_at: Utc::now(),
+        ..cash.clone()
+    };
+    assert_eq!(db.get_session_payments(session_id, Decoding::Strict), Ok(vec![]));
+
+    assert_eq!(db.add_payment(card.clone()), Ok(card.id));
+    assert_eq!(db.add_payment(cash.clone()), Ok(cash.id));
+    assert!(matches!(db.add_payment(cash.clone()), Err(StorageError::Conflict(_))));
+    let held = db.get_session_payments(session_id, Decoding::Strict).unwrap();
+    assert_eq!(held.iter().map(|payment| payment.id).collect::<Vec<_>>(), vec![cash.id, card.id]);
+    assert_eq!(
+        (held[0].tender, held[0].bill_id, held[0].amount, held[0].tip, held[0].change),
+        (Tender::Cash, None, 1500, 200, 300)
+    );
+    assert_eq!(held[0].paid_at.timestamp_micros(), cash.paid_at.timestamp_micros());
+    assert_eq!((held[1].tender, held[1].bill_id), (Tender::Card, Some(share.id)));
+    assert_eq!(held[1].reference.as_deref(), Some("VISA-4242"));
+    assert_eq!(db.get_session_payments(Uuid::new_v4(), Decoding::Strict), Ok(vec![]));
+
+    db.update_split_bill_status(session_id, share.id, BillStatus::Open, BillStatus::Paid)
+        .unwrap();
+    db.update_split_bill_status(session_id, share.id, BillStatus::Paid, BillStatus::Closed)
+        .unwrap();
+    assert_eq!(db.get_split_bill(session_id, share.id).unwrap().status, BillStatus::Closed);
+}
+
+pub fn webhooks_and_dead_letters_round_trip(db: &dyn Storage) {
+    let inventory = Webhook {
+        id: Uuid::new_v4(),
+        url: "https://inventory.example.com/hooks/orders".to_string(),
+        events: vec!["order_created".to_string(), "order_deleted".to_string()],
+        created_at: Utc::now() - Duration::minutes(1),
+    };
+    let loyalty = Webhook {
+        id: Uuid::new_v4(),
+        url: "http://loyalty.local/events".to_string(),
+        events: vec![],
+        created_at: Utc::now(),
+    };
+    assert_eq!(db.add_webhook(loyalty.clone()), Ok(loyalty.id));
+    assert_eq!(db.add_webhook(inventory.clone()), Ok(inventory.id));
+    assert!(matches!(db.add_webhook(loyalty.clone()), Err(StorageError::Conflict(_))));
+
+    let ours = |webhooks: Vec<Webhook>| -> Vec<Uuid> {
+        webhooks.into_iter().map(|webhook| webhook.id).filter(|id| [inventory.id, loyalty.id].contains(id)).collect()
+    };
+    assert_eq!(ours(db.get_webhooks().unwrap()), vec![inventory.id, loyalty.id]);
+    let held = db.get_webhook(inventory.id).unwrap();
+    assert_eq!((held.url.as_str(), &held.events), (inventory.url.as_str(), &inventory.events));
+    assert_eq!(held.created_at.timestamp_micros(), inventory.created_at.timestamp_micros());
+    assert_eq!(db.get_webhook(loyalty.id).unwrap().events, Vec::<String>::new());
+
+    let dead_letter = DeadLetter {
+        id: Uuid::new_v4(),
+        webhook_id: inventory.id,
+        event_id: 42,
+        event: "order_created".to_string(),
+        payload: r#"{"id":42}"#.to_string(),
+        attempts: 5,
+        last_error: "HTTP 503".to_string(),
+        failed_at: Utc::now(),
+    };
+    assert_eq!(db.add_dead_letter(dead_letter.clone()), Ok(dead_letter.id));
+    let held = db.get_dead_letters().unwrap().into_iter().find(|held| held.id == dead_letter.id).unwrap();
+    assert_eq!(held.failed_at.timestamp_micros(), dead_letter.failed_at.timestamp_micros());
+    assert_eq!(DeadLetter { failed_at: dead_letter.failed_at, ..held }, dead_letter);
+
+    assert_eq!(db.delete_webhook(inventory.id), Ok(()));
+    assert!(matches!(db.get_webhook(inventory.id), Err(StorageError::NotFound(_))));
+    assert!(matches!(db.delete_webhook(inventory.id), Err(StorageError::NotFound(_))));
+    assert_eq!(ours(db.get_webhooks().unwrap()), vec![loyalty.id]);
+    assert_eq!(db.delete_dead_letter(dead_letter.id), Ok(()));
+    assert!(matches!(db.delete_dead_letter(dead_letter.id), Err(StorageError::NotFound(_))));
+    assert!(db.get_dead_letters().unwrap().iter().all(|held| held.id != dead_letter.id));
+}
+
+/// Generates one test per conformance check, each calling `$make` for a fresh backend.
+/// Attributes given after it, such as `#[ignore = "..."]`, are copied onto every test.
+macro_rules! storage_conformance_tests {
+    ($make:expr $(, #[$attr:meta])* $(,)?) => {
+        mod conformance {
+            use super::*;
+            use crate::db::conformance;
+
+            fn storage() -> Box<dyn crate::db::Storage> {
+                Box::new($make)
+            }
+
+            storage_conformance_tests!(@test orders_round_trip $(#[$attr])*);
+            storage_conformance_tests!(@test tables_are_isolated $(#[$attr])*);
+            storage_conformance_tests!(@test delete_removes_only_that_order $(#[$attr])*);
+            storage_conformance_tests!(@test missing_records_are_not_found $(#[$attr])*);
+            storage_conformance_tests!(@test batch_insert_is_all_or_nothing $(#[$attr])*);
+            storage_conformance_tests!(@test status_updates_compare_and_set $(#[$attr])*);
+            storage_conformance_tests!(@test open_orders_span_tables_in_fire_order $(#[$attr])*);
+            storage_conformance_tests!(@test concurrent_inserts_are_all_kept $(#[$attr])*);
+            storage_conformance_tests!(@test menu_items_round_trip $(#[$attr])*);
+            storage_conformance_tests!(@test tables_round_trip $(#[$attr])*);
+            storage_conformance_tests!(@test sessions_keep_their_orders $(#[$attr])*);
+            storage_conformance_tests!(@test split_bills_replace_and_close $(#[$attr])*);
+            storage_conformance_tests!(@test payments_round_trip $(#[$attr])*);
+            storage_conformance_tests!(@test webhooks_and_dead_letters_round_trip $(#[$attr])*);
+        }
+    };
+    (@test $check:ident $(#[$attr:meta])*) => {
+        #[test]
+        $(#[$attr])*
+        fn $check() {
+            conformance::$check(storage().as_ref());
+        }
+    };
+}
+
+pub(crate) use storage_conformance_tests;
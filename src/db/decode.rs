@@ -0,0 +1,96 @@
+use crate::db::StorageError;
+use std::fmt;
+
+/// A stored row that could not be turned back into a record.
+///
+/// `key` identifies the row as it is stored (the id when it can be read, otherwise the
+/// raw bytes in hex) so it can be found and repaired by hand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorruptRow {
+    pub table: &'static str,
+    pub key: String,
+    pub reason: String,
+}
+
+impl CorruptRow {
+    pub fn new(table: &'static str, key: impl Into<String>, reason: impl Into<String>) -> Self {
+        CorruptRow {
+            table,
+            key: key.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for CorruptRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Corrupt row {} in {}: {}", self.key, self.table, self.reason)
+    }
+}
+
+/// Formats a stored key that is not a valid id, for use as `CorruptRow::key`.
+pub fn hex_key(raw: &[u8]) -> String {
+    raw.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Keeps the rows that decoded and logs a warning for each one that did not, so a
+/// single bad record does not hide the rest of a list.
+pub fn skip_corrupt<T>(rows: Vec<Result<T, CorruptRow>>) -> Vec<T> {
+    rows.into_iter()
+        .filter_map(|row| match row {
+            Ok(record) => Some(record),
+            Err(corrupt) => {
+                rocket::warn!("Skipping {}", corrupt);
+                None
+            }
+        })
+        .collect()
+}
+
+/// What a list query does with rows that do not decode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decoding {
+    /// Leaves them out with a warning, for lists that are only shown.
+    SkipCorrupt,
+    /// Fails with `StorageError::Corrupt`, for lists that money is worked out from,
+    /// where a missing order or payment would change the amount due.
+    Strict,
+}
+
+/// Collects decoded rows as `decoding` asks.
+pub fn collect<T>(rows: Vec<Result<T, CorruptRow>>, decoding: Decoding) -> Result<Vec<T>, StorageError> {
+    match decoding {
+        Decoding::SkipCorrupt => Ok(skip_corrupt(rows)),
+        Decoding::Strict => rows.into_iter().map(|row| row.map_err(StorageError::from)).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skip_corrupt_keeps_good_rows() {
+        let rows = vec![
+            Ok(1),
+            Err(CorruptRow::new("orders", "abc", "status 'exploded' is not valid")),
+            Ok(3),
+        ];
+        assert_eq!(skip_corrupt(rows), vec![1, 3]);
+    }
+
+    #[test]
+    fn test_strict_decoding_fails_on_corrupt_rows() {
+        let corrupt = CorruptRow::new("payments", "abc", "tender 'cheque' is not valid");
+        let rows = || vec![Ok(1), Err(corrupt.clone()), Ok(3)];
+        assert_eq!(collect(rows(), Decoding::SkipCorrupt), Ok(vec![1, 3]));
+        assert_eq!(collect(rows(), Decoding::Strict), Err(StorageError::Corrupt(corrupt.clone())));
+        assert_eq!(collect(vec![Ok(1), Ok(3)], Decoding::Strict), Ok(vec![1, 3]));
+    }
+
+    #[test]
+    fn test_corrupt_row_names_the_row() {
+        let corrupt = CorruptRow::new("orders", hex_key(&[0x0a, 0xff]), "id is not a UUID");
+        assert_eq!(corrupt.to_string(), "Corrupt row 0aff in orders: id is not a UUID");
+    }
+}
@@ -0,0 +1,555 @@
+use crate::db::{
+    BillStatus, DeadLetter, Decoding, MenuItem, Order, OrderStatus, Payment, Session, SplitBill, Storage, StorageError,
+    Table, TableStatus, Webhook,
+};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use uuid::Uuid;
+
+/// Storage backend that keeps every table's orders, the floor plan with its sessions
+/// and their bills and payments, the menu, and webhooks with their dead letters in
+/// process memory.
+///
+/// Nothing survives a restart, so this is meant for local development and tests.
+#[derive(Default)]
+pub struct InMemoryStorage {
+    tables: RwLock<HashMap<u64, Vec<Order>>>,
+    menu: RwLock<Vec<MenuItem>>,
+    floor: RwLock<Vec<Table>>,
+    sessions: RwLock<Vec<Session>>,
+    split_bills: RwLock<Vec<SplitBill>>,
+    payments: RwLock<Vec<Payment>>,
+    webhooks: RwLock<Vec<Webhook>>,
+    dead_letters: RwLock<Vec<DeadLetter>>,
+}
+
+fn name_taken(menu: &[MenuItem], item: &MenuItem) -> bool {
+    menu.iter()
+        .any(|existing| existing.id != item.id && existing.name.eq_ignore_ascii_case(&item.name))
+}
+
+impl InMemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Storage for InMemoryStorage {
+    fn get_table_orders(&self, table_id: u64) -> Result<Vec<Order>, StorageError> {
+        let tables = self.tables.read().map_err(|e| StorageError::Internal(e.to_string()))?;
+        Ok(tables.get(&table_id).cloned().unwrap_or_default())
+    }
+
+    fn get_table_order(&self, table_id: u64, order_id: Uuid) -> Result<Order, StorageError> {
+        let tables = self.tables.read().map_err(|e| StorageError::Internal(e.to_string()))?;
+        tables
+            .get(&table_id)
+            .and_then(|orders| orders.iter().find(|order| order.id == order_id))
+            .cloned()
+            .ok_or(StorageError::NotFound("Order not found".to_string()))
+    }
+
+    fn add_table_orders(&self, table_id: u64, orders: Vec<Order>) -> Result<Vec<Uuid>, StorageError> {
+        let mut tables = self.tables.write().map_err(|e| StorageError::Internal(e.to_string()))?;
+        let order_ids: Vec<Uuid> = orders.iter().map(|order| order.id).collect();
+        // Check the whole batch before touching the table so a bad order stores nothing.
+        for (position, order_id) in order_ids.iter().enumerate() {
+            let exists = order_ids[..position].contains(order_id)
+                || tables.values().flatten().any(|order| order.id == *order_id);
+            if exists {
+                return Err(StorageError::Conflict(format!("Order {} already exists", order_id)));
+            }
+        }
+        tables.entry(table_id).or_default().extend(orders);
+        Ok(order_ids)
+    }
+
+    fn delete_table_order(&self, table_id: u64, order_id: Uuid) -> Result<(), StorageError> {
+        let mut tables = self.tables.write().map_err(|e| StorageError::Internal(e.to_string()))?;
+        let orders = tables
+            .get_mut(&table_id)
+            .ok_or(StorageError::NotFound("Order not found".to_string()))?;
+        let position = orders
+            .iter()
+            .position(|order| order.id == order_id)
+            .ok_or(StorageError::NotFound("Order not found".to_string()))?;
+        orders.remove(position);
+        Ok(())
+    }
+
+    fn update_order_status(
+        &self,
+        table_id: u64,
+        order_id: Uuid,
+        from: OrderStatus,
+        to: OrderStatus,
+        at: DateTime<Utc>,
+    ) -> Result<(), StorageError> {
+        let mut tables = self.tables.write().map_err(|e| StorageError::Internal(e.to_string()))?;
+        let order = tables
+            .get_mut(&table_id)
+            .and_then(|orders| orders.iter_mut().find(|order| order.id == order_id))
+            .ok_or(StorageError::NotFound("Order not found".to_string()))?;
+        if order.status != from {
+            return Err(StorageError::Conflict(format!(
+                "Order is {}, not {}",
+                order.status, from
+            )));
+        }
+        order.set_status(to, at);
+        Ok(())
+    }
+
+    fn count_open_orders(&self) -> Result<usize, StorageError> {
+        let tables = self.tables.read().map_err(|e| StorageError::Internal(e.to_string()))?;
+        Ok(tables
+            .values()
+            .flatten()
+            .filter(|order| matches!(order.status, OrderStatus::Placed | OrderStatus::Cooking))
+            .count())
+    }
+
+    fn get_open_orders(&self) -> Result<Vec<(u64, Order)>, StorageError> {
+        let tables = self.tables.read().map_err(|e| StorageError::Internal(e.to_string()))?;
+        let mut orders: Vec<(u64, Order)> = tables
+            .iter()
+            .flat_map(|(table_id, orders)| orders.iter().map(move |order| (*table_id, order.clone())))
+            .filter(|(_, order)| matches!(order.status, OrderStatus::Placed | OrderStatus::Cooking))
+            .collect();
+        orders.sort_by_key(|(_, order)| order.created_at);
+        Ok(orders)
+    }
+
+    fn get_menu_items(&self) -> Result<Vec<MenuItem>, StorageError> {
+        let menu = self.menu.read().map_err(|e| StorageError::Internal(e.to_string()))?;
+        let mut items = menu.clone();
+        items.sort_by_key(|item| (item.category.to_lowercase(), item.name.to_lowercase()));
+        Ok(items)
+    }
+
+    fn get_menu_item(&self, menu_item_id: Uuid) -> Result<MenuItem, StorageError> {
+        let menu = self.menu.read().map_err(|e| StorageError::Internal(e.to_string()))?;
+        menu.iter()
+            .find(|item| item.id == menu_item_id)
+            .cloned()
+            .ok_or(StorageError::NotFound("Menu item not found".to_string()))
+    }
+
+    fn add_menu_item(&self, item: MenuItem) -> Result<Uuid, StorageError> {
+        let mut menu = self.menu.write().map_err(|e| StorageError::Internal(e.to_string()))?;
+        if menu.iter().any(|existing| existing.id == item.id) || name_taken(&menu, &item) {
+            return Err(StorageError::Conflict(format!("Menu item '{}' already exists", item.name)));
+        }
+        let id = item.id;
+        menu.push(item);
+        Ok(id)
+    }
+
+    fn update_menu_item(&self, item: MenuItem) -> Result<(), StorageError> {
+        let mut menu = self.menu.write().map_err(|e| StorageError::Internal(e.to_string()))?;
+        if name_taken(&menu, &item) {
+            return Err(StorageError::Conflict(format!("Menu item '{}' already exists", item.name)));
+        }
+        let existing = menu
+            .iter_mut()
+            .find(|existing| existing.id == item.id)
+            .ok_or(StorageError::NotFound("Menu item not found".to_string()))?;
+        *existing = item;
+        Ok(())
+    }
+
+    fn delete_menu_item(&self, menu_item_id: Uuid) -> Result<(), StorageError> {
+        let mut menu = self.menu.write().map_err(|e| StorageError::Internal(e.to_string()))?;
+        let position = menu
+            .iter()
+            .position(|item| item.id == menu_item_id)
+            .ok_or(StorageError::NotFound("Menu item not found".to_string()))?;
+        menu.remove(position);
+        Ok(())
+    }
+
+    fn get_tables(&self) -> Result<Vec<Table>, StorageError> {
+        let floor = self.floor.read().map_err(|e| StorageError::Internal(e.to_string()))?;
+        let mut tables = floor.clone();
+        tables.sort_by_key(|table| table.number);
+        Ok(tables)
+    }
+
+    fn get_table(&self, number: u64) -> Result<Table, StorageError> {
+        let floor = self.floor.read().map_err(|e| StorageError::Internal(e.to_string()))?;
+        floor
+            .iter()
+            .find(|table| table.number == number)
+            .cloned()
+            .ok_or(StorageError::NotFound(format!("Table {} is not registered", number)))
+    }
+
+    fn add_table(&self, table: Table) -> Result<u64, StorageError> {
+        let mut floor = self.floor.write().map_err(|e| StorageError::Internal(e.to_string()))?;
+        if floor.iter().any(|existing| existing.number == table.number) {
+            return Err(StorageError::Conflict(format!("Table {} already exists", table.number)));
+        }
+        let number = table.number;
+        floor.push(table);
+        Ok(number)
+    }
+
+    fn open_table_session(&self, number: u64, session: Session) -> Result<(), StorageError> {
+        let mut floor = self.floor.write().map_err(|e| StorageError::Internal(e.to_string()))?;
+        let mut sessions = self.sessions.write().map_err(|e| StorageError::Internal(e.to_string()))?;
+        let table = floor
+            .iter_mut()
+            .find(|table| table.number == number)
+            .ok_or(StorageError::NotFound(format!("Table {} is not registered", number)))?;
+        if table.status != TableStatus::Closed {
+            return Err(StorageError::Conflict(format!("Table {} is already open", number)));
+        }
+        if sessions.iter().any(|existing| existing.id == session.id) {
+            return Err(StorageError::Conflict(format!("Session {} already exists", session.id)));
+        }
+        table.status = TableStatus::Open;
+        table.session_id = Some(session.id);
+        sessions.push(session);
+        Ok(())
+    }
+
+    fn close_table_session(&self, number: u64, session_id: Uuid, closed_at: DateTime<Utc>) -> Result<(), StorageError> {
+        let mut floor = self.floor.write().map_err(|e| StorageError::Internal(e.to_string()))?;
+        let mut sessions = self.sessions.write().map_err(|e| StorageError::Internal(e.to_string()))?;
+        let table = floor
+            .iter_mut()
+            .find(|table| table.number == number)
+            .ok_or(StorageError::NotFound(format!("Table {} is not registered", number)))?;
+        if table.session_id != Some(session_id) {
+            return Err(StorageError::Conflict(format!("Session {} is not in progress at table {}", session_id, number)));
+        }
+        table.status = TableStatus::Closed;
+        table.session_id = None;
+        if let Some(session) = sessions.iter_mut().find(|session| session.id == session_id) {
+            session.closed_at = Some(closed_at);
+        }
+        Ok(())
+    }
+
+    fn get_table_sessions(&self, number: u64) -> Result<Vec<Session>, StorageError> {
+        let sessions = self.sessions.read().map_err(|e| StorageError::Internal(e.to_string()))?;
+        let mut held: Vec<Session> = sessions.iter().filter(|session| session.table_number == number).cloned().collect();
+        held.sort_by_key(|session| session.opened_at);
+        Ok(held)
+    }
+
+    fn get_table_session(&self, number: u64, session_id: Uuid) -> Result<Session, StorageError> {
+        let sessions = self.sessions.read().map_err(|e| StorageError::Internal(e.to_string()))?;
+        sessions
+            .iter()
+            .find(|session| session.table_number == number && session.id == session_id)
+            .cloned()
+            .ok_or(StorageError::NotFound("Session not found".to_string()))
+    }
+
+    fn get_session_orders(&self, session_id: Uuid, _decoding: Decoding) -> Result<Vec<Order>, StorageError> {
+        let tables = self.tables.read().map_err(|e| StorageError::Internal(e.to_string()))?;
+        let mut orders: Vec<Order> = tables
+            .values()
+            .flatten()
+            .filter(|order| order.session_id == Some(session_id))
+            .cloned()
+            .collect();
+        orders.sort_by_key(|order| order.created_at);
+        Ok(orders)
+    }
+
+    fn replace_split_bills(&self, session_id: Uuid, bills: Vec<SplitBill>) -> Result<(), StorageError> {
+        let mut split_bills = self.split_bills.write().map_err(|e| StorageError::Internal(e.to_string()))?;
+        if split_bills
+            .iter()
+            .any(|bill| bill.session_id == session_id && bill.status != BillStatus::Open)
+        {
+            return Err(StorageError::Conflict("A split bill has already been paid or closed".to_string()));
+        }
+        split_bills.retain(|bill| bill.session_id != session_id);
+        split_bills.extend(bills.into_iter().map(|mut bill| {
+            bill.order_ids.sort();
+            bill
+        }));
+        Ok(())
+    }
+
+    fn get_split_bills(&self, session_id: Uuid, _decoding: Decoding) -> Result<Vec<SplitBill>, StorageError> {
+        let split_bills = self.split_bills.read().map_err(|e| StorageError::Internal(e.to_string()))?;
+        let mut bills: Vec<SplitBill> = split_bills.iter().filter(|bill| bill.session_id == session_id).cloned().collect();
+        bills.sort_by_key(|bill| bill.position);
+        Ok(bills)
+    }
+
+    fn get_split_bill(&self, session_id: Uuid, bill_id: Uuid) -> Result<SplitBill, StorageError> {
+        let split_bills = self.split_bills.read().map_err(|e| StorageError::Internal(e.to_string()))?;
+        split_bills
+            .iter()
+            .find(|bill| bill.session_id == session_id && bill.id == bill_id)
+            .cloned()
+            .ok_or(StorageError::NotFound("Bill not found".to_string()))
+    }
+
+    fn update_split_bill_status(
+        &self,
+        session_id: Uuid,
+        bill_id: Uuid,
+        from: BillStatus,
+        to: BillStatus,
+    ) -> Result<(), StorageError> {
+        let mut split_bills = self.split_bills.write().map_err(|e| StorageError::Internal(e.to_string()))?;
+        let bill = split_bills
+            .iter_mut()
+            .find(|bill| bill.session_id == session_id && bill.id == bill_id)
+            .ok_or(StorageError::NotFound("Bill not found".to_string()))?;
+        if bill.status != from {
+            return Err(StorageError::Conflict(format!("Bill is {}, not {}", bill.status, from)));
+        }
+        bill.status = to;
+        Ok(())
+    }
+
+    fn add_payment(&self, payment: Payment) -> Result<Uuid, StorageError> {
+        let mut payments = self.payments.write().map_err(|e| StorageError::Internal(e.to_string()))?;
+        if payments.iter().any(|existing| existing.id == payment.id) {
+            return Err(StorageError::Conflict(format!("Payment {} already exists", payment.id)));
+        }
+        let id = payment.id;
+        payments.push(payment);
+        Ok(id)
+    }
+
+    fn get_session_payments(&self, session_id: Uuid, _decoding: Decoding) -> Result<Vec<Payment>, StorageError> {
+        let payments = self.payments.read().map_err(|e| StorageError::Internal(e.to_string()))?;
+        let mut held: Vec<Payment> = payments.iter().filter(|payment| payment.session_id == session_id).cloned().collect();
+        held.sort_by_key(|payment| payment.paid_at);
+        Ok(held)
+    }
+
+    fn add_webhook(&self, webhook: Webhook) -> Result<Uuid, StorageError> {
+        let mut webhooks = self.webhooks.write().map_err(|e| StorageError::Internal(e.to_string()))?;
+        if webhooks.iter().any(|existing| existing.id == webhook.id) {
+            return Err(StorageError::Conflict(format!("Webhook {} already exists", webhook.id)));
+        }
+        let id = webhook.id;
+        webhooks.push(webhook);
+        Ok(id)
+    }
+
+    fn get_webhooks(&self) -> Result<Vec<Webhook>, StorageError> {
+        let webhooks = self.webhooks.read().map_err(|e| StorageError::Internal(e.to_string()))?;
+        let mut held = webhooks.clone();
+        held.sort_by_key(|webhook| webhook.created_at);
+        Ok(held)
+    }
+
+    fn get_webhook(&self, webhook_id: Uuid) -> Result<Webhook, StorageError> {
+        let webhooks = self.webhooks.read().map_err(|e| StorageError::Internal(e.to_string()))?;
+        webhooks
+            .iter()
+            .find(|webhook| webhook.id == webhook_id)
+            .cloned()
+            .ok_or(StorageError::NotFound("Webhook not found".to_string()))
+    }
+
+    fn delete_webhook(&self, webhook_id: Uuid) -> Result<(), StorageError> {
+        let mut webhooks = self.webhooks.write().map_err(|e| StorageError::Internal(e.to_string()))?;
+        let position = webhooks
+            .iter()
+            .position(|webhook| webhook.id == webhook_id)
+            .ok_or(StorageError::NotFound("Webhook not found".to_string()))?;
+        webhooks.remove(position);
+        Ok(())
+    }
+
+    fn add_dead_letter(&self, dead_letter: DeadLetter) -> Result<Uuid, StorageError> {
+        let mut dead_letters = self.dead_letters.write().map_err(|e| StorageError::Internal(e.to_string()))?;
+        if dead_letters.iter().any(|existing| existing.id == dead_letter.id) {
+            return Err(StorageError::Conflict(format!("Dead letter {} already exists", dead_letter.id)));
+        }
+        let id = dead_letter.id;
+        dead_letters.push(dead_letter);
+        Ok(id)
+    }
+
+    fn get_dead_letters(&self) -> Result<Vec<DeadLetter>, StorageError> {
+        let dead_letters = self.dead_letters.read().map_err(|e| StorageError::Internal(e.to_string()))?;
+        let mut held = dead_letters.clone();
+        held.sort_by_key(|dead_letter| dead_letter.failed_at);
+        Ok(held)
+    }
+
+    fn delete_dead_letter(&self, dead_letter_id: Uuid) -> Result<(), StorageError> {
+        let mut dead_letters = self.dead_letters.write().map_err(|e| StorageError::Internal(e.to_string()))?;
+        let position = dead_letters
+            .iter()
+            .position(|dead_letter| dead_letter.id == dead_letter_id)
+            .ok_or(StorageError::NotFound("Dead letter not found".to_string()))?;
+        dead_letters.remove(position);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::conformance::storage_conformance_tests;
+    use crate::db::Station;
+
+    storage_conformance_tests!(InMemoryStorage::new());
+
+    fn order(menu_item: &str) -> Order {
+        Order::placed(Uuid::new_v4(), menu_item.to_string(), Utc::now(), 600)
+    }
+
+    fn menu_item(name: &str, category: &str) -> MenuItem {
+        MenuItem {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            category: category.to_string(),
+            price: 1250,
+            prep_time_minutes: 12,
+            available: true,
+            station: Station::Grill,
+        }
+    }
+
+    #[test]
+    fn test_orders_round_trip() {
+        let db = InMemoryStorage::new();
+        let pizza = order("Pizza");
+        let salad = order("Salad");
+
+        let ids = db.add_table_orders(1, vec![pizza.clone(), salad.clone()]).unwrap();
+        assert_eq!(ids, vec![pizza.id, salad.id]);
+
+        let orders = db.get_table_orders(1).unwrap();
+        assert_eq!(orders.len(), 2);
+        assert_eq!(orders[0].menu_item, "Pizza");
+        assert_eq!(orders[1].menu_item, "Salad");
+
+        let fetched = db.get_table_order(1, salad.id).unwrap();
+        assert_eq!(fetched.menu_item, "Salad");
+        assert_eq!(fetched.cooking_duration_secs, 600);
+    }
+
+    #[test]
+    fn test_orders_are_kept_per_table() {
+        let db = InMemoryStorage::new();
+        let pizza = order("Pizza");
+        db.add_table_orders(1, vec![pizza.clone()]).unwrap();
+
+        assert!(db.get_table_orders(2).unwrap().is_empty());
+        assert!(matches!(db.get_table_order(2, pizza.id), Err(StorageError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_add_table_orders_is_all_or_nothing() {
+        let db = InMemoryStorage::new();
+        let pizza = order("Pizza");
+        db.add_table_orders(1, vec![pizza.clone()]).unwrap();
+
+        let salad = order("Salad");
+        let result = db.add_table_orders(2, vec![salad.clone(), pizza.clone()]);
+        assert!(matches!(result, Err(StorageError::Conflict(_))));
+        let result = db.add_table_orders(2, vec![salad.clone(), salad.clone()]);
+        assert!(matches!(result, Err(StorageError::Conflict(_))));
+
+        assert!(db.get_table_orders(2).unwrap().is_empty());
+        assert_eq!(db.get_table_orders(1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_delete_table_order() {
+        let db = InMemoryStorage::new();
+        let pizza = order("Pizza");
+        let salad = order("Salad");
+        db.add_table_orders(1, vec![pizza.clone(), salad.clone()]).unwrap();
+
+        db.delete_table_order(1, pizza.id).unwrap();
+
+        let orders = db.get_table_orders(1).unwrap();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].id, salad.id);
+        assert!(matches!(db.get_table_order(1, pizza.id), Err(StorageError::NotFound(_))));
+        assert!(matches!(db.delete_table_order(1, pizza.id), Err(StorageError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_update_order_status() {
+        let db = InMemoryStorage::new();
+        let pizza = order("Pizza");
+        db.add_table_orders(1, vec![pizza.clone()]).unwrap();
+
+        let at = Utc::now();
+        db.update_order_status(1, pizza.id, OrderStatus::Placed, OrderStatus::Cooking, at).unwrap();
+        let fetched = db.get_table_order(1, pizza.id).unwrap();
+        assert_eq!(fetched.status, OrderStatus::Cooking);
+        assert_eq!(fetched.started_at, Some(at));
+
+        let result = db.update_order_status(1, pizza.id, OrderStatus::Placed, OrderStatus::Cancelled, at);
+        assert!(matches!(result, Err(StorageError::Conflict(_))));
+        let result = db.update_order_status(2, pizza.id, OrderStatus::Cooking, OrderStatus::Ready, at);
+        assert!(matches!(result, Err(StorageError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_count_open_orders() {
+        let db = InMemoryStorage::new();
+        let pizza = order("Pizza");
+        let salad = order("Salad");
+        let soup = order("Soup");
+        db.add_table_orders(1, vec![pizza.clone(), salad.clone()]).unwrap();
+        db.add_table_orders(2, vec![soup]).unwrap();
+        assert_eq!(db.count_open_orders(), Ok(3));
+
+        let at = Utc::now();
+        db.update_order_status(1, pizza.id, OrderStatus::Placed, OrderStatus::Cooking, at).unwrap();
+        db.update_order_status(1, salad.id, OrderStatus::Placed, OrderStatus::Cancelled, at).unwrap();
+        assert_eq!(db.count_open_orders(), Ok(2));
+    }
+
+    #[test]
+    fn test_menu_items_round_trip() {
+        let db = InMemoryStorage::new();
+        let pizza = menu_item("Pizza", "Mains");
+        let salad = menu_item("Salad", "Starters");
+        let burger = menu_item("Burger", "Mains");
+        for item in [pizza.clone(), salad.clone(), burger.clone()] {
+            db.add_menu_item(item).unwrap();
+        }
+
+        let names: Vec<String> = db.get_menu_items().unwrap().into_iter().map(|item| item.name).collect();
+        assert_eq!(names, vec!["Burger", "Pizza", "Salad"]);
+
+        let mut updated = pizza.clone();
+        updated.price = 1400;
+        updated.available = false;
+        db.update_menu_item(updated).unwrap();
+        let fetched = db.get_menu_item(pizza.id).unwrap();
+        assert_eq!(fetched.price, 1400);
+        assert!(!fetched.available);
+
+        db.delete_menu_item(salad.id).unwrap();
+        assert!(matches!(db.get_menu_item(salad.id), Err(StorageError::NotFound(_))));
+        assert!(matches!(db.delete_menu_item(salad.id), Err(StorageError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_menu_item_names_are_unique() {
+        let db = InMemoryStorage::new();
+        let pizza = menu_item("Pizza", "Mains");
+        let salad = menu_item("Salad", "Starters");
+        db.add_menu_item(pizza).unwrap();
+        db.add_menu_item(salad.clone()).unwrap();
+
+        let result = db.add_menu_item(menu_item("pizza", "Mains"));
+        assert!(matches!(result, Err(StorageError::Conflict(_))));
+
+        let mut renamed = salad;
+        renamed.name = "PIZZA".to_string();
+        assert!(matches!(db.update_menu_item(renamed), Err(StorageError::Conflict(_))));
+    }
+}
@@ -0,0 +1,143 @@
+use crate::db::StorageError;
+
+/// A numbered, forward-only schema change.
+///
+/// Versions start at 1 and must be listed in ascending order without gaps.
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+/// A database that can record which migrations it has applied.
+///
+/// Implementations keep the applied versions in a `schema_migrations` table, creating
+/// it on first use.
+pub trait Migrator {
+    fn applied_versions(&self) -> Result<Vec<u32>, StorageError>;
+    fn apply_migration(&self, migration: &Migration) -> Result<(), StorageError>;
+}
+
+/// Returns the migrations that still have to run, or an error if the database has
+/// applied a migration this binary does not know about.
+pub fn pending<'a>(
+    migrations: &'a [Migration],
+    applied: &[u32],
+) -> Result<Vec<&'a Migration>, StorageError> {
+    let latest = migrations.last().map(|migration| migration.version).unwrap_or(0);
+    if let Some(unknown) = applied
+        .iter()
+        .find(|version| !migrations.iter().any(|migration| migration.version == **version))
+    {
+        return Err(StorageError::Conflict(format!(
+            "Database schema is at migration {} but this binary only knows migrations up to {}",
+            unknown, latest
+        )));
+    }
+    Ok(migrations
+        .iter()
+        .filter(|migration| !applied.contains(&migration.version))
+        .collect())
+}
+
+/// Applies every pending migration in order and returns the versions that ran.
+pub fn run(migrator: &dyn Migrator, migrations: &[Migration]) -> Result<Vec<u32>, StorageError> {
+    let applied = migrator.applied_versions()?;
+    let mut ran = Vec::new();
+    for migration in pending(migrations, &applied)? {
+        migrator.apply_migration(migration).map_err(|e| {
+            StorageError::Internal(format!(
+                "Migration {} ({}) failed: {}",
+                migration.version, migration.name, e
+            ))
+        })?;
+        ran.push(migration.version);
+    }
+    Ok(ran)
+}
+
+/// Checks that the database schema matches this binary exactly, without changing it.
+pub fn verify(migrator: &dyn Migrator, migrations: &[Migration]) -> Result<(), StorageError> {
+    let applied = migrator.applied_versions()?;
+    let pending = pending(migrations, &applied)?;
+    match pending.first() {
+        Some(migration) => Err(StorageError::Conflict(format!(
+            "Database schema is missing migration {} ({}); run the migrate command first",
+            migration.version, migration.name
+        ))),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    const MIGRATIONS: &[Migration] = &[
+        Migration { version: 1, name: "create_orders", sql: "" },
+        Migration { version: 2, name: "add_status", sql: "" },
+    ];
+
+    struct FakeMigrator {
+        applied: Mutex<Vec<u32>>,
+        fail_on: Option<u32>,
+    }
+
+    impl FakeMigrator {
+        fn new(applied: Vec<u32>) -> Self {
+            FakeMigrator { applied: Mutex::new(applied), fail_on: None }
+        }
+    }
+
+    impl Migrator for FakeMigrator {
+        fn applied_versions(&self) -> Result<Vec<u32>, StorageError> {
+            Ok(self.applied.lock().unwrap().clone())
+        }
+
+        fn apply_migration(&self, migration: &Migration) -> Result<(), StorageError> {
+            if self.fail_on == Some(migration.version) {
+                return Err(StorageError::Internal("syntax error".into()));
+            }
+            self.applied.lock().unwrap().push(migration.version);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_run_applies_pending_in_order() {
+        let migrator = FakeMigrator::new(vec![]);
+        assert_eq!(run(&migrator, MIGRATIONS), Ok(vec![1, 2]));
+        assert_eq!(*migrator.applied.lock().unwrap(), vec![1, 2]);
+
+        assert_eq!(run(&migrator, MIGRATIONS), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_run_only_applies_missing_versions() {
+        let migrator = FakeMigrator::new(vec![1]);
+        assert_eq!(run(&migrator, MIGRATIONS), Ok(vec![2]));
+    }
+
+    #[test]
+    fn test_run_stops_at_failing_migration() {
+        let migrator = FakeMigrator { applied: Mutex::new(vec![]), fail_on: Some(2) };
+        let result = run(&migrator, MIGRATIONS);
+        assert!(matches!(result, Err(StorageError::Internal(message)) if message.contains("add_status")));
+        assert_eq!(*migrator.applied.lock().unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn test_database_ahead_of_binary_is_rejected() {
+        let migrator = FakeMigrator::new(vec![1, 2, 3]);
+        assert!(matches!(run(&migrator, MIGRATIONS), Err(StorageError::Conflict(_))));
+        assert!(matches!(verify(&migrator, MIGRATIONS), Err(StorageError::Conflict(_))));
+        assert_eq!(*migrator.applied.lock().unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_verify_requires_every_migration() {
+        assert!(matches!(verify(&FakeMigrator::new(vec![1]), MIGRATIONS), Err(StorageError::Conflict(_))));
+        assert_eq!(verify(&FakeMigrator::new(vec![1, 2]), MIGRATIONS), Ok(()));
+    }
+}
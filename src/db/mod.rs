@@ -1,16 +1,609 @@
+pub mod blocking;
+#[cfg(test)]
+pub mod conformance;
+pub mod decode;
+pub mod memory;
+pub mod migrations;
 pub mod mysql;
+pub mod postgres;
+pub mod sqlite;
 
+use crate::db::decode::CorruptRow;
+pub use crate::db::decode::Decoding;
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::str::FromStr;
 use uuid::Uuid;
 
+/// Where an order is in its lifecycle: placed → cooking → ready → served, with
+/// cancellation possible until the dish is ready.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderStatus {
+    Placed,
+    Cooking,
+    Ready,
+    Served,
+    Cancelled,
+}
+
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Placed => "placed",
+            OrderStatus::Cooking => "cooking",
+            OrderStatus::Ready => "ready",
+            OrderStatus::Served => "served",
+            OrderStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn can_transition_to(&self, next: OrderStatus) -> bool {
+        matches!(
+            (self, next),
+            (OrderStatus::Placed, OrderStatus::Cooking)
+                | (OrderStatus::Placed, OrderStatus::Cancelled)
+                | (OrderStatus::Cooking, OrderStatus::Ready)
+                | (OrderStatus::Cooking, OrderStatus::Cancelled)
+                | (OrderStatus::Ready, OrderStatus::Served)
+        )
+    }
+}
+
+impl fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for OrderStatus {
+    type Err = StorageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "placed" => Ok(OrderStatus::Placed),
+            "cooking" => Ok(OrderStatus::Cooking),
+            "ready" => Ok(OrderStatus::Ready),
+            "served" => Ok(OrderStatus::Served),
+            "cancelled" => Ok(OrderStatus::Cancelled),
+            other => Err(StorageError::Validation(format!("Unknown order status '{}'", other))),
+        }
+    }
+}
+
+/// The column that records when an order entered `status`, in the SQL backends.
+pub(crate) fn status_timestamp_column(status: OrderStatus) -> Option<&'static str> {
+    match status {
+        OrderStatus::Placed => None,
+        OrderStatus::Cooking => Some("started_at"),
+        OrderStatus::Ready => Some("finished_at"),
+        OrderStatus::Served => Some("served_at"),
+        OrderStatus::Cancelled => Some("cancelled_at"),
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Order {
     pub id: Uuid,
     pub menu_item: String,
-    pub cooking_time: String,
+    pub created_at: DateTime<Utc>,
+    pub cooking_duration_secs: u64,
+    pub status: OrderStatus,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub served_at: Option<DateTime<Utc>>,
+    pub cancelled_at: Option<DateTime<Utc>>,
+    /// The seating the order was taken in. Orders placed before sessions existed have
+    /// none.
+    pub session_id: Option<Uuid>,
+    /// The menu price when the order was placed, in minor currency units, so later menu
+    /// changes do not alter what the table is charged.
+    pub price: u64,
+    /// The seat the order is for, when the server noted one.
+    pub seat: Option<u32>,
+    /// The kitchen station that cooks it, taken from the menu when it was placed.
+    pub station: Station,
+    /// Orders sent to the kitchen together share a ticket; on its own an order is its
+    /// own ticket.
+    pub ticket_id: Uuid,
+}
+
+impl Order {
+    /// Creates an order that has just been placed.
+    pub fn placed(
+        id: Uuid,
+        menu_item: String,
+        created_at: DateTime<Utc>,
+        cooking_duration_secs: u64,
+    ) -> Self {
+        Order {
+            id,
+            menu_item,
+            created_at,
+            cooking_duration_secs,
+            status: OrderStatus::Placed,
+            started_at: None,
+            finished_at: None,
+            served_at: None,
+            cancelled_at: None,
+            session_id: None,
+            price: 0,
+            seat: None,
+            station: Station::default(),
+            ticket_id: id,
+        }
+    }
+
+    /// Moves the order to `status`, stamping the matching transition time.
+    pub fn set_status(&mut self, status: OrderStatus, at: DateTime<Utc>) {
+        self.status = status;
+        match status {
+            OrderStatus::Placed => {}
+            OrderStatus::Cooking => self.started_at = Some(at),
+            OrderStatus::Ready => self.finished_at = Some(at),
+            OrderStatus::Served => self.served_at = Some(at),
+            OrderStatus::Cancelled => self.cancelled_at = Some(at),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MenuItem {
+    pub id: Uuid,
+    pub name: String,
+    pub category: String,
+    /// Price in minor currency units, e.g. cents.
+    pub price: u64,
+    pub prep_time_minutes: u32,
+    pub available: bool,
+    pub station: Station,
+}
+
+/// The part of the kitchen an item is made in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Station {
+    #[default]
+    Grill,
+    Fryer,
+    Cold,
+    Bar,
+}
+
+impl Station {
+    pub const ALL: [Station; 4] = [Station::Grill, Station::Fryer, Station::Cold, Station::Bar];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Station::Grill => "grill",
+            Station::Fryer => "fryer",
+            Station::Cold => "cold",
+            Station::Bar => "bar",
+        }
+    }
+}
+
+impl fmt::Display for Station {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Station {
+    type Err = StorageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "grill" => Ok(Station::Grill),
+            "fryer" => Ok(Station::Fryer),
+            "cold" => Ok(Station::Cold),
+            "bar" => Ok(Station::Bar),
+            other => Err(StorageError::Validation(format!(
+                "Unknown station '{}', expected grill, fryer, cold or bar",
+                other
+            ))),
+        }
+    }
+}
+
+/// Whether guests are seated at a table. Orders are only taken for open tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableStatus {
+    Closed,
+    Open,
+}
+
+impl TableStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TableStatus::Closed => "closed",
+            TableStatus::Open => "open",
+        }
+    }
+}
+
+impl fmt::Display for TableStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TableStatus {
+    type Err = StorageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "closed" => Ok(TableStatus::Closed),
+            "open" => Ok(TableStatus::Open),
+            other => Err(StorageError::Validation(format!("Unknown table status '{}'", other))),
+        }
+    }
+}
+
+/// A table on the floor plan, identified by the number guests see on it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    pub number: u64,
+    pub seats: u32,
+    pub section: String,
+    pub status: TableStatus,
+    /// The seating in progress while the table is open.
+    pub session_id: Option<Uuid>,
+}
+
+/// One seating at a table, from opening it to closing it. Orders taken in between
+/// belong to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    pub id: Uuid,
+    pub table_number: u64,
+    pub opened_at: DateTime<Utc>,
+    pub closed_at: Option<DateTime<Utc>>,
+}
+
+/// Where a bill is in being settled: open until payments cover it, then paid, then
+/// closed once the guests have gone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BillStatus {
+    Open,
+    Paid,
+    Closed,
+}
+
+impl BillStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BillStatus::Open => "open",
+            BillStatus::Paid => "paid",
+            BillStatus::Closed => "closed",
+        }
+    }
+}
+
+impl fmt::Display for BillStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for BillStatus {
+    type Err = StorageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(BillStatus::Open),
+            "paid" => Ok(BillStatus::Paid),
+            "closed" => Ok(BillStatus::Closed),
+            other => Err(StorageError::Validation(format!("Unknown bill status '{}'", other))),
+        }
+    }
+}
+
+/// One share of a session's bill after splitting it, with its amounts fixed in minor
+/// currency units when the split was made.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitBill {
+    pub id: Uuid,
+    pub session_id: Uuid,
+    /// Where the share comes in the split, from 0.
+    pub position: u32,
+    pub label: String,
+    /// The orders charged on this share in ascending order; empty when the bill was
+    /// split evenly.
+    pub order_ids: Vec<Uuid>,
+    pub subtotal: u64,
+    pub service_charge: u64,
+    pub tax: u64,
+    pub total: u64,
+    pub status: BillStatus,
+}
+
+/// How a payment was made. Cards are processed outside the app, so only a reference
+/// is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tender {
+    Cash,
+    Card,
+    GiftCard,
+}
+
+impl Tender {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Tender::Cash => "cash",
+            Tender::Card => "card",
+            Tender::GiftCard => "gift_card",
+        }
+    }
+}
+
+impl fmt::Display for Tender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Tender {
+    type Err = StorageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cash" => Ok(Tender::Cash),
+            "card" => Ok(Tender::Card),
+            "gift_card" => Ok(Tender::GiftCard),
+            other => Err(StorageError::Validation(format!(
+                "Unknown tender '{}', expected cash, card or gift_card",
+                other
+            ))),
+        }
+    }
+}
+
+/// Money taken for a session's bill, in minor currency units.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Payment {
+    pub id: Uuid,
+    pub session_id: Uuid,
+    /// The split bill paid, or `None` for the session's whole bill.
+    pub bill_id: Option<Uuid>,
+    pub tender: Tender,
+    /// The card or gift card reference.
+    pub reference: Option<String>,
+    /// What went towards the bill.
+    pub amount: u64,
+    pub tip: u64,
+    /// Cash handed back.
+    pub change: u64,
+    pub paid_at: DateTime<Utc>,
+}
+
+/// An external endpoint that order events are posted to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub url: String,
+    /// The event names it wants, or every event when empty.
+    pub events: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// How the SQL backends store a webhook's event names, as one comma-separated column.
+pub(crate) fn join_events(events: &[String]) -> String {
+    events.join(",")
+}
+
+/// Reads back a column written by `join_events`.
+pub(crate) fn split_events(events: &str) -> Vec<String> {
+    events.split(',').filter(|name| !name.is_empty()).map(str::to_string).collect()
+}
+
+/// An event that could not be delivered to a webhook, kept for a manager to look at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadLetter {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event_id: u64,
+    pub event: String,
+    /// The body every attempt posted.
+    pub payload: String,
+    pub attempts: u32,
+    pub last_error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+/// Errors returned by every `Storage` backend, classified so callers can react to the
+/// kind of failure instead of its wording.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageError {
+    /// The requested record does not exist.
+    NotFound(String),
+    /// The write clashes with a record that already exists.
+    Conflict(String),
+    /// The input was rejected before it was stored.
+    Validation(String),
+    /// The caller's role does not allow the change.
+    Forbidden(String),
+    /// The backing store could not be reached.
+    Unavailable(String),
+    /// Anything else that went wrong inside the backend.
+    Internal(String),
+    /// A stored row could not be decoded.
+    Corrupt(CorruptRow),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::NotFound(message)
+            | StorageError::Conflict(message)
+            | StorageError::Validation(message)
+            | StorageError::Forbidden(message)
+            | StorageError::Unavailable(message)
+            | StorageError::Internal(message) => f.write_str(message),
+            StorageError::Corrupt(corrupt) => corrupt.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<CorruptRow> for StorageError {
+    fn from(corrupt: CorruptRow) -> Self {
+        StorageError::Corrupt(corrupt)
+    }
 }
 
 pub trait Storage: Send + Sync {
-    fn get_table_orders(&self, table_id: u64) -> Result<Vec<Order>, String>;
-    fn get_table_order(&self, table_id: u64, order_id: Uuid) -> Result<Order, String>;
-    fn add_table_orders(&self, table_id: u64, orders: Vec<Order>) -> Result<Vec<Uuid>, String>;
-    fn delete_table_order(&self, table_id: u64, order_id: Uuid) -> Result<(), String>;
+    fn get_table_orders(&self, table_id: u64) -> Result<Vec<Order>, StorageError>;
+    fn get_table_order(&self, table_id: u64, order_id: Uuid) -> Result<Order, StorageError>;
+    fn add_table_orders(&self, table_id: u64, orders: Vec<Order>) -> Result<Vec<Uuid>, StorageError>;
+    fn delete_table_order(&self, table_id: u64, order_id: Uuid) -> Result<(), StorageError>;
+    /// Moves an order from `from` to `to`, failing with `Conflict` if its status is no
+    /// longer `from`.
+    fn update_order_status(
+        &self,
+        table_id: u64,
+        order_id: Uuid,
+        from: OrderStatus,
+        to: OrderStatus,
+        at: DateTime<Utc>,
+    ) -> Result<(), StorageError>;
+    /// Counts orders across every table that are still placed or cooking.
+    fn count_open_orders(&self) -> Result<usize, StorageError>;
+    /// Lists every placed or cooking order across all tables with its table, in the
+    /// order they were placed.
+    fn get_open_orders(&self) -> Result<Vec<(u64, Order)>, StorageError>;
+
+    fn get_menu_items(&self) -> Result<Vec<MenuItem>, StorageError>;
+    fn get_menu_item(&self, menu_item_id: Uuid) -> Result<MenuItem, StorageError>;
+    fn add_menu_item(&self, item: MenuItem) -> Result<Uuid, StorageError>;
+    fn update_menu_item(&self, item: MenuItem) -> Result<(), StorageError>;
+    fn delete_menu_item(&self, menu_item_id: Uuid) -> Result<(), StorageError>;
+
+    /// Lists the registered tables in number order.
+    fn get_tables(&self) -> Result<Vec<Table>, StorageError>;
+    fn get_table(&self, number: u64) -> Result<Table, StorageError>;
+    fn add_table(&self, table: Table) -> Result<u64, StorageError>;
+    /// Opens a closed table and starts `session` at it, failing with `Conflict` if the
+    /// table is already open.
+    fn open_table_session(&self, number: u64, session: Session) -> Result<(), StorageError>;
+    /// Closes the table and ends its session, failing with `Conflict` unless
+    /// `session_id` is the session in progress.
+    fn close_table_session(&self, number: u64, session_id: Uuid, closed_at: DateTime<Utc>) -> Result<(), StorageError>;
+    /// Lists every session held at a table, oldest first.
+    fn get_table_sessions(&self, number: u64) -> Result<Vec<Session>, StorageError>;
+    fn get_table_session(&self, number: u64, session_id: Uuid) -> Result<Session, StorageError>;
+    /// Lists the orders taken during a session, oldest first, handling rows that do not
+    /// decode as `decoding` asks.
+    fn get_session_orders(&self, session_id: Uuid, decoding: Decoding) -> Result<Vec<Order>, StorageError>;
+
+    /// Replaces a session's split bills with `bills`, failing with `Conflict` if any of
+    /// the current ones is no longer open.
+    fn replace_split_bills(&self, session_id: Uuid, bills: Vec<SplitBill>) -> Result<(), StorageError>;
+    /// Lists a session's split bills by position, handling rows that do not decode as
+    /// `decoding` asks.
+    fn get_split_bills(&self, session_id: Uuid, decoding: Decoding) -> Result<Vec<SplitBill>, StorageError>;
+    fn get_split_bill(&self, session_id: Uuid, bill_id: Uuid) -> Result<SplitBill, StorageError>;
+    /// Moves a split bill from `from` to `to`, failing with `Conflict` if its status is
+    /// no longer `from`.
+    fn update_split_bill_status(
+        &self,
+        session_id: Uuid,
+        bill_id: Uuid,
+        from: BillStatus,
+        to: BillStatus,
+    ) -> Result<(), StorageError>;
+    fn add_payment(&self, payment: Payment) -> Result<Uuid, StorageError>;
+    /// Lists the payments taken during a session, oldest first, handling rows that do not
+    /// decode as `decoding` asks.
+    fn get_session_payments(&self, session_id: Uuid, decoding: Decoding) -> Result<Vec<Payment>, StorageError>;
+
+    fn add_webhook(&self, webhook: Webhook) -> Result<Uuid, StorageError>;
+    /// Lists the webhooks, oldest first.
+    fn get_webhooks(&self) -> Result<Vec<Webhook>, StorageError>;
+    fn get_webhook(&self, webhook_id: Uuid) -> Result<Webhook, StorageError>;
+    fn delete_webhook(&self, webhook_id: Uuid) -> Result<(), StorageError>;
+    fn add_dead_letter(&self, dead_letter: DeadLetter) -> Result<Uuid, StorageError>;
+    /// Lists undelivered events, oldest failure first.
+    fn get_dead_letters(&self) -> Result<Vec<DeadLetter>, StorageError>;
+    fn delete_dead_letter(&self, dead_letter_id: Uuid) -> Result<(), StorageError>;
+}
+
+/// Asynchronous counterpart of `Storage`, used by the Rocket handlers so database
+/// calls never block the async runtime.
+///
+/// Synchronous backends are wrapped in `blocking::BlockingStorage`.
+#[rocket::async_trait]
+pub trait AsyncStorage: Send + Sync {
+    async fn get_table_orders(&self, table_id: u64) -> Result<Vec<Order>, StorageError>;
+    async fn get_table_order(&self, table_id: u64, order_id: Uuid) -> Result<Order, StorageError>;
+    async fn add_table_orders(&self, table_id: u64, orders: Vec<Order>) -> Result<Vec<Uuid>, StorageError>;
+    async fn delete_table_order(&self, table_id: u64, order_id: Uuid) -> Result<(), StorageError>;
+    /// Moves an order from `from` to `to`, failing with `Conflict` if its status is no
+    /// longer `from`.
+    async fn update_order_status(
+        &self,
+        table_id: u64,
+        order_id: Uuid,
+        from: OrderStatus,
+        to: OrderStatus,
+        at: DateTime<Utc>,
+    ) -> Result<(), StorageError>;
+    /// Counts orders across every table that are still placed or cooking.
+    async fn count_open_orders(&self) -> Result<usize, StorageError>;
+    /// Lists every placed or cooking order across all tables with its table, in the
+    /// order they were placed.
+    async fn get_open_orders(&self) -> Result<Vec<(u64, Order)>, StorageError>;
+
+    async fn get_menu_items(&self) -> Result<Vec<MenuItem>, StorageError>;
+    async fn get_menu_item(&self, menu_item_id: Uuid) -> Result<MenuItem, StorageError>;
+    async fn add_menu_item(&self, item: MenuItem) -> Result<Uuid, StorageError>;
+    async fn update_menu_item(&self, item: MenuItem) -> Result<(), StorageError>;
+    async fn delete_menu_item(&self, menu_item_id: Uuid) -> Result<(), StorageError>;
+
+    /// Lists the registered tables in number order.
+    async fn get_tables(&self) -> Result<Vec<Table>, StorageError>;
+    async fn get_table(&self, number: u64) -> Result<Table, StorageError>;
+    async fn add_table(&self, table: Table) -> Result<u64, StorageError>;
+    /// Opens a closed table and starts `session` at it, failing with `Conflict` if the
+    /// table is already open.
+    async fn open_table_session(&self, number: u64, session: Session) -> Result<(), StorageError>;
+    /// Closes the table and ends its session, failing with `Conflict` unless
+    /// `session_id` is the session in progress.
+    async fn close_table_session(&self, number: u64, session_id: Uuid, closed_at: DateTime<Utc>) -> Result<(), StorageError>;
+    /// Lists every session held at a table, oldest first.
+    async fn get_table_sessions(&self, number: u64) -> Result<Vec<Session>, StorageError>;
+    async fn get_table_session(&self, number: u64, session_id: Uuid) -> Result<Session, StorageError>;
+    /// Lists the orders taken during a session, oldest first, handling rows that do not
+    /// decode as `decoding` asks.
+    async fn get_session_orders(&self, session_id: Uuid, decoding: Decoding) -> Result<Vec<Order>, StorageError>;
+
+    /// Replaces a session's split bills with `bills`, failing with `Conflict` if any of
+    /// the current ones is no longer open.
+    async fn replace_split_bills(&self, session_id: Uuid, bills: Vec<SplitBill>) -> Result<(), StorageError>;
+    /// Lists a session's split bills by position, handling rows that do not decode as
+    /// `decoding` asks.
+    async fn get_split_bills(&self, session_id: Uuid, decoding: Decoding) -> Result<Vec<SplitBill>, StorageError>;
+    async fn get_split_bill(&self, session_id: Uuid, bill_id: Uuid) -> Result<SplitBill, StorageError>;
+    /// Moves a split bill from `from` to `to`, failing with `Conflict` if its status is
+    /// no longer `from`.
+    async fn update_split_bill_status(
+        &self,
+        session_id: Uuid,
+        bill_id: Uuid,
+        from: BillStatus,
+        to: BillStatus,
+    ) -> Result<(), StorageError>;
+    async fn add_payment(&self, payment: Payment) -> Result<Uuid, StorageError>;
+    /// Lists the payments taken during a session, oldest first, handling rows that do not
+    /// decode as `decoding` asks.
+    async fn get_session_payments(&self, session_id: Uuid, decoding: Decoding) -> Result<Vec<Payment>, StorageError>;
+
+    async fn add_webhook(&self, webhook: Webhook) -> Result<Uuid, StorageError>;
+    /// Lists the webhooks, oldest first.
+    async fn get_webhooks(&self) -> Result<Vec<Webhook>, StorageError>;
+    async fn get_webhook(&self, webhook_id: Uuid) -> Result<Webhook, StorageError>;
+    async fn delete_webhook(&self, webhook_id: Uuid) -> Result<(), StorageError>;
+    async fn add_dead_letter(&self, dead_letter: DeadLetter) -> Result<Uuid, StorageError>;
+    /// Lists undelivered events, oldest failure first.
+    async fn get_dead_letters(&self) -> Result<Vec<DeadLetter>, StorageError>;
+    async fn delete_dead_letter(&self, dead_letter_id: Uuid) -> Result<(), StorageError>;
 }
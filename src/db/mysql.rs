@@ -1,102 +1,1222 @@
-use crate::db::{Order, Storage};
+use crate::db::decode::{self, hex_key, CorruptRow, Decoding};
+use crate::db::migrations::{self, Migration, Migrator};
+use crate::db::{
+    join_events, split_events, status_timestamp_column, BillStatus, DeadLetter, MenuItem, Order, OrderStatus, Payment,
+    Session, SplitBill, Station, Storage, StorageError, Table, Webhook,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
 use mysql::prelude::*;
 use mysql::*;
 use uuid::Uuid;
 
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_orders",
+        sql: include_str!("../../migrations/mysql/0001_create_orders.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "create_menu_items",
+        sql: include_str!("../../migrations/mysql/0002_create_menu_items.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "add_order_status",
+        sql: include_str!("../../migrations/mysql/0003_add_order_status.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "structured_cooking_time",
+        sql: include_str!("../../migrations/mysql/0004_structured_cooking_time.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "orders_keys_and_indexes",
+        sql: include_str!("../../migrations/mysql/0005_orders_keys_and_indexes.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "create_dining_tables",
+        sql: include_str!("../../migrations/mysql/0006_create_dining_tables.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "add_table_sessions",
+        sql: include_str!("../../migrations/mysql/0007_add_table_sessions.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "add_order_prices",
+        sql: include_str!("../../migrations/mysql/0008_add_order_prices.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "add_split_bills",
+        sql: include_str!("../../migrations/mysql/0009_add_split_bills.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "create_payments",
+        sql: include_str!("../../migrations/mysql/0010_create_payments.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "add_kitchen_stations",
+        sql: include_str!("../../migrations/mysql/0011_add_kitchen_stations.sql"),
+    },
+    Migration {
+        version: 12,
+        name: "create_webhooks",
+        sql: include_str!("../../migrations/mysql/0012_create_webhooks.sql"),
+    },
+];
+
+const SELECT_ORDERS_SQL: &str = "SELECT order_id, menu_item, created_at, cooking_duration_secs, status, started_at, finished_at, served_at, cancelled_at, session_id, price, seat, station, ticket_id, table_id FROM orders";
+
+/// Takes one column out of `row`, describing the problem if it is missing or has the
+/// wrong type.
+fn take_column<T: FromValue>(row: &mut Row, column: &str) -> std::result::Result<T, String> {
+    match row.take_opt::<T, _>(column) {
+        Some(Ok(value)) => Ok(value),
+        Some(Err(FromValueError(value))) => Err(format!("{} has unexpected value {:?}", column, value)),
+        None => Err(format!("{} is missing", column)),
+    }
+}
+
+/// Takes a nullable UUID stored as `BINARY(16)`.
+fn take_uuid_column(row: &mut Row, column: &str) -> std::result::Result<Option<Uuid>, String> {
+    let raw: Option<Vec<u8>> = take_column(row, column)?;
+    raw.map(|raw| Uuid::from_slice(&raw).map_err(|e| format!("{} is not a UUID: {}", column, e)))
+        .transpose()
+}
+
+fn take_station_column(row: &mut Row, column: &str) -> std::result::Result<Station, String> {
+    let raw: String = take_column(row, column)?;
+    raw.parse().map_err(|e: StorageError| e.to_string())
+}
+
+fn order_from_row(mut row: Row) -> std::result::Result<Order, CorruptRow> {
+    let raw_id: Vec<u8> =
+        take_column(&mut row, "order_id").map_err(|reason| CorruptRow::new("orders", "?", reason))?;
+    let id = Uuid::from_slice(&raw_id)
+        .map_err(|e| CorruptRow::new("orders", hex_key(&raw_id), format!("order_id is not a UUID: {}", e)))?;
+    let corrupt = |reason: String| CorruptRow::new("orders", id.to_string(), reason);
+    let status: String = take_column(&mut row, "status").map_err(corrupt)?;
+    let created_at: NaiveDateTime = take_column(&mut row, "created_at").map_err(corrupt)?;
+    let started_at: Option<NaiveDateTime> = take_column(&mut row, "started_at").map_err(corrupt)?;
+    let finished_at: Option<NaiveDateTime> = take_column(&mut row, "finished_at").map_err(corrupt)?;
+    let served_at: Option<NaiveDateTime> = take_column(&mut row, "served_at").map_err(corrupt)?;
+    let cancelled_at: Option<NaiveDateTime> = take_column(&mut row, "cancelled_at").map_err(corrupt)?;
+    Ok(Order {
+        id,
+        menu_item: take_column(&mut row, "menu_item").map_err(corrupt)?,
+        created_at: created_at.and_utc(),
+        cooking_duration_secs: take_column(&mut row, "cooking_duration_secs").map_err(corrupt)?,
+        status: status.parse().map_err(|e: StorageError| corrupt(e.to_string()))?,
+        started_at: started_at.map(|at| at.and_utc()),
+        finished_at: finished_at.map(|at| at.and_utc()),
+        served_at: served_at.map(|at| at.and_utc()),
+        cancelled_at: cancelled_at.map(|at| at.and_utc()),
+        session_id: take_uuid_column(&mut row, "session_id").map_err(corrupt)?,
+        price: take_column(&mut row, "price").map_err(corrupt)?,
+        seat: take_column(&mut row, "seat").map_err(corrupt)?,
+        station: take_station_column(&mut row, "station").map_err(corrupt)?,
+        // Orders from before tickets existed are tickets of their own.
+        ticket_id: take_uuid_column(&mut row, "ticket_id").map_err(corrupt)?.unwrap_or(id),
+    })
+}
+
+const SELECT_MENU_ITEMS_SQL: &str = "SELECT menu_item_id, name, category, price, prep_time_minutes, available, station FROM menu_items";
+
+fn menu_item_from_row(mut row: Row) -> std::result::Result<MenuItem, CorruptRow> {
+    let raw_id: String =
+        take_column(&mut row, "menu_item_id").map_err(|reason| CorruptRow::new("menu_items", "?", reason))?;
+    let id = Uuid::parse_str(&raw_id)
+        .map_err(|e| CorruptRow::new("menu_items", raw_id.clone(), format!("menu_item_id is not a UUID: {}", e)))?;
+    let corrupt = |reason: String| CorruptRow::new("menu_items", id.to_string(), reason);
+    Ok(MenuItem {
+        id,
+        name: take_column(&mut row, "name").map_err(corrupt)?,
+        category: take_column(&mut row, "category").map_err(corrupt)?,
+        price: take_column(&mut row, "price").map_err(corrupt)?,
+        prep_time_minutes: take_column(&mut row, "prep_time_minutes").map_err(corrupt)?,
+        available: take_column(&mut row, "available").map_err(corrupt)?,
+        station: take_station_column(&mut row, "station").map_err(corrupt)?,
+    })
+}
+
+const SELECT_TABLES_SQL: &str = "SELECT table_number, seats, section, status, current_session_id FROM dining_tables";
+
+fn table_from_row(mut row: Row) -> std::result::Result<Table, CorruptRow> {
+    let number: u64 =
+        take_column(&mut row, "table_number").map_err(|reason| CorruptRow::new("dining_tables", "?", reason))?;
+    let corrupt = |reason: String| CorruptRow::new("dining_tables", number.to_string(), reason);
+    let status: String = take_column(&mut row, "status").map_err(corrupt)?;
+    Ok(Table {
+        number,
+        seats: take_column(&mut row, "seats").map_err(corrupt)?,
+        section: take_column(&mut row, "section").map_err(corrupt)?,
+        status: status.parse().map_err(|e: StorageError| corrupt(e.to_string()))?,
+        session_id: take_uuid_column(&mut row, "current_session_id").map_err(corrupt)?,
+    })
+}
+
+const SELECT_SESSIONS_SQL: &str = "SELECT session_id, table_number, opened_at, closed_at FROM table_sessions";
+
+fn session_from_row(mut row: Row) -> std::result::Result<Session, CorruptRow> {
+    let raw_id: Vec<u8> =
+        take_column(&mut row, "session_id").map_err(|reason| CorruptRow::new("table_sessions", "?", reason))?;
+    let id = Uuid::from_slice(&raw_id).map_err(|e| {
+        CorruptRow::new("table_sessions", hex_key(&raw_id), format!("session_id is not a UUID: {}", e))
+    })?;
+    let corrupt = |reason: String| CorruptRow::new("table_sessions", id.to_string(), reason);
+    let opened_at: NaiveDateTime = take_column(&mut row, "opened_at").map_err(corrupt)?;
+    let closed_at: Option<NaiveDateTime> = take_column(&mut row, "closed_at").map_err(corrupt)?;
+    Ok(Session {
+        id,
+        table_number: take_column(&mut row, "table_number").map_err(corrupt)?,
+        opened_at: opened_at.and_utc(),
+        closed_at: closed_at.map(|at| at.and_utc()),
+    })
+}
+
+const SELECT_SPLIT_BILLS_SQL: &str =
+    "SELECT bill_id, session_id, position, label, subtotal, service_charge, tax, total, status FROM split_bills";
+
+/// Reads a split bill without its orders, which live in `split_bill_orders`.
+fn split_bill_from_row(mut row: Row) -> std::result::Result<SplitBill, CorruptRow> {
+    let raw_id: Vec<u8> =
+        take_column(&mut row, "bill_id").map_err(|reason| CorruptRow::new("split_bills", "?", reason))?;
+    let id = Uuid::from_slice(&raw_id)
+        .map_err(|e| CorruptRow::new("split_bills", hex_key(&raw_id), format!("bill_id is not a UUID: {}", e)))?;
+    let corrupt = |reason: String| CorruptRow::new("split_bills", id.to_string(), reason);
+    let status: String = take_column(&mut row, "status").map_err(corrupt)?;
+    Ok(SplitBill {
+        id,
+        session_id: take_uuid_column(&mut row, "session_id")
+            .and_then(|id| id.ok_or_else(|| "session_id is NULL".to_string()))
+            .map_err(corrupt)?,
+        position: take_column(&mut row, "position").map_err(corrupt)?,
+        label: take_column(&mut row, "label").map_err(corrupt)?,
+        order_ids: Vec::new(),
+        subtotal: take_column(&mut row, "subtotal").map_err(corrupt)?,
+        service_charge: take_column(&mut row, "service_charge").map_err(corrupt)?,
+        tax: take_column(&mut row, "tax").map_err(corrupt)?,
+        total: take_column(&mut row, "total").map_err(corrupt)?,
+        status: status.parse().map_err(|e: StorageError| corrupt(e.to_string()))?,
+    })
+}
+
+/// Fills in the orders of each bill from `(bill_id, order_id)` pairs, in ascending order.
+fn attach_split_bill_orders(bills: &mut [SplitBill], pairs: Vec<(Vec<u8>, Vec<u8>)>) -> Result<(), StorageError> {
+    for (raw_bill_id, raw_order_id) in pairs {
+        let order_id = Uuid::from_slice(&raw_order_id).map_err(|e| {
+            CorruptRow::new("split_bill_orders", hex_key(&raw_bill_id), format!("order_id is not a UUID: {}", e))
+        })?;
+        if let Some(bill) = bills.iter_mut().find(|bill| bill.id.as_bytes()[..] == raw_bill_id[..]) {
+            bill.order_ids.push(order_id);
+        }
+    }
+    Ok(())
+}
+
+const SELECT_PAYMENTS_SQL: &str =
+    "SELECT payment_id, session_id, bill_id, tender, reference, amount, tip, change_due, paid_at FROM payments";
+
+fn payment_from_row(mut row: Row) -> std::result::Result<Payment, CorruptRow> {
+    let raw_id: Vec<u8> =
+        take_column(&mut row, "payment_id").map_err(|reason| CorruptRow::new("payments", "?", reason))?;
+    let id = Uuid::from_slice(&raw_id)
+        .map_err(|e| CorruptRow::new("payments", hex_key(&raw_id), format!("payment_id is not a UUID: {}", e)))?;
+    let corrupt = |reason: String| CorruptRow::new("payments", id.to_string(), reason);
+    let tender: String = take_column(&mut row, "tender").map_err(corrupt)?;
+    let paid_at: NaiveDateTime = take_column(&mut row, "paid_at").map_err(corrupt)?;
+    Ok(Payment {
+        id,
+        session_id: take_uuid_column(&mut row, "session_id")
+            .and_then(|id| id.ok_or_else(|| "session_id is NULL".to_string()))
+            .map_err(corrupt)?,
+        bill_id: take_uuid_column(&mut row, "bill_id").map_err(corrupt)?,
+        tender: tender.parse().map_err(|e: StorageError| corrupt(e.to_string()))?,
+        reference: take_column(&mut row, "reference").map_err(corrupt)?,
+        amount: take_column(&mut row, "amount").map_err(corrupt)?,
+        tip: take_column(&mut row, "tip").map_err(corrupt)?,
+        change: take_column(&mut row, "change_due").map_err(corrupt)?,
+        paid_at: paid_at.and_utc(),
+    })
+}
+
+const SELECT_WEBHOOKS_SQL: &str = "SELECT webhook_id, url, events, created_at FROM webhooks";
+
+fn webhook_from_row(mut row: Row) -> std::result::Result<Webhook, CorruptRow> {
+    let raw_id: Vec<u8> =
+        take_column(&mut row, "webhook_id").map_err(|reason| CorruptRow::new("webhooks", "?", reason))?;
+    let id = Uuid::from_slice(&raw_id)
+        .map_err(|e| CorruptRow::new("webhooks", hex_key(&raw_id), format!("webhook_id is not a UUID: {}", e)))?;
+    let corrupt = |reason: String| CorruptRow::new("webhooks", id.to_string(), reason);
+    let events: String = take_column(&mut row, "events").map_err(corrupt)?;
+    let created_at: NaiveDateTime = take_column(&mut row, "created_at").map_err(corrupt)?;
+    Ok(Webhook {
+        id,
+        url: take_column(&mut row, "url").map_err(corrupt)?,
+        events: split_events(&events),
+        created_at: created_at.and_utc(),
+    })
+}
+
+const SELECT_DEAD_LETTERS_SQL: &str = "SELECT dead_letter_id, webhook_id, event_id, event, payload, attempts, last_error, failed_at FROM webhook_dead_letters";
+
+fn dead_letter_from_row(mut row: Row) -> std::result::Result<DeadLetter, CorruptRow> {
+    let raw_id: Vec<u8> = take_column(&mut row, "dead_letter_id")
+        .map_err(|reason| CorruptRow::new("webhook_dead_letters", "?", reason))?;
+    let id = Uuid::from_slice(&raw_id).map_err(|e| {
+        CorruptRow::new("webhook_dead_letters", hex_key(&raw_id), format!("dead_letter_id is not a UUID: {}", e))
+    })?;
+    let corrupt = |reason: String| CorruptRow::new("webhook_dead_letters", id.to_string(), reason);
+    let failed_at: NaiveDateTime = take_column(&mut row, "failed_at").map_err(corrupt)?;
+    Ok(DeadLetter {
+        id,
+        webhook_id: take_uuid_column(&mut row, "webhook_id")
+            .and_then(|id| id.ok_or_else(|| "webhook_id is NULL".to_string()))
+            .map_err(corrupt)?,
+        event_id: take_column(&mut row, "event_id").map_err(corrupt)?,
+        event: take_column(&mut row, "event").map_err(corrupt)?,
+        payload: take_column(&mut row, "payload").map_err(corrupt)?,
+        attempts: take_column(&mut row, "attempts").map_err(corrupt)?,
+        last_error: take_column(&mut row, "last_error").map_err(corrupt)?,
+        failed_at: failed_at.and_utc(),
+    })
+}
+
 pub struct MySqlDb {
     pub pool: Pool,
 }
 
 impl MySqlDb {
+    /// Connects and brings the schema up to date, panicking if either step fails.
     pub fn new(database_url: &str) -> Self {
-        let opts = Opts::from_url(database_url).expect("Incorrect database URL");
-        let pool = Pool::new(opts).expect("Failed to create MySQL connection pool.");
+        let db = Self::connect(database_url).expect("Failed to connect to MySQL");
+        db.migrate().expect("Failed to migrate database");
+        db
+    }
 
-        let conn = pool.get_conn().expect("Failed to establish connection.");
-        Self::setup(conn).expect("Failed to create tables");
+    /// Creates the connection pool without touching the schema.
+    pub fn connect(database_url: &str) -> Result<Self, StorageError> {
+        let opts = Opts::from_url(database_url)
+            .map_err(|e| StorageError::Validation(format!("Incorrect database URL: {}", e)))?;
+        let pool = Pool::new(opts).map_err(StorageError::from)?;
+        Ok(MySqlDb { pool })
+    }
 
-        MySqlDb { pool }
+    pub fn migrate(&self) -> Result<Vec<u32>, StorageError> {
+        migrations::run(self, MIGRATIONS)
     }
 
-    pub fn setup(mut conn: PooledConn) -> Result<(), String> {
+    pub fn verify_schema(&self) -> Result<(), StorageError> {
+        migrations::verify(self, MIGRATIONS)
+    }
+}
+
+impl Migrator for MySqlDb {
+    fn applied_versions(&self) -> Result<Vec<u32>, StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
         conn.query_drop(
             r"
-        DROP TABLE IF EXISTS orders;
-        CREATE TABLE orders (
-            order_id VARCHAR(255),
-            menu_item VARCHAR(255),
-            cooking_time VARCHAR(255),
-            table_id INT
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INT UNSIGNED NOT NULL PRIMARY KEY,
+            name VARCHAR(255) NOT NULL,
+            applied_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
         );
         ",
         )
-            .map_err(|e| format!("Failed to setup table: {:?}", e))
+        .map_err(StorageError::from)?;
+        conn.query("SELECT version FROM schema_migrations ORDER BY version")
+            .map_err(StorageError::from)
+    }
+
+    fn apply_migration(&self, migration: &Migration) -> Result<(), StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        // MySQL stops a multi-statement batch at the first failing statement, but only the
+        // first statement's error comes back from the query itself; the rest arrive as
+        // result sets, which `query_drop` would throw away unread.
+        let mut results = conn.query_iter(migration.sql).map_err(StorageError::from)?;
+        while let Some(set) = results.iter() {
+            for row in set {
+                row.map_err(StorageError::from)?;
+            }
+        }
+        drop(results);
+        conn.exec_drop(
+            "INSERT INTO schema_migrations (version, name) VALUES (:version, :name)",
+            params! {
+                "version" => migration.version,
+                "name" => migration.name,
+            },
+        )
+        .map_err(StorageError::from)
+    }
+}
+
+impl From<Error> for StorageError {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::IoError(_) | Error::CodecError(_) | Error::DriverError(_) => {
+                StorageError::Unavailable(e.to_string())
+            }
+            // ER_DUP_ENTRY
+            Error::MySqlError(ref err) if err.code == 1062 => StorageError::Conflict(e.to_string()),
+            // ER_CON_COUNT_ERROR, ER_SERVER_SHUTDOWN
+            Error::MySqlError(ref err) if err.code == 1040 || err.code == 1053 => {
+                StorageError::Unavailable(e.to_string())
+            }
+            _ => StorageError::Internal(e.to_string()),
+        }
     }
 }
 
 impl Storage for MySqlDb {
-    fn get_table_orders(&self, table_id: u64) -> Result<Vec<Order>, String> {
-        let mut conn = self.pool.get_conn().map_err(|e| e.to_string())?;
-        conn.exec_map(
-            "SELECT order_id, menu_item, cooking_time FROM orders WHERE table_id = :table_id",
+    fn get_table_orders(&self, table_id: u64) -> Result<Vec<Order>, StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        let rows = conn
+            .exec_map(
+                format!("{} WHERE table_id = :table_id ORDER BY created_at", SELECT_ORDERS_SQL),
+                params! {
+                    "table_id" => table_id,
+                },
+                order_from_row,
+            )
+            .map_err(StorageError::from)?;
+        Ok(decode::skip_corrupt(rows))
+    }
+
+    fn get_table_order(&self, table_id: u64, order_id: Uuid) -> Result<Order, StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        let row: Option<Row> = conn
+            .exec_first(
+                format!("{} WHERE table_id = :table_id AND order_id = :order_id", SELECT_ORDERS_SQL),
+                params! {
+                    "table_id" => table_id,
+                    "order_id" => order_id.as_bytes().to_vec(),
+                },
+            )
+            .map_err(StorageError::from)?;
+        let row = row.ok_or(StorageError::NotFound("Order not found".to_string()))?;
+        Ok(order_from_row(row)?)
+    }
+
+    fn add_table_orders(&self, table_id: u64, orders: Vec<Order>) -> Result<Vec<Uuid>, StorageError> {
+        if orders.is_empty() {
+            return Ok(Vec::new());
+        }
+        let order_ids: Vec<Uuid> = orders.iter().map(|order| order.id).collect();
+        let placeholders = vec!["(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"; orders.len()].join(", ");
+        let mut values: Vec<Value> = Vec::with_capacity(orders.len() * 11);
+        for order in orders {
+            values.push(order.id.as_bytes().to_vec().into());
+            values.push(table_id.into());
+            values.push(order.menu_item.into());
+            values.push(order.created_at.naive_utc().into());
+            values.push(order.cooking_duration_secs.into());
+            values.push(order.status.as_str().into());
+            values.push(order.session_id.map(|id| id.as_bytes().to_vec()).into());
+            values.push(order.price.into());
+            values.push(order.seat.into());
+            values.push(order.station.as_str().into());
+            values.push(order.ticket_id.as_bytes().to_vec().into());
+        }
+
+        // One statement inside one transaction, so either every order is stored or none is.
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        let mut tx = conn.start_transaction(TxOpts::default()).map_err(StorageError::from)?;
+        tx.exec_drop(
+            format!(
+                "INSERT INTO orders (order_id, table_id, menu_item, created_at, cooking_duration_secs, status, session_id, price, seat, station, ticket_id) VALUES {}",
+                placeholders
+            ),
+            Params::Positional(values),
+        )
+        .map_err(StorageError::from)?;
+        tx.commit().map_err(StorageError::from)?;
+        Ok(order_ids)
+    }
+
+    fn delete_table_order(&self, table_id: u64, order_id: Uuid) -> Result<(), StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        conn.exec_drop(
+            "DELETE FROM orders WHERE table_id = :table_id AND order_id = :order_id",
             params! {
                 "table_id" => table_id,
+                "order_id" => order_id.as_bytes().to_vec(),
             },
-            |(order_id, menu_item, cooking_time): (String, String, String)| Order {
-                id: Uuid::parse_str(&order_id).unwrap(),
-                menu_item,
-                cooking_time,
+        )
+        .map_err(StorageError::from)?;
+        if conn.affected_rows() == 0 {
+            return Err(StorageError::NotFound("Order not found".to_string()));
+        }
+        Ok(())
+    }
+
+    fn update_order_status(
+        &self,
+        table_id: u64,
+        order_id: Uuid,
+        from: OrderStatus,
+        to: OrderStatus,
+        at: DateTime<Utc>,
+    ) -> Result<(), StorageError> {
+        let column = status_timestamp_column(to)
+            .ok_or(StorageError::Validation(format!("Orders cannot move back to {}", to)))?;
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        conn.exec_drop(
+            format!(
+                "UPDATE orders SET status = :to, {} = :at WHERE table_id = :table_id AND order_id = :order_id AND status = :from",
+                column
+            ),
+            params! {
+                "to" => to.as_str(),
+                "at" => at.naive_utc(),
+                "table_id" => table_id,
+                "order_id" => order_id.as_bytes().to_vec(),
+                "from" => from.as_str(),
+            },
+        )
+        .map_err(StorageError::from)?;
+        if conn.affected_rows() == 0 {
+            let order = self.get_table_order(table_id, order_id)?;
+            return Err(StorageError::Conflict(format!(
+                "Order is {}, not {}",
+                order.status, from
+            )));
+        }
+        Ok(())
+    }
+
+    fn count_open_orders(&self) -> Result<usize, StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        conn.query_first("SELECT COUNT(*) FROM orders WHERE status IN ('placed', 'cooking')")
+            .map(|count: Option<usize>| count.unwrap_or(0))
+            .map_err(StorageError::from)
+    }
+
+    fn get_open_orders(&self) -> Result<Vec<(u64, Order)>, StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        let rows = conn
+            .query_map(
+                format!("{} WHERE status IN ('placed', 'cooking') ORDER BY created_at", SELECT_ORDERS_SQL),
+                |mut row: Row| {
+                    let table_id = take_column(&mut row, "table_id")
+                        .map_err(|reason| CorruptRow::new("orders", "?", reason))?;
+                    Ok((table_id, order_from_row(row)?))
+                },
+            )
+            .map_err(StorageError::from)?;
+        Ok(decode::skip_corrupt(rows))
+    }
+
+    fn get_menu_items(&self) -> Result<Vec<MenuItem>, StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        let rows = conn
+            .query_map(format!("{} ORDER BY category, name", SELECT_MENU_ITEMS_SQL), menu_item_from_row)
+            .map_err(StorageError::from)?;
+        Ok(decode::skip_corrupt(rows))
+    }
+
+    fn get_menu_item(&self, menu_item_id: Uuid) -> Result<MenuItem, StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        let row: Option<Row> = conn
+            .exec_first(
+                format!("{} WHERE menu_item_id = :menu_item_id", SELECT_MENU_ITEMS_SQL),
+                params! {
+                    "menu_item_id" => menu_item_id.to_string(),
+                },
+            )
+            .map_err(StorageError::from)?;
+        let row = row.ok_or(StorageError::NotFound("Menu item not found".to_string()))?;
+        Ok(menu_item_from_row(row)?)
+    }
+
+    fn add_menu_item(&self, item: MenuItem) -> Result<Uuid, StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        conn.exec_drop(
+            "INSERT INTO menu_items (menu_item_id, name, category, price, prep_time_minutes, available, station) VALUES (:menu_item_id, :name, :category, :price, :prep_time_minutes, :available, :station)",
+            params! {
+                "menu_item_id" => item.id.to_string(),
+                "name" => &item.name,
+                "category" => &item.category,
+                "price" => item.price,
+                "prep_time_minutes" => item.prep_time_minutes,
+                "available" => item.available,
+                "station" => item.station.as_str(),
+            },
+        )
+        .map_err(StorageError::from)?;
+        Ok(item.id)
+    }
+
+    fn update_menu_item(&self, item: MenuItem) -> Result<(), StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        conn.exec_drop(
+            "UPDATE menu_items SET name = :name, category = :category, price = :price, prep_time_minutes = :prep_time_minutes, available = :available, station = :station WHERE menu_item_id = :menu_item_id",
+            params! {
+                "menu_item_id" => item.id.to_string(),
+                "name" => &item.name,
+                "category" => &item.category,
+                "price" => item.price,
+                "prep_time_minutes" => item.prep_time_minutes,
+                "available" => item.available,
+                "station" => item.station.as_str(),
+            },
+        )
+        .map_err(StorageError::from)?;
+        // MySQL reports rows changed rather than rows matched, so an update that
+        // leaves the row as it was also reports zero.
+        if conn.affected_rows() == 0 {
+            self.get_menu_item(item.id)?;
+        }
+        Ok(())
+    }
+
+    fn delete_menu_item(&self, menu_item_id: Uuid) -> Result<(), StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        conn.exec_drop(
+            "DELETE FROM menu_items WHERE menu_item_id = :menu_item_id",
+            params! {
+                "menu_item_id" => menu_item_id.to_string(),
+            },
+        )
+        .map_err(StorageError::from)?;
+        if conn.affected_rows() == 0 {
+            return Err(StorageError::NotFound("Menu item not found".to_string()));
+        }
+        Ok(())
+    }
+
+    fn get_tables(&self) -> Result<Vec<Table>, StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        let rows = conn
+            .query_map(format!("{} ORDER BY table_number", SELECT_TABLES_SQL), table_from_row)
+            .map_err(StorageError::from)?;
+        Ok(decode::skip_corrupt(rows))
+    }
+
+    fn get_table(&self, number: u64) -> Result<Table, StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        let row: Option<Row> = conn
+            .exec_first(
+                format!("{} WHERE table_number = :number", SELECT_TABLES_SQL),
+                params! {
+                    "number" => number,
+                },
+            )
+            .map_err(StorageError::from)?;
+        let row = row.ok_or(StorageError::NotFound(format!("Table {} is not registered", number)))?;
+        Ok(table_from_row(row)?)
+    }
+
+    fn add_table(&self, table: Table) -> Result<u64, StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        conn.exec_drop(
+            "INSERT INTO dining_tables (table_number, seats, section, status, current_session_id) VALUES (:number, :seats, :section, :status, :session_id)",
+            params! {
+                "number" => table.number,
+                "seats" => table.seats,
+                "section" => &table.section,
+                "status" => table.status.as_str(),
+                "session_id" => table.session_id.map(|id| id.as_bytes().to_vec()),
             },
         )
-        .map_err(|e| e.to_string())
+        .map_err(StorageError::from)?;
+        Ok(table.number)
     }
 
-    fn get_table_order(&self, table_id: u64, order_id: Uuid) -> Result<Order, String> {
-        let mut conn = self.pool.get_conn().map_err(|e| e.to_string())?;
-        conn.exec_first(
-            "SELECT order_id, menu_item, cooking_time FROM orders WHERE table_id = :table_id AND order_id = :order_id",
+    fn open_table_session(&self, number: u64, session: Session) -> Result<(), StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        let mut tx = conn.start_transaction(TxOpts::default()).map_err(StorageError::from)?;
+        tx.exec_drop(
+            "UPDATE dining_tables SET status = 'open', current_session_id = :session_id WHERE table_number = :number AND status = 'closed'",
             params! {
-            "table_id" => table_id,
-            "order_id" => order_id.to_string(),
+                "session_id" => session.id.as_bytes().to_vec(),
+                "number" => number,
+            },
+        )
+        .map_err(StorageError::from)?;
+        if tx.affected_rows() == 0 {
+            drop(tx);
+            self.get_table(number)?;
+            return Err(StorageError::Conflict(format!("Table {} is already open", number)));
         }
-        ).map(|row| {
-            row.map(|(order_id, menu_item, cooking_time): (String, String, String)| Order {
-                id: Uuid::parse_str(&order_id).unwrap(),
-                menu_item,
-                cooking_time,
-            })
-        }).map_err(|e| e.to_string()).and_then(|opt| opt.ok_or("Order not found".to_string()))
+        tx.exec_drop(
+            "INSERT INTO table_sessions (session_id, table_number, opened_at) VALUES (:session_id, :number, :opened_at)",
+            params! {
+                "session_id" => session.id.as_bytes().to_vec(),
+                "number" => number,
+                "opened_at" => session.opened_at.naive_utc(),
+            },
+        )
+        .map_err(StorageError::from)?;
+        tx.commit().map_err(StorageError::from)
     }
 
-    fn add_table_orders(&self, table_id: u64, orders: Vec<Order>) -> Result<Vec<Uuid>, String> {
-        let mut conn = self.pool.get_conn().map_err(|e| e.to_string())?;
-        let mut order_ids = Vec::new();
+    fn close_table_session(&self, number: u64, session_id: Uuid, closed_at: DateTime<Utc>) -> Result<(), StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        let mut tx = conn.start_transaction(TxOpts::default()).map_err(StorageError::from)?;
+        tx.exec_drop(
+            "UPDATE dining_tables SET status = 'closed', current_session_id = NULL WHERE table_number = :number AND current_session_id = :session_id",
+            params! {
+                "number" => number,
+                "session_id" => session_id.as_bytes().to_vec(),
+            },
+        )
+        .map_err(StorageError::from)?;
+        if tx.affected_rows() == 0 {
+            drop(tx);
+            self.get_table(number)?;
+            return Err(StorageError::Conflict(format!(
+                "Session {} is not in progress at table {}",
+                session_id, number
+            )));
+        }
+        tx.exec_drop(
+            "UPDATE table_sessions SET closed_at = :closed_at WHERE session_id = :session_id",
+            params! {
+                "closed_at" => closed_at.naive_utc(),
+                "session_id" => session_id.as_bytes().to_vec(),
+            },
+        )
+        .map_err(StorageError::from)?;
+        tx.commit().map_err(StorageError::from)
+    }
+
+    fn get_table_sessions(&self, number: u64) -> Result<Vec<Session>, StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        let rows = conn
+            .exec_map(
+                format!("{} WHERE table_number = :number ORDER BY opened_at", SELECT_SESSIONS_SQL),
+                params! {
+                    "number" => number,
+                },
+                session_from_row,
+            )
+            .map_err(StorageError::from)?;
+        Ok(decode::skip_corrupt(rows))
+    }
 
-        for order_input in orders {
-            conn.exec_drop(
-                "INSERT INTO orders (order_id, table_id, menu_item, cooking_time) VALUES (:order_id, :table_id, :menu_item, :cooking_time)",
+    fn get_table_session(&self, number: u64, session_id: Uuid) -> Result<Session, StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        let row: Option<Row> = conn
+            .exec_first(
+                format!("{} WHERE table_number = :number AND session_id = :session_id", SELECT_SESSIONS_SQL),
                 params! {
-                "order_id" => order_input.id.to_string(),
+                    "number" => number,
+                    "session_id" => session_id.as_bytes().to_vec(),
+                },
+            )
+            .map_err(StorageError::from)?;
+        let row = row.ok_or(StorageError::NotFound("Session not found".to_string()))?;
+        Ok(session_from_row(row)?)
+    }
+
+    fn get_session_orders(&self, session_id: Uuid, decoding: Decoding) -> Result<Vec<Order>, StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        let rows = conn
+            .exec_map(
+                format!("{} WHERE session_id = :session_id ORDER BY created_at", SELECT_ORDERS_SQL),
+                params! {
+                    "session_id" => session_id.as_bytes().to_vec(),
+                },
+                order_from_row,
+            )
+            .map_err(StorageError::from)?;
+        decode::collect(rows, decoding)
+    }
+
+    fn replace_split_bills(&self, session_id: Uuid, bills: Vec<SplitBill>) -> Result<(), StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        let mut tx = conn.start_transaction(TxOpts::default()).map_err(StorageError::from)?;
+        // Locking the current bills stops one being closed while it is replaced.
+        let statuses: Vec<String> = tx
+            .exec(
+                "SELECT status FROM split_bills WHERE session_id = :session_id FOR UPDATE",
+                params! {
+                    "session_id" => session_id.as_bytes().to_vec(),
+                },
+            )
+            .map_err(StorageError::from)?;
+        if statuses.iter().any(|status| status != BillStatus::Open.as_str()) {
+            return Err(StorageError::Conflict("A split bill has already been paid or closed".to_string()));
+        }
+        tx.exec_drop(
+            "DELETE split_bill_orders FROM split_bill_orders JOIN split_bills ON split_bills.bill_id = split_bill_orders.bill_id WHERE split_bills.session_id = :session_id",
+            params! {
+                "session_id" => session_id.as_bytes().to_vec(),
+            },
+        )
+        .map_err(StorageError::from)?;
+        tx.exec_drop(
+            "DELETE FROM split_bills WHERE session_id = :session_id",
+            params! {
+                "session_id" => session_id.as_bytes().to_vec(),
+            },
+        )
+        .map_err(StorageError::from)?;
+        for bill in bills {
+            tx.exec_drop(
+                "INSERT INTO split_bills (bill_id, session_id, position, label, subtotal, service_charge, tax, total, status) VALUES (:bill_id, :session_id, :position, :label, :subtotal, :service_charge, :tax, :total, :status)",
+                params! {
+                    "bill_id" => bill.id.as_bytes().to_vec(),
+                    "session_id" => session_id.as_bytes().to_vec(),
+                    "position" => bill.position,
+                    "label" => bill.label,
+                    "subtotal" => bill.subtotal,
+                    "service_charge" => bill.service_charge,
+                    "tax" => bill.tax,
+                    "total" => bill.total,
+                    "status" => bill.status.as_str(),
+                },
+            )
+            .map_err(StorageError::from)?;
+            tx.exec_batch(
+                "INSERT INTO split_bill_orders (bill_id, order_id) VALUES (:bill_id, :order_id)",
+                bill.order_ids.iter().map(|order_id| {
+                    params! {
+                        "bill_id" => bill.id.as_bytes().to_vec(),
+                        "order_id" => order_id.as_bytes().to_vec(),
+                    }
+                }),
+            )
+            .map_err(StorageError::from)?;
+        }
+        tx.commit().map_err(StorageError::from)
+    }
+
+    fn get_split_bills(&self, session_id: Uuid, decoding: Decoding) -> Result<Vec<SplitBill>, StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        let rows = conn
+            .exec_map(
+                format!("{} WHERE session_id = :session_id ORDER BY position", SELECT_SPLIT_BILLS_SQL),
+                params! {
+                    "session_id" => session_id.as_bytes().to_vec(),
+                },
+                split_bill_from_row,
+            )
+            .map_err(StorageError::from)?;
+        let mut bills = decode::collect(rows, decoding)?;
+        let pairs = conn
+            .exec(
+                "SELECT split_bill_orders.bill_id, split_bill_orders.order_id FROM split_bill_orders JOIN split_bills ON split_bills.bill_id = split_bill_orders.bill_id WHERE split_bills.session_id = :session_id ORDER BY split_bill_orders.order_id",
+                params! {
+                    "session_id" => session_id.as_bytes().to_vec(),
+                },
+            )
+            .map_err(StorageError::from)?;
+        attach_split_bill_orders(&mut bills, pairs)?;
+        Ok(bills)
+    }
+
+    fn get_split_bill(&self, session_id: Uuid, bill_id: Uuid) -> Result<SplitBill, StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        let row: Option<Row> = conn
+            .exec_first(
+                format!("{} WHERE session_id = :session_id AND bill_id = :bill_id", SELECT_SPLIT_BILLS_SQL),
+                params! {
+                    "session_id" => session_id.as_bytes().to_vec(),
+                    "bill_id" => bill_id.as_bytes().to_vec(),
+                },
+            )
+            .map_err(StorageError::from)?;
+        let row = row.ok_or(StorageError::NotFound("Bill not found".to_string()))?;
+        let mut bill = split_bill_from_row(row)?;
+        let pairs = conn
+            .exec(
+                "SELECT bill_id, order_id FROM split_bill_orders WHERE bill_id = :bill_id ORDER BY order_id",
+                params! {
+                    "bill_id" => bill_id.as_bytes().to_vec(),
+                },
+            )
+            .map_err(StorageError::from)?;
+        attach_split_bill_orders(std::slice::from_mut(&mut bill), pairs)?;
+        Ok(bill)
+    }
+
+    fn update_split_bill_status(
+        &self,
+        session_id: Uuid,
+        bill_id: Uuid,
+        from: BillStatus,
+        to: BillStatus,
+    ) -> Result<(), StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        conn.exec_drop(
+            "UPDATE split_bills SET status = :to WHERE session_id = :session_id AND bill_id = :bill_id AND status = :from",
+            params! {
+                "to" => to.as_str(),
+                "session_id" => session_id.as_bytes().to_vec(),
+                "bill_id" => bill_id.as_bytes().to_vec(),
+                "from" => from.as_str(),
+            },
+        )
+        .map_err(StorageError::from)?;
+        if conn.affected_rows() == 0 {
+            let bill = self.get_split_bill(session_id, bill_id)?;
+            return Err(StorageError::Conflict(format!("Bill is {}, not {}", bill.status, from)));
+        }
+        Ok(())
+    }
+
+    fn add_payment(&self, payment: Payment) -> Result<Uuid, StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        conn.exec_drop(
+            "INSERT INTO payments (payment_id, session_id, bill_id, tender, reference, amount, tip, change_due, paid_at) VALUES (:payment_id, :session_id, :bill_id, :tender, :reference, :amount, :tip, :change_due, :paid_at)",
+            params! {
+                "payment_id" => payment.id.as_bytes().to_vec(),
+                "session_id" => payment.session_id.as_bytes().to_vec(),
+                "bill_id" => payment.bill_id.map(|id| id.as_bytes().to_vec()),
+                "tender" => payment.tender.as_str(),
+                "reference" => payment.reference,
+                "amount" => payment.amount,
+                "tip" => payment.tip,
+                "change_due" => payment.change,
+                "paid_at" => payment.paid_at.naive_utc(),
+            },
+        )
+        .map_err(StorageError::from)?;
+        Ok(payment.id)
+    }
+
+    fn get_session_payments(&self, session_id: Uuid, decoding: Decoding) -> Result<Vec<Payment>, StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        let rows = conn
+            .exec_map(
+                format!("{} WHERE session_id = :session_id ORDER BY paid_at", SELECT_PAYMENTS_SQL),
+                params! {
+                    "session_id" => session_id.as_bytes().to_vec(),
+                },
+                payment_from_row,
+            )
+            .map_err(StorageError::from)?;
+        decode::collect(rows, decoding)
+    }
+
+    fn add_webhook(&self, webhook: Webhook) -> Result<Uuid, StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        conn.exec_drop(
+            "INSERT INTO webhooks (webhook_id, url, events, created_at) VALUES (:webhook_id, :url, :events, :created_at)",
+            params! {
+                "webhook_id" => webhook.id.as_bytes().to_vec(),
+                "url" => &webhook.url,
+                "events" => join_events(&webhook.events),
+                "created_at" => webhook.created_at.naive_utc(),
+            },
+        )
+        .map_err(StorageError::from)?;
+        Ok(webhook.id)
+    }
+
+    fn get_webhooks(&self) -> Result<Vec<Webhook>, StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        let rows = conn
+            .query_map(format!("{} ORDER BY created_at", SELECT_WEBHOOKS_SQL), webhook_from_row)
+            .map_err(StorageError::from)?;
+        Ok(decode::skip_corrupt(rows))
+    }
+
+    fn get_webhook(&self, webhook_id: Uuid) -> Result<Webhook, StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        let row: Option<Row> = conn
+            .exec_first(
+                format!("{} WHERE webhook_id = :webhook_id", SELECT_WEBHOOKS_SQL),
+                params! {
+                    "webhook_id" => webhook_id.as_bytes().to_vec(),
+                },
+            )
+            .map_err(StorageError::from)?;
+        let row = row.ok_or(StorageError::NotFound("Webhook not found".to_string()))?;
+        Ok(webhook_from_row(row)?)
+    }
+
+    fn delete_webhook(&self, webhook_id: Uuid) -> Result<(), StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        conn.exec_drop(
+            "DELETE FROM webhooks WHERE webhook_id = :webhook_id",
+            params! {
+                "webhook_id" => webhook_id.as_bytes().to_vec(),
+            },
+        )
+        .map_err(StorageError::from)?;
+        if conn.affected_rows() == 0 {
+            return Err(StorageError::NotFound("Webhook not found".to_string()));
+        }
+        Ok(())
+    }
+
+    fn add_dead_letter(&self, dead_letter: DeadLetter) -> Result<Uuid, StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        conn.exec_drop(
+            "INSERT INTO webhook_dead_letters (dead_letter_id, webhook_id, event_id, event, payload, attempts, last_error, failed_at) VALUES (:dead_letter_id, :webhook_id, :event_id, :event, :payload, :attempts, :last_error, :failed_at)",
+            params! {
+                "dead_letter_id" => dead_letter.id.as_bytes().to_vec(),
+                "webhook_id" => dead_letter.webhook_id.as_bytes().to_vec(),
+                "event_id" => dead_letter.event_id,
+                "event" => dead_letter.event,
+                "payload" => dead_letter.payload,
+                "attempts" => dead_letter.attempts,
+                "last_error" => dead_letter.last_error,
+                "failed_at" => dead_letter.failed_at.naive_utc(),
+            },
+        )
+        .map_err(StorageError::from)?;
+        Ok(dead_letter.id)
+    }
+
+    fn get_dead_letters(&self) -> Result<Vec<DeadLetter>, StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        let rows = conn
+            .query_map(format!("{} ORDER BY failed_at", SELECT_DEAD_LETTERS_SQL), dead_letter_from_row)
+            .map_err(StorageError::from)?;
+        Ok(decode::skip_corrupt(rows))
+    }
+
+    fn delete_dead_letter(&self, dead_letter_id: Uuid) -> Result<(), StorageError> {
+        let mut conn = self.pool.get_conn().map_err(StorageError::from)?;
+        conn.exec_drop(
+            "DELETE FROM webhook_dead_letters WHERE dead_letter_id = :dead_letter_id",
+            params! {
+                "dead_letter_id" => dead_letter_id.as_bytes().to_vec(),
+            },
+        )
+        .map_err(StorageError::from)?;
+        if conn.affected_rows() == 0 {
+            return Err(StorageError::NotFound("Dead letter not found".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::conformance::storage_conformance_tests;
+    use dotenv::from_filename;
+    use std::env;
+
+    fn setup_test_db() -> MySqlDb {
+        from_filename(".env.test").ok();
+        let database_url = env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must be established");
+        MySqlDb::new(&database_url)
+    }
+
+    storage_conformance_tests!(
+        setup_test_db(),
+        #[ignore = "requires a MySQL server at TEST_DATABASE_URL"]
+    );
+
+    const INSERT_ORDER_SQL: &str = "INSERT INTO orders (order_id, table_id, menu_item, created_at, cooking_duration_secs) VALUES (:order_id, :table_id, 'Mock Item', UTC_TIMESTAMP(6), 600)";
+    const DELETE_ORDER_SQL: &str = "DELETE FROM orders WHERE order_id = :order_id";
+
+    #[test]
+    #[ignore = "requires a MySQL server at TEST_DATABASE_URL"]
+    fn test_get_table_orders() {
+        let db = setup_test_db();
+        let mut conn = db.pool.get_conn().expect("Failed to get connection");
+        let table_id = 1;
+        let order_id = Uuid::new_v4();
+
+        conn.exec_drop(
+            INSERT_ORDER_SQL,
+            params! {
+                "order_id" => order_id.as_bytes().to_vec(),
                 "table_id" => table_id,
-                "menu_item" => order_input.menu_item,
-                "cooking_time" => order_input.cooking_time,
-            }
-            ).map_err(|e| e.to_string())?;
-            order_ids.push(order_input.id);
+            },
+        ).unwrap();
+
+        let result = db.get_table_orders(table_id);
+        assert!(
+            result.is_ok(),
+            "Expected Ok but got Err: {:?}",
+            result.err()
+        );
+        assert!(result.unwrap().iter().any(|order| order.id == order_id));
+
+        conn.exec_drop(
+            DELETE_ORDER_SQL,
+            params! {
+                "order_id" => order_id.as_bytes().to_vec(),
+            },
+        ).unwrap();
+    }
+
+    #[test]
+    #[ignore = "requires a MySQL server at TEST_DATABASE_URL"]
+    fn test_get_table_order() {
+        let db = setup_test_db();
+        let mut conn = db.pool.get_conn().expect("Failed to get connection");
+        let table_id = 1;
+        let order_id = Uuid::new_v4();
+
+        conn.exec_drop(
+            INSERT_ORDER_SQL,
+            params! {
+                "order_id" => order_id.as_bytes().to_vec(),
+                "table_id" => table_id,
+            },
+        ).unwrap();
+
+        let result = db.get_table_order(table_id, order_id);
+        assert!(
+            result.is_ok(),
+            "Expected Ok but got Err: {:?}",
+            result.err()
+        );
+
+        conn.exec_drop(
+            DELETE_ORDER_SQL,
+            params! {
+                "order_id" => order_id.as_bytes().to_vec(),
+            },
+        ).unwrap();
+    }
+
+    #[test]
+    #[ignore = "requires a MySQL server at TEST_DATABASE_URL"]
+    fn test_add_and_delete_table_order() {
+        let db = setup_test_db();
+        let order = Order::placed(Uuid::new_v4(), "Mock Item".to_string(), Utc::now(), 600);
+
+        let result = db.add_table_orders(1, vec![order.clone()]);
+        assert_eq!(result, Ok(vec![order.id]));
+
+        assert!(db.delete_table_order(1, order.id).is_ok());
+        assert!(matches!(db.get_table_order(1, order.id), Err(StorageError::NotFound(_))));
+    }
+
+    #[test]
+    #[ignore = "requires a MySQL server at TEST_DATABASE_URL"]
+    fn test_add_table_orders_inserts_batch() {
+        let db = setup_test_db();
+        let table_id = 9_000 + rand::random::<u16>() as u64;
+        let orders: Vec<Order> = ["Pizza", "Salad", "Soup"]
+            .iter()
+            .map(|item| Order::placed(Uuid::new_v4(), item.to_string(), Utc::now(), 600))
+            .collect();
+        let ids: Vec<Uuid> = orders.iter().map(|order| order.id).collect();
+        let orders_copy = orders.clone();
+
+        assert_eq!(db.add_table_orders(table_id, orders), Ok(ids.clone()));
+        assert_eq!(db.add_table_orders(table_id, vec![]), Ok(vec![]));
+        let duplicate = db.add_table_orders(table_id, vec![orders_copy[0].clone()]);
+        assert!(matches!(duplicate, Err(StorageError::Conflict(_))));
+
+        let too_long = Order::placed(Uuid::new_v4(), "x".repeat(300), Utc::now(), 600);
+        let pizza = Order::placed(Uuid::new_v4(), "Pizza".to_string(), Utc::now(), 600);
+        assert!(db.add_table_orders(table_id, vec![pizza, too_long]).is_err());
+
+        let stored = db.get_table_orders(table_id).unwrap();
+        assert_eq!(stored.len(), 3);
+        for id in ids {
+            db.delete_table_order(table_id, id).unwrap();
         }
-        Ok(order_ids)
     }
 
-    fn delete_table_order(&self, table_id: u64, order_id: Uuid) -> Result<(), String> {
-        let mut conn = self.pool.get_conn().map_err(|e| e.to_string())?;
+    #[test]
+    #[ignore = "requires a MySQL server at TEST_DATABASE_URL"]
+    fn test_corrupt_rows_are_reported() {
+        let db = setup_test_db();
+        let mut conn = db.pool.get_conn().expect("Failed to get connection");
+        let table_id = 9_000 + rand::random::<u16>() as u64;
+        let good = Order::placed(Uuid::new_v4(), "Pizza".to_string(), Utc::now(), 600);
+        db.add_table_orders(table_id, vec![good.clone()]).unwrap();
+
+        let bad_id = Uuid::new_v4();
         conn.exec_drop(
-            "DELETE FROM orders WHERE table_id = :table_id AND order_id = :order_id",
+            "INSERT INTO orders (order_id, table_id, menu_item, created_at, cooking_duration_secs, status) VALUES (:order_id, :table_id, 'Soup', UTC_TIMESTAMP(6), 600, 'exploded')",
             params! {
+                "order_id" => bad_id.as_bytes().to_vec(),
                 "table_id" => table_id,
-                "order_id" => order_id.to_string(),
+            },
+        ).unwrap();
+
+        let orders = db.get_table_orders(table_id).unwrap();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].id, good.id);
+        match db.get_table_order(table_id, bad_id) {
+            Err(StorageError::Corrupt(corrupt)) => assert_eq!(corrupt.key, bad_id.to_string()),
+            other => panic!("Expected a corrupt row but got {:?}", other),
+        }
+
+        db.delete_table_order(table_id, good.id).unwrap();
+        db.delete_table_order(table_id, bad_id).unwrap();
+    }
+
+    #[test]
+    #[ignore = "requires a MySQL server at TEST_DATABASE_URL"]
+    fn test_migrations_are_recorded_once() {
+        let db = setup_test_db();
+        assert_eq!(db.migrate(), Ok(vec![]));
+        assert_eq!(db.verify_schema(), Ok(()));
+
+        let applied = db.applied_versions().unwrap();
+        let known: Vec<u32> = MIGRATIONS.iter().map(|migration| migration.version).collect();
+        assert_eq!(applied, known);
+    }
+
+    #[test]
+    #[ignore = "requires a MySQL server at TEST_DATABASE_URL whose user can create databases"]
+    fn test_orders_key_migration_stops_at_malformed_rows() {
+        from_filename(".env.test").ok();
+        let database_url = env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must be established");
+        let opts = Opts::from_url(&database_url).unwrap();
+        let scratch = format!("restaurant_migration_{}", rand::random::<u32>());
+        let mut admin = Conn::new(opts.clone()).expect("Failed to connect");
+        admin.query_drop(format!("CREATE DATABASE {}", scratch)).unwrap();
+
+        let db = MySqlDb {
+            pool: Pool::new(OptsBuilder::from_opts(opts).db_name(Some(&scratch))).unwrap(),
+        };
+        assert_eq!(migrations::run(&db, &MIGRATIONS[..4]), Ok(vec![1, 2, 3, 4]));
+        let mut conn = db.pool.get_conn().expect("Failed to get connection");
+        conn.query_drop(
+            "INSERT INTO orders (order_id, menu_item, table_id, created_at, cooking_duration_secs) VALUES ('not-a-uuid', 'Soup', 3, UTC_TIMESTAMP(6), 600)",
+        )
+        .unwrap();
+        drop(conn);
+
+        let result = db.migrate();
+        let applied = db.applied_versions();
+        let order_id: Option<String> = db.pool.get_conn().unwrap().query_first("SELECT order_id FROM orders").unwrap();
+        admin.query_drop(format!("DROP DATABASE {}", scratch)).unwrap();
+
+        assert!(
+            matches!(&result, Err(StorageError::Internal(message)) if message.contains("orders_keys_and_indexes")),
+            "Expected migration 5 to fail but got {:?}",
+            result
+        );
+        assert_eq!(applied, Ok(vec![1, 2, 3, 4]));
+        assert_eq!(order_id.as_deref(), Some("not-a-uuid"));
+    }
+
+    #[test]
+    #[ignore = "requires a MySQL server at TEST_DATABASE_URL"]
+    fn test_migration_failing_after_first_statement_is_not_recorded() {
+        let db = setup_test_db();
+        let migration = Migration {
+            version: 60_000 + rand::random::<u16>() as u32 % 5_000,
+            name: "fails_halfway",
+            sql: "DO 1; SELECT no_such_column FROM schema_migrations; DO 2;",
+        };
+
+        let result = db.apply_migration(&migration);
+        let applied = db.applied_versions().unwrap();
+        let mut conn = db.pool.get_conn().expect("Failed to get connection");
+        conn.exec_drop(
+            "DELETE FROM schema_migrations WHERE version = :version",
+            params! {
+                "version" => migration.version,
             },
         )
-        .map_err(|e| e.to_string())?;
-        Ok(())
+        .unwrap();
+
+        assert!(matches!(result, Err(StorageError::Internal(_))), "Expected an error but got {:?}", result);
+        assert!(!applied.contains(&migration.version));
     }
 }
@@ -0,0 +1,1114 @@
+use crate::db::decode::{self, CorruptRow, Decoding};
+use crate::db::migrations::{self, Migration, Migrator};
+use crate::db::{
+    join_events, split_events, status_timestamp_column, BillStatus, DeadLetter, MenuItem, Order, OrderStatus, Payment,
+    Session, SplitBill, Station, Storage, StorageError, Table, Webhook,
+};
+use chrono::{DateTime, Utc};
+use postgres::error::SqlState;
+use postgres::types::FromSqlOwned;
+use postgres::{Config, NoTls, Row};
+use r2d2::PooledConnection;
+use r2d2_postgres::PostgresConnectionManager;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Same versions and names as the MySQL migrations, written for Postgres.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_orders",
+        sql: include_str!("../../migrations/postgres/0001_create_orders.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "create_menu_items",
+        sql: include_str!("../../migrations/postgres/0002_create_menu_items.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "add_order_status",
+        sql: include_str!("../../migrations/postgres/0003_add_order_status.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "structured_cooking_time",
+        sql: include_str!("../../migrations/postgres/0004_structured_cooking_time.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "orders_keys_and_indexes",
+        sql: include_str!("../../migrations/postgres/0005_orders_keys_and_indexes.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "create_dining_tables",
+        sql: include_str!("../../migrations/postgres/0006_create_dining_tables.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "add_table_sessions",
+        sql: include_str!("../../migrations/postgres/0007_add_table_sessions.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "add_order_prices",
+        sql: include_str!("../../migrations/postgres/0008_add_order_prices.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "add_split_bills",
+        sql: include_str!("../../migrations/postgres/0009_add_split_bills.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "create_payments",
+        sql: include_str!("../../migrations/postgres/0010_create_payments.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "add_kitchen_stations",
+        sql: include_str!("../../migrations/postgres/0011_add_kitchen_stations.sql"),
+    },
+    Migration {
+        version: 12,
+        name: "create_webhooks",
+        sql: include_str!("../../migrations/postgres/0012_create_webhooks.sql"),
+    },
+];
+
+const POSTGRES_SCHEMES: [&str; 2] = ["postgres://", "postgresql://"];
+
+const SELECT_ORDERS_SQL: &str = "SELECT order_id, menu_item, created_at, cooking_duration_secs, status, started_at, finished_at, served_at, cancelled_at, session_id, price, seat, station, ticket_id, table_id FROM orders";
+
+const SELECT_MENU_ITEMS_SQL: &str = "SELECT menu_item_id, name, category, price, prep_time_minutes, available, station FROM menu_items";
+
+const SELECT_TABLES_SQL: &str = "SELECT table_number, seats, section, status, current_session_id FROM dining_tables";
+
+const SELECT_SESSIONS_SQL: &str = "SELECT session_id, table_number, opened_at, closed_at FROM table_sessions";
+
+const SELECT_PAYMENTS_SQL: &str =
+    "SELECT payment_id, session_id, bill_id, tender, reference, amount, tip, change_due, paid_at FROM payments";
+
+const SELECT_SPLIT_BILLS_SQL: &str =
+    "SELECT bill_id, session_id, position, label, subtotal, service_charge, tax, total, status FROM split_bills";
+
+type Pool = r2d2::Pool<PostgresConnectionManager<NoTls>>;
+
+/// Postgres has no unsigned integers, so ids and amounts are stored as `BIGINT`.
+fn to_i64(value: u64, field: &str) -> Result<i64, StorageError> {
+    i64::try_from(value).map_err(|_| StorageError::Validation(format!("{} is too large", field)))
+}
+
+/// Reads one column from `row`, describing the problem if it is missing or has the
+/// wrong type.
+fn column<T: FromSqlOwned>(row: &Row, column: &str) -> Result<T, String> {
+    row.try_get(column).map_err(|e| format!("{}: {}", column, e))
+}
+
+/// Reads a signed column that must hold a non-negative value.
+fn unsigned_column<T: TryFrom<i64>>(row: &Row, name: &str) -> Result<T, String> {
+    let value: i64 = match column::<i64>(row, name) {
+        Ok(value) => value,
+        Err(_) => column::<i32>(row, name)?.into(),
+    };
+    T::try_from(value).map_err(|_| format!("{} has out of range value {}", name, value))
+}
+
+/// Like `unsigned_column`, for a nullable `INT` column.
+fn optional_unsigned_column<T: TryFrom<i32>>(row: &Row, name: &str) -> Result<Option<T>, String> {
+    column::<Option<i32>>(row, name)?
+        .map(|value| T::try_from(value).map_err(|_| format!("{} has out of range value {}", name, value)))
+        .transpose()
+}
+
+fn station_column(row: &Row, name: &str) -> Result<Station, String> {
+    let raw: String = column(row, name)?;
+    raw.parse().map_err(|e: StorageError| e.to_string())
+}
+
+fn order_from_row(row: &Row) -> Result<Order, CorruptRow> {
+    let id: Uuid = column(row, "order_id").map_err(|reason| CorruptRow::new("orders", "?", reason))?;
+    let corrupt = |reason: String| CorruptRow::new("orders", id.to_string(), reason);
+    let status: String = column(row, "status").map_err(corrupt)?;
+    Ok(Order {
+        id,
+        menu_item: column(row, "menu_item").map_err(corrupt)?,
+        created_at: column(row, "created_at").map_err(corrupt)?,
+        cooking_duration_secs: unsigned_column(row, "cooking_duration_secs").map_err(corrupt)?,
+        status: status.parse().map_err(|e: StorageError| corrupt(e.to_string()))?,
+        started_at: column(row, "started_at").map_err(corrupt)?,
+        finished_at: column(row, "finished_at").map_err(corrupt)?,
+        served_at: column(row, "served_at").map_err(corrupt)?,
+        cancelled_at: column(row, "cancelled_at").map_err(corrupt)?,
+        session_id: column(row, "session_id").map_err(corrupt)?,
+        price: unsigned_column(row, "price").map_err(corrupt)?,
+        seat: optional_unsigned_column(row, "seat").map_err(corrupt)?,
+        station: station_column(row, "station").map_err(corrupt)?,
+        // Orders from before tickets existed are tickets of their own.
+        ticket_id: column::<Option<Uuid>>(row, "ticket_id").map_err(corrupt)?.unwrap_or(id),
+    })
+}
+
+fn menu_item_from_row(row: &Row) -> Result<MenuItem, CorruptRow> {
+    let id: Uuid = column(row, "menu_item_id").map_err(|reason| CorruptRow::new("menu_items", "?", reason))?;
+    let corrupt = |reason: String| CorruptRow::new("menu_items", id.to_string(), reason);
+    Ok(MenuItem {
+        id,
+        name: column(row, "name").map_err(corrupt)?,
+        category: column(row, "category").map_err(corrupt)?,
+        price: unsigned_column(row, "price").map_err(corrupt)?,
+        prep_time_minutes: unsigned_column(row, "prep_time_minutes").map_err(corrupt)?,
+        available: column(row, "available").map_err(corrupt)?,
+        station: station_column(row, "station").map_err(corrupt)?,
+    })
+}
+
+fn table_from_row(row: &Row) -> Result<Table, CorruptRow> {
+    let number: u64 =
+        unsigned_column(row, "table_number").map_err(|reason| CorruptRow::new("dining_tables", "?", reason))?;
+    let corrupt = |reason: String| CorruptRow::new("dining_tables", number.to_string(), reason);
+    let status: String = column(row, "status").map_err(corrupt)?;
+    Ok(Table {
+        number,
+        seats: unsigned_column(row, "seats").map_err(corrupt)?,
+        section: column(row, "section").map_err(corrupt)?,
+        status: status.parse().map_err(|e: StorageError| corrupt(e.to_string()))?,
+        session_id: column(row, "current_session_id").map_err(corrupt)?,
+    })
+}
+
+fn session_from_row(row: &Row) -> Result<Session, CorruptRow> {
+    let id: Uuid = column(row, "session_id").map_err(|reason| CorruptRow::new("table_sessions", "?", reason))?;
+    let corrupt = |reason: String| CorruptRow::new("table_sessions", id.to_string(), reason);
+    Ok(Session {
+        id,
+        table_number: unsigned_column(row, "table_number").map_err(corrupt)?,
+        opened_at: column(row, "opened_at").map_err(corrupt)?,
+        closed_at: column(row, "closed_at").map_err(corrupt)?,
+    })
+}
+
+/// Reads a split bill without its orders, which live in `split_bill_orders`.
+fn split_bill_from_row(row: &Row) -> Result<SplitBill, CorruptRow> {
+    let id: Uuid = column(row, "bill_id").map_err(|reason| CorruptRow::new("split_bills", "?", reason))?;
+    let corrupt = |reason: String| CorruptRow::new("split_bills", id.to_string(), reason);
+    let status: String = column(row, "status").map_err(corrupt)?;
+    Ok(SplitBill {
+        id,
+        session_id: column(row, "session_id").map_err(corrupt)?,
+        position: unsigned_column(row, "position").map_err(corrupt)?,
+        label: column(row, "label").map_err(corrupt)?,
+        order_ids: Vec::new(),
+        subtotal: unsigned_column(row, "subtotal").map_err(corrupt)?,
+        service_charge: unsigned_column(row, "service_charge").map_err(corrupt)?,
+        tax: unsigned_column(row, "tax").map_err(corrupt)?,
+        total: unsigned_column(row, "total").map_err(corrupt)?,
+        status: status.parse().map_err(|e: StorageError| corrupt(e.to_string()))?,
+    })
+}
+
+fn payment_from_row(row: &Row) -> Result<Payment, CorruptRow> {
+    let id: Uuid = column(row, "payment_id").map_err(|reason| CorruptRow::new("payments", "?", reason))?;
+    let corrupt = |reason: String| CorruptRow::new("payments", id.to_string(), reason);
+    let tender: String = column(row, "tender").map_err(corrupt)?;
+    Ok(Payment {
+        id,
+        session_id: column(row, "session_id").map_err(corrupt)?,
+        bill_id: column(row, "bill_id").map_err(corrupt)?,
+        tender: tender.parse().map_err(|e: StorageError| corrupt(e.to_string()))?,
+        reference: column(row, "reference").map_err(corrupt)?,
+        amount: unsigned_column(row, "amount").map_err(corrupt)?,
+        tip: unsigned_column(row, "tip").map_err(corrupt)?,
+        change: unsigned_column(row, "change_due").map_err(corrupt)?,
+        paid_at: column(row, "paid_at").map_err(corrupt)?,
+    })
+}
+
+const SELECT_WEBHOOKS_SQL: &str = "SELECT webhook_id, url, events, created_at FROM webhooks";
+
+fn webhook_from_row(row: &Row) -> Result<Webhook, CorruptRow> {
+    let id: Uuid = column(row, "webhook_id").map_err(|reason| CorruptRow::new("webhooks", "?", reason))?;
+    let corrupt = |reason: String| CorruptRow::new("webhooks", id.to_string(), reason);
+    let events: String = column(row, "events").map_err(corrupt)?;
+    Ok(Webhook {
+        id,
+        url: column(row, "url").map_err(corrupt)?,
+        events: split_events(&events),
+        created_at: column(row, "created_at").map_err(corrupt)?,
+    })
+}
+
+const SELECT_DEAD_LETTERS_SQL: &str = "SELECT dead_letter_id, webhook_id, event_id, event, payload, attempts, last_error, failed_at FROM webhook_dead_letters";
+
+fn dead_letter_from_row(row: &Row) -> Result<DeadLetter, CorruptRow> {
+    let id: Uuid =
+        column(row, "dead_letter_id").map_err(|reason| CorruptRow::new("webhook_dead_letters", "?", reason))?;
+    let corrupt = |reason: String| CorruptRow::new("webhook_dead_letters", id.to_string(), reason);
+    Ok(DeadLetter {
+        id,
+        webhook_id: column(row, "webhook_id").map_err(corrupt)?,
+        event_id: unsigned_column(row, "event_id").map_err(corrupt)?,
+        event: column(row, "event").map_err(corrupt)?,
+        payload: column(row, "payload").map_err(corrupt)?,
+        attempts: unsigned_column(row, "attempts").map_err(corrupt)?,
+        last_error: column(row, "last_error").map_err(corrupt)?,
+        failed_at: column(row, "failed_at").map_err(corrupt)?,
+    })
+}
+
+pub struct PostgresDb {
+    pub pool: Pool,
+}
+
+impl PostgresDb {
+    /// Connects and brings the schema up to date, panicking if either step fails.
+    pub fn new(database_url: &str) -> Self {
+        let db = Self::connect(database_url).expect("Failed to connect to Postgres");
+        db.migrate().expect("Failed to migrate database");
+        db
+    }
+
+    /// Creates the connection pool without touching the schema.
+    pub fn connect(database_url: &str) -> Result<Self, StorageError> {
+        let config: Config = database_url
+            .parse()
+            .map_err(|e| StorageError::Validation(format!("Incorrect database URL: {}", e)))?;
+        let pool = r2d2::Pool::builder()
+            .connection_timeout(Duration::from_secs(5))
+            .build(PostgresConnectionManager::new(config, NoTls))
+            .map_err(StorageError::from)?;
+        Ok(PostgresDb { pool })
+    }
+
+    /// Whether `database_url` names a Postgres database.
+    pub fn handles(database_url: &str) -> bool {
+        POSTGRES_SCHEMES.iter().any(|scheme| database_url.starts_with(scheme))
+    }
+
+    pub fn migrate(&self) -> Result<Vec<u32>, StorageError> {
+        migrations::run(self, MIGRATIONS)
+    }
+
+    pub fn verify_schema(&self) -> Result<(), StorageError> {
+        migrations::verify(self, MIGRATIONS)
+    }
+
+    fn conn(&self) -> Result<PooledConnection<PostgresConnectionManager<NoTls>>, StorageError> {
+        self.pool.get().map_err(StorageError::from)
+    }
+}
+
+impl Migrator for PostgresDb {
+    fn applied_versions(&self) -> Result<Vec<u32>, StorageError> {
+        let mut conn = self.conn()?;
+        conn.batch_execute(
+            r"
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INT NOT NULL PRIMARY KEY,
+            name VARCHAR(255) NOT NULL,
+            applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        );
+        ",
+        )
+        .map_err(StorageError::from)?;
+        let rows = conn
+            .query("SELECT version FROM schema_migrations ORDER BY version", &[])
+            .map_err(StorageError::from)?;
+        rows.iter()
+            .map(|row| {
+                let version: i32 = row.get(0);
+                u32::try_from(version)
+                    .map_err(|_| StorageError::Internal(format!("Invalid migration version {}", version)))
+            })
+            .collect()
+    }
+
+    fn apply_migration(&self, migration: &Migration) -> Result<(), StorageError> {
+        // Postgres schema changes are transactional, so a failed migration leaves no trace.
+        let mut conn = self.conn()?;
+        let mut tx = conn.transaction().map_err(StorageError::from)?;
+        tx.batch_execute(migration.sql).map_err(StorageError::from)?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version, name) VALUES ($1, $2)",
+            &[&(migration.version as i32), &migration.name],
+        )
+        .map_err(StorageError::from)?;
+        tx.commit().map_err(StorageError::from)
+    }
+}
+
+impl From<postgres::Error> for StorageError {
+    fn from(e: postgres::Error) -> Self {
+        match e.code() {
+            Some(code) if *code == SqlState::UNIQUE_VIOLATION => StorageError::Conflict(e.to_string()),
+            Some(code)
+                if *code == SqlState::TOO_MANY_CONNECTIONS
+                    || *code == SqlState::ADMIN_SHUTDOWN
+                    || *code == SqlState::CANNOT_CONNECT_NOW =>
+            {
+                StorageError::Unavailable(e.to_string())
+            }
+            None if e.is_closed() => StorageError::Unavailable(e.to_string()),
+            _ => StorageError::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<r2d2::Error> for StorageError {
+    fn from(e: r2d2::Error) -> Self {
+        StorageError::Unavailable(e.to_string())
+    }
+}
+
+impl Storage for PostgresDb {
+    fn get_table_orders(&self, table_id: u64) -> Result<Vec<Order>, StorageError> {
+        let table_id = to_i64(table_id, "table_id")?;
+        let rows = self
+            .conn()?
+            .query(
+                &format!("{} WHERE table_id = $1 ORDER BY created_at", SELECT_ORDERS_SQL),
+                &[&table_id],
+            )
+            .map_err(StorageError::from)?;
+        Ok(decode::skip_corrupt(rows.iter().map(order_from_row).collect()))
+    }
+
+    fn get_table_order(&self, table_id: u64, order_id: Uuid) -> Result<Order, StorageError> {
+        let table_id = to_i64(table_id, "table_id")?;
+        let row = self
+            .conn()?
+            .query_opt(
+                &format!("{} WHERE table_id = $1 AND order_id = $2", SELECT_ORDERS_SQL),
+                &[&table_id, &order_id],
+            )
+            .map_err(StorageError::from)?
+            .ok_or(StorageError::NotFound("Order not found".to_string()))?;
+        Ok(order_from_row(&row)?)
+    }
+
+    fn add_table_orders(&self, table_id: u64, orders: Vec<Order>) -> Result<Vec<Uuid>, StorageError> {
+        if orders.is_empty() {
+            return Ok(Vec::new());
+        }
+        let table_id = to_i64(table_id, "table_id")?;
+        let order_ids: Vec<Uuid> = orders.iter().map(|order| order.id).collect();
+        let table_ids = vec![table_id; orders.len()];
+        let mut menu_items = Vec::with_capacity(orders.len());
+        let mut created_ats = Vec::with_capacity(orders.len());
+        let mut cooking_durations = Vec::with_capacity(orders.len());
+        let mut statuses = Vec::with_capacity(orders.len());
+        let mut session_ids = Vec::with_capacity(orders.len());
+        let mut prices = Vec::with_capacity(orders.len());
+        let mut seats = Vec::with_capacity(orders.len());
+        let mut stations = Vec::with_capacity(orders.len());
+        let mut ticket_ids = Vec::with_capacity(orders.len());
+        for order in orders {
+            menu_items.push(order.menu_item);
+            created_ats.push(order.created_at);
+            cooking_durations.push(to_i64(order.cooking_duration_secs, "cooking_duration_secs")?);
+            statuses.push(order.status.as_str().to_string());
+            session_ids.push(order.session_id);
+            prices.push(to_i64(order.price, "price")?);
+            seats.push(
+                order
+                    .seat
+                    .map(i32::try_from)
+                    .transpose()
+                    .map_err(|_| StorageError::Validation("seat is too large".to_string()))?,
+            );
+            stations.push(order.station.as_str().to_string());
+            ticket_ids.push(order.ticket_id);
+        }
+
+        // One statement inside one transaction, so either every order is stored or none is.
+        let mut conn = self.conn()?;
+        let mut tx = conn.transaction().map_err(StorageError::from)?;
+        tx.execute(
+            "INSERT INTO orders (order_id, table_id, menu_item, created_at, cooking_duration_secs, status, session_id, price, seat, station, ticket_id) SELECT * FROM UNNEST($1::UUID[], $2::BIGINT[], $3::VARCHAR[], $4::TIMESTAMPTZ[], $5::BIGINT[], $6::VARCHAR[], $7::UUID[], $8::BIGINT[], $9::INT[], $10::VARCHAR[], $11::UUID[])",
+            &[&order_ids, &table_ids, &menu_items, &created_ats, &cooking_durations, &statuses, &session_ids, &prices, &seats, &stations, &ticket_ids],
+        )
+        .map_err(StorageError::from)?;
+        tx.commit().map_err(StorageError::from)?;
+        Ok(order_ids)
+    }
+
+    fn delete_table_order(&self, table_id: u64, order_id: Uuid) -> Result<(), StorageError> {
+        let table_id = to_i64(table_id, "table_id")?;
+        let deleted = self
+            .conn()?
+            .execute(
+                "DELETE FROM orders WHERE table_id = $1 AND order_id = $2",
+                &[&table_id, &order_id],
+            )
+            .map_err(StorageError::from)?;
+        if deleted == 0 {
+            return Err(StorageError::NotFound("Order not found".to_string()));
+        }
+        Ok(())
+    }
+
+    fn update_order_status(
+        &self,
+        table_id: u64,
+        order_id: Uuid,
+        from: OrderStatus,
+        to: OrderStatus,
+        at: DateTime<Utc>,
+    ) -> Result<(), StorageError> {
+        let column = status_timestamp_column(to)
+            .ok_or(StorageError::Validation(format!("Orders cannot move back to {}", to)))?;
+        let table_id_param = to_i64(table_id, "table_id")?;
+        let updated = self
+            .conn()?
+            .execute(
+                &format!(
+                    "UPDATE orders SET status = $1, {} = $2 WHERE table_id = $3 AND order_id = $4 AND status = $5",
+                    column
+                ),
+                &[&to.as_str(), &at, &table_id_param, &order_id, &from.as_str()],
+            )
+            .map_err(StorageError::from)?;
+        if updated == 0 {
+            let order = self.get_table_order(table_id, order_id)?;
+            return Err(StorageError::Conflict(format!(
+                "Order is {}, not {}",
+                order.status, from
+            )));
+        }
+        Ok(())
+    }
+
+    fn count_open_orders(&self) -> Result<usize, StorageError> {
+        let count: i64 = self
+            .conn()?
+            .query_one("SELECT COUNT(*) FROM orders WHERE status IN ('placed', 'cooking')", &[])
+            .map_err(StorageError::from)?
+            .get(0);
+        Ok(count as usize)
+    }
+
+    fn get_open_orders(&self) -> Result<Vec<(u64, Order)>, StorageError> {
+        let rows = self
+            .conn()?
+            .query(
+                &format!("{} WHERE status IN ('placed', 'cooking') ORDER BY created_at", SELECT_ORDERS_SQL),
+                &[],
+            )
+            .map_err(StorageError::from)?;
+        Ok(decode::skip_corrupt(
+            rows.iter()
+                .map(|row| {
+                    let order = order_from_row(row)?;
+                    let table_id = unsigned_column(row, "table_id")
+                        .map_err(|reason| CorruptRow::new("orders", order.id.to_string(), reason))?;
+                    Ok((table_id, order))
+                })
+                .collect(),
+        ))
+    }
+
+    fn get_menu_items(&self) -> Result<Vec<MenuItem>, StorageError> {
+        let rows = self
+            .conn()?
+            .query(
+                &format!("{} ORDER BY lower(category), lower(name)", SELECT_MENU_ITEMS_SQL),
+                &[],
+            )
+            .map_err(StorageError::from)?;
+        Ok(decode::skip_corrupt(rows.iter().map(menu_item_from_row).collect()))
+    }
+
+    fn get_menu_item(&self, menu_item_id: Uuid) -> Result<MenuItem, StorageError> {
+        let row = self
+            .conn()?
+            .query_opt(
+                &format!("{} WHERE menu_item_id = $1", SELECT_MENU_ITEMS_SQL),
+                &[&menu_item_id],
+            )
+            .map_err(StorageError::from)?
+            .ok_or(StorageError::NotFound("Menu item not found".to_string()))?;
+        Ok(menu_item_from_row(&row)?)
+    }
+
+    fn add_menu_item(&self, item: MenuItem) -> Result<Uuid, StorageError> {
+        let price = to_i64(item.price, "price")?;
+        let prep_time_minutes = i32::try_from(item.prep_time_minutes)
+            .map_err(|_| StorageError::Validation("prep_time_minutes is too large".to_string()))?;
+        self.conn()?
+            .execute(
+                "INSERT INTO menu_items (menu_item_id, name, category, price, prep_time_minutes, available, station) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                &[&item.id, &item.name, &item.category, &price, &prep_time_minutes, &item.available, &item.station.as_str()],
+            )
+            .map_err(StorageError::from)?;
+        Ok(item.id)
+    }
+
+    fn update_menu_item(&self, item: MenuItem) -> Result<(), StorageError> {
+        let price = to_i64(item.price, "price")?;
+        let prep_time_minutes = i32::try_from(item.prep_time_minutes)
+            .map_err(|_| StorageError::Validation("prep_time_minutes is too large".to_string()))?;
+        let updated = self
+            .conn()?
+            .execute(
+                "UPDATE menu_items SET name = $2, category = $3, price = $4, prep_time_minutes = $5, available = $6, station = $7 WHERE menu_item_id = $1",
+                &[&item.id, &item.name, &item.category, &price, &prep_time_minutes, &item.available, &item.station.as_str()],
+            )
+            .map_err(StorageError::from)?;
+        if updated == 0 {
+            return Err(StorageError::NotFound("Menu item not found".to_string()));
+        }
+        Ok(())
+    }
+
+    fn delete_menu_item(&self, menu_item_id: Uuid) -> Result<(), StorageError> {
+        let deleted = self
+            .conn()?
+            .execute("DELETE FROM menu_items WHERE menu_item_id = $1", &[&menu_item_id])
+            .map_err(StorageError::from)?;
+        if deleted == 0 {
+            return Err(StorageError::NotFound("Menu item not found".to_string()));
+        }
+        Ok(())
+    }
+
+    fn get_tables(&self) -> Result<Vec<Table>, StorageError> {
+        let rows = self
+            .conn()?
+            .query(&format!("{} ORDER BY table_number", SELECT_TABLES_SQL), &[])
+            .map_err(StorageError::from)?;
+        Ok(decode::skip_corrupt(rows.iter().map(table_from_row).collect()))
+    }
+
+    fn get_table(&self, number: u64) -> Result<Table, StorageError> {
+        let number_param = to_i64(number, "number")?;
+        let row = self
+            .conn()?
+            .query_opt(
+                &format!("{} WHERE table_number = $1", SELECT_TABLES_SQL),
+                &[&number_param],
+            )
+            .map_err(StorageError::from)?
+            .ok_or(StorageError::NotFound(format!("Table {} is not registered", number)))?;
+        Ok(table_from_row(&row)?)
+    }
+
+    fn add_table(&self, table: Table) -> Result<u64, StorageError> {
+        let number = to_i64(table.number, "number")?;
+        let seats = i32::try_from(table.seats)
+            .map_err(|_| StorageError::Validation("seats is too large".to_string()))?;
+        self.conn()?
+            .execute(
+                "INSERT INTO dining_tables (table_number, seats, section, status, current_session_id) VALUES ($1, $2, $3, $4, $5)",
+                &[&number, &seats, &table.section, &table.status.as_str(), &table.session_id],
+            )
+            .map_err(StorageError::from)?;
+        Ok(table.number)
+    }
+
+    fn open_table_session(&self, number: u64, session: Session) -> Result<(), StorageError> {
+        let number_param = to_i64(number, "number")?;
+        let mut conn = self.conn()?;
+        let mut tx = conn.transaction().map_err(StorageError::from)?;
+        let updated = tx
+            .execute(
+                "UPDATE dining_tables SET status = 'open', current_session_id = $1 WHERE table_number = $2 AND status = 'closed'",
+                &[&session.id, &number_param],
+            )
+            .map_err(StorageError::from)?;
+        if updated == 0 {
+            drop(tx);
+            self.get_table(number)?;
+            return Err(StorageError::Conflict(format!("Table {} is already open", number)));
+        }
+        tx.execute(
+            "INSERT INTO table_sessions (session_id, table_number, opened_at) VALUES ($1, $2, $3)",
+            &[&session.id, &number_param, &session.opened_at],
+        )
+        .map_err(StorageError::from)?;
+        tx.commit().map_err(StorageError::from)
+    }
+
+    fn close_table_session(&self, number: u64, session_id: Uuid, closed_at: DateTime<Utc>) -> Result<(), StorageError> {
+        let number_param = to_i64(number, "number")?;
+        let mut conn = self.conn()?;
+        let mut tx = conn.transaction().map_err(StorageError::from)?;
+        let updated = tx
+            .execute(
+                "UPDATE dining_tables SET status = 'closed', current_session_id = NULL WHERE table_number = $1 AND current_session_id = $2",
+                &[&number_param, &session_id],
+            )
+            .map_err(StorageError::from)?;
+        if updated == 0 {
+            drop(tx);
+            self.get_table(number)?;
+            return Err(StorageError::Conflict(format!(
+                "Session {} is not in progress at table {}",
+                session_id, number
+            )));
+        }
+        tx.execute(
+            "UPDATE table_sessions SET closed_at = $1 WHERE session_id = $2",
+            &[&closed_at, &session_id],
+        )
+        .map_err(StorageError::from)?;
+        tx.commit().map_err(StorageError::from)
+    }
+
+    fn get_table_sessions(&self, number: u64) -> Result<Vec<Session>, StorageError> {
+        let number = to_i64(number, "number")?;
+        let rows = self
+            .conn()?
+            .query(
+                &format!("{} WHERE table_number = $1 ORDER BY opened_at", SELECT_SESSIONS_SQL),
+                &[&number],
+            )
+            .map_err(StorageError::from)?;
+        Ok(decode::skip_corrupt(rows.iter().map(session_from_row).collect()))
+    }
+
+    fn get_table_session(&self, number: u64, session_id: Uuid) -> Result<Session, StorageError> {
+        let number = to_i64(number, "number")?;
+        let row = self
+            .conn()?
+            .query_opt(
+                &format!("{} WHERE table_number = $1 AND session_id = $2", SELECT_SESSIONS_SQL),
+                &[&number, &session_id],
+            )
+            .map_err(StorageError::from)?
+            .ok_or(StorageError::NotFound("Session not found".to_string()))?;
+        Ok(session_from_row(&row)?)
+    }
+
+    fn get_session_orders(&self, session_id: Uuid, decoding: Decoding) -> Result<Vec<Order>, StorageError> {
+        let rows = self
+            .conn()?
+            .query(
+                &format!("{} WHERE session_id = $1 ORDER BY created_at", SELECT_ORDERS_SQL),
+                &[&session_id],
+            )
+            .map_err(StorageError::from)?;
+        decode::collect(rows.iter().map(order_from_row).collect(), decoding)
+    }
+
+    fn replace_split_bills(&self, session_id: Uuid, bills: Vec<SplitBill>) -> Result<(), StorageError> {
+        let mut conn = self.conn()?;
+        let mut tx = conn.transaction().map_err(StorageError::from)?;
+        // Locking the current bills stops one being closed while it is replaced.
+        let statuses = tx
+            .query("SELECT status FROM split_bills WHERE session_id = $1 FOR UPDATE", &[&session_id])
+            .map_err(StorageError::from)?;
+        if statuses.iter().any(|row| row.get::<_, String>(0) != BillStatus::Open.as_str()) {
+            return Err(StorageError::Conflict("A split bill has already been paid or closed".to_string()));
+        }
+        tx.execute(
+            "DELETE FROM split_bill_orders WHERE bill_id IN (SELECT bill_id FROM split_bills WHERE session_id = $1)",
+            &[&session_id],
+        )
+        .map_err(StorageError::from)?;
+        tx.execute("DELETE FROM split_bills WHERE session_id = $1", &[&session_id])
+            .map_err(StorageError::from)?;
+        for bill in bills {
+            let position = i32::try_from(bill.position)
+                .map_err(|_| StorageError::Validation("position is too large".to_string()))?;
+            tx.execute(
+                "INSERT INTO split_bills (bill_id, session_id, position, label, subtotal, service_charge, tax, total, status) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+                &[
+                    &bill.id,
+                    &session_id,
+                    &position,
+                    &bill.label,
+                    &to_i64(bill.subtotal, "subtotal")?,
+                    &to_i64(bill.service_charge, "service_charge")?,
+                    &to_i64(bill.tax, "tax")?,
+                    &to_i64(bill.total, "total")?,
+                    &bill.status.as_str(),
+                ],
+            )
+            .map_err(StorageError::from)?;
+            tx.execute(
+                "INSERT INTO split_bill_orders (bill_id, order_id) SELECT $1, order_id FROM UNNEST($2::UUID[]) AS order_id",
+                &[&bill.id, &bill.order_ids],
+            )
+            .map_err(StorageError::from)?;
+        }
+        tx.commit().map_err(StorageError::from)
+    }
+
+    fn get_split_bills(&self, session_id: Uuid, decoding: Decoding) -> Result<Vec<SplitBill>, StorageError> {
+        let mut conn = self.conn()?;
+        let rows = conn
+            .query(
+                &format!("{} WHERE session_id = $1 ORDER BY position", SELECT_SPLIT_BILLS_SQL),
+                &[&session_id],
+            )
+            .map_err(StorageError::from)?;
+        let mut bills = decode::collect(rows.iter().map(split_bill_from_row).collect(), decoding)?;
+        let rows = conn
+            .query(
+                "SELECT split_bill_orders.bill_id, order_id FROM split_bill_orders JOIN split_bills ON split_bills.bill_id = split_bill_orders.bill_id WHERE session_id = $1 ORDER BY order_id",
+                &[&session_id],
+            )
+            .map_err(StorageError::from)?;
+        for row in rows {
+            let bill_id: Uuid = row.get(0);
+            if let Some(bill) = bills.iter_mut().find(|bill| bill.id == bill_id) {
+                bill.order_ids.push(row.get(1));
+            }
+        }
+        Ok(bills)
+    }
+
+    fn get_split_bill(&self, session_id: Uuid, bill_id: Uuid) -> Result<SplitBill, StorageError> {
+        let mut conn = self.conn()?;
+        let row = conn
+            .query_opt(
+                &format!("{} WHERE session_id = $1 AND bill_id = $2", SELECT_SPLIT_BILLS_SQL),
+                &[&session_id, &bill_id],
+            )
+            .map_err(StorageError::from)?
+            .ok_or(StorageError::NotFound("Bill not found".to_string()))?;
+        let mut bill = split_bill_from_row(&row)?;
+        bill.order_ids = conn
+            .query("SELECT order_id FROM split_bill_orders WHERE bill_id = $1 ORDER BY order_id", &[&bill_id])
+            .map_err(StorageError::from)?
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        Ok(bill)
+    }
+
+    fn update_split_bill_status(
+        &self,
+        session_id: Uuid,
+        bill_id: Uuid,
+        from: BillStatus,
+        to: BillStatus,
+    ) -> Result<(), StorageError> {
+        let updated = self
+            .conn()?
+            .execute(
+                "UPDATE split_bills SET status = $1 WHERE session_id = $2 AND bill_id = $3 AND status = $4",
+                &[&to.as_str(), &session_id, &bill_id, &from.as_str()],
+            )
+            .map_err(StorageError::from)?;
+        if updated == 0 {
+            let bill = self.get_split_bill(session_id, bill_id)?;
+            return Err(StorageError::Conflict(format!("Bill is {}, not {}", bill.status, from)));
+        }
+        Ok(())
+    }
+
+    fn add_payment(&self, payment: Payment) -> Result<Uuid, StorageError> {
+        self.conn()?
+            .execute(
+                "INSERT INTO payments (payment_id, session_id, bill_id, tender, reference, amount, tip, change_due, paid_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+                &[
+                    &payment.id,
+                    &payment.session_id,
+                    &payment.bill_id,
+                    &payment.tender.as_str(),
+                    &payment.reference,
+                    &to_i64(payment.amount, "amount")?,
+                    &to_i64(payment.tip, "tip")?,
+                    &to_i64(payment.change, "change")?,
+                    &payment.paid_at,
+                ],
+            )
+            .map_err(StorageError::from)?;
+        Ok(payment.id)
+    }
+
+    fn get_session_payments(&self, session_id: Uuid, decoding: Decoding) -> Result<Vec<Payment>, StorageError> {
+        let rows = self
+            .conn()?
+            .query(
+                &format!("{} WHERE session_id = $1 ORDER BY paid_at", SELECT_PAYMENTS_SQL),
+                &[&session_id],
+            )
+            .map_err(StorageError::from)?;
+        decode::collect(rows.iter().map(payment_from_row).collect(), decoding)
+    }
+
+    fn add_webhook(&self, webhook: Webhook) -> Result<Uuid, StorageError> {
+        self.conn()?
+            .execute(
+                "INSERT INTO webhooks (webhook_id, url, events, created_at) VALUES ($1, $2, $3, $4)",
+                &[&webhook.id, &webhook.url, &join_events(&webhook.events), &webhook.created_at],
+            )
+            .map_err(StorageError::from)?;
+        Ok(webhook.id)
+    }
+
+    fn get_webhooks(&self) -> Result<Vec<Webhook>, StorageError> {
+        let rows = self
+            .conn()?
+            .query(&format!("{} ORDER BY created_at", SELECT_WEBHOOKS_SQL), &[])
+            .map_err(StorageError::from)?;
+        Ok(decode::skip_corrupt(rows.iter().map(webhook_from_row).collect()))
+    }
+
+    fn get_webhook(&self, webhook_id: Uuid) -> Result<Webhook, StorageError> {
+        let row = self
+            .conn()?
+            .query_opt(&format!("{} WHERE webhook_id = $1", SELECT_WEBHOOKS_SQL), &[&webhook_id])
+            .map_err(StorageError::from)?
+            .ok_or(StorageError::NotFound("Webhook not found".to_string()))?;
+        Ok(webhook_from_row(&row)?)
+    }
+
+    fn delete_webhook(&self, webhook_id: Uuid) -> Result<(), StorageError> {
+        let deleted = self
+            .conn()?
+            .execute("DELETE FROM webhooks WHERE webhook_id = $1", &[&webhook_id])
+            .map_err(StorageError::from)?;
+        if deleted == 0 {
+            return Err(StorageError::NotFound("Webhook not found".to_string()));
+        }
+        Ok(())
+    }
+
+    fn add_dead_letter(&self, dead_letter: DeadLetter) -> Result<Uuid, StorageError> {
+        self.conn()?
+            .execute(
+                "INSERT INTO webhook_dead_letters (dead_letter_id, webhook_id, event_id, event, payload, attempts, last_error, failed_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+                &[
+                    &dead_letter.id,
+                    &dead_letter.webhook_id,
+                    &to_i64(dead_letter.event_id, "event_id")?,
+                    &dead_letter.event,
+                    &dead_letter.payload,
+                    &i64::from(dead_letter.attempts),
+                    &dead_letter.last_error,
+                    &dead_letter.failed_at,
+                ],
+            )
+            .map_err(StorageError::from)?;
+        Ok(dead_letter.id)
+    }
+
+    fn get_dead_letters(&self) -> Result<Vec<DeadLetter>, StorageError> {
+        let rows = self
+            .conn()?
+            .query(&format!("{} ORDER BY failed_at", SELECT_DEAD_LETTERS_SQL), &[])
+            .map_err(StorageError::from)?;
+        Ok(decode::skip_corrupt(rows.iter().map(dead_letter_from_row).collect()))
+    }
+
+    fn delete_dead_letter(&self, dead_letter_id: Uuid) -> Result<(), StorageError> {
+        let deleted = self
+            .conn()?
+            .execute("DELETE FROM webhook_dead_letters WHERE dead_letter_id = $1", &[&dead_letter_id])
+            .map_err(StorageError::from)?;
+        if deleted == 0 {
+            return Err(StorageError::NotFound("Dead letter not found".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::conformance::storage_conformance_tests;
+    use dotenv::from_filename;
+    use std::env;
+    use std::sync::Once;
+
+    static MIGRATE: Once = Once::new();
+
+    fn setup_test_db() -> PostgresDb {
+        from_filename(".env.test").ok();
+        let database_url = env::var("TEST_POSTGRES_URL").expect("TEST_POSTGRES_URL must be established");
+        let db = PostgresDb::connect(&database_url).expect("Failed to connect to Postgres");
+        MIGRATE.call_once(|| {
+            db.migrate().expect("Failed to migrate database");
+        });
+        db
+    }
+
+    storage_conformance_tests!(
+        setup_test_db(),
+        #[ignore = "requires a Postgres server at TEST_POSTGRES_URL"]
+    );
+
+    fn random_table_id() -> u64 {
+        9_000 + rand::random::<u16>() as u64
+    }
+
+    const INSERT_ORDER_SQL: &str = "INSERT INTO orders (order_id, table_id, menu_item, created_at, cooking_duration_secs) VALUES ($1, $2, 'Mock Item', now(), 600)";
+
+    #[test]
+    #[ignore = "requires a Postgres server at TEST_POSTGRES_URL"]
+    fn test_get_table_orders() {
+        let db = setup_test_db();
+        let table_id = random_table_id();
+        let order_id = Uuid::new_v4();
+        db.conn().unwrap().execute(INSERT_ORDER_SQL, &[&order_id, &(table_id as i64)]).unwrap();
+
+        let result = db.get_table_orders(table_id);
+        assert!(
+            result.is_ok(),
+            "Expected Ok but got Err: {:?}",
+            result.err()
+        );
+        assert!(result.unwrap().iter().any(|order| order.id == order_id));
+
+        db.delete_table_order(table_id, order_id).unwrap();
+    }
+
+    #[test]
+    #[ignore = "requires a Postgres server at TEST_POSTGRES_URL"]
+    fn test_get_table_order() {
+        let db = setup_test_db();
+        let table_id = random_table_id();
+        let order_id = Uuid::new_v4();
+        db.conn().unwrap().execute(INSERT_ORDER_SQL, &[&order_id, &(table_id as i64)]).unwrap();
+
+        let result = db.get_table_order(table_id, order_id);
+        assert!(
+            result.is_ok(),
+            "Expected Ok but got Err: {:?}",
+            result.err()
+        );
+        assert!(matches!(db.get_table_order(table_id + 1, order_id), Err(StorageError::NotFound(_))));
+
+        db.delete_table_order(table_id, order_id).unwrap();
+    }
+
+    #[test]
+    #[ignore = "requires a Postgres server at TEST_POSTGRES_URL"]
+    fn test_add_and_delete_table_order() {
+        let db = setup_test_db();
+        let table_id = random_table_id();
+        let order = Order::placed(Uuid::new_v4(), "Mock Item".to_string(), Utc::now(), 600);
+
+        let result = db.add_table_orders(table_id, vec![order.clone()]);
+        assert_eq!(result, Ok(vec![order.id]));
+
+        assert!(db.delete_table_order(table_id, order.id).is_ok());
+        assert!(matches!(db.get_table_order(table_id, order.id), Err(StorageError::NotFound(_))));
+        assert!(matches!(db.delete_table_order(table_id, order.id), Err(StorageError::NotFound(_))));
+    }
+
+    #[test]
+    #[ignore = "requires a Postgres server at TEST_POSTGRES_URL"]
+    fn test_add_table_orders_inserts_batch() {
+        let db = setup_test_db();
+        let table_id = random_table_id();
+        let orders: Vec<Order> = ["Pizza", "Salad", "Soup"]
+            .iter()
+            .map(|item| Order::placed(Uuid::new_v4(), item.to_string(), Utc::now(), 600))
+            .collect();
+        let ids: Vec<Uuid> = orders.iter().map(|order| order.id).collect();
+        let orders_copy = orders.clone();
+
+        assert_eq!(db.add_table_orders(table_id, orders), Ok(ids.clone()));
+        assert_eq!(db.add_table_orders(table_id, vec![]), Ok(vec![]));
+        let duplicate = db.add_table_orders(table_id, vec![orders_copy[0].clone()]);
+        assert!(matches!(duplicate, Err(StorageError::Conflict(_))));
+
+        let too_long = Order::placed(Uuid::new_v4(), "x".repeat(300), Utc::now(), 600);
+        let pizza = Order::placed(Uuid::new_v4(), "Pizza".to_string(), Utc::now(), 600);
+        assert!(db.add_table_orders(table_id, vec![pizza, too_long]).is_err());
+
+        let stored = db.get_table_orders(table_id).unwrap();
+        assert_eq!(stored.len(), 3);
+        assert_eq!(stored[0].created_at.timestamp_micros(), orders_copy[0].created_at.timestamp_micros());
+        for id in ids {
+            db.delete_table_order(table_id, id).unwrap();
+        }
+    }
+
+    #[test]
+    #[ignore = "requires a Postgres server at TEST_POSTGRES_URL"]
+    fn test_update_order_status() {
+        let db = setup_test_db();
+        let table_id = random_table_id();
+        let pizza = Order::placed(Uuid::new_v4(), "Pizza".to_string(), Utc::now(), 600);
+        db.add_table_orders(table_id, vec![pizza.clone()]).unwrap();
+
+        let at = Utc::now();
+        db.update_order_status(table_id, pizza.id, OrderStatus::Placed, OrderStatus::Cooking, at).unwrap();
+        let fetched = db.get_table_order(table_id, pizza.id).unwrap();
+        assert_eq!(fetched.status, OrderStatus::Cooking);
+        assert_eq!(fetched.started_at.map(|at| at.timestamp_micros()), Some(at.timestamp_micros()));
+
+        let result = db.update_order_status(table_id, pizza.id, OrderStatus::Placed, OrderStatus::Cancelled, at);
+        assert!(matches!(result, Err(StorageError::Conflict(_))));
+
+        db.delete_table_order(table_id, pizza.id).unwrap();
+    }
+
+    #[test]
+    #[ignore = "requires a Postgres server at TEST_POSTGRES_URL"]
+    fn test_corrupt_rows_are_reported() {
+        let db = setup_test_db();
+        let table_id = random_table_id();
+        let good = Order::placed(Uuid::new_v4(), "Pizza".to_string(), Utc::now(), 600);
+        db.add_table_orders(table_id, vec![good.clone()]).unwrap();
+
+        let bad_id = Uuid::new_v4();
+        db.conn()
+            .unwrap()
+            .execute(
+                "INSERT INTO orders (order_id, table_id, menu_item, created_at, cooking_duration_secs, status) VALUES ($1, $2, 'Soup', now(), 600, 'exploded')",
+                &[&bad_id, &(table_id as i64)],
+            )
+            .unwrap();
+
+        let orders = db.get_table_orders(table_id).unwrap();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].id, good.id);
+        match db.get_table_order(table_id, bad_id) {
+            Err(StorageError::Corrupt(corrupt)) => assert_eq!(corrupt.key, bad_id.to_string()),
+            other => panic!("Expected a corrupt row but got {:?}", other),
+        }
+
+        db.delete_table_order(table_id, good.id).unwrap();
+        db.delete_table_order(table_id, bad_id).unwrap();
+    }
+
+    #[test]
+    #[ignore = "requires a Postgres server at TEST_POSTGRES_URL"]
+    fn test_menu_items_round_trip() {
+        let db = setup_test_db();
+        let name = format!("Pizza {}", Uuid::new_v4());
+        let pizza = MenuItem {
+            id: Uuid::new_v4(),
+            name: name.clone(),
+            category: "Mains".to_string(),
+            price: 1250,
+            prep_time_minutes: 12,
+            available: true,
+            station: Station::Grill,
+        };
+        db.add_menu_item(pizza.clone()).unwrap();
+
+        let mut clash = pizza.clone();
+        clash.id = Uuid::new_v4();
+        clash.name = name.to_uppercase();
+        assert!(matches!(db.add_menu_item(clash), Err(StorageError::Conflict(_))));
+
+        let mut updated = pizza.clone();
+        updated.price = 1400;
+        db.update_menu_item(updated).unwrap();
+        assert_eq!(db.get_menu_item(pizza.id).unwrap().price, 1400);
+        assert!(db.get_menu_items().unwrap().iter().any(|item| item.id == pizza.id));
+
+        db.delete_menu_item(pizza.id).unwrap();
+        assert!(matches!(db.get_menu_item(pizza.id), Err(StorageError::NotFound(_))));
+    }
+
+    #[test]
+    #[ignore = "requires a Postgres server at TEST_POSTGRES_URL"]
+    fn test_migrations_are_recorded_once() {
+        let db = setup_test_db();
+        assert_eq!(db.migrate(), Ok(vec![]));
+        assert_eq!(db.verify_schema(), Ok(()));
+
+        let applied = db.applied_versions().unwrap();
+        let known: Vec<u32> = MIGRATIONS.iter().map(|migration| migration.version).collect();
+        assert_eq!(applied, known);
+    }
+}
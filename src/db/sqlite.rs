@@ -0,0 +1,1247 @@
+use crate::db::decode::{self, hex_key, CorruptRow, Decoding};
+use crate::db::migrations::{self, Migration, Migrator};
+use crate::db::{
+    join_events, split_events, status_timestamp_column, BillStatus, DeadLetter, MenuItem, Order, OrderStatus, Payment,
+    Session, SplitBill, Station, Storage, StorageError, Table, Webhook,
+};
+use chrono::{DateTime, Utc};
+use rusqlite::types::FromSql;
+use rusqlite::{named_params, params, Connection, ErrorCode, OptionalExtension, Row};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Same versions and names as the MySQL migrations, written for SQLite.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_orders",
+        sql: include_str!("../../migrations/sqlite/0001_create_orders.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "create_menu_items",
+        sql: include_str!("../../migrations/sqlite/0002_create_menu_items.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "add_order_status",
+        sql: include_str!("../../migrations/sqlite/0003_add_order_status.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "structured_cooking_time",
+        sql: include_str!("../../migrations/sqlite/0004_structured_cooking_time.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "orders_keys_and_indexes",
+        sql: include_str!("../../migrations/sqlite/0005_orders_keys_and_indexes.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "create_dining_tables",
+        sql: include_str!("../../migrations/sqlite/0006_create_dining_tables.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "add_table_sessions",
+        sql: include_str!("../../migrations/sqlite/0007_add_table_sessions.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "add_order_prices",
+        sql: include_str!("../../migrations/sqlite/0008_add_order_prices.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "add_split_bills",
+        sql: include_str!("../../migrations/sqlite/0009_add_split_bills.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "create_payments",
+        sql: include_str!("../../migrations/sqlite/0010_create_payments.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "add_kitchen_stations",
+        sql: include_str!("../../migrations/sqlite/0011_add_kitchen_stations.sql"),
+    },
+    Migration {
+        version: 12,
+        name: "create_webhooks",
+        sql: include_str!("../../migrations/sqlite/0012_create_webhooks.sql"),
+    },
+];
+
+const SQLITE_SCHEME: &str = "sqlite://";
+
+const SELECT_ORDERS_SQL: &str = "SELECT order_id, menu_item, created_at, cooking_duration_secs, status, started_at, finished_at, served_at, cancelled_at, session_id, price, seat, station, ticket_id, table_id FROM orders";
+
+const SELECT_MENU_ITEMS_SQL: &str = "SELECT menu_item_id, name, category, price, prep_time_minutes, available, station FROM menu_items";
+
+/// Reads one column from `row`, describing the problem if it has the wrong type.
+fn column<T: FromSql>(row: &Row, column: &str) -> Result<T, String> {
+    row.get(column).map_err(|e| format!("{}: {}", column, e))
+}
+
+/// Reads a nullable UUID stored as 16 bytes.
+fn uuid_column(row: &Row, name: &str) -> Result<Option<Uuid>, String> {
+    let raw: Option<Vec<u8>> = column(row, name)?;
+    raw.map(|raw| Uuid::from_slice(&raw).map_err(|e| format!("{} is not a UUID: {}", name, e)))
+        .transpose()
+}
+
+fn station_column(row: &Row, name: &str) -> Result<Station, String> {
+    let raw: String = column(row, name)?;
+    raw.parse().map_err(|e: StorageError| e.to_string())
+}
+
+fn order_from_row(row: &Row) -> Result<Order, CorruptRow> {
+    let raw_id: Vec<u8> = column(row, "order_id").map_err(|reason| CorruptRow::new("orders", "?", reason))?;
+    let id = Uuid::from_slice(&raw_id)
+        .map_err(|e| CorruptRow::new("orders", hex_key(&raw_id), format!("order_id is not a UUID: {}", e)))?;
+    let corrupt = |reason: String| CorruptRow::new("orders", id.to_string(), reason);
+    let status: String = column(row, "status").map_err(corrupt)?;
+    Ok(Order {
+        id,
+        menu_item: column(row, "menu_item").map_err(corrupt)?,
+        created_at: column(row, "created_at").map_err(corrupt)?,
+        cooking_duration_secs: column(row, "cooking_duration_secs").map_err(corrupt)?,
+        status: status.parse().map_err(|e: StorageError| corrupt(e.to_string()))?,
+        started_at: column(row, "started_at").map_err(corrupt)?,
+        finished_at: column(row, "finished_at").map_err(corrupt)?,
+        served_at: column(row, "served_at").map_err(corrupt)?,
+        cancelled_at: column(row, "cancelled_at").map_err(corrupt)?,
+        session_id: uuid_column(row, "session_id").map_err(corrupt)?,
+        price: column(row, "price").map_err(corrupt)?,
+        seat: column(row, "seat").map_err(corrupt)?,
+        station: station_column(row, "station").map_err(corrupt)?,
+        // Orders from before tickets existed are tickets of their own.
+        ticket_id: uuid_column(row, "ticket_id").map_err(corrupt)?.unwrap_or(id),
+    })
+}
+
+fn menu_item_from_row(row: &Row) -> Result<MenuItem, CorruptRow> {
+    let raw_id: String =
+        column(row, "menu_item_id").map_err(|reason| CorruptRow::new("menu_items", "?", reason))?;
+    let id = Uuid::parse_str(&raw_id)
+        .map_err(|e| CorruptRow::new("menu_items", raw_id.clone(), format!("menu_item_id is not a UUID: {}", e)))?;
+    let corrupt = |reason: String| CorruptRow::new("menu_items", id.to_string(), reason);
+    Ok(MenuItem {
+        id,
+        name: column(row, "name").map_err(corrupt)?,
+        category: column(row, "category").map_err(corrupt)?,
+        price: column(row, "price").map_err(corrupt)?,
+        prep_time_minutes: column(row, "prep_time_minutes").map_err(corrupt)?,
+        available: column(row, "available").map_err(corrupt)?,
+        station: station_column(row, "station").map_err(corrupt)?,
+    })
+}
+
+const SELECT_TABLES_SQL: &str = "SELECT table_number, seats, section, status, current_session_id FROM dining_tables";
+
+const SELECT_SESSIONS_SQL: &str = "SELECT session_id, table_number, opened_at, closed_at FROM table_sessions";
+
+fn table_from_row(row: &Row) -> Result<Table, CorruptRow> {
+    let number: u64 =
+        column(row, "table_number").map_err(|reason| CorruptRow::new("dining_tables", "?", reason))?;
+    let corrupt = |reason: String| CorruptRow::new("dining_tables", number.to_string(), reason);
+    let status: String = column(row, "status").map_err(corrupt)?;
+    Ok(Table {
+        number,
+        seats: column(row, "seats").map_err(corrupt)?,
+        section: column(row, "section").map_err(corrupt)?,
+        status: status.parse().map_err(|e: StorageError| corrupt(e.to_string()))?,
+        session_id: uuid_column(row, "current_session_id").map_err(corrupt)?,
+    })
+}
+
+fn session_from_row(row: &Row) -> Result<Session, CorruptRow> {
+    let raw_id: Vec<u8> =
+        column(row, "session_id").map_err(|reason| CorruptRow::new("table_sessions", "?", reason))?;
+    let id = Uuid::from_slice(&raw_id).map_err(|e| {
+        CorruptRow::new("table_sessions", hex_key(&raw_id), format!("session_id is not a UUID: {}", e))
+    })?;
+    let corrupt = |reason: String| CorruptRow::new("table_sessions", id.to_string(), reason);
+    Ok(Session {
+        id,
+        table_number: column(row, "table_number").map_err(corrupt)?,
+        opened_at: column(row, "opened_at").map_err(corrupt)?,
+        closed_at: column(row, "closed_at").map_err(corrupt)?,
+    })
+}
+
+const SELECT_SPLIT_BILLS_SQL: &str =
+    "SELECT bill_id, session_id, position, label, subtotal, service_charge, tax, total, status FROM split_bills";
+
+/// Reads a split bill without its orders, which live in `split_bill_orders`.
+fn split_bill_from_row(row: &Row) -> Result<SplitBill, CorruptRow> {
+    let raw_id: Vec<u8> = column(row, "bill_id").map_err(|reason| CorruptRow::new("split_bills", "?", reason))?;
+    let id = Uuid::from_slice(&raw_id)
+        .map_err(|e| CorruptRow::new("split_bills", hex_key(&raw_id), format!("bill_id is not a UUID: {}", e)))?;
+    let corrupt = |reason: String| CorruptRow::new("split_bills", id.to_string(), reason);
+    let status: String = column(row, "status").map_err(corrupt)?;
+    Ok(SplitBill {
+        id,
+        session_id: uuid_column(row, "session_id")
+            .and_then(|id| id.ok_or_else(|| "session_id is NULL".to_string()))
+            .map_err(corrupt)?,
+        position: column(row, "position").map_err(corrupt)?,
+        label: column(row, "label").map_err(corrupt)?,
+        order_ids: Vec::new(),
+        subtotal: column(row, "subtotal").map_err(corrupt)?,
+        service_charge: column(row, "service_charge").map_err(corrupt)?,
+        tax: column(row, "tax").map_err(corrupt)?,
+        total: column(row, "total").map_err(corrupt)?,
+        status: status.parse().map_err(|e: StorageError| corrupt(e.to_string()))?,
+    })
+}
+
+/// Fills in the orders of each bill, in ascending order.
+fn attach_split_bill_orders(conn: &Connection, bills: &mut [SplitBill]) -> Result<(), StorageError> {
+    let mut statement = conn
+        .prepare("SELECT order_id FROM split_bill_orders WHERE bill_id = ?1 ORDER BY order_id")
+        .map_err(StorageError::from)?;
+    for bill in bills {
+        let raw_ids = statement
+            .query_map(params![bill.id.as_bytes().to_vec()], |row| row.get::<_, Vec<u8>>(0))
+            .map_err(StorageError::from)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(StorageError::from)?;
+        bill.order_ids = raw_ids
+            .iter()
+            .map(|raw| {
+                Uuid::from_slice(raw).map_err(|e| {
+                    StorageError::from(CorruptRow::new("split_bill_orders", bill.id.to_string(), format!("order_id is not a UUID: {}", e)))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+    }
+    Ok(())
+}
+
+const SELECT_PAYMENTS_SQL: &str =
+    "SELECT payment_id, session_id, bill_id, tender, reference, amount, tip, change_due, paid_at FROM payments";
+
+fn payment_from_row(row: &Row) -> Result<Payment, CorruptRow> {
+    let raw_id: Vec<u8> = column(row, "payment_id").map_err(|reason| CorruptRow::new("payments", "?", reason))?;
+    let id = Uuid::from_slice(&raw_id)
+        .map_err(|e| CorruptRow::new("payments", hex_key(&raw_id), format!("payment_id is not a UUID: {}", e)))?;
+    let corrupt = |reason: String| CorruptRow::new("payments", id.to_string(), reason);
+    let tender: String = column(row, "tender").map_err(corrupt)?;
+    Ok(Payment {
+        id,
+        session_id: uuid_column(row, "session_id")
+            .and_then(|id| id.ok_or_else(|| "session_id is NULL".to_string()))
+            .map_err(corrupt)?,
+        bill_id: uuid_column(row, "bill_id").map_err(corrupt)?,
+        tender: tender.parse().map_err(|e: StorageError| corrupt(e.to_string()))?,
+        reference: column(row, "reference").map_err(corrupt)?,
+        amount: column(row, "amount").map_err(corrupt)?,
+        tip: column(row, "tip").map_err(corrupt)?,
+        change: column(row, "change_due").map_err(corrupt)?,
+        paid_at: column(row, "paid_at").map_err(corrupt)?,
+    })
+}
+
+const SELECT_WEBHOOKS_SQL: &str = "SELECT webhook_id, url, events, created_at FROM webhooks";
+
+fn webhook_from_row(row: &Row) -> Result<Webhook, CorruptRow> {
+    let raw_id: Vec<u8> = column(row, "webhook_id").map_err(|reason| CorruptRow::new("webhooks", "?", reason))?;
+    let id = Uuid::from_slice(&raw_id)
+        .map_err(|e| CorruptRow::new("webhooks", hex_key(&raw_id), format!("webhook_id is not a UUID: {}", e)))?;
+    let corrupt = |reason: String| CorruptRow::new("webhooks", id.to_string(), reason);
+    let events: String = column(row, "events").map_err(corrupt)?;
+    Ok(Webhook {
+        id,
+        url: column(row, "url").map_err(corrupt)?,
+        events: split_events(&events),
+        created_at: column(row, "created_at").map_err(corrupt)?,
+    })
+}
+
+const SELECT_DEAD_LETTERS_SQL: &str = "SELECT dead_letter_id, webhook_id, event_id, event, payload, attempts, last_error, failed_at FROM webhook_dead_letters";
+
+fn dead_letter_from_row(row: &Row) -> Result<DeadLetter, CorruptRow> {
+    let raw_id: Vec<u8> =
+        column(row, "dead_letter_id").map_err(|reason| CorruptRow::new("webhook_dead_letters", "?", reason))?;
+    let id = Uuid::from_slice(&raw_id).map_err(|e| {
+        CorruptRow::new("webhook_dead_letters", hex_key(&raw_id), format!("dead_letter_id is not a UUID: {}", e))
+    })?;
+    let corrupt = |reason: String| CorruptRow::new("webhook_dead_letters", id.to_string(), reason);
+    Ok(DeadLetter {
+        id,
+        webhook_id: uuid_column(row, "webhook_id")
+            .and_then(|id| id.ok_or_else(|| "webhook_id is NULL".to_string()))
+            .map_err(corrupt)?,
+        event_id: column(row, "event_id").map_err(corrupt)?,
+        event: column(row, "event").map_err(corrupt)?,
+        payload: column(row, "payload").map_err(corrupt)?,
+        attempts: column(row, "attempts").map_err(corrupt)?,
+        last_error: column(row, "last_error").map_err(corrupt)?,
+        failed_at: column(row, "failed_at").map_err(corrupt)?,
+    })
+}
+
+/// Storage backend for a single SQLite file, for locations that run one terminal.
+///
+/// SQLite allows one writer at a time, so every call goes through one connection.
+pub struct SqliteDb {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteDb {
+    /// Opens the database and brings the schema up to date, panicking if either step
+    /// fails.
+    pub fn new(database_url: &str) -> Self {
+        let db = Self::connect(database_url).expect("Failed to open SQLite database");
+        db.migrate().expect("Failed to migrate database");
+        db
+    }
+
+    /// Opens `sqlite://path.db` (or `sqlite://:memory:`) without touching the schema.
+    pub fn connect(database_url: &str) -> Result<Self, StorageError> {
+        let path = database_url
+            .strip_prefix(SQLITE_SCHEME)
+            .filter(|path| !path.is_empty())
+            .ok_or(StorageError::Validation(format!(
+                "Incorrect database URL: expected {}<path>",
+                SQLITE_SCHEME
+            )))?;
+        let conn = if path == ":memory:" {
+            Connection::open_in_memory()
+        } else {
+            Connection::open(path)
+        }
+        .map_err(StorageError::from)?;
+        conn.busy_timeout(Duration::from_secs(5)).map_err(StorageError::from)?;
+        Ok(SqliteDb { conn: Mutex::new(conn) })
+    }
+
+    /// Whether `database_url` names a SQLite database.
+    pub fn handles(database_url: &str) -> bool {
+        database_url.starts_with(SQLITE_SCHEME)
+    }
+
+    pub fn migrate(&self) -> Result<Vec<u32>, StorageError> {
+        migrations::run(self, MIGRATIONS)
+    }
+
+    pub fn verify_schema(&self) -> Result<(), StorageError> {
+        migrations::verify(self, MIGRATIONS)
+    }
+
+    fn conn(&self) -> Result<MutexGuard<'_, Connection>, StorageError> {
+        self.conn.lock().map_err(|e| StorageError::Internal(e.to_string()))
+    }
+}
+
+impl Migrator for SqliteDb {
+    fn applied_versions(&self) -> Result<Vec<u32>, StorageError> {
+        let conn = self.conn()?;
+        conn.execute_batch(
+            r"
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER NOT NULL PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        ",
+        )
+        .map_err(StorageError::from)?;
+        let mut statement = conn
+            .prepare("SELECT version FROM schema_migrations ORDER BY version")
+            .map_err(StorageError::from)?;
+        let versions = statement
+            .query_map([], |row| row.get(0))
+            .map_err(StorageError::from)?
+            .collect::<Result<Vec<u32>, _>>()
+            .map_err(StorageError::from)?;
+        Ok(versions)
+    }
+
+    fn apply_migration(&self, migration: &Migration) -> Result<(), StorageError> {
+        // SQLite schema changes are transactional, so a failed migration leaves no trace.
+        let mut conn = self.conn()?;
+        let tx = conn.transaction().map_err(StorageError::from)?;
+        tx.execute_batch(migration.sql).map_err(StorageError::from)?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version, name) VALUES (?1, ?2)",
+            params![migration.version, migration.name],
+        )
+        .map_err(StorageError::from)?;
+        tx.commit().map_err(StorageError::from)
+    }
+}
+
+impl From<rusqlite::Error> for StorageError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e {
+            rusqlite::Error::SqliteFailure(ref err, _) => match err.code {
+                ErrorCode::ConstraintViolation
+                    if err.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE
+                        || err.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_PRIMARYKEY =>
+                {
+                    StorageError::Conflict(e.to_string())
+                }
+                ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked | ErrorCode::CannotOpen => {
+                    StorageError::Unavailable(e.to_string())
+                }
+                _ => StorageError::Internal(e.to_string()),
+            },
+            _ => StorageError::Internal(e.to_string()),
+        }
+    }
+}
+
+impl Storage for SqliteDb {
+    fn get_table_orders(&self, table_id: u64) -> Result<Vec<Order>, StorageError> {
+        let conn = self.conn()?;
+        let mut statement = conn
+            .prepare(&format!("{} WHERE table_id = ?1 ORDER BY created_at", SELECT_ORDERS_SQL))
+            .map_err(StorageError::from)?;
+        let rows = statement
+            .query_map(params![table_id], |row| Ok(order_from_row(row)))
+            .map_err(StorageError::from)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(StorageError::from)?;
+        Ok(decode::skip_corrupt(rows))
+    }
+
+    fn get_table_order(&self, table_id: u64, order_id: Uuid) -> Result<Order, StorageError> {
+        let conn = self.conn()?;
+        conn.query_row(
+            &format!("{} WHERE table_id = ?1 AND order_id = ?2", SELECT_ORDERS_SQL),
+            params![table_id, order_id.as_bytes().to_vec()],
+            |row| Ok(order_from_row(row)),
+        )
+        .optional()
+        .map_err(StorageError::from)?
+        .ok_or(StorageError::NotFound("Order not found".to_string()))?
+        .map_err(StorageError::from)
+    }
+
+    fn add_table_orders(&self, table_id: u64, orders: Vec<Order>) -> Result<Vec<Uuid>, StorageError> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction().map_err(StorageError::from)?;
+        let mut order_ids = Vec::new();
+        {
+            let mut statement = tx
+                .prepare("INSERT INTO orders (order_id, table_id, menu_item, created_at, cooking_duration_secs, status, session_id, price, seat, station, ticket_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)")
+                .map_err(StorageError::from)?;
+            for order in orders {
+                statement
+                    .execute(params![
+                        order.id.as_bytes().to_vec(),
+                        table_id,
+                        order.menu_item,
+                        order.created_at,
+                        order.cooking_duration_secs,
+                        order.status.as_str(),
+                        order.session_id.map(|id| id.as_bytes().to_vec()),
+                        order.price,
+                        order.seat,
+                        order.station.as_str(),
+                        order.ticket_id.as_bytes().to_vec(),
+                    ])
+                    .map_err(StorageError::from)?;
+                order_ids.push(order.id);
+            }
+        }
+        // Dropping the transaction on an error above rolls every insert back.
+        tx.commit().map_err(StorageError::from)?;
+        Ok(order_ids)
+    }
+
+    fn delete_table_order(&self, table_id: u64, order_id: Uuid) -> Result<(), StorageError> {
+        let conn = self.conn()?;
+        let deleted = conn
+            .execute(
+                "DELETE FROM orders WHERE table_id = ?1 AND order_id = ?2",
+                params![table_id, order_id.as_bytes().to_vec()],
+            )
+            .map_err(StorageError::from)?;
+        if deleted == 0 {
+            return Err(StorageError::NotFound("Order not found".to_string()));
+        }
+        Ok(())
+    }
+
+    fn update_order_status(
+        &self,
+        table_id: u64,
+        order_id: Uuid,
+        from: OrderStatus,
+        to: OrderStatus,
+        at: DateTime<Utc>,
+    ) -> Result<(), StorageError> {
+        let column = status_timestamp_column(to)
+            .ok_or(StorageError::Validation(format!("Orders cannot move back to {}", to)))?;
+        let updated = self
+            .conn()?
+            .execute(
+                &format!(
+                    "UPDATE orders SET status = :to, {} = :at WHERE table_id = :table_id AND order_id = :order_id AND status = :from",
+                    column
+                ),
+                named_params! {
+                    ":to": to.as_str(),
+                    ":at": at,
+                    ":table_id": table_id,
+                    ":order_id": order_id.as_bytes().to_vec(),
+                    ":from": from.as_str(),
+                },
+            )
+            .map_err(StorageError::from)?;
+        if updated == 0 {
+            let order = self.get_table_order(table_id, order_id)?;
+            return Err(StorageError::Conflict(format!(
+                "Order is {}, not {}",
+                order.status, from
+            )));
+        }
+        Ok(())
+    }
+
+    fn count_open_orders(&self) -> Result<usize, StorageError> {
+        self.conn()?
+            .query_row(
+                "SELECT COUNT(*) FROM orders WHERE status IN ('placed', 'cooking')",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(StorageError::from)
+    }
+
+    fn get_open_orders(&self) -> Result<Vec<(u64, Order)>, StorageError> {
+        let conn = self.conn()?;
+        let mut statement = conn
+            .prepare(&format!(
+                "{} WHERE status IN ('placed', 'cooking') ORDER BY created_at",
+                SELECT_ORDERS_SQL
+            ))
+            .map_err(StorageError::from)?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok(order_from_row(row).and_then(|order| {
+                    let table_id = column(row, "table_id")
+                        .map_err(|reason| CorruptRow::new("orders", order.id.to_string(), reason))?;
+                    Ok((table_id, order))
+                }))
+            })
+            .map_err(StorageError::from)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(StorageError::from)?;
+        Ok(decode::skip_corrupt(rows))
+    }
+
+    fn get_menu_items(&self) -> Result<Vec<MenuItem>, StorageError> {
+        let conn = self.conn()?;
+        let mut statement = conn
+            .prepare(&format!("{} ORDER BY category, name", SELECT_MENU_ITEMS_SQL))
+            .map_err(StorageError::from)?;
+        let rows = statement
+            .query_map([], |row| Ok(menu_item_from_row(row)))
+            .map_err(StorageError::from)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(StorageError::from)?;
+        Ok(decode::skip_corrupt(rows))
+    }
+
+    fn get_menu_item(&self, menu_item_id: Uuid) -> Result<MenuItem, StorageError> {
+        let conn = self.conn()?;
+        conn.query_row(
+            &format!("{} WHERE menu_item_id = ?1", SELECT_MENU_ITEMS_SQL),
+            params![menu_item_id.to_string()],
+            |row| Ok(menu_item_from_row(row)),
+        )
+        .optional()
+        .map_err(StorageError::from)?
+        .ok_or(StorageError::NotFound("Menu item not found".to_string()))?
+        .map_err(StorageError::from)
+    }
+
+    fn add_menu_item(&self, item: MenuItem) -> Result<Uuid, StorageError> {
+        self.conn()?
+            .execute(
+                "INSERT INTO menu_items (menu_item_id, name, category, price, prep_time_minutes, available, station) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    item.id.to_string(),
+                    item.name,
+                    item.category,
+                    item.price,
+                    item.prep_time_minutes,
+                    item.available,
+                    item.station.as_str(),
+                ],
+            )
+            .map_err(StorageError::from)?;
+        Ok(item.id)
+    }
+
+    fn update_menu_item(&self, item: MenuItem) -> Result<(), StorageError> {
+        let updated = self
+            .conn()?
+            .execute(
+                "UPDATE menu_items SET name = ?2, category = ?3, price = ?4, prep_time_minutes = ?5, available = ?6, station = ?7 WHERE menu_item_id = ?1",
+                params![
+                    item.id.to_string(),
+                    item.name,
+                    item.category,
+                    item.price,
+                    item.prep_time_minutes,
+                    item.available,
+                    item.station.as_str(),
+                ],
+            )
+            .map_err(StorageError::from)?;
+        if updated == 0 {
+            return Err(StorageError::NotFound("Menu item not found".to_string()));
+        }
+        Ok(())
+    }
+
+    fn delete_menu_item(&self, menu_item_id: Uuid) -> Result<(), StorageError> {
+        let deleted = self
+            .conn()?
+            .execute(
+                "DELETE FROM menu_items WHERE menu_item_id = ?1",
+                params![menu_item_id.to_string()],
+            )
+            .map_err(StorageError::from)?;
+        if deleted == 0 {
+            return Err(StorageError::NotFound("Menu item not found".to_string()));
+        }
+        Ok(())
+    }
+
+    fn get_tables(&self) -> Result<Vec<Table>, StorageError> {
+        let conn = self.conn()?;
+        let mut statement = conn
+            .prepare(&format!("{} ORDER BY table_number", SELECT_TABLES_SQL))
+            .map_err(StorageError::from)?;
+        let rows = statement
+            .query_map([], |row| Ok(table_from_row(row)))
+            .map_err(StorageError::from)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(StorageError::from)?;
+        Ok(decode::skip_corrupt(rows))
+    }
+
+    fn get_table(&self, number: u64) -> Result<Table, StorageError> {
+        let conn = self.conn()?;
+        conn.query_row(
+            &format!("{} WHERE table_number = ?1", SELECT_TABLES_SQL),
+            params![number],
+            |row| Ok(table_from_row(row)),
+        )
+        .optional()
+        .map_err(StorageError::from)?
+        .ok_or(StorageError::NotFound(format!("Table {} is not registered", number)))?
+        .map_err(StorageError::from)
+    }
+
+    fn add_table(&self, table: Table) -> Result<u64, StorageError> {
+        self.conn()?
+            .execute(
+                "INSERT INTO dining_tables (table_number, seats, section, status, current_session_id) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    table.number,
+                    table.seats,
+                    table.section,
+                    table.status.as_str(),
+                    table.session_id.map(|id| id.as_bytes().to_vec()),
+                ],
+            )
+            .map_err(StorageError::from)?;
+        Ok(table.number)
+    }
+
+    fn open_table_session(&self, number: u64, session: Session) -> Result<(), StorageError> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction().map_err(StorageError::from)?;
+        let updated = tx
+            .execute(
+                "UPDATE dining_tables SET status = 'open', current_session_id = ?1 WHERE table_number = ?2 AND status = 'closed'",
+                params![session.id.as_bytes().to_vec(), number],
+            )
+            .map_err(StorageError::from)?;
+        if updated == 0 {
+            // Release the connection so the lookup can tell a missing table from a busy one.
+            drop(tx);
+            drop(conn);
+            self.get_table(number)?;
+            return Err(StorageError::Conflict(format!("Table {} is already open", number)));
+        }
+        tx.execute(
+            "INSERT INTO table_sessions (session_id, table_number, opened_at) VALUES (?1, ?2, ?3)",
+            params![session.id.as_bytes().to_vec(), number, session.opened_at],
+        )
+        .map_err(StorageError::from)?;
+        tx.commit().map_err(StorageError::from)
+    }
+
+    fn close_table_session(&self, number: u64, session_id: Uuid, closed_at: DateTime<Utc>) -> Result<(), StorageError> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction().map_err(StorageError::from)?;
+        let updated = tx
+            .execute(
+                "UPDATE dining_tables SET status = 'closed', current_session_id = NULL WHERE table_number = ?1 AND current_session_id = ?2",
+                params![number, session_id.as_bytes().to_vec()],
+            )
+            .map_err(StorageError::from)?;
+        if updated == 0 {
+            // Release the connection so the lookup can tell a missing table from a busy one.
+            drop(tx);
+            drop(conn);
+            self.get_table(number)?;
+            return Err(StorageError::Conflict(format!(
+                "Session {} is not in progress at table {}",
+                session_id, number
+            )));
+        }
+        tx.execute(
+            "UPDATE table_sessions SET closed_at = ?1 WHERE session_id = ?2",
+            params![closed_at, session_id.as_bytes().to_vec()],
+        )
+        .map_err(StorageError::from)?;
+        tx.commit().map_err(StorageError::from)
+    }
+
+    fn get_table_sessions(&self, number: u64) -> Result<Vec<Session>, StorageError> {
+        let conn = self.conn()?;
+        let mut statement = conn
+            .prepare(&format!("{} WHERE table_number = ?1 ORDER BY opened_at", SELECT_SESSIONS_SQL))
+            .map_err(StorageError::from)?;
+        let rows = statement
+            .query_map(params![number], |row| Ok(session_from_row(row)))
+            .map_err(StorageError::from)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(StorageError::from)?;
+        Ok(decode::skip_corrupt(rows))
+    }
+
+    fn get_table_session(&self, number: u64, session_id: Uuid) -> Result<Session, StorageError> {
+        let conn = self.conn()?;
+        conn.query_row(
+            &format!("{} WHERE table_number = ?1 AND session_id = ?2", SELECT_SESSIONS_SQL),
+            params![number, session_id.as_bytes().to_vec()],
+            |row| Ok(session_from_row(row)),
+        )
+        .optional()
+        .map_err(StorageError::from)?
+        .ok_or(StorageError::NotFound("Session not found".to_string()))?
+        .map_err(StorageError::from)
+    }
+
+    fn get_session_orders(&self, session_id: Uuid, decoding: Decoding) -> Result<Vec<Order>, StorageError> {
+        let conn = self.conn()?;
+        let mut statement = conn
+            .prepare(&format!("{} WHERE session_id = ?1 ORDER BY created_at", SELECT_ORDERS_SQL))
+            .map_err(StorageError::from)?;
+        let rows = statement
+            .query_map(params![session_id.as_bytes().to_vec()], |row| Ok(order_from_row(row)))
+            .map_err(StorageError::from)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(StorageError::from)?;
+        decode::collect(rows, decoding)
+    }
+
+    fn replace_split_bills(&self, session_id: Uuid, bills: Vec<SplitBill>) -> Result<(), StorageError> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction().map_err(StorageError::from)?;
+        let closed: u64 = tx
+            .query_row(
+                "SELECT COUNT(*) FROM split_bills WHERE session_id = ?1 AND status <> 'open'",
+                params![session_id.as_bytes().to_vec()],
+                |row| row.get(0),
+            )
+            .map_err(StorageError::from)?;
+        if closed > 0 {
+            return Err(StorageError::Conflict("A split bill has already been paid or closed".to_string()));
+        }
+        tx.execute(
+            "DELETE FROM split_bill_orders WHERE bill_id IN (SELECT bill_id FROM split_bills WHERE session_id = ?1)",
+            params![session_id.as_bytes().to_vec()],
+        )
+        .map_err(StorageError::from)?;
+        tx.execute(
+            "DELETE FROM split_bills WHERE session_id = ?1",
+            params![session_id.as_bytes().to_vec()],
+        )
+        .map_err(StorageError::from)?;
+        {
+            let mut insert_bill = tx
+                .prepare("INSERT INTO split_bills (bill_id, session_id, position, label, subtotal, service_charge, tax, total, status) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)")
+                .map_err(StorageError::from)?;
+            let mut insert_order = tx
+                .prepare("INSERT INTO split_bill_orders (bill_id, order_id) VALUES (?1, ?2)")
+                .map_err(StorageError::from)?;
+            for bill in bills {
+                insert_bill
+                    .execute(params![
+                        bill.id.as_bytes().to_vec(),
+                        session_id.as_bytes().to_vec(),
+                        bill.position,
+                        bill.label,
+                        bill.subtotal,
+                        bill.service_charge,
+                        bill.tax,
+                        bill.total,
+                        bill.status.as_str(),
+                    ])
+                    .map_err(StorageError::from)?;
+                for order_id in bill.order_ids {
+                    insert_order
+                        .execute(params![bill.id.as_bytes().to_vec(), order_id.as_bytes().to_vec()])
+                        .map_err(StorageError::from)?;
+                }
+            }
+        }
+        tx.commit().map_err(StorageError::from)
+    }
+
+    fn get_split_bills(&self, session_id: Uuid, decoding: Decoding) -> Result<Vec<SplitBill>, StorageError> {
+        let conn = self.conn()?;
+        let mut statement = conn
+            .prepare(&format!("{} WHERE session_id = ?1 ORDER BY position", SELECT_SPLIT_BILLS_SQL))
+            .map_err(StorageError::from)?;
+        let rows = statement
+            .query_map(params![session_id.as_bytes().to_vec()], |row| Ok(split_bill_from_row(row)))
+            .map_err(StorageError::from)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(StorageError::from)?;
+        let mut bills = decode::collect(rows, decoding)?;
+        attach_split_bill_orders(&conn, &mut bills)?;
+        Ok(bills)
+    }
+
+    fn get_split_bill(&self, session_id: Uuid, bill_id: Uuid) -> Result<SplitBill, StorageError> {
+        let conn = self.conn()?;
+        let mut bill = conn
+            .query_row(
+                &format!("{} WHERE session_id = ?1 AND bill_id = ?2", SELECT_SPLIT_BILLS_SQL),
+                params![session_id.as_bytes().to_vec(), bill_id.as_bytes().to_vec()],
+                |row| Ok(split_bill_from_row(row)),
+            )
+            .optional()
+            .map_err(StorageError::from)?
+            .ok_or(StorageError::NotFound("Bill not found".to_string()))?
+            .map_err(StorageError::from)?;
+        attach_split_bill_orders(&conn, std::slice::from_mut(&mut bill))?;
+        Ok(bill)
+    }
+
+    fn update_split_bill_status(
+        &self,
+        session_id: Uuid,
+        bill_id: Uuid,
+        from: BillStatus,
+        to: BillStatus,
+    ) -> Result<(), StorageError> {
+        let updated = self
+            .conn()?
+            .execute(
+                "UPDATE split_bills SET status = ?1 WHERE session_id = ?2 AND bill_id = ?3 AND status = ?4",
+                params![
+                    to.as_str(),
+                    session_id.as_bytes().to_vec(),
+                    bill_id.as_bytes().to_vec(),
+                    from.as_str(),
+                ],
+            )
+            .map_err(StorageError::from)?;
+        if updated == 0 {
+            let bill = self.get_split_bill(session_id, bill_id)?;
+            return Err(StorageError::Conflict(format!("Bill is {}, not {}", bill.status, from)));
+        }
+        Ok(())
+    }
+
+    fn add_payment(&self, payment: Payment) -> Result<Uuid, StorageError> {
+        self.conn()?
+            .execute(
+                "INSERT INTO payments (payment_id, session_id, bill_id, tender, reference, amount, tip, change_due, paid_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    payment.id.as_bytes().to_vec(),
+                    payment.session_id.as_bytes().to_vec(),
+                    payment.bill_id.map(|id| id.as_bytes().to_vec()),
+                    payment.tender.as_str(),
+                    payment.reference,
+                    payment.amount,
+                    payment.tip,
+                    payment.change,
+                    payment.paid_at,
+                ],
+            )
+            .map_err(StorageError::from)?;
+        Ok(payment.id)
+    }
+
+    fn get_session_payments(&self, session_id: Uuid, decoding: Decoding) -> Result<Vec<Payment>, StorageError> {
+        let conn = self.conn()?;
+        let mut statement = conn
+            .prepare(&format!("{} WHERE session_id = ?1 ORDER BY paid_at", SELECT_PAYMENTS_SQL))
+            .map_err(StorageError::from)?;
+        let rows = statement
+            .query_map(params![session_id.as_bytes().to_vec()], |row| Ok(payment_from_row(row)))
+            .map_err(StorageError::from)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(StorageError::from)?;
+        decode::collect(rows, decoding)
+    }
+
+    fn add_webhook(&self, webhook: Webhook) -> Result<Uuid, StorageError> {
+        self.conn()?
+            .execute(
+                "INSERT INTO webhooks (webhook_id, url, events, created_at) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    webhook.id.as_bytes().to_vec(),
+                    webhook.url,
+                    join_events(&webhook.events),
+                    webhook.created_at,
+                ],
+            )
+            .map_err(StorageError::from)?;
+        Ok(webhook.id)
+    }
+
+    fn get_webhooks(&self) -> Result<Vec<Webhook>, StorageError> {
+        let conn = self.conn()?;
+        let mut statement = conn
+            .prepare(&format!("{} ORDER BY created_at", SELECT_WEBHOOKS_SQL))
+            .map_err(StorageError::from)?;
+        let rows = statement
+            .query_map([], |row| Ok(webhook_from_row(row)))
+            .map_err(StorageError::from)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(StorageError::from)?;
+        Ok(decode::skip_corrupt(rows))
+    }
+
+    fn get_webhook(&self, webhook_id: Uuid) -> Result<Webhook, StorageError> {
+        let conn = self.conn()?;
+        conn.query_row(
+            &format!("{} WHERE webhook_id = ?1", SELECT_WEBHOOKS_SQL),
+            params![webhook_id.as_bytes().to_vec()],
+            |row| Ok(webhook_from_row(row)),
+        )
+        .optional()
+        .map_err(StorageError::from)?
+        .ok_or(StorageError::NotFound("Webhook not found".to_string()))?
+        .map_err(StorageError::from)
+    }
+
+    fn delete_webhook(&self, webhook_id: Uuid) -> Result<(), StorageError> {
+        let deleted = self
+            .conn()?
+            .execute("DELETE FROM webhooks WHERE webhook_id = ?1", params![webhook_id.as_bytes().to_vec()])
+            .map_err(StorageError::from)?;
+        if deleted == 0 {
+            return Err(StorageError::NotFound("Webhook not found".to_string()));
+        }
+        Ok(())
+    }
+
+    fn add_dead_letter(&self, dead_letter: DeadLetter) -> Result<Uuid, StorageError> {
+        self.conn()?
+            .execute(
+                "INSERT INTO webhook_dead_letters (dead_letter_id, webhook_id, event_id, event, payload, attempts, last_error, failed_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    dead_letter.id.as_bytes().to_vec(),
+                    dead_letter.webhook_id.as_bytes().to_vec(),
+                    dead_letter.event_id,
+                    dead_letter.event,
+                    dead_letter.payload,
+                    dead_letter.attempts,
+                    dead_letter.last_error,
+                    dead_letter.failed_at,
+                ],
+            )
+            .map_err(StorageError::from)?;
+        Ok(dead_letter.id)
+    }
+
+    fn get_dead_letters(&self) -> Result<Vec<DeadLetter>, StorageError> {
+        let conn = self.conn()?;
+        let mut statement = conn
+            .prepare(&format!("{} ORDER BY failed_at", SELECT_DEAD_LETTERS_SQL))
+            .map_err(StorageError::from)?;
+        let rows = statement
+            .query_map([], |row| Ok(dead_letter_from_row(row)))
+            .map_err(StorageError::from)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(StorageError::from)?;
+        Ok(decode::skip_corrupt(rows))
+    }
+
+    fn delete_dead_letter(&self, dead_letter_id: Uuid) -> Result<(), StorageError> {
+        let deleted = self
+            .conn()?
+            .execute(
+                "DELETE FROM webhook_dead_letters WHERE dead_letter_id = ?1",
+                params![dead_letter_id.as_bytes().to_vec()],
+            )
+            .map_err(StorageError::from)?;
+        if deleted == 0 {
+            return Err(StorageError::NotFound("Dead letter not found".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::conformance::storage_conformance_tests;
+
+    fn setup_test_db() -> SqliteDb {
+        SqliteDb::new("sqlite://:memory:")
+    }
+
+    storage_conformance_tests!(setup_test_db());
+
+    fn order(menu_item: &str) -> Order {
+        Order::placed(Uuid::new_v4(), menu_item.to_string(), Utc::now(), 600)
+    }
+
+    fn menu_item(name: &str, category: &str) -> MenuItem {
+        MenuItem {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            category: category.to_string(),
+            price: 1250,
+            prep_time_minutes: 12,
+            available: true,
+            station: Station::Grill,
+        }
+    }
+
+    #[test]
+    fn test_migrations_are_recorded_once() {
+        let db = setup_test_db();
+        assert_eq!(db.migrate(), Ok(vec![]));
+        assert_eq!(db.verify_schema(), Ok(()));
+
+        let known: Vec<u32> = MIGRATIONS.iter().map(|migration| migration.version).collect();
+        assert_eq!(db.applied_versions().unwrap(), known);
+    }
+
+    #[test]
+    fn test_migrations_convert_existing_orders() {
+        let db = SqliteDb::connect("sqlite://:memory:").unwrap();
+        migrations::run(&db, &MIGRATIONS[..3]).unwrap();
+        let order_id = Uuid::new_v4();
+        db.conn()
+            .unwrap()
+            .execute(
+                "INSERT INTO orders (order_id, menu_item, cooking_time, table_id) VALUES (?1, 'Pizza', '12 minutes', 4)",
+                params![order_id.to_string()],
+            )
+            .unwrap();
+
+        assert_eq!(db.migrate(), Ok(vec![4, 5, 6, 7, 8, 9, 10, 11, 12]));
+        let order = db.get_table_order(4, order_id).unwrap();
+        assert_eq!(order.cooking_duration_secs, 720);
+        assert_eq!(order.status, OrderStatus::Placed);
+        assert_eq!(order.station, Station::Grill);
+        assert_eq!(order.ticket_id, order_id);
+    }
+
+    #[test]
+    fn test_migrations_backfill_sessions_and_prices() {
+        let db = SqliteDb::connect("sqlite://:memory:").unwrap();
+        migrations::run(&db, &MIGRATIONS[..6]).unwrap();
+        let (placed, served) = (Uuid::new_v4(), Uuid::new_v4());
+        db.conn()
+            .unwrap()
+            .execute_batch(&format!(
+                "INSERT INTO orders (order_id, table_id, menu_item, created_at, cooking_duration_secs, status) VALUES \
+                 (x'{}', 4, 'Pizza', '2024-05-01 18:00:00+00:00', 600, 'placed'), \
+                 (x'{}', 4, 'Salad', '2024-05-01 18:00:00+00:00', 600, 'served'); \
+                 INSERT INTO dining_tables (table_number, seats, section, status) VALUES (4, 2, 'Bar', 'open'), (5, 2, 'Bar', 'closed'); \
+                 INSERT INTO menu_items (menu_item_id, name, category, price, prep_time_minutes) VALUES ('{}', 'PIZZA', 'Mains', 1250, 12);",
+                placed.simple(),
+                served.simple(),
+                Uuid::new_v4()
+            ))
+            .unwrap();
+
+        assert_eq!(db.migrate(), Ok(vec![7, 8, 9, 10, 11, 12]));
+        let session_id = db.get_table(4).unwrap().session_id.unwrap();
+        assert_eq!(db.get_table(5).unwrap().session_id, None);
+        assert_eq!(db.get_table_sessions(4).unwrap().len(), 1);
+        let orders: Vec<Uuid> =
+            db.get_session_orders(session_id, Decoding::Strict).unwrap().iter().map(|order| order.id).collect();
+        assert_eq!(orders, vec![placed]);
+        assert_eq!(db.get_table_order(4, placed).unwrap().price, 1250);
+        assert_eq!(db.get_table_order(4, served).unwrap().price, 0);
+    }
+
+    #[test]
+    fn test_connect_rejects_other_urls() {
+        assert!(SqliteDb::handles("sqlite://restaurant.db"));
+        assert!(!SqliteDb::handles("mysql://localhost/restaurant"));
+        assert!(matches!(
+            SqliteDb::connect("sqlite://"),
+            Err(StorageError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_orders_round_trip() {
+        let db = setup_test_db();
+        let pizza = order("Pizza");
+        let salad = order("Salad");
+
+        let ids = db.add_table_orders(1, vec![pizza.clone(), salad.clone()]).unwrap();
+        assert_eq!(ids, vec![pizza.id, salad.id]);
+
+        let orders = db.get_table_orders(1).unwrap();
+        assert_eq!(orders.len(), 2);
+        assert_eq!(orders[0].menu_item, "Pizza");
+        assert_eq!(orders[0].created_at, pizza.created_at);
+        assert!(db.get_table_orders(2).unwrap().is_empty());
+
+        let fetched = db.get_table_order(1, salad.id).unwrap();
+        assert_eq!(fetched.menu_item, "Salad");
+        assert_eq!(fetched.cooking_duration_secs, 600);
+        assert!(matches!(db.get_table_order(2, salad.id), Err(StorageError::NotFound(_))));
+
+        db.delete_table_order(1, pizza.id).unwrap();
+        assert!(matches!(db.delete_table_order(1, pizza.id), Err(StorageError::NotFound(_))));
+        assert_eq!(db.get_table_orders(1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_add_table_orders_is_all_or_nothing() {
+        let db = setup_test_db();
+        let pizza = order("Pizza");
+        db.add_table_orders(1, vec![pizza.clone()]).unwrap();
+
+        let result = db.add_table_orders(2, vec![order("Salad"), pizza]);
+        assert!(matches!(result, Err(StorageError::Conflict(_))));
+        assert!(db.get_table_orders(2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_update_order_status() {
+        let db = setup_test_db();
+        let pizza = order("Pizza");
+        let salad = order("Salad");
+        db.add_table_orders(1, vec![pizza.clone(), salad.clone()]).unwrap();
+        assert_eq!(db.count_open_orders(), Ok(2));
+
+        let at = Utc::now();
+        db.update_order_status(1, pizza.id, OrderStatus::Placed, OrderStatus::Cooking, at).unwrap();
+        let fetched = db.get_table_order(1, pizza.id).unwrap();
+        assert_eq!(fetched.status, OrderStatus::Cooking);
+        assert_eq!(fetched.started_at, Some(at));
+
+        let result = db.update_order_status(1, pizza.id, OrderStatus::Placed, OrderStatus::Cancelled, at);
+        assert!(matches!(result, Err(StorageError::Conflict(_))));
+        let result = db.update_order_status(2, pizza.id, OrderStatus::Cooking, OrderStatus::Ready, at);
+        assert!(matches!(result, Err(StorageError::NotFound(_))));
+
+        db.update_order_status(1, salad.id, OrderStatus::Placed, OrderStatus::Cancelled, at).unwrap();
+        assert_eq!(db.count_open_orders(), Ok(1));
+    }
+
+    #[test]
+    fn test_corrupt_rows_are_reported() {
+        let db = setup_test_db();
+        let pizza = order("Pizza");
+        db.add_table_orders(1, vec![pizza.clone()]).unwrap();
+        let bad_id = Uuid::new_v4();
+        db.conn()
+            .unwrap()
+            .execute(
+                "INSERT INTO orders (order_id, table_id, menu_item, created_at, cooking_duration_secs, status) VALUES (?1, 1, 'Soup', ?2, 600, 'exploded')",
+                params![bad_id.as_bytes().to_vec(), Utc::now()],
+            )
+            .unwrap();
+
+        let orders = db.get_table_orders(1).unwrap();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].id, pizza.id);
+        match db.get_table_order(1, bad_id) {
+            Err(StorageError::Corrupt(corrupt)) => assert_eq!(corrupt.key, bad_id.to_string()),
+            other => panic!("Expected a corrupt row but got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_strict_session_reads_fail_on_corrupt_rows() {
+        let db = setup_test_db();
+        let table = Table {
+            number: 1,
+            seats: 4,
+            section: "Patio".to_string(),
+            status: crate::db::TableStatus::Closed,
+            session_id: None,
+        };
+        db.add_table(table).unwrap();
+        let session = Session { id: Uuid::new_v4(), table_number: 1, opened_at: Utc::now(), closed_at: None };
+        db.open_table_session(1, session.clone()).unwrap();
+        let pizza = Order { session_id: Some(session.id), ..order("Pizza") };
+        db.add_table_orders(1, vec![pizza.clone()]).unwrap();
+        let conn = db.conn().unwrap();
+        conn.execute(
+            "INSERT INTO orders (order_id, table_id, menu_item, created_at, cooking_duration_secs, status, session_id) VALUES (?1, 1, 'Soup', ?2, 600, 'exploded', ?3)",
+            params![Uuid::new_v4().as_bytes().to_vec(), Utc::now(), session.id.as_bytes().to_vec()],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO payments (payment_id, session_id, tender, amount, tip, change_due, paid_at) VALUES (?1, ?2, 'cheque', 500, 0, 0, ?3)",
+            params![Uuid::new_v4().as_bytes().to_vec(), session.id.as_bytes().to_vec(), Utc::now()],
+        )
+        .unwrap();
+        drop(conn);
+
+        let shown = db.get_session_orders(session.id, Decoding::SkipCorrupt).unwrap();
+        assert_eq!(shown.iter().map(|order| order.id).collect::<Vec<_>>(), vec![pizza.id]);
+        assert_eq!(db.get_session_payments(session.id, Decoding::SkipCorrupt), Ok(vec![]));
+        assert!(matches!(db.get_session_orders(session.id, Decoding::Strict), Err(StorageError::Corrupt(_))));
+        assert!(matches!(db.get_session_payments(session.id, Decoding::Strict), Err(StorageError::Corrupt(_))));
+    }
+
+    #[test]
+    fn test_menu_items_round_trip() {
+        let db = setup_test_db();
+        let pizza = menu_item("Pizza", "Mains");
+        let salad = menu_item("Salad", "Starters");
+        let burger = menu_item("Burger", "Mains");
+        for item in [pizza.clone(), salad.clone(), burger.clone()] {
+            db.add_menu_item(item).unwrap();
+        }
+
+        let names: Vec<String> = db.get_menu_items().unwrap().into_iter().map(|item| item.name).collect();
+        assert_eq!(names, vec!["Burger", "Pizza", "Salad"]);
+
+        let mut updated = pizza.clone();
+        updated.price = 1400;
+        updated.available = false;
+        db.update_menu_item(updated).unwrap();
+        let fetched = db.get_menu_item(pizza.id).unwrap();
+        assert_eq!(fetched.price, 1400);
+        assert!(!fetched.available);
+        assert!(matches!(
+            db.update_menu_item(menu_item("Calzone", "Mains")),
+            Err(StorageError::NotFound(_))
+        ));
+
+        db.delete_menu_item(salad.id).unwrap();
+        assert!(matches!(db.get_menu_item(salad.id), Err(StorageError::NotFound(_))));
+        assert!(matches!(db.delete_menu_item(salad.id), Err(StorageError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_menu_item_names_are_unique() {
+        let db = setup_test_db();
+        db.add_menu_item(menu_item("Pizza", "Mains")).unwrap();
+        let result = db.add_menu_item(menu_item("pizza", "Mains"));
+        assert!(matches!(result, Err(StorageError::Conflict(_))));
+    }
+}
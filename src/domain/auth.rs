@@ -0,0 +1,329 @@
+use crate::db::StorageError;
+use crate::domain::signing::{from_hex, hmac_sha256, to_hex, verify_hmac_sha256};
+use crate::protocol::protocol::TokenInput;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use std::fmt;
+use std::str::FromStr;
+
+/// How long an issued token lasts when the request does not say: one long shift.
+pub const DEFAULT_TOKEN_MINUTES: u32 = 12 * 60;
+/// The longest a token may be issued for.
+pub const MAX_TOKEN_MINUTES: u32 = 7 * 24 * 60;
+/// API keys shorter than this are refused at startup.
+const MIN_API_KEY_LEN: usize = 16;
+
+/// What a member of staff, or a device acting for one, is allowed to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Role {
+    Server,
+    Kitchen,
+    Manager,
+    Admin,
+}
+
+impl Role {
+    pub const ALL: [Role; 4] = [Role::Server, Role::Kitchen, Role::Manager, Role::Admin];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Server => "server",
+            Role::Kitchen => "kitchen",
+            Role::Manager => "manager",
+            Role::Admin => "admin",
+        }
+    }
+
+    /// Whether this role may do whatever `other` may. Managers cover servers and the
+    /// kitchen, and admins cover everyone.
+    pub fn includes(self, other: Role) -> bool {
+        match self {
+            Role::Admin => true,
+            Role::Manager => other != Role::Admin,
+            role => role == other,
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = StorageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "server" => Ok(Role::Server),
+            "kitchen" => Ok(Role::Kitchen),
+            "manager" => Ok(Role::Manager),
+            "admin" => Ok(Role::Admin),
+            other => Err(StorageError::Validation(format!(
+                "Unknown role '{}', expected server, kitchen, manager or admin",
+                other
+            ))),
+        }
+    }
+}
+
+/// Whoever made a request, as established by their API key or token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    /// The key's or token's name, such as a till or a person.
+    pub name: String,
+    pub role: Role,
+}
+
+impl Principal {
+    /// Fails with `Forbidden` unless the role covers one of `roles`.
+    pub fn require(&self, roles: &[Role]) -> Result<(), StorageError> {
+        if roles.iter().any(|role| self.role.includes(*role)) {
+            return Ok(());
+        }
+        let allowed: Vec<&str> = roles.iter().map(Role::as_str).collect();
+        Err(StorageError::Forbidden(format!(
+            "This needs the {} role, and {} is {}",
+            allowed.join(" or "),
+            self.name,
+            self.role
+        )))
+    }
+}
+
+/// A token issued to a principal.
+pub struct Token {
+    pub token: String,
+    pub principal: Principal,
+    pub expires_at: DateTime<Utc>,
+}
+
+struct ApiKey {
+    principal: Principal,
+    /// The key's HMAC, so the key itself is not kept in memory.
+    digest: Vec<u8>,
+}
+
+fn validate_name(name: &str) -> Result<(), StorageError> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(StorageError::Validation(format!(
+            "name '{}' must be 1 to 64 letters, digits, '-' or '_'",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// Checks API keys and issues and checks signed tokens. Tokens look like
+/// `<name>.<role>.<expires at, unix seconds>.<hex signature>`, signed with a key
+/// derived from the server's `SECRET_KEY`, so they survive a restart and stop working
+/// when that key changes.
+pub struct Authenticator {
+    key: Vec<u8>,
+    api_keys: Vec<ApiKey>,
+}
+
+impl Authenticator {
+    pub fn new(secret_key: &str, api_keys: Vec<(Principal, String)>) -> Self {
+        Authenticator::with_key(hmac_sha256(secret_key.as_bytes(), b"restaurant tokens"), api_keys)
+    }
+
+    /// An authenticator with a key of its own and no API keys, for the state
+    /// `ServerState::new` builds in tests and tools.
+    pub fn random() -> Self {
+        Authenticator::with_key(rand::random::<[u8; 32]>().to_vec(), Vec::new())
+    }
+
+    fn with_key(key: Vec<u8>, api_keys: Vec<(Principal, String)>) -> Self {
+        let api_keys = api_keys
+            .into_iter()
+            .map(|(principal, api_key)| ApiKey { digest: hmac_sha256(&key, api_key.as_bytes()), principal })
+            .collect();
+        Authenticator { key, api_keys }
+    }
+
+    /// Finds who an API key belongs to.
+    pub fn check_api_key(&self, api_key: &str) -> Result<Principal, String> {
+        self.api_keys
+            .iter()
+            .find(|known| verify_hmac_sha256(&self.key, api_key.as_bytes(), &known.digest))
+            .map(|known| known.principal.clone())
+            .ok_or_else(|| "API key is not recognised".to_string())
+    }
+
+    /// Signs a token for `principal` that stops working at `expires_at`.
+    pub fn sign(&self, principal: &Principal, expires_at: DateTime<Utc>) -> String {
+        let claims = format!("{}.{}.{}", principal.name, principal.role, expires_at.timestamp());
+        format!("{}.{}", claims, to_hex(&hmac_sha256(&self.key, claims.as_bytes())))
+    }
+
+    /// Finds who a token was issued to, if it is genuine and has not expired by `now`.
+    pub fn check_token(&self, token: &str, now: DateTime<Utc>) -> Result<Principal, String> {
+        let malformed = || "Token is malformed".to_string();
+        let (claims, signature) = token.rsplit_once('.').ok_or_else(malformed)?;
+        let signature = from_hex(signature).ok_or_else(malformed)?;
+        if !verify_hmac_sha256(&self.key, claims.as_bytes(), &signature) {
+            return Err("Token signature is not valid".to_string());
+        }
+        let mut parts = claims.split('.');
+        let (Some(name), Some(role), Some(expires_at), None) = (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(malformed());
+        };
+        let expires_at = expires_at
+            .parse()
+            .ok()
+            .and_then(|seconds| Utc.timestamp_opt(seconds, 0).single())
+            .ok_or_else(malformed)?;
+        if expires_at <= now {
+            return Err("Token has expired".to_string());
+        }
+        Ok(Principal { name: name.to_string(), role: role.parse().map_err(|_| malformed())? })
+    }
+
+    /// Issues a token as `issuer` asks, for a role the issuer's own role covers.
+    pub fn issue(&self, issuer: &Principal, input: TokenInput, now: DateTime<Utc>) -> Result<Token, StorageError> {
+        let name = input.name.trim().to_string();
+        validate_name(&name)?;
+        let role: Role = input.role.trim().to_lowercase().parse()?;
+        if !issuer.role.includes(role) {
+            return Err(StorageError::Forbidden(format!("A {} cannot issue {} tokens", issuer.role, role)));
+        }
+        let minutes = input.ttl_minutes.unwrap_or(DEFAULT_TOKEN_MINUTES);
+        if minutes == 0 || minutes > MAX_TOKEN_MINUTES {
+            return Err(StorageError::Validation(format!(
+                "ttl_minutes must be from 1 to {}, got {}",
+                MAX_TOKEN_MINUTES, minutes
+            )));
+        }
+        let principal = Principal { name, role };
+        let expires_at = now + Duration::minutes(i64::from(minutes));
+        Ok(Token { token: self.sign(&principal, expires_at), principal, expires_at })
+    }
+}
+
+/// Reads the API keys from the server config, as comma-separated `name:role:key`
+/// entries.
+pub fn api_keys_from_config(value: &str) -> Result<Vec<(Principal, String)>, String> {
+    let mut api_keys: Vec<(Principal, String)> = Vec::new();
+    for entry in value.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let mut parts = entry.splitn(3, ':');
+        let (Some(name), Some(role), Some(api_key)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(format!("API key entries must look like name:role:key, got '{}'", entry));
+        };
+        validate_name(name).map_err(|e| e.to_string())?;
+        let role: Role = role.parse().map_err(|e: StorageError| e.to_string())?;
+        if api_key.len() < MIN_API_KEY_LEN {
+            return Err(format!("API key for '{}' must be at least {} characters", name, MIN_API_KEY_LEN));
+        }
+        if api_keys.iter().any(|(known, key)| known.name == name || key == api_key) {
+            return Err(format!("API key for '{}' is listed twice", name));
+        }
+        api_keys.push((Principal { name: name.to_string(), role }, api_key.to_string()));
+    }
+    Ok(api_keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn principal(name: &str, role: Role) -> Principal {
+        Principal { name: name.to_string(), role }
+    }
+
+    fn token_input(name: &str, role: &str, ttl_minutes: Option<u32>) -> TokenInput {
+        TokenInput { name: name.to_string(), role: role.to_string(), ttl_minutes }
+    }
+
+    #[test]
+    fn test_roles_include_the_ones_below() {
+        assert!(Role::Admin.includes(Role::Manager));
+        assert!(Role::Manager.includes(Role::Kitchen) && Role::Manager.includes(Role::Server));
+        assert!(!Role::Manager.includes(Role::Admin));
+        assert!(!Role::Server.includes(Role::Kitchen));
+        assert!(!Role::Kitchen.includes(Role::Manager));
+
+        let server = principal("till-1", Role::Server);
+        assert_eq!(server.require(&[Role::Server, Role::Kitchen]), Ok(()));
+        assert!(matches!(server.require(&[Role::Manager]), Err(StorageError::Forbidden(_))));
+        assert_eq!(principal("ana", Role::Manager).require(&[Role::Kitchen]), Ok(()));
+        for role in Role::ALL {
+            assert_eq!(role.as_str().parse::<Role>(), Ok(role));
+        }
+    }
+
+    #[test]
+    fn test_tokens_round_trip_until_they_expire() {
+        let auth = Authenticator::new("server key", Vec::new());
+        let now = Utc::now();
+        let pass = principal("pass-2", Role::Kitchen);
+        let token = auth.sign(&pass, now + Duration::minutes(5));
+
+        assert_eq!(auth.check_token(&token, now), Ok(pass.clone()));
+        assert_eq!(Authenticator::new("server key", Vec::new()).check_token(&token, now), Ok(pass));
+        assert_eq!(auth.check_token(&token, now + Duration::minutes(5)), Err("Token has expired".to_string()));
+        assert!(Authenticator::new("other key", Vec::new()).check_token(&token, now).is_err());
+    }
+
+    #[test]
+    fn test_tampered_tokens_are_refused() {
+        let auth = Authenticator::new("server key", Vec::new());
+        let now = Utc::now();
+        let token = auth.sign(&principal("till-1", Role::Server), now + Duration::minutes(5));
+
+        let promoted = token.replacen(".server.", ".admin.", 1);
+        assert_eq!(auth.check_token(&promoted, now), Err("Token signature is not valid".to_string()));
+        for garbage in ["", "till-1", "till-1.server.1.zz", &format!("{}0", token)] {
+            assert!(auth.check_token(garbage, now).is_err(), "{}", garbage);
+        }
+    }
+
+    #[test]
+    fn test_issue_limits_roles_and_lifetimes() {
+        let auth = Authenticator::new("server key", Vec::new());
+        let now = Utc::now();
+        let manager = principal("ana", Role::Manager);
+
+        let issued = auth.issue(&manager, token_input(" till-3 ", "Server", None), now).unwrap();
+        assert_eq!(issued.principal, principal("till-3", Role::Server));
+        assert_eq!(issued.expires_at, now + Duration::minutes(i64::from(DEFAULT_TOKEN_MINUTES)));
+        assert_eq!(auth.check_token(&issued.token, now), Ok(issued.principal));
+
+        assert!(matches!(
+            auth.issue(&manager, token_input("root", "admin", None), now),
+            Err(StorageError::Forbidden(_))
+        ));
+        for input in [
+            token_input("till.3", "server", None),
+            token_input("till-3", "owner", None),
+            token_input("till-3", "server", Some(0)),
+            token_input("till-3", "server", Some(MAX_TOKEN_MINUTES + 1)),
+        ] {
+            assert!(matches!(auth.issue(&manager, input, now), Err(StorageError::Validation(_))));
+        }
+    }
+
+    #[test]
+    fn test_api_keys_from_config() {
+        let api_keys = api_keys_from_config("till-1:server:0123456789abcdef, pass:kitchen:fedcba9876543210:x").unwrap();
+        assert_eq!(api_keys[1], (principal("pass", Role::Kitchen), "fedcba9876543210:x".to_string()));
+        assert_eq!(api_keys_from_config(""), Ok(vec![]));
+
+        let auth = Authenticator::new("server key", api_keys);
+        assert_eq!(auth.check_api_key("0123456789abcdef"), Ok(principal("till-1", Role::Server)));
+        assert!(auth.check_api_key("0123456789abcdeF").is_err());
+
+        for bad in [
+            "till-1:server",
+            "till-1:owner:0123456789abcdef",
+            "till-1:server:short",
+            "till-1:server:0123456789abcdef,till-1:admin:fedcba9876543210",
+        ] {
+            assert!(api_keys_from_config(bad).is_err(), "{}", bad);
+        }
+    }
+}
@@ -0,0 +1,870 @@
+use crate::db::{Decoding, SplitBill as DBSplitBill, StorageError};
+use crate::domain::floor::active_session;
+use crate::domain::tables::{get_session_orders, Order, OrderStatus};
+use crate::protocol::protocol::{PaymentInput, SplitInput};
+use crate::ServerState;
+use chrono::{DateTime, Utc};
+use rocket::State;
+use std::collections::{BTreeMap, HashSet};
+use uuid::Uuid;
+
+pub use crate::db::{BillStatus, Payment, Tender};
+
+const BASIS_POINTS: u64 = 10_000;
+
+/// How bills are charged. Rates are in basis points, so 825 is 8.25%.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pricing {
+    /// ISO 4217 code every amount is in, e.g. `USD`.
+    pub currency: String,
+    pub tax_rate_bps: u32,
+    pub service_charge_bps: u32,
+}
+
+impl Default for Pricing {
+    fn default() -> Self {
+        Pricing {
+            currency: "USD".to_string(),
+            tax_rate_bps: 0,
+            service_charge_bps: 0,
+        }
+    }
+}
+
+fn parse_rate(name: &str, value: &str) -> Result<u32, String> {
+    value
+        .parse::<u32>()
+        .ok()
+        .filter(|rate| u64::from(*rate) <= BASIS_POINTS)
+        .ok_or_else(|| format!("{} must be a whole number of basis points from 0 to 10000, got '{}'", name, value))
+}
+
+/// Builds the pricing from the server config, using `Pricing::default` for anything
+/// not given.
+pub fn pricing_from_config(
+    currency: Option<&str>,
+    tax_rate_bps: Option<&str>,
+    service_charge_bps: Option<&str>,
+) -> Result<Pricing, String> {
+    let mut pricing = Pricing::default();
+    if let Some(currency) = currency {
+        if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err(format!("Currency must be a three letter code such as USD, got '{}'", currency));
+        }
+        pricing.currency = currency.to_string();
+    }
+    if let Some(rate) = tax_rate_bps {
+        pricing.tax_rate_bps = parse_rate("Tax rate", rate)?;
+    }
+    if let Some(rate) = service_charge_bps {
+        pricing.service_charge_bps = parse_rate("Service charge", rate)?;
+    }
+    Ok(pricing)
+}
+
+/// `amount * rate_bps / 10000`, rounded to the nearest minor unit with halves rounded up.
+pub fn apply_rate(amount: u64, rate_bps: u32) -> u64 {
+    let scaled = u128::from(amount) * u128::from(rate_bps) + u128::from(BASIS_POINTS / 2);
+    u64::try_from(scaled / u128::from(BASIS_POINTS)).unwrap_or(u64::MAX)
+}
+
+/// Every order of one menu item at one price, as a single line on the bill.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BillLine {
+    pub menu_item: String,
+    pub unit_price: u64,
+    pub quantity: u32,
+    pub line_total: u64,
+}
+
+/// What a session owes, in minor units of `currency`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bill {
+    pub table_number: u64,
+    pub session_id: Uuid,
+    pub currency: String,
+    pub lines: Vec<BillLine>,
+    pub subtotal: u64,
+    pub service_charge: u64,
+    pub tax: u64,
+    pub total: u64,
+    /// What payments have put towards `total` so far.
+    pub paid: u64,
+    pub balance: u64,
+    pub status: BillStatus,
+}
+
+/// Paid once nothing is left to pay, open until then.
+fn status_for(balance: u64) -> BillStatus {
+    if balance == 0 {
+        BillStatus::Paid
+    } else {
+        BillStatus::Open
+    }
+}
+
+fn paid<'a>(payments: impl IntoIterator<Item = &'a Payment>) -> u64 {
+    payments.into_iter().map(|payment| payment.amount).sum()
+}
+
+fn is_billable(order: &Order) -> bool {
+    order.status != OrderStatus::Cancelled
+}
+
+/// Groups `orders` into lines in the order they were first placed.
+fn lines<'a>(orders: impl IntoIterator<Item = &'a Order>) -> Vec<BillLine> {
+    let mut lines: Vec<BillLine> = Vec::new();
+    for order in orders {
+        match lines
+            .iter_mut()
+            .find(|line| line.menu_item == order.menu_item && line.unit_price == order.price)
+        {
+            Some(line) => {
+                line.quantity += 1;
+                line.line_total += order.price;
+            }
+            None => lines.push(BillLine {
+                menu_item: order.menu_item.clone(),
+                unit_price: order.price,
+                quantity: 1,
+                line_total: order.price,
+            }),
+        }
+    }
+    lines
+}
+
+/// Groups `orders` into lines in the order they were first placed and works out the
+/// charges, before any payment. Cancelled orders are left off. The service charge is a
+/// share of the subtotal, and tax is charged on the subtotal plus the service charge.
+pub fn itemize(table_number: u64, session_id: Uuid, orders: &[Order], pricing: &Pricing) -> Bill {
+    let lines = lines(orders.iter().filter(|order| is_billable(order)));
+    let subtotal: u64 = lines.iter().map(|line| line.line_total).sum();
+    let service_charge = apply_rate(subtotal, pricing.service_charge_bps);
+    let tax = apply_rate(subtotal + service_charge, pricing.tax_rate_bps);
+    let total = subtotal + service_charge + tax;
+    Bill {
+        table_number,
+        session_id,
+        currency: pricing.currency.clone(),
+        lines,
+        subtotal,
+        service_charge,
+        tax,
+        total,
+        paid: 0,
+        balance: total,
+        status: status_for(total),
+    }
+}
+
+/// Counts every payment taken during the session, for the whole bill or one of its
+/// shares, against `bill`.
+pub fn apply_payments(mut bill: Bill, payments: &[Payment]) -> Bill {
+    bill.paid = paid(payments);
+    bill.balance = bill.total.saturating_sub(bill.paid);
+    bill.status = status_for(bill.balance);
+    bill
+}
+
+/// The bill for one session at the table, open or already ended.
+pub async fn get_session_bill(
+    table_id: u64,
+    session_id: Uuid,
+    state: &State<Box<ServerState>>,
+) -> Result<Bill, StorageError> {
+    let orders = get_session_orders(table_id, session_id, Decoding::Strict, state).await?;
+    let payments = state.db.get_session_payments(session_id, Decoding::Strict).await?;
+    Ok(apply_payments(itemize(table_id, session_id, &orders, &state.pricing), &payments))
+}
+
+/// The bill for the seating in progress. Fails with `Conflict` while the table is
+/// closed.
+pub async fn get_bill(table_id: u64, state: &State<Box<ServerState>>) -> Result<Bill, StorageError> {
+    let session_id = active_session(table_id, state).await?;
+    get_session_bill(table_id, session_id, state).await
+}
+
+/// Shares `amount` out in proportion to `weights`. Each share is rounded down and the
+/// minor units left over go one each to the shares that lost the most to rounding,
+/// earlier shares first on a tie, so the shares always add up to `amount`. With no
+/// weight at all the amount is shared equally.
+pub fn allocate(amount: u64, weights: &[u64]) -> Vec<u64> {
+    let equal = vec![1; weights.len()];
+    let weights = if weights.iter().all(|weight| *weight == 0) { &equal } else { weights };
+    let total_weight: u128 = weights.iter().map(|weight| u128::from(*weight)).sum();
+    if total_weight == 0 {
+        return Vec::new();
+    }
+    let mut shares = Vec::with_capacity(weights.len());
+    let mut remainders = Vec::with_capacity(weights.len());
+    for weight in weights {
+        let scaled = u128::from(amount) * u128::from(*weight);
+        // Each share is at most `amount`, so it fits back into a u64.
+        shares.push((scaled / total_weight) as u64);
+        remainders.push(scaled % total_weight);
+    }
+    let leftover = amount - shares.iter().sum::<u64>();
+    let mut order: Vec<usize> = (0..shares.len()).collect();
+    order.sort_by(|a, b| remainders[*b].cmp(&remainders[*a]).then(a.cmp(b)));
+    for index in order.into_iter().take(leftover as usize) {
+        shares[index] += 1;
+    }
+    shares
+}
+
+/// Shares each of `amounts` equally `ways` ways. The minor units left over go one each
+/// to successive shares, carrying on from where the previous amount's stopped, so the
+/// shares' totals never differ by more than one.
+pub fn share_evenly(amounts: &[u64], ways: u32) -> Vec<Vec<u64>> {
+    let ways = u64::from(ways.max(1));
+    let mut next = 0;
+    amounts
+        .iter()
+        .map(|amount| {
+            let mut shares = vec![amount / ways; ways as usize];
+            for _ in 0..amount % ways {
+                shares[next] += 1;
+                next = (next + 1) % shares.len();
+            }
+            shares
+        })
+        .collect()
+}
+
+/// How a bill is shared out between guests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SplitMode {
+    /// Equal shares of every amount, without any lines.
+    Even(u32),
+    /// One share per seat with an order.
+    BySeat,
+    /// One share per group of order ids.
+    ByItems(Vec<Vec<Uuid>>),
+}
+
+/// Most shares a bill can be split evenly into.
+pub const MAX_SPLIT_WAYS: u32 = 50;
+
+pub fn split_mode(input: SplitInput) -> Result<SplitMode, StorageError> {
+    match input.mode.as_str() {
+        "even" => match input.ways {
+            Some(ways) if (2..=MAX_SPLIT_WAYS).contains(&ways) => Ok(SplitMode::Even(ways)),
+            _ => Err(StorageError::Validation(format!(
+                "An even split needs ways from 2 to {}",
+                MAX_SPLIT_WAYS
+            ))),
+        },
+        "seat" => Ok(SplitMode::BySeat),
+        "items" => {
+            if input.parts.is_empty() || input.parts.iter().any(|part| part.is_empty()) {
+                return Err(StorageError::Validation("Every part needs at least one order".to_string()));
+            }
+            Ok(SplitMode::ByItems(input.parts))
+        }
+        other => Err(StorageError::Validation(format!(
+            "Unknown split mode '{}', expected even, seat or items",
+            other
+        ))),
+    }
+}
+
+/// Puts the billable orders into labelled groups for a seat or item split.
+fn group_orders(orders: &[Order], mode: &SplitMode) -> Result<Vec<(String, Vec<Uuid>)>, StorageError> {
+    let billable: Vec<&Order> = orders.iter().filter(|order| is_billable(order)).collect();
+    match mode {
+        SplitMode::Even(_) => Ok(Vec::new()),
+        SplitMode::BySeat => {
+            let mut seats: BTreeMap<u32, Vec<Uuid>> = BTreeMap::new();
+            for order in billable {
+                let seat = order.seat.ok_or_else(|| {
+                    StorageError::Validation(format!("Order {} has no seat, so the bill cannot be split by seat", order.id))
+                })?;
+                seats.entry(seat).or_default().push(order.id);
+            }
+            Ok(seats.into_iter().map(|(seat, ids)| (format!("Seat {}", seat), ids)).collect())
+        }
+        SplitMode::ByItems(parts) => {
+            let billable_ids: HashSet<Uuid> = billable.iter().map(|order| order.id).collect();
+            let mut assigned = HashSet::new();
+            for id in parts.iter().flatten() {
+                if !billable_ids.contains(id) {
+                    return Err(StorageError::Validation(format!("Order {} is not on this bill", id)));
+                }
+                if !assigned.insert(*id) {
+                    return Err(StorageError::Validation(format!("Order {} is in more than one part", id)));
+                }
+            }
+            if let Some(order) = billable.iter().find(|order| !assigned.contains(&order.id)) {
+                return Err(StorageError::Validation(format!("Order {} is not in any part", order.id)));
+            }
+            Ok(parts
+                .iter()
+                .enumerate()
+                .map(|(index, ids)| (format!("Part {}", index + 1), ids.clone()))
+                .collect())
+        }
+    }
+}
+
+/// Shares `bill` out as `mode` asks. The shares of the subtotal, service charge and tax
+/// each add up to the whole bill's. Seat and item shares carry the subtotal of their
+/// own orders and a part of the charges in proportion to it.
+pub fn split(bill: &Bill, orders: &[Order], mode: &SplitMode) -> Result<Vec<DBSplitBill>, StorageError> {
+    if !orders.iter().any(is_billable) {
+        return Err(StorageError::Validation("There is nothing on the bill to split".to_string()));
+    }
+    let groups = match mode {
+        SplitMode::Even(ways) => (1..=*ways).map(|part| (format!("{} of {}", part, ways), Vec::new())).collect(),
+        _ => group_orders(orders, mode)?,
+    };
+    let (subtotals, service_charges, taxes) = match mode {
+        SplitMode::Even(ways) => {
+            let mut shares = share_evenly(&[bill.subtotal, bill.service_charge, bill.tax], *ways).into_iter();
+            (shares.next().unwrap(), shares.next().unwrap(), shares.next().unwrap())
+        }
+        _ => {
+            let subtotals: Vec<u64> = groups
+                .iter()
+                .map(|(_, ids)| {
+                    orders
+                        .iter()
+                        .filter(|order| ids.contains(&order.id))
+                        .map(|order| order.price)
+                        .sum()
+                })
+                .collect();
+            let service_charges = allocate(bill.service_charge, &subtotals);
+            let taxes = allocate(bill.tax, &subtotals);
+            (subtotals, service_charges, taxes)
+        }
+    };
+    Ok(groups
+        .into_iter()
+        .enumerate()
+        .map(|(index, (label, order_ids))| DBSplitBill {
+            id: Uuid::new_v4(),
+            session_id: bill.session_id,
+            position: index as u32,
+            label,
+            order_ids,
+            subtotal: subtotals[index],
+            service_charge: service_charges[index],
+            tax: taxes[index],
+            total: subtotals[index] + service_charges[index] + taxes[index],
+            status: BillStatus::Open,
+        })
+        .collect())
+}
+
+/// One share of a split bill, in minor units of `currency`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitBill {
+    pub id: Uuid,
+    pub table_number: u64,
+    pub session_id: Uuid,
+    pub position: u32,
+    pub label: String,
+    pub currency: String,
+    pub order_ids: Vec<Uuid>,
+    pub lines: Vec<BillLine>,
+    pub subtotal: u64,
+    pub service_charge: u64,
+    pub tax: u64,
+    pub total: u64,
+    pub paid: u64,
+    pub balance: u64,
+    pub status: BillStatus,
+}
+
+/// A share's balance is what is left of its own total, but never more than is left of
+/// the whole bill, so money taken for the whole bill cannot be charged again on a share.
+fn from_db(
+    table_number: u64,
+    bill: DBSplitBill,
+    orders: &[Order],
+    payments: &[Payment],
+    session_balance: u64,
+    pricing: &Pricing,
+) -> SplitBill {
+    let paid = paid(payments.iter().filter(|payment| payment.bill_id == Some(bill.id)));
+    SplitBill {
+        lines: lines(orders.iter().filter(|order| bill.order_ids.contains(&order.id))),
+        id: bill.id,
+        table_number,
+        session_id: bill.session_id,
+        position: bill.position,
+        label: bill.label,
+        currency: pricing.currency.clone(),
+        order_ids: bill.order_ids,
+        subtotal: bill.subtotal,
+        service_charge: bill.service_charge,
+        tax: bill.tax,
+        total: bill.total,
+        paid,
+        balance: bill.total.saturating_sub(paid).min(session_balance),
+        status: bill.status,
+    }
+}
+
+/// The shares the bill for the seating in progress has been split into, or none if it
+/// has not been split.
+pub async fn get_split_bills(table_id: u64, state: &State<Box<ServerState>>) -> Result<Vec<SplitBill>, StorageError> {
+    let session_id = active_session(table_id, state).await?;
+    let orders = get_session_orders(table_id, session_id, Decoding::Strict, state).await?;
+    let payments = state.db.get_session_payments(session_id, Decoding::Strict).await?;
+    let bills = state.db.get_split_bills(session_id, Decoding::Strict).await?;
+    let session_balance = apply_payments(itemize(table_id, session_id, &orders, &state.pricing), &payments).balance;
+    Ok(bills
+        .into_iter()
+        .map(|bill| from_db(table_id, bill, &orders, &payments, session_balance, &state.pricing))
+        .collect())
+}
+
+pub async fn get_split_bill(
+    table_id: u64,
+    bill_id: Uuid,
+    state: &State<Box<ServerState>>,
+) -> Result<SplitBill, StorageError> {
+    let session_id = active_session(table_id, state).await?;
+    let orders = get_session_orders(table_id, session_id, Decoding::Strict, state).await?;
+    let payments = state.db.get_session_payments(session_id, Decoding::Strict).await?;
+    let bill = state.db.get_split_bill(session_id, bill_id).await?;
+    let session_balance = apply_payments(itemize(table_id, session_id, &orders, &state.pricing), &payments).balance;
+    Ok(from_db(table_id, bill, &orders, &payments, session_balance, &state.pricing))
+}
+
+/// Fails with `Conflict` once money has been taken, since the shares it was taken for
+/// would no longer exist after splitting again.
+async fn ensure_unpaid(session_id: Uuid, state: &State<Box<ServerState>>) -> Result<(), StorageError> {
+    if state.db.get_session_payments(session_id, Decoding::Strict).await?.is_empty() {
+        Ok(())
+    } else {
+        Err(StorageError::Conflict("Payments have already been taken for this bill".to_string()))
+    }
+}
+
+/// Called before the billable orders of a session change. Once a payment has been
+/// taken the change fails with `Conflict`, as the bill or shares paid towards must stay
+/// as they were; before that a split of the bill would no longer add up, so it is
+/// dropped.
+pub async fn orders_changing(session_id: Uuid, state: &State<Box<ServerState>>) -> Result<(), StorageError> {
+    if !state.db.get_session_payments(session_id, Decoding::Strict).await?.is_empty() {
+        return Err(StorageError::Conflict(
+            "The bill has been paid towards, so its orders cannot change".to_string(),
+        ));
+    }
+    if state.db.get_split_bills(session_id, Decoding::Strict).await?.is_empty() {
+        return Ok(());
+    }
+    state.db.replace_split_bills(session_id, Vec::new()).await
+}
+
+/// Splits the bill for the seating in progress, replacing any earlier split. Fails
+/// with `Conflict` once a payment has been taken.
+pub async fn split_bill(
+    table_id: u64,
+    input: SplitInput,
+    state: &State<Box<ServerState>>,
+) -> Result<Vec<SplitBill>, StorageError> {
+    let mode = split_mode(input)?;
+    let _guard = state.table_locks.lock(table_id).await;
+    let session_id = active_session(table_id, state).await?;
+    ensure_unpaid(session_id, state).await?;
+    let orders = get_session_orders(table_id, session_id, Decoding::Strict, state).await?;
+    let bill = itemize(table_id, session_id, &orders, &state.pricing);
+    let shares = split(&bill, &orders, &mode)?;
+    state.db.replace_split_bills(session_id, shares).await?;
+    get_split_bills(table_id, state).await
+}
+
+/// Goes back to a single bill for the seating in progress.
+pub async fn remove_split(table_id: u64, state: &State<Box<ServerState>>) -> Result<(), StorageError> {
+    let _guard = state.table_locks.lock(table_id).await;
+    let session_id = active_session(table_id, state).await?;
+    ensure_unpaid(session_id, state).await?;
+    state.db.replace_split_bills(session_id, Vec::new()).await
+}
+
+/// Closes one share of the split bill once it is paid, leaving the others as they are.
+pub async fn close_split_bill(
+    table_id: u64,
+    bill_id: Uuid,
+    state: &State<Box<ServerState>>,
+) -> Result<SplitBill, StorageError> {
+    let _guard = state.table_locks.lock(table_id).await;
+    let bill = get_split_bill(table_id, bill_id, state).await?;
+    if bill.status == BillStatus::Closed {
+        return Err(StorageError::Conflict("Bill is already closed".to_string()));
+    }
+    if bill.balance > 0 {
+        return Err(StorageError::Conflict(format!("Bill has an unpaid balance of {}", bill.balance)));
+    }
+    state
+        .db
+        .update_split_bill_status(bill.session_id, bill_id, bill.status, BillStatus::Closed)
+        .await?;
+    get_split_bill(table_id, bill_id, state).await
+}
+
+/// Works out a payment of `input` towards a bill with `balance` left to pay. `amount`
+/// is everything the guest hands over or is charged, `tip` the part of it kept as a
+/// tip; cash beyond the balance comes back as change, and cards and gift cards cannot
+/// be charged more than the balance and tip.
+pub fn take_payment(
+    session_id: Uuid,
+    bill_id: Option<Uuid>,
+    input: PaymentInput,
+    balance: u64,
+    paid_at: DateTime<Utc>,
+) -> Result<Payment, StorageError> {
+    let tender: Tender = input.tender.parse()?;
+    let reference = input
+        .reference
+        .map(|reference| reference.trim().to_string())
+        .filter(|reference| !reference.is_empty());
+    if tender != Tender::Cash && reference.is_none() {
+        return Err(StorageError::Validation(format!("A {} payment needs a reference", tender)));
+    }
+    let towards_bill = input
+        .amount
+        .checked_sub(input.tip)
+        .ok_or_else(|| StorageError::Validation("tip cannot be more than the amount".to_string()))?;
+    if towards_bill == 0 {
+        return Err(StorageError::Validation("A payment must put something towards the bill".to_string()));
+    }
+    if balance == 0 {
+        return Err(StorageError::Conflict("Bill is already paid".to_string()));
+    }
+    let amount = towards_bill.min(balance);
+    let change = towards_bill - amount;
+    if change > 0 && tender != Tender::Cash {
+        return Err(StorageError::Validation(format!(
+            "A {} payment cannot be more than the balance of {}; record the rest as a tip",
+            tender, balance
+        )));
+    }
+    Ok(Payment {
+        id: Uuid::new_v4(),
+        session_id,
+        bill_id,
+        tender,
+        reference,
+        amount,
+        tip: input.tip,
+        change,
+        paid_at,
+    })
+}
+
+/// Takes a payment towards the whole bill for the seating in progress, or towards one
+/// share of it when `bill_id` is given. A share is marked paid once it is covered.
+/// Once the bill is split, payments have to go towards a share, which fails with
+/// `Conflict` otherwise.
+pub async fn record_payment(
+    table_id: u64,
+    bill_id: Option<Uuid>,
+    input: PaymentInput,
+    state: &State<Box<ServerState>>,
+) -> Result<Payment, StorageError> {
+    let _guard = state.table_locks.lock(table_id).await;
+    let session_id = active_session(table_id, state).await?;
+    let balance = match bill_id {
+        Some(bill_id) => {
+            let bill = get_split_bill(table_id, bill_id, state).await?;
+            if bill.status == BillStatus::Closed {
+                return Err(StorageError::Conflict("Bill is already closed".to_string()));
+            }
+            bill.balance
+        }
+        None => {
+            if !state.db.get_split_bills(session_id, Decoding::Strict).await?.is_empty() {
+                return Err(StorageError::Conflict(
+                    "The bill has been split, so payments go towards one of its shares".to_string(),
+                ));
+            }
+            get_session_bill(table_id, session_id, state).await?.balance
+        }
+    };
+    let payment = take_payment(session_id, bill_id, input, balance, Utc::now())?;
+    state.db.add_payment(payment.clone()).await?;
+    if let Some(bill_id) = bill_id {
+        if payment.amount == balance {
+            state
+                .db
+                .update_split_bill_status(session_id, bill_id, BillStatus::Open, BillStatus::Paid)
+                .await?;
+        }
+    }
+    Ok(payment)
+}
+
+/// Every payment taken during the seating in progress, oldest first.
+pub async fn get_payments(table_id: u64, state: &State<Box<ServerState>>) -> Result<Vec<Payment>, StorageError> {
+    let session_id = active_session(table_id, state).await?;
+    state.db.get_session_payments(session_id, Decoding::SkipCorrupt).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Order as DBOrder;
+    use crate::domain::tables::from_db;
+    use chrono::Utc;
+
+    fn order(menu_item: &str, price: u64, status: OrderStatus) -> Order {
+        let mut order = DBOrder { price, ..DBOrder::placed(Uuid::new_v4(), menu_item.to_string(), Utc::now(), 600) };
+        order.set_status(status, Utc::now());
+        from_db(order)
+    }
+
+    fn pricing(tax_rate_bps: u32, service_charge_bps: u32) -> Pricing {
+        Pricing {
+            currency: "EUR".to_string(),
+            tax_rate_bps,
+            service_charge_bps,
+        }
+    }
+
+    #[test]
+    fn test_itemize_groups_lines() {
+        let orders = vec![
+            order("Pizza", 1250, OrderStatus::Placed),
+            order("Salad", 750, OrderStatus::Served),
+            order("Pizza", 1250, OrderStatus::Cooking),
+            order("Pizza", 1400, OrderStatus::Placed),
+            order("Soup", 600, OrderStatus::Cancelled),
+        ];
+        let session_id = Uuid::new_v4();
+        let bill = itemize(4, session_id, &orders, &pricing(0, 0));
+
+        let lines: Vec<(&str, u64, u32, u64)> = bill
+            .lines
+            .iter()
+            .map(|line| (line.menu_item.as_str(), line.unit_price, line.quantity, line.line_total))
+            .collect();
+        assert_eq!(lines, vec![("Pizza", 1250, 2, 2500), ("Salad", 750, 1, 750), ("Pizza", 1400, 1, 1400)]);
+        assert_eq!((bill.table_number, bill.session_id), (4, session_id));
+        assert_eq!(bill.currency, "EUR");
+        assert_eq!((bill.subtotal, bill.service_charge, bill.tax, bill.total), (4650, 0, 0, 4650));
+    }
+
+    #[test]
+    fn test_itemize_applies_service_charge_then_tax() {
+        let orders = vec![order("Pizza", 1250, OrderStatus::Placed), order("Salad", 749, OrderStatus::Placed)];
+        let bill = itemize(1, Uuid::new_v4(), &orders, &pricing(825, 1000));
+
+        assert_eq!(bill.subtotal, 1999);
+        // 10% of 19.99 is 1.999, rounded to 2.00; 8.25% of 21.99 is 1.814175.
+        assert_eq!(bill.service_charge, 200);
+        assert_eq!(bill.tax, 181);
+        assert_eq!(bill.total, 2380);
+        assert_eq!(itemize(1, Uuid::new_v4(), &[], &pricing(825, 1000)).total, 0);
+    }
+
+    #[test]
+    fn test_apply_rate_rounds_half_up() {
+        assert_eq!(apply_rate(10, 500), 1);
+        assert_eq!(apply_rate(9, 500), 0);
+        assert_eq!(apply_rate(1000, 10_000), 1000);
+        assert_eq!(apply_rate(u64::MAX, 10_000), u64::MAX);
+    }
+
+    #[test]
+    fn test_allocate_hands_out_remainders_deterministically() {
+        assert_eq!(allocate(1000, &[1, 1, 1]), vec![334, 333, 333]);
+        assert_eq!(allocate(200, &[1250, 749]), vec![125, 75]);
+        // 181 shared 1250:749 is 113.18 and 67.82, so the second share gets the cent.
+        assert_eq!(allocate(181, &[1250, 749]), vec![113, 68]);
+        assert_eq!(allocate(5, &[0, 0]), vec![3, 2]);
+        assert_eq!(allocate(7, &[]), Vec::<u64>::new());
+        assert_eq!(allocate(u64::MAX, &[u64::MAX, 1]).iter().sum::<u64>(), u64::MAX);
+    }
+
+    #[test]
+    fn test_share_evenly_rotates_remainders() {
+        assert_eq!(share_evenly(&[1999, 200, 181], 3), vec![vec![667, 666, 666], vec![66, 67, 67], vec![61, 60, 60]]);
+        assert_eq!(share_evenly(&[10, 10], 4), vec![vec![3, 3, 2, 2], vec![2, 2, 3, 3]]);
+        assert_eq!(share_evenly(&[0], 2), vec![vec![0, 0]]);
+    }
+
+    fn seated(menu_item: &str, price: u64, seat: u32) -> Order {
+        Order { seat: Some(seat), ..order(menu_item, price, OrderStatus::Placed) }
+    }
+
+    fn amounts(shares: &[DBSplitBill]) -> Vec<(&str, u64, u64, u64, u64)> {
+        shares
+            .iter()
+            .map(|share| (share.label.as_str(), share.subtotal, share.service_charge, share.tax, share.total))
+            .collect()
+    }
+
+    #[test]
+    fn test_split_evenly() {
+        let orders = vec![order("Pizza", 1250, OrderStatus::Placed), order("Salad", 749, OrderStatus::Placed)];
+        let bill = itemize(1, Uuid::new_v4(), &orders, &pricing(825, 1000));
+        let shares = split(&bill, &orders, &SplitMode::Even(3)).unwrap();
+
+        assert_eq!(
+            amounts(&shares),
+            vec![("1 of 3", 667, 66, 61, 794), ("2 of 3", 666, 67, 60, 793), ("3 of 3", 666, 67, 60, 793)]
+        );
+        assert_eq!(shares.iter().map(|share| share.total).sum::<u64>(), bill.total);
+        assert!(shares.iter().all(|share| share.order_ids.is_empty() && share.session_id == bill.session_id));
+        assert_eq!(shares.iter().map(|share| share.position).collect::<Vec<_>>(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_split_by_seat() {
+        let orders = vec![
+            seated("Pizza", 1250, 2),
+            seated("Salad", 749, 1),
+            seated("Soup", 600, 1),
+            Order { seat: None, ..order("Pizza", 1250, OrderStatus::Cancelled) },
+        ];
+        let bill = itemize(1, Uuid::new_v4(), &orders, &pricing(825, 1000));
+        let shares = split(&bill, &orders, &SplitMode::BySeat).unwrap();
+
+        assert_eq!(amounts(&shares), vec![("Seat 1", 1349, 135, 122, 1606), ("Seat 2", 1250, 125, 114, 1489)]);
+        assert_eq!(shares.iter().map(|share| share.total).sum::<u64>(), bill.total);
+        assert_eq!(shares[0].order_ids, vec![orders[1].id, orders[2].id]);
+
+        let unseated = vec![seated("Pizza", 1250, 1), order("Salad", 749, OrderStatus::Placed)];
+        let bill = itemize(1, Uuid::new_v4(), &unseated, &pricing(0, 0));
+        assert!(matches!(split(&bill, &unseated, &SplitMode::BySeat), Err(StorageError::Validation(_))));
+    }
+
+    #[test]
+    fn test_split_by_items() {
+        let orders = vec![
+            order("Pizza", 1250, OrderStatus::Placed),
+            order("Salad", 749, OrderStatus::Placed),
+            order("Soup", 600, OrderStatus::Cancelled),
+        ];
+        let bill = itemize(1, Uuid::new_v4(), &orders, &pricing(825, 1000));
+        let parts = |parts: &[&[usize]]| {
+            SplitMode::ByItems(parts.iter().map(|part| part.iter().map(|index| orders[*index].id).collect()).collect())
+        };
+
+        let shares = split(&bill, &orders, &parts(&[&[1], &[0]])).unwrap();
+        assert_eq!(amounts(&shares), vec![("Part 1", 749, 75, 68, 892), ("Part 2", 1250, 125, 113, 1488)]);
+        assert_eq!(shares.iter().map(|share| share.total).sum::<u64>(), bill.total);
+
+        for bad in [parts(&[&[0]]), parts(&[&[0, 1], &[1]]), parts(&[&[0], &[1, 2]])] {
+            assert!(matches!(split(&bill, &orders, &bad), Err(StorageError::Validation(_))));
+        }
+        let stranger = SplitMode::ByItems(vec![vec![orders[0].id, orders[1].id, Uuid::new_v4()]]);
+        assert!(matches!(split(&bill, &orders, &stranger), Err(StorageError::Validation(_))));
+        assert!(matches!(
+            split(&itemize(1, Uuid::new_v4(), &[], &pricing(0, 0)), &[], &SplitMode::Even(2)),
+            Err(StorageError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_split_mode() {
+        let input = |mode: &str, ways: Option<u32>, parts: Vec<Vec<Uuid>>| SplitInput {
+            mode: mode.to_string(),
+            ways,
+            parts,
+        };
+        assert_eq!(split_mode(input("even", Some(4), vec![])), Ok(SplitMode::Even(4)));
+        assert_eq!(split_mode(input("seat", None, vec![])), Ok(SplitMode::BySeat));
+        let id = Uuid::new_v4();
+        assert_eq!(split_mode(input("items", None, vec![vec![id]])), Ok(SplitMode::ByItems(vec![vec![id]])));
+        for bad in [
+            input("even", None, vec![]),
+            input("even", Some(1), vec![]),
+            input("even", Some(MAX_SPLIT_WAYS + 1), vec![]),
+            input("items", None, vec![]),
+            input("items", None, vec![vec![id], vec![]]),
+            input("halves", None, vec![]),
+        ] {
+            assert!(matches!(split_mode(bad), Err(StorageError::Validation(_))));
+        }
+    }
+
+    fn tendered(tender: &str, amount: u64, tip: u64, reference: Option<&str>) -> PaymentInput {
+        PaymentInput {
+            tender: tender.to_string(),
+            amount,
+            tip,
+            reference: reference.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_take_payment() {
+        let session_id = Uuid::new_v4();
+        let take = |input, balance| take_payment(session_id, None, input, balance, Utc::now());
+
+        let cash = take(tendered("cash", 5000, 500, None), 2380).unwrap();
+        assert_eq!((cash.tender, cash.amount, cash.tip, cash.change), (Tender::Cash, 2380, 500, 2120));
+        let partial = take(tendered("card", 1000, 0, Some(" VISA-4242 ")), 2380).unwrap();
+        assert_eq!((partial.amount, partial.change), (1000, 0));
+        assert_eq!(partial.reference.as_deref(), Some("VISA-4242"));
+        let tipped = take(tendered("gift_card", 2880, 500, Some("GC-77")), 2380).unwrap();
+        assert_eq!((tipped.tender, tipped.amount, tipped.tip), (Tender::GiftCard, 2380, 500));
+
+        for (input, balance) in [
+            (tendered("card", 3000, 0, Some("VISA-4242")), 2380),
+            (tendered("card", 1000, 0, Some("  ")), 2380),
+            (tendered("cash", 100, 200, None), 2380),
+            (tendered("cash", 200, 200, None), 2380),
+            (tendered("voucher", 100, 0, None), 2380),
+        ] {
+            assert!(matches!(take(input, balance), Err(StorageError::Validation(_))));
+        }
+        assert!(matches!(take(tendered("cash", 100, 0, None), 0), Err(StorageError::Conflict(_))));
+    }
+
+    #[test]
+    fn test_apply_payments() {
+        let orders = vec![order("Pizza", 1250, OrderStatus::Placed)];
+        let bill = itemize(1, Uuid::new_v4(), &orders, &pricing(0, 0));
+        assert_eq!((bill.paid, bill.balance, bill.status), (0, 1250, BillStatus::Open));
+
+        let payment = take_payment(bill.session_id, None, tendered("cash", 1000, 0, None), 1250, Utc::now()).unwrap();
+        let bill = apply_payments(bill, std::slice::from_ref(&payment));
+        assert_eq!((bill.paid, bill.balance, bill.status), (1000, 250, BillStatus::Open));
+        let rest = Payment { id: Uuid::new_v4(), amount: 250, ..payment.clone() };
+        let bill = apply_payments(bill, &[payment, rest]);
+        assert_eq!((bill.paid, bill.balance, bill.status), (1250, 0, BillStatus::Paid));
+    }
+
+    #[test]
+    fn test_share_balance_is_capped_by_the_whole_bill() {
+        let orders = vec![order("Pizza", 1250, OrderStatus::Placed), order("Salad", 750, OrderStatus::Placed)];
+        let bill = itemize(1, Uuid::new_v4(), &orders, &pricing(0, 0));
+        let shares = split(&bill, &orders, &SplitMode::Even(2)).unwrap();
+        let whole = take_payment(bill.session_id, None, tendered("cash", 1500, 0, None), 2000, Utc::now()).unwrap();
+        let share =
+            take_payment(bill.session_id, Some(shares[0].id), tendered("cash", 400, 0, None), 1000, Utc::now()).unwrap();
+        let payments = [whole, share];
+        let session_balance = apply_payments(bill, &payments).balance;
+        assert_eq!(session_balance, 100);
+
+        let balances: Vec<(u64, u64)> = shares
+            .into_iter()
+            .map(|share| super::from_db(1, share, &orders, &payments, session_balance, &pricing(0, 0)))
+            .map(|share| (share.paid, share.balance))
+            .collect();
+        assert_eq!(balances, vec![(400, 100), (0, 100)]);
+    }
+
+    #[test]
+    fn test_pricing_from_config() {
+        assert_eq!(pricing_from_config(None, None, None), Ok(Pricing::default()));
+        assert_eq!(
+            pricing_from_config(Some("GBP"), Some("2000"), Some("1250")),
+            Ok(Pricing {
+                currency: "GBP".to_string(),
+                tax_rate_bps: 2000,
+                service_charge_bps: 1250,
+            })
+        );
+        assert!(pricing_from_config(Some("pounds"), None, None).is_err());
+        assert!(pricing_from_config(None, Some("8.25"), None).is_err());
+        assert!(pricing_from_config(None, None, Some("10001")).is_err());
+    }
+}
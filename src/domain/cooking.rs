@@ -0,0 +1,148 @@
+use crate::domain::menu::MenuItem;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::Mutex;
+use std::time::Duration;
+
+const MINUTE: Duration = Duration::from_secs(60);
+
+/// Estimates how long the kitchen will take to cook one order.
+///
+/// `queued_orders` is the number of orders already placed or cooking across every
+/// table, counting earlier orders from the same request.
+pub trait CookingTimeEstimator: Send + Sync {
+    fn estimate(&self, item: &MenuItem, queued_orders: usize) -> Duration;
+}
+
+/// Uses the prep time recorded on the menu item.
+pub struct MenuEstimator;
+
+impl CookingTimeEstimator for MenuEstimator {
+    fn estimate(&self, item: &MenuItem, _queued_orders: usize) -> Duration {
+        MINUTE * item.prep_time_minutes
+    }
+}
+
+/// Adds time on top of the menu prep time for every order already in the kitchen,
+/// up to `max_delay`.
+pub struct LoadAwareEstimator {
+    pub delay_per_order: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for LoadAwareEstimator {
+    fn default() -> Self {
+        LoadAwareEstimator {
+            delay_per_order: MINUTE,
+            max_delay: MINUTE * 30,
+        }
+    }
+}
+
+impl CookingTimeEstimator for LoadAwareEstimator {
+    fn estimate(&self, item: &MenuItem, queued_orders: usize) -> Duration {
+        let queued_orders = u32::try_from(queued_orders).unwrap_or(u32::MAX);
+        let delay = self.delay_per_order.saturating_mul(queued_orders).min(self.max_delay);
+        MenuEstimator.estimate(item, 0) + delay
+    }
+}
+
+/// Picks a whole number of minutes in `min_minutes..=max_minutes` from a seeded
+/// generator, so tests get the same sequence on every run.
+pub struct SeededRandomEstimator {
+    rng: Mutex<StdRng>,
+    min_minutes: u32,
+    max_minutes: u32,
+}
+
+impl SeededRandomEstimator {
+    pub fn new(seed: u64, min_minutes: u32, max_minutes: u32) -> Self {
+        SeededRandomEstimator {
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+            min_minutes,
+            max_minutes,
+        }
+    }
+}
+
+impl CookingTimeEstimator for SeededRandomEstimator {
+    fn estimate(&self, _item: &MenuItem, _queued_orders: usize) -> Duration {
+        let mut rng = self.rng.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        MINUTE * rng.gen_range(self.min_minutes..=self.max_minutes)
+    }
+}
+
+/// Builds the estimator named in the server config: `menu`, `load` or `random`.
+///
+/// `random` draws 5 to 15 minutes, seeded with `seed` when one is given.
+pub fn estimator_from_config(
+    name: &str,
+    seed: Option<u64>,
+) -> Result<Box<dyn CookingTimeEstimator>, String> {
+    match name {
+        "menu" => Ok(Box::new(MenuEstimator)),
+        "load" => Ok(Box::new(LoadAwareEstimator::default())),
+        "random" => {
+            let seed = seed.unwrap_or_else(|| rand::thread_rng().gen());
+            Ok(Box::new(SeededRandomEstimator::new(seed, 5, 15)))
+        }
+        other => Err(format!(
+            "Unknown cooking time estimator '{}', expected 'menu', 'load' or 'random'",
+            other
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Station;
+    use uuid::Uuid;
+
+    fn item(prep_time_minutes: u32) -> MenuItem {
+        MenuItem {
+            id: Uuid::new_v4(),
+            name: "Pizza".to_string(),
+            category: "Mains".to_string(),
+            price: 1250,
+            prep_time_minutes,
+            available: true,
+            station: Station::Grill,
+        }
+    }
+
+    #[test]
+    fn test_menu_estimator_uses_prep_time() {
+        assert_eq!(MenuEstimator.estimate(&item(12), 40), MINUTE * 12);
+    }
+
+    #[test]
+    fn test_load_aware_estimator_adds_queue_delay() {
+        let estimator = LoadAwareEstimator {
+            delay_per_order: MINUTE,
+            max_delay: MINUTE * 10,
+        };
+        assert_eq!(estimator.estimate(&item(12), 0), MINUTE * 12);
+        assert_eq!(estimator.estimate(&item(12), 3), MINUTE * 15);
+        assert_eq!(estimator.estimate(&item(12), 500), MINUTE * 22);
+    }
+
+    #[test]
+    fn test_seeded_random_estimator_is_repeatable() {
+        let first = SeededRandomEstimator::new(7, 5, 15);
+        let second = SeededRandomEstimator::new(7, 5, 15);
+        for _ in 0..20 {
+            let estimate = first.estimate(&item(12), 0);
+            assert_eq!(estimate, second.estimate(&item(12), 0));
+            assert!(estimate >= MINUTE * 5 && estimate <= MINUTE * 15);
+        }
+    }
+
+    #[test]
+    fn test_estimator_from_config() {
+        assert_eq!(estimator_from_config("menu", None).unwrap().estimate(&item(9), 4), MINUTE * 9);
+        assert_eq!(estimator_from_config("load", None).unwrap().estimate(&item(9), 4), MINUTE * 13);
+        assert!(estimator_from_config("random", Some(1)).is_ok());
+        assert!(estimator_from_config("psychic", None).is_err());
+    }
+}
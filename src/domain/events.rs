@@ -0,0 +1,230 @@
+use crate::db::StorageError;
+use crate::domain::tables::{Order, OrderStatus};
+use crate::ServerState;
+use chrono::{DateTime, Utc};
+use rocket::tokio::sync::broadcast::{self, error::RecvError};
+use rocket::State;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+/// How many recent events are kept for clients that reconnect.
+pub const HISTORY_LEN: usize = 1024;
+
+/// How many events a slow subscriber may fall behind before it has to catch up from
+/// the history.
+const CHANNEL_CAPACITY: usize = 256;
+
+/// The name of every kind of `OrderEvent`, as `OrderEvent::name` gives it.
+pub const EVENT_NAMES: [&str; 3] = ["order_created", "order_deleted", "order_status_changed"];
+
+/// Something that happened to an order.
+#[derive(Clone)]
+pub enum OrderEvent {
+    Created(Order),
+    /// The order as it was when it was deleted.
+    Deleted(Order),
+    StatusChanged { order_id: Uuid, from: OrderStatus, to: OrderStatus },
+}
+
+impl OrderEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OrderEvent::Created(_) => "order_created",
+            OrderEvent::Deleted(_) => "order_deleted",
+            OrderEvent::StatusChanged { .. } => "order_status_changed",
+        }
+    }
+}
+
+/// An order event as published, numbered in the order it happened. Ids start again
+/// from 1 when the server restarts.
+#[derive(Clone)]
+pub struct Event {
+    pub id: u64,
+    pub at: DateTime<Utc>,
+    pub table_number: u64,
+    pub kind: OrderEvent,
+}
+
+struct History {
+    last_id: u64,
+    events: VecDeque<Event>,
+}
+
+/// In-process bus that hands order events to every subscriber and keeps the most
+/// recent ones so a subscriber can resume where it left off.
+pub struct EventBus {
+    history: Mutex<History>,
+    sender: broadcast::Sender<Event>,
+    capacity: usize,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        EventBus::new(HISTORY_LEN)
+    }
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        EventBus {
+            history: Mutex::new(History { last_id: 0, events: VecDeque::with_capacity(capacity) }),
+            sender,
+            capacity,
+        }
+    }
+
+    /// Records an event and sends it to current subscribers, returning its id.
+    pub fn publish(&self, table_number: u64, kind: OrderEvent) -> u64 {
+        let mut history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+        history.last_id += 1;
+        let event = Event { id: history.last_id, at: Utc::now(), table_number, kind };
+        if history.events.len() == self.capacity {
+            history.events.pop_front();
+        }
+        history.events.push_back(event.clone());
+        // Sending under the lock keeps ids in order for every subscriber. Nobody
+        // listening is not an error.
+        let _ = self.sender.send(event);
+        history.last_id
+    }
+
+    /// Starts following events, for one table or all of them. With `last_event_id`
+    /// the kept events after it are replayed first; an id the bus has not reached yet
+    /// comes from before a restart, so everything kept is replayed.
+    pub fn subscribe(self: &Arc<Self>, table_number: Option<u64>, last_event_id: Option<u64>) -> Subscription {
+        let history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+        let after = match last_event_id {
+            Some(id) if id <= history.last_id => id,
+            Some(_) => 0,
+            None => history.last_id,
+        };
+        Subscription {
+            bus: self.clone(),
+            receiver: self.sender.subscribe(),
+            pending: history.events.iter().filter(|event| event.id > after).cloned().collect(),
+            last_id: after,
+            table_number,
+        }
+    }
+
+    fn since(&self, after: u64) -> VecDeque<Event> {
+        let history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+        history.events.iter().filter(|event| event.id > after).cloned().collect()
+    }
+}
+
+/// One subscriber's view of the bus.
+pub struct Subscription {
+    bus: Arc<EventBus>,
+    receiver: broadcast::Receiver<Event>,
+    pending: VecDeque<Event>,
+    last_id: u64,
+    table_number: Option<u64>,
+}
+
+impl Subscription {
+    /// Waits for the next event this subscriber wants, or `None` once the bus is gone.
+    pub async fn next(&mut self) -> Option<Event> {
+        loop {
+            let event = match self.pending.pop_front() {
+                Some(event) => event,
+                None => match self.receiver.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(_)) => {
+                        self.pending = self.bus.since(self.last_id);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return None,
+                },
+            };
+            // A replay can overlap with what the channel delivers afterwards.
+            if event.id <= self.last_id {
+                continue;
+            }
+            self.last_id = event.id;
+            if self.table_number.is_none_or(|number| number == event.table_number) {
+                return Some(event);
+            }
+        }
+    }
+}
+
+/// Follows events for every table.
+pub fn subscribe(last_event_id: Option<u64>, state: &State<Box<ServerState>>) -> Subscription {
+    state.events.subscribe(None, last_event_id)
+}
+
+/// Follows events for one registered table.
+pub async fn subscribe_table(
+    table_id: u64,
+    last_event_id: Option<u64>,
+    state: &State<Box<ServerState>>,
+) -> Result<Subscription, StorageError> {
+    state.db.get_table(table_id).await?;
+    Ok(state.events.subscribe(Some(table_id), last_event_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Order as DBOrder;
+    use crate::domain::tables::from_db;
+
+    fn deleted() -> OrderEvent {
+        let order = DBOrder::placed(Uuid::new_v4(), "Pizza".to_string(), Utc::now(), 600);
+        OrderEvent::Deleted(from_db(order))
+    }
+
+    async fn ids(subscription: &mut Subscription, count: usize) -> Vec<u64> {
+        let mut ids = Vec::new();
+        for _ in 0..count {
+            ids.push(subscription.next().await.unwrap().id);
+        }
+        ids
+    }
+
+    #[rocket::async_test]
+    async fn test_subscribers_see_new_events_for_their_table() {
+        let bus = Arc::new(EventBus::default());
+        bus.publish(1, deleted());
+        let mut all = bus.subscribe(None, None);
+        let mut table = bus.subscribe(Some(2), None);
+
+        bus.publish(1, deleted());
+        bus.publish(2, deleted());
+        assert_eq!(ids(&mut all, 2).await, vec![2, 3]);
+        let event = table.next().await.unwrap();
+        assert_eq!((event.id, event.table_number, event.kind.name()), (3, 2, "order_deleted"));
+    }
+
+    #[rocket::async_test]
+    async fn test_resume_replays_kept_events() {
+        let bus = Arc::new(EventBus::new(3));
+        for _ in 0..5 {
+            bus.publish(1, deleted());
+        }
+
+        assert_eq!(ids(&mut bus.subscribe(None, Some(3)), 2).await, vec![4, 5]);
+        assert_eq!(ids(&mut bus.subscribe(None, Some(0)), 3).await, vec![3, 4, 5]);
+        // An id from before a restart replays everything kept.
+        assert_eq!(ids(&mut bus.subscribe(None, Some(90)), 3).await, vec![3, 4, 5]);
+
+        let mut resumed = bus.subscribe(Some(1), Some(4));
+        bus.publish(1, deleted());
+        assert_eq!(ids(&mut resumed, 2).await, vec![5, 6]);
+    }
+
+    #[rocket::async_test]
+    async fn test_lagging_subscriber_catches_up_from_history() {
+        let bus = Arc::new(EventBus::new(CHANNEL_CAPACITY * 2));
+        let mut subscription = bus.subscribe(None, None);
+        let published = CHANNEL_CAPACITY as u64 + 10;
+        for _ in 0..published {
+            bus.publish(1, deleted());
+        }
+        assert_eq!(ids(&mut subscription, published as usize).await, (1..=published).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,280 @@
+use crate::db::{Session as DBSession, StorageError, Table as DBTable};
+use crate::domain::billing::get_session_bill;
+use crate::protocol::protocol::TableInput;
+use crate::ServerState;
+use chrono::{DateTime, Utc};
+use rocket::tokio::sync::{Mutex, OwnedMutexGuard};
+use rocket::State;
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub use crate::db::TableStatus;
+
+pub struct Table {
+    pub number: u64,
+    pub seats: u32,
+    pub section: String,
+    pub status: TableStatus,
+    pub session_id: Option<Uuid>,
+}
+
+/// One seating at a table. `closed_at` is empty while it is still in progress.
+pub struct Session {
+    pub id: Uuid,
+    pub table_number: u64,
+    pub opened_at: DateTime<Utc>,
+    pub closed_at: Option<DateTime<Utc>>,
+}
+
+/// One lock per table, held by changes that check the seating in progress and then
+/// write to it (taking orders, payments, splits and closing the table), so concurrent
+/// requests cannot slip a write in between another's check and write. The locks only
+/// cover one server process.
+#[derive(Default)]
+pub struct TableLocks(std::sync::Mutex<HashMap<u64, Arc<Mutex<()>>>>);
+
+impl TableLocks {
+    /// Waits for the table's lock, holding it until the guard is dropped.
+    pub async fn lock(&self, number: u64) -> OwnedMutexGuard<()> {
+        let lock = {
+            let mut locks = self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            // Forget the locks nobody holds or waits for, so the map only keeps the
+            // tables in use rather than every number a request has named.
+            locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+            locks.entry(number).or_default().clone()
+        };
+        lock.lock_owned().await
+    }
+}
+
+fn from_db(table: DBTable) -> Table {
+    Table {
+        number: table.number,
+        seats: table.seats,
+        section: table.section,
+        status: table.status,
+        session_id: table.session_id,
+    }
+}
+
+fn session_from_db(session: DBSession) -> Session {
+    Session {
+        id: session.id,
+        table_number: session.table_number,
+        opened_at: session.opened_at,
+        closed_at: session.closed_at,
+    }
+}
+
+fn validate(input: &TableInput) -> Result<(), StorageError> {
+    if input.number == 0 {
+        return Err(StorageError::Validation("number must be at least 1".to_string()));
+    }
+    if input.seats == 0 {
+        return Err(StorageError::Validation("seats must be at least 1".to_string()));
+    }
+    if input.section.trim().is_empty() {
+        return Err(StorageError::Validation("section must not be empty".to_string()));
+    }
+    Ok(())
+}
+
+/// Every registered table and whether guests are seated at it.
+pub async fn get_floor(state: &State<Box<ServerState>>) -> Result<Vec<Table>, StorageError> {
+    state
+        .db
+        .get_tables()
+        .await
+        .map(|tables| tables.into_iter().map(from_db).collect())
+}
+
+pub async fn get_table(number: u64, state: &State<Box<ServerState>>) -> Result<Table, StorageError> {
+    state.db.get_table(number).await.map(from_db)
+}
+
+/// Adds a table to the floor plan. New tables start closed.
+pub async fn register_table(
+    input: TableInput,
+    state: &State<Box<ServerState>>,
+) -> Result<Table, StorageError> {
+    validate(&input)?;
+    let table = DBTable {
+        number: input.number,
+        seats: input.seats,
+        section: input.section.trim().to_string(),
+        status: TableStatus::Closed,
+        session_id: None,
+    };
+    state.db.add_table(table.clone()).await?;
+    Ok(from_db(table))
+}
+
+/// Seats guests at a closed table, starting a new session that takes its orders.
+pub async fn open_table(number: u64, state: &State<Box<ServerState>>) -> Result<Table, StorageError> {
+    let session = DBSession {
+        id: Uuid::new_v4(),
+        table_number: number,
+        opened_at: Utc::now(),
+        closed_at: None,
+    };
+    state.db.open_table_session(number, session).await?;
+    get_table(number, state).await
+}
+
+/// Ends the seating at an open table. Its orders stay in the session's history and the
+/// table takes no more orders until it is reopened. Fails with `Conflict` while the
+/// bill has an unpaid balance.
+pub async fn close_table(number: u64, state: &State<Box<ServerState>>) -> Result<Table, StorageError> {
+    let _guard = state.table_locks.lock(number).await;
+    let session_id = active_session(number, state).await?;
+    let bill = get_session_bill(number, session_id, state).await?;
+    if bill.balance > 0 {
+        return Err(StorageError::Conflict(format!(
+            "Table {} has an unpaid balance of {}",
+            number, bill.balance
+        )));
+    }
+    state.db.close_table_session(number, session_id, Utc::now()).await?;
+    get_table(number, state).await
+}
+
+/// The session orders at `number` are taken in. Fails with `NotFound` for a table that
+/// is not registered and `Conflict` for one that is closed.
+pub async fn active_session(number: u64, state: &State<Box<ServerState>>) -> Result<Uuid, StorageError> {
+    let table = state.db.get_table(number).await?;
+    match (table.status, table.session_id) {
+        (TableStatus::Open, Some(session_id)) => Ok(session_id),
+        _ => Err(StorageError::Conflict(format!("Table {} is closed", number))),
+    }
+}
+
+/// Every session held at a table, oldest first.
+pub async fn get_sessions(number: u64, state: &State<Box<ServerState>>) -> Result<Vec<Session>, StorageError> {
+    state.db.get_table(number).await?;
+    state
+        .db
+        .get_table_sessions(number)
+        .await
+        .map(|sessions| sessions.into_iter().map(session_from_db).collect())
+}
+
+pub async fn get_session(
+    number: u64,
+    session_id: Uuid,
+    state: &State<Box<ServerState>>,
+) -> Result<Session, StorageError> {
+    state.db.get_table_session(number, session_id).await.map(session_from_db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::memory::InMemoryStorage;
+    use rocket::figment::Figment;
+    use rocket::{local::asynchronous::Client, Build, Rocket};
+    use std::sync::Arc;
+
+    fn setup_rocket() -> Rocket<Build> {
+        let server_state = Box::new(ServerState::new(Arc::new(InMemoryStorage::new())));
+
+        rocket::custom(Figment::from(rocket::Config::default()))
+            .manage(server_state)
+            .mount("/", rocket::routes![])
+    }
+
+    fn input(number: u64, seats: u32, section: &str) -> TableInput {
+        TableInput {
+            number,
+            seats,
+            section: section.to_string(),
+        }
+    }
+
+    #[rocket::async_test]
+    async fn test_register_table() {
+        let client = Client::tracked(setup_rocket()).await.expect("valid rocket instance");
+        let state = State::from(client.rocket().state::<Box<ServerState>>().expect("ServerState"));
+
+        register_table(input(12, 4, " Patio "), state).await.unwrap();
+        register_table(input(3, 2, "Bar"), state).await.unwrap();
+        let result = register_table(input(12, 6, "Main"), state).await;
+        assert!(matches!(result, Err(StorageError::Conflict(_))));
+
+        let floor = get_floor(state).await.unwrap();
+        let numbers: Vec<u64> = floor.iter().map(|table| table.number).collect();
+        assert_eq!(numbers, vec![3, 12]);
+        assert_eq!(floor[1].section, "Patio");
+        assert_eq!(floor[1].status, TableStatus::Closed);
+    }
+
+    #[rocket::async_test]
+    async fn test_register_table_rejects_invalid_input() {
+        let client = Client::tracked(setup_rocket()).await.expect("valid rocket instance");
+        let state = State::from(client.rocket().state::<Box<ServerState>>().expect("ServerState"));
+
+        for table in [input(0, 4, "Patio"), input(1, 0, "Patio"), input(1, 4, " ")] {
+            let result = register_table(table, state).await;
+            assert!(matches!(result, Err(StorageError::Validation(_))));
+        }
+        assert!(get_floor(state).await.unwrap().is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn test_open_and_close_table() {
+        let client = Client::tracked(setup_rocket()).await.expect("valid rocket instance");
+        let state = State::from(client.rocket().state::<Box<ServerState>>().expect("ServerState"));
+        register_table(input(1, 4, "Patio"), state).await.unwrap();
+
+        assert!(matches!(active_session(1, state).await, Err(StorageError::Conflict(_))));
+        assert!(matches!(active_session(2, state).await, Err(StorageError::NotFound(_))));
+
+        let table = open_table(1, state).await.unwrap();
+        assert_eq!(table.status, TableStatus::Open);
+        assert_eq!(active_session(1, state).await.ok(), table.session_id);
+        assert!(matches!(open_table(1, state).await, Err(StorageError::Conflict(_))));
+
+        let table = close_table(1, state).await.unwrap();
+        assert_eq!(table.status, TableStatus::Closed);
+        assert_eq!(table.session_id, None);
+        assert!(matches!(close_table(1, state).await, Err(StorageError::Conflict(_))));
+        assert!(matches!(open_table(2, state).await, Err(StorageError::NotFound(_))));
+    }
+
+    #[rocket::async_test]
+    async fn test_each_opening_starts_a_session() {
+        let client = Client::tracked(setup_rocket()).await.expect("valid rocket instance");
+        let state = State::from(client.rocket().state::<Box<ServerState>>().expect("ServerState"));
+        register_table(input(1, 4, "Patio"), state).await.unwrap();
+
+        let first = open_table(1, state).await.unwrap().session_id.unwrap();
+        close_table(1, state).await.unwrap();
+        let second = open_table(1, state).await.unwrap().session_id.unwrap();
+        assert_ne!(first, second);
+
+        let sessions = get_sessions(1, state).await.unwrap();
+        let ids: Vec<Uuid> = sessions.iter().map(|session| session.id).collect();
+        assert_eq!(ids, vec![first, second]);
+        assert!(sessions[0].closed_at.is_some());
+        assert!(sessions[1].closed_at.is_none());
+
+        assert_eq!(get_session(1, first, state).await.unwrap().id, first);
+        assert!(matches!(get_session(2, first, state).await, Err(StorageError::NotFound(_))));
+        assert!(matches!(get_sessions(2, state).await, Err(StorageError::NotFound(_))));
+    }
+
+    #[rocket::async_test]
+    async fn test_table_locks_only_keep_tables_in_use() {
+        let locks = TableLocks::default();
+        for number in 0..1_000 {
+            drop(locks.lock(number).await);
+        }
+        let held = locks.lock(7).await;
+        drop(locks.lock(8).await);
+        assert_eq!(locks.0.lock().unwrap().len(), 2);
+
+        drop(held);
+        drop(locks.lock(9).await);
+        assert_eq!(locks.0.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,164 @@
+use crate::db::{Order as DBOrder, StorageError};
+use crate::domain::events::{Event, OrderEvent};
+use crate::domain::tables::{from_db, Order, OrderStatus, Station};
+use crate::ServerState;
+use chrono::{DateTime, Utc};
+use rocket::State;
+use uuid::Uuid;
+
+/// Orders sent to the kitchen together for one table, as a cook sees them.
+pub struct Ticket {
+    pub id: Uuid,
+    pub table_number: u64,
+    /// When the first order on the ticket was placed.
+    pub fired_at: DateTime<Utc>,
+    pub orders: Vec<Order>,
+}
+
+/// Whether an order made at `station` belongs to a view of `stations`, where no
+/// stations means the whole kitchen.
+pub fn covers(stations: &[Station], station: Station) -> bool {
+    stations.is_empty() || stations.contains(&station)
+}
+
+/// Groups open orders into tickets, keeping the order they arrive in. Only orders for
+/// `stations` are kept, and tickets with none of them are left out.
+fn group_tickets(open_orders: Vec<(u64, DBOrder)>, stations: &[Station]) -> Vec<Ticket> {
+    let mut tickets: Vec<Ticket> = Vec::new();
+    for (table_number, order) in open_orders {
+        if !covers(stations, order.station) {
+            continue;
+        }
+        match tickets.iter_mut().find(|ticket| ticket.id == order.ticket_id) {
+            Some(ticket) => ticket.orders.push(from_db(order)),
+            None => tickets.push(Ticket {
+                id: order.ticket_id,
+                table_number,
+                fired_at: order.created_at,
+                orders: vec![from_db(order)],
+            }),
+        }
+    }
+    tickets
+}
+
+/// Every ticket with orders still to be made at `stations`, oldest first, across all
+/// tables.
+pub async fn get_queue(stations: &[Station], state: &State<Box<ServerState>>) -> Result<Vec<Ticket>, StorageError> {
+    let open_orders = state.db.get_open_orders().await?;
+    Ok(group_tickets(open_orders, stations))
+}
+
+/// What is still to be made on one ticket at `stations`, or `None` once nothing is.
+pub async fn get_ticket(
+    ticket_id: Uuid,
+    stations: &[Station],
+    state: &State<Box<ServerState>>,
+) -> Result<Option<Ticket>, StorageError> {
+    let tickets = get_queue(stations, state).await?;
+    Ok(tickets.into_iter().find(|ticket| ticket.id == ticket_id))
+}
+
+/// The ticket an order event changes the queue for at `stations`, if any. Events for
+/// orders that had already left the kitchen change nothing.
+pub async fn affected_ticket(
+    event: &Event,
+    stations: &[Station],
+    state: &State<Box<ServerState>>,
+) -> Result<Option<Uuid>, StorageError> {
+    let (ticket_id, station) = match &event.kind {
+        OrderEvent::Created(order) => (order.ticket_id, order.station),
+        OrderEvent::Deleted(order) if is_open(order.status) => (order.ticket_id, order.station),
+        OrderEvent::StatusChanged { order_id, from, .. } if is_open(*from) => {
+            match state.db.get_table_order(event.table_number, *order_id).await {
+                Ok(order) => (order.ticket_id, order.station),
+                // Deleted since; its own event covers it.
+                Err(StorageError::NotFound(_)) => return Ok(None),
+                Err(e) => return Err(e),
+            }
+        }
+        _ => return Ok(None),
+    };
+    Ok(covers(stations, station).then_some(ticket_id))
+}
+
+fn is_open(status: OrderStatus) -> bool {
+    matches!(status, OrderStatus::Placed | OrderStatus::Cooking)
+}
+
+/// Marks a station's part of a ticket as ready, starting any of its orders that were
+/// still waiting. Returns what the station bumped.
+pub async fn bump_ticket(
+    station: Station,
+    ticket_id: Uuid,
+    state: &State<Box<ServerState>>,
+) -> Result<Ticket, StorageError> {
+    let ticket = get_ticket(ticket_id, &[station], state).await?.ok_or_else(|| {
+        StorageError::NotFound(format!("Ticket {} has nothing open at the {} station", ticket_id, station))
+    })?;
+
+    let at = Utc::now();
+    let mut bumped = Vec::with_capacity(ticket.orders.len());
+    for order in ticket.orders {
+        let steps: &[(OrderStatus, OrderStatus)] = match order.status {
+            OrderStatus::Placed => &[
+                (OrderStatus::Placed, OrderStatus::Cooking),
+                (OrderStatus::Cooking, OrderStatus::Ready),
+            ],
+            _ => &[(OrderStatus::Cooking, OrderStatus::Ready)],
+        };
+        for &(from, to) in steps {
+            state.db.update_order_status(ticket.table_number, order.id, from, to, at).await?;
+            state
+                .events
+                .publish(ticket.table_number, OrderEvent::StatusChanged { order_id: order.id, from, to });
+        }
+        bumped.push(from_db(state.db.get_table_order(ticket.table_number, order.id).await?));
+    }
+    Ok(Ticket { orders: bumped, ..ticket })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn open(table_number: u64, ticket_id: Uuid, station: Station, minutes: i64) -> (u64, DBOrder) {
+        let created_at = Utc::now() - Duration::minutes(30) + Duration::minutes(minutes);
+        let order = DBOrder {
+            station,
+            ticket_id,
+            ..DBOrder::placed(Uuid::new_v4(), "Pizza".to_string(), created_at, 600)
+        };
+        (table_number, order)
+    }
+
+    #[test]
+    fn test_group_tickets_in_fire_order() {
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        let open_orders = vec![
+            open(4, first, Station::Grill, 0),
+            open(7, second, Station::Bar, 1),
+            open(4, first, Station::Bar, 0),
+            open(7, second, Station::Grill, 1),
+        ];
+
+        let tickets = group_tickets(open_orders.clone(), &[]);
+        let summary: Vec<(Uuid, u64, usize)> =
+            tickets.iter().map(|ticket| (ticket.id, ticket.table_number, ticket.orders.len())).collect();
+        assert_eq!(summary, vec![(first, 4, 2), (second, 7, 2)]);
+        assert_eq!(tickets[0].fired_at, open_orders[0].1.created_at);
+
+        let bar = group_tickets(open_orders, &[Station::Bar]);
+        assert_eq!(bar.len(), 2);
+        assert!(bar.iter().flat_map(|ticket| &ticket.orders).all(|order| order.station == Station::Bar));
+    }
+
+    #[test]
+    fn test_group_tickets_leaves_out_other_stations() {
+        let ticket_id = Uuid::new_v4();
+        let open_orders = vec![open(4, ticket_id, Station::Grill, 0)];
+        assert!(group_tickets(open_orders.clone(), &[Station::Cold]).is_empty());
+        assert_eq!(group_tickets(open_orders, &[Station::Cold, Station::Grill]).len(), 1);
+    }
+}
@@ -0,0 +1,193 @@
+use crate::db::{MenuItem as DBMenuItem, Station, StorageError};
+use crate::protocol::protocol::MenuItemInput;
+use crate::ServerState;
+use rocket::State;
+use uuid::Uuid;
+
+pub struct MenuItem {
+    pub id: Uuid,
+    pub name: String,
+    pub category: String,
+    /// Price in minor currency units, e.g. cents.
+    pub price: u64,
+    pub prep_time_minutes: u32,
+    pub available: bool,
+    pub station: Station,
+}
+
+fn from_db(item: DBMenuItem) -> MenuItem {
+    MenuItem {
+        id: item.id,
+        name: item.name,
+        category: item.category,
+        price: item.price,
+        prep_time_minutes: item.prep_time_minutes,
+        available: item.available,
+        station: item.station,
+    }
+}
+
+fn to_db(id: Uuid, input: MenuItemInput) -> Result<DBMenuItem, StorageError> {
+    Ok(DBMenuItem {
+        id,
+        name: input.name.trim().to_string(),
+        category: input.category.trim().to_string(),
+        price: input.price,
+        prep_time_minutes: input.prep_time_minutes,
+        available: input.available,
+        station: input.station.trim().to_lowercase().parse()?,
+    })
+}
+
+fn validate(input: &MenuItemInput) -> Result<(), StorageError> {
+    if input.name.trim().is_empty() {
+        return Err(StorageError::Validation("name must not be empty".to_string()));
+    }
+    if input.category.trim().is_empty() {
+        return Err(StorageError::Validation("category must not be empty".to_string()));
+    }
+    Ok(())
+}
+
+/// Finds the menu item an order refers to, ignoring case, and checks it can be ordered.
+pub fn find_orderable<'a>(menu: &'a [MenuItem], name: &str) -> Result<&'a MenuItem, StorageError> {
+    let item = menu
+        .iter()
+        .find(|item| item.name.eq_ignore_ascii_case(name.trim()))
+        .ok_or_else(|| StorageError::Validation(format!("'{}' is not on the menu", name)))?;
+    if !item.available {
+        return Err(StorageError::Validation(format!("'{}' is currently unavailable", item.name)));
+    }
+    Ok(item)
+}
+
+pub async fn get_menu(state: &State<Box<ServerState>>) -> Result<Vec<MenuItem>, StorageError> {
+    state
+        .db
+        .get_menu_items()
+        .await
+        .map(|items| items.into_iter().map(from_db).collect())
+}
+
+pub async fn get_menu_item(
+    menu_item_id: Uuid,
+    state: &State<Box<ServerState>>,
+) -> Result<MenuItem, StorageError> {
+    state.db.get_menu_item(menu_item_id).await.map(from_db)
+}
+
+pub async fn add_menu_item(
+    input: MenuItemInput,
+    state: &State<Box<ServerState>>,
+) -> Result<MenuItem, StorageError> {
+    validate(&input)?;
+    let item = to_db(Uuid::new_v4(), input)?;
+    state.db.add_menu_item(item.clone()).await?;
+    Ok(from_db(item))
+}
+
+pub async fn update_menu_item(
+    menu_item_id: Uuid,
+    input: MenuItemInput,
+    state: &State<Box<ServerState>>,
+) -> Result<MenuItem, StorageError> {
+    validate(&input)?;
+    let item = to_db(menu_item_id, input)?;
+    state.db.update_menu_item(item.clone()).await?;
+    Ok(from_db(item))
+}
+
+pub async fn remove_menu_item(
+    menu_item_id: Uuid,
+    state: &State<Box<ServerState>>,
+) -> Result<(), StorageError> {
+    state.db.delete_menu_item(menu_item_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::memory::InMemoryStorage;
+    use rocket::figment::Figment;
+    use rocket::{local::asynchronous::Client, Build, Rocket};
+    use std::sync::Arc;
+
+    fn setup_rocket() -> Rocket<Build> {
+        let server_state = Box::new(ServerState::new(Arc::new(InMemoryStorage::new())));
+
+        rocket::custom(Figment::from(rocket::Config::default()))
+            .manage(server_state)
+            .mount("/", rocket::routes![])
+    }
+
+    fn input(name: &str, available: bool) -> MenuItemInput {
+        MenuItemInput {
+            name: name.to_string(),
+            category: "Mains".to_string(),
+            price: 1250,
+            prep_time_minutes: 12,
+            available,
+            station: "grill".to_string(),
+        }
+    }
+
+    #[rocket::async_test]
+    async fn test_add_and_update_menu_item() {
+        let client = Client::tracked(setup_rocket()).await.expect("valid rocket instance");
+        let state = State::from(client.rocket().state::<Box<ServerState>>().expect("ServerState"));
+
+        let pizza = add_menu_item(input(" Pizza ", true), state).await.unwrap();
+        assert_eq!(pizza.name, "Pizza");
+
+        let updated = update_menu_item(pizza.id, input("Pizza", false), state).await.unwrap();
+        assert!(!updated.available);
+        assert!(!get_menu_item(pizza.id, state).await.unwrap().available);
+
+        let result = update_menu_item(Uuid::new_v4(), input("Calzone", true), state).await;
+        assert!(matches!(result, Err(StorageError::NotFound(_))));
+    }
+
+    #[rocket::async_test]
+    async fn test_menu_item_station() {
+        let client = Client::tracked(setup_rocket()).await.expect("valid rocket instance");
+        let state = State::from(client.rocket().state::<Box<ServerState>>().expect("ServerState"));
+
+        let mut fries = input("Fries", true);
+        fries.station = " Fryer ".to_string();
+        assert_eq!(add_menu_item(fries, state).await.unwrap().station, Station::Fryer);
+
+        let mut tea = input("Tea", true);
+        tea.station = "pastry".to_string();
+        let result = add_menu_item(tea, state).await;
+        assert!(matches!(result, Err(StorageError::Validation(_))));
+        assert_eq!(get_menu(state).await.unwrap().len(), 1);
+    }
+
+    #[rocket::async_test]
+    async fn test_add_menu_item_rejects_blank_fields() {
+        let client = Client::tracked(setup_rocket()).await.expect("valid rocket instance");
+        let state = State::from(client.rocket().state::<Box<ServerState>>().expect("ServerState"));
+
+        let result = add_menu_item(input(" ", true), state).await;
+        assert!(matches!(result, Err(StorageError::Validation(_))));
+
+        let mut blank_category = input("Pizza", true);
+        blank_category.category = String::new();
+        let result = add_menu_item(blank_category, state).await;
+        assert!(matches!(result, Err(StorageError::Validation(_))));
+        assert!(get_menu(state).await.unwrap().is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn test_find_orderable() {
+        let client = Client::tracked(setup_rocket()).await.expect("valid rocket instance");
+        let state = State::from(client.rocket().state::<Box<ServerState>>().expect("ServerState"));
+        add_menu_item(input("Pizza", true), state).await.unwrap();
+        add_menu_item(input("Soup", false), state).await.unwrap();
+        let menu = get_menu(state).await.unwrap();
+
+        assert_eq!(find_orderable(&menu, "pizza").unwrap().name, "Pizza");
+        assert!(matches!(find_orderable(&menu, "Piza"), Err(StorageError::Validation(_))));
+        assert!(matches!(find_orderable(&menu, "Soup"), Err(StorageError::Validation(_))));
+    }
+}
@@ -1 +1,10 @@
+pub mod auth;
+pub mod billing;
+pub mod cooking;
+pub mod events;
+pub mod floor;
+pub mod kitchen;
+pub mod menu;
+pub mod signing;
 pub mod tables;
+pub mod webhooks;
@@ -1,226 +1,663 @@
-use crate::db::Order as DBOrder;
-use crate::protocol::protocol::OrdersInput;
+use crate::db::{Order as DBOrder, StorageError};
+use crate::domain::auth::{Principal, Role};
+use crate::domain::billing::orders_changing;
+use crate::domain::events::OrderEvent;
+use crate::domain::floor::active_session;
+use crate::domain::menu::{find_orderable, get_menu};
+use crate::protocol::protocol::{OrderStatusInput, OrdersInput};
 use crate::ServerState;
-use rand::Rng;
+use chrono::{DateTime, Duration, Utc};
 use rocket::State;
 use uuid::Uuid;
 
+pub use crate::db::{Decoding, OrderStatus, Station};
+
+#[derive(Clone)]
 pub struct Order {
     pub id: Uuid,
     pub menu_item: String,
-    pub cooking_time: String,
+    pub created_at: DateTime<Utc>,
+    pub cooking_duration_secs: u64,
+    pub status: OrderStatus,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub served_at: Option<DateTime<Utc>>,
+    pub cancelled_at: Option<DateTime<Utc>>,
+    /// Price in minor currency units, as it was on the menu when the order was placed.
+    pub price: u64,
+    pub seat: Option<u32>,
+    pub station: Station,
+    /// Shared by every order sent to the kitchen in the same request.
+    pub ticket_id: Uuid,
 }
 
-pub fn get_orders(table_id: u64, state: &State<Box<ServerState>>) -> Result<Vec<Order>, String> {
-    let mut order_results = Vec::new();
-    state.db.get_table_orders(table_id).and_then(|orders| {
-        for order_input in orders {
-            let order = Order {
-                id: order_input.id,
-                menu_item: order_input.menu_item,
-                cooking_time: order_input.cooking_time,
-            };
-            order_results.push(order);
+impl Order {
+    /// When the kitchen expects the dish to be ready.
+    pub fn ready_at(&self) -> DateTime<Utc> {
+        let cooking_duration_secs = i64::try_from(self.cooking_duration_secs).unwrap_or(i64::MAX);
+        self.created_at + Duration::seconds(cooking_duration_secs)
+    }
+
+    /// Seconds until `ready_at`, or zero once it has passed or the kitchen is done
+    /// with the order.
+    pub fn remaining_seconds(&self, now: DateTime<Utc>) -> u64 {
+        if !matches!(self.status, OrderStatus::Placed | OrderStatus::Cooking) {
+            return 0;
         }
-        Ok(order_results)
-    })
+        u64::try_from((self.ready_at() - now).num_seconds()).unwrap_or(0)
+    }
+}
+
+pub(crate) fn from_db(order: DBOrder) -> Order {
+    Order {
+        id: order.id,
+        menu_item: order.menu_item,
+        created_at: order.created_at,
+        cooking_duration_secs: order.cooking_duration_secs,
+        status: order.status,
+        started_at: order.started_at,
+        finished_at: order.finished_at,
+        served_at: order.served_at,
+        cancelled_at: order.cancelled_at,
+        price: order.price,
+        seat: order.seat,
+        station: order.station,
+        ticket_id: order.ticket_id,
+    }
+}
+
+/// Orders taken during the seating in progress at the table, or none while it is
+/// closed.
+pub async fn get_orders(table_id: u64, state: &State<Box<ServerState>>) -> Result<Vec<Order>, StorageError> {
+    let table = state.db.get_table(table_id).await?;
+    match table.session_id {
+        Some(session_id) => get_session_orders(table_id, session_id, Decoding::SkipCorrupt, state).await,
+        None => Ok(Vec::new()),
+    }
 }
 
-pub fn get_order(
+/// Orders taken during one seating at the table, including ones that have ended.
+/// Bills read them with `Decoding::Strict`, so an order that cannot be read fails the
+/// bill rather than leaving it off.
+pub async fn get_session_orders(
     table_id: u64,
-    order_id: Uuid,
+    session_id: Uuid,
+    decoding: Decoding,
     state: &State<Box<ServerState>>,
-) -> Result<Order, String> {
+) -> Result<Vec<Order>, StorageError> {
+    state.db.get_table_session(table_id, session_id).await?;
     state
         .db
-        .get_table_order(table_id, order_id)
-        .map(|order| Order {
-            id: order.id,
-            menu_item: order.menu_item,
-            cooking_time: order.cooking_time,
-        })
+        .get_session_orders(session_id, decoding)
+        .await
+        .map(|orders| orders.into_iter().map(from_db).collect())
+}
+
+/// An order taken during the seating in progress at the table, with that session's
+/// id. Orders from earlier seatings are only reachable through their session, so the
+/// bills they were paid on cannot change.
+async fn current_order(
+    table_id: u64,
+    order_id: Uuid,
+    state: &State<Box<ServerState>>,
+) -> Result<(Uuid, DBOrder), StorageError> {
+    let order = state.db.get_table_order(table_id, order_id).await?;
+    let table = state.db.get_table(table_id).await?;
+    match table.session_id {
+        Some(session_id) if order.session_id == Some(session_id) => Ok((session_id, order)),
+        _ => Err(StorageError::NotFound("Order not found".to_string())),
+    }
+}
+
+/// One order from the seating in progress, like `get_orders`.
+pub async fn get_order(
+    table_id: u64,
+    order_id: Uuid,
+    state: &State<Box<ServerState>>,
+) -> Result<Order, StorageError> {
+    current_order(table_id, order_id, state).await.map(|(_, order)| from_db(order))
 }
 
-pub fn add_orders(
+pub async fn add_orders(
     table_id: u64,
     orders_data: OrdersInput,
     state: &State<Box<ServerState>>,
-) -> Result<Vec<Uuid>, String> {
+) -> Result<Vec<Uuid>, StorageError> {
     let orders = orders_data.orders;
-    let mut domain_orders = Vec::new();
-    for order_input in orders {
-        let cooking_time = format!("{} minutes", rand::thread_rng().gen_range(5..=15));
-        let id = Uuid::new_v4();
-        let order = Order {
-            id,
-            menu_item: order_input.menu_item,
-            cooking_time,
-        };
-        domain_orders.push(order);
+    if orders.is_empty() {
+        return Err(StorageError::Validation("At least one order is required".to_string()));
+    }
+    if orders.iter().any(|order| order.menu_item.trim().is_empty()) {
+        return Err(StorageError::Validation("menu_item must not be empty".to_string()));
     }
+    if orders.iter().any(|order| order.seat == Some(0)) {
+        return Err(StorageError::Validation("Seats are numbered from 1".to_string()));
+    }
+    let _guard = state.table_locks.lock(table_id).await;
+    let session_id = active_session(table_id, state).await?;
+    let menu = get_menu(state).await?;
+    let open_orders = state.db.count_open_orders().await?;
+    let created_at = Utc::now();
+    let ticket_id = Uuid::new_v4();
     let mut db_orders = Vec::new();
-    for order_input in domain_orders {
-        let order = DBOrder {
-            id: order_input.id,
-            menu_item: order_input.menu_item,
-            cooking_time: order_input.cooking_time,
-        };
-        db_orders.push(order);
+    for (position, order_input) in orders.into_iter().enumerate() {
+        let menu_item = find_orderable(&menu, &order_input.menu_item)?;
+        let estimate = state.estimator.estimate(menu_item, open_orders + position);
+        db_orders.push(DBOrder {
+            session_id: Some(session_id),
+            price: menu_item.price,
+            seat: order_input.seat,
+            station: menu_item.station,
+            ticket_id,
+            ..DBOrder::placed(Uuid::new_v4(), menu_item.name.clone(), created_at, estimate.as_secs())
+        });
     }
-    state.db.add_table_orders(table_id, db_orders)
+    orders_changing(session_id, state).await?;
+    let order_ids = state.db.add_table_orders(table_id, db_orders.clone()).await?;
+    for order in db_orders {
+        state.events.publish(table_id, OrderEvent::Created(from_db(order)));
+    }
+    Ok(order_ids)
 }
 
-pub fn remove_order(
+/// Moves an order to the requested status, rejecting transitions the lifecycle does
+/// not allow with `Conflict`.
+pub async fn change_order_status(
     table_id: u64,
     order_id: Uuid,
+    input: OrderStatusInput,
     state: &State<Box<ServerState>>,
-) -> Result<(), String> {
-    state.db.delete_table_order(table_id, order_id)
+) -> Result<Order, StorageError> {
+    let next: OrderStatus = input.status.parse()?;
+    let _guard = state.table_locks.lock(table_id).await;
+    let (session_id, order) = current_order(table_id, order_id, state).await?;
+    if !order.status.can_transition_to(next) {
+        return Err(StorageError::Conflict(format!(
+            "Cannot move order from {} to {}",
+            order.status, next
+        )));
+    }
+    if next == OrderStatus::Cancelled {
+        orders_changing(session_id, state).await?;
+    }
+    state
+        .db
+        .update_order_status(table_id, order_id, order.status, next, Utc::now())
+        .await?;
+    state
+        .events
+        .publish(table_id, OrderEvent::StatusChanged { order_id, from: order.status, to: next });
+    get_order(table_id, order_id, state).await
+}
+
+/// Deletes an order. Once the kitchen has started on it only a manager may.
+pub async fn remove_order(
+    table_id: u64,
+    order_id: Uuid,
+    caller: &Principal,
+    state: &State<Box<ServerState>>,
+) -> Result<(), StorageError> {
+    let _guard = state.table_locks.lock(table_id).await;
+    let (session_id, order) = current_order(table_id, order_id, state).await?;
+    if order.status != OrderStatus::Placed && !caller.role.includes(Role::Manager) {
+        return Err(StorageError::Forbidden(format!(
+            "Only a manager can delete an order that is {}",
+            order.status
+        )));
+    }
+    if order.status != OrderStatus::Cancelled {
+        orders_changing(session_id, state).await?;
+    }
+    state.db.delete_table_order(table_id, order_id).await?;
+    state.events.publish(table_id, OrderEvent::Deleted(from_db(order)));
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::db::mysql::MySqlDb;
-    use crate::protocol::protocol::{OrderInput, OrdersInput};
+    use crate::db::memory::InMemoryStorage;
+    use crate::domain::cooking::LoadAwareEstimator;
+    use crate::protocol::protocol::{OrderInput, OrdersInput, PaymentInput};
     use crate::ServerState;
-    use dotenv::from_filename;
-    use mysql::{params, PooledConn};
-    use mysql::prelude::Queryable;
-    use rocket::{State, local::blocking::Client, Build, Rocket};
+    use rocket::{State, local::asynchronous::Client, Build, Rocket};
     use rocket::figment::Figment;
-    use std::env;
-    use crate::db::Storage;
+    use crate::db::{MenuItem as DBMenuItem, Order as DBOrder, Session as DBSession, Table as DBTable, TableStatus};
+    use std::sync::Arc;
     use uuid::Uuid;
 
-    fn setup_test_db() -> MySqlDb {
-        from_filename(".env.test").ok();
-        let database_url = env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must be established");
-        MySqlDb::new(&database_url)
-    }
-
-    fn get_connection() -> PooledConn {
-        let db = setup_test_db();
-        db.pool.get_conn().expect("Failed to get connection")
+    fn setup_rocket() -> Rocket<Build> {
+        setup_rocket_with(ServerState::new(Arc::new(InMemoryStorage::new())))
     }
 
-    fn setup_rocket() -> Rocket<Build> {
-        let db = setup_test_db();
-        let server_state = Box::new(ServerState {
-            db: Box::new(db) as Box<dyn Storage + Send + Sync>,
-        });
+    fn setup_rocket_with(server_state: ServerState) -> Rocket<Build> {
+        let server_state = Box::new(server_state);
 
         rocket::custom(Figment::from(rocket::Config::default()))
             .manage(server_state)
             .mount("/", rocket::routes![])
     }
 
-    const INSERT_ORDER_SQL: &str = "INSERT INTO orders (order_id, table_id, menu_item, cooking_time) VALUES (:order_id, :table_id, 'Mock Item', '10 minutes')";
-    const DELETE_ORDER_SQL: &str = "DELETE FROM orders WHERE order_id = :order_id";
+    async fn add_menu_item(state: &ServerState, name: &str, available: bool) {
+        state.db.add_menu_item(DBMenuItem {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            category: "Mains".to_string(),
+            price: 1000,
+            prep_time_minutes: 10,
+            available,
+            station: Station::Grill,
+        }).await.unwrap();
+    }
+
+    async fn add_table(state: &ServerState, number: u64, status: TableStatus) {
+        state.db.add_table(DBTable {
+            number,
+            seats: 4,
+            section: "Main".to_string(),
+            status: TableStatus::Closed,
+            session_id: None,
+        }).await.unwrap();
+        if status == TableStatus::Open {
+            add_table_session(state, number).await;
+        }
+    }
+
+    async fn add_table_session(state: &ServerState, number: u64) {
+        state.db.open_table_session(number, DBSession {
+            id: Uuid::new_v4(),
+            table_number: number,
+            opened_at: Utc::now(),
+            closed_at: None,
+        }).await.unwrap();
+    }
+
+    /// Stores an order directly, in the table's session when it has one open.
+    async fn insert_order(state: &ServerState, table_id: u64) -> Uuid {
+        let order_id = Uuid::new_v4();
+        let session_id = state.db.get_table(table_id).await.ok().and_then(|table| table.session_id);
+        state.db.add_table_orders(table_id, vec![DBOrder {
+            session_id,
+            ..DBOrder::placed(order_id, "Mock Item".to_string(), Utc::now(), 600)
+        }]).await.unwrap();
+        order_id
+    }
 
-    #[test]
-    fn test_get_orders() {
+    #[rocket::async_test]
+    async fn test_get_orders() {
         let rocket = setup_rocket();
-        let client = Client::tracked(rocket).expect("valid rocket instance");
+        let client = Client::tracked(rocket).await.expect("valid rocket instance");
         let state = client.rocket().state::<Box<ServerState>>().expect("ServerState");
 
-        let mut conn = get_connection();
         let table_id = 1;
-        let order_id = Uuid::new_v4();
-
-        conn.exec_drop(
-            INSERT_ORDER_SQL,
-            params! {
-                "order_id" => order_id.to_string(),
-                "table_id" => table_id,
-            },
-        ).unwrap();
+        add_table(state, table_id, TableStatus::Open).await;
+        let order_id = insert_order(state, table_id).await;
+        insert_order(state, 2).await;
 
-        let result = get_orders(table_id, &State::from(state));
+        let result = get_orders(table_id, State::from(state)).await;
         assert!(
             result.is_ok(),
             "Expected Ok but got Err: {:?}",
             result.err()
         );
-
-        conn.exec_drop(
-            DELETE_ORDER_SQL,
-            params! {
-                "order_id" => order_id.to_string(),
-            },
-        ).unwrap();
+        let orders = result.unwrap();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].id, order_id);
+        assert_eq!(orders[0].menu_item, "Mock Item");
     }
 
-    #[test]
-    fn test_get_order() {
+    #[rocket::async_test]
+    async fn test_get_order() {
         let rocket = setup_rocket();
-        let client = Client::tracked(rocket).expect("valid rocket instance");
+        let client = Client::tracked(rocket).await.expect("valid rocket instance");
         let state = client.rocket().state::<Box<ServerState>>().expect("ServerState");
 
-        let mut conn = get_connection();
         let table_id = 1;
-        let order_id = Uuid::new_v4();
-
-        conn.exec_drop(
-            INSERT_ORDER_SQL,
-            params! {
-                "order_id" => order_id.to_string(),
-                "table_id" => table_id,
-            },
-        ).unwrap();
+        add_table(state, table_id, TableStatus::Open).await;
+        let order_id = insert_order(state, table_id).await;
 
-        let result = get_order(table_id, order_id, &State::from(state));
+        let result = get_order(table_id, order_id, State::from(state)).await;
         assert!(
             result.is_ok(),
             "Expected Ok but got Err: {:?}",
             result.err()
         );
+        let order = result.unwrap();
+        assert_eq!(order.id, order_id);
+        assert_eq!(order.menu_item, "Mock Item");
+        assert_eq!(order.status, OrderStatus::Placed);
+    }
+
+    #[rocket::async_test]
+    async fn test_add_orders() {
+        let rocket = setup_rocket();
+        let client = Client::tracked(rocket).await.expect("valid rocket instance");
+        let state = client.rocket().state::<Box<ServerState>>().expect("ServerState");
+        add_menu_item(state, "Mock Item", true).await;
+        add_table(state, 1, TableStatus::Open).await;
+
+        let orders_input = OrdersInput {
+            orders: vec![OrderInput {
+                menu_item: "mock item".into(),
+                seat: Some(2),
+            }],
+        };
+        let result = add_orders(1, orders_input, State::from(state)).await;
+        assert!(result.is_ok());
 
-        conn.exec_drop(
-            DELETE_ORDER_SQL,
-            params! {
-                "order_id" => order_id.to_string(),
-            },
-        ).unwrap();
+        let order_ids = result.unwrap();
+        assert_eq!(order_ids.len(), 1);
+        let order = get_order(1, order_ids[0], State::from(state)).await.unwrap();
+        assert_eq!(order.menu_item, "Mock Item");
+        assert_eq!(order.cooking_duration_secs, 600);
+        assert_eq!(order.price, 1000);
+        assert_eq!(order.seat, Some(2));
     }
 
-    #[test]
-    fn test_add_orders() {
+    #[rocket::async_test]
+    async fn test_add_orders_uses_configured_estimator() {
+        let mut server_state = ServerState::new(Arc::new(InMemoryStorage::new()));
+        server_state.estimator = Box::new(LoadAwareEstimator::default());
+        let client = Client::tracked(setup_rocket_with(server_state)).await.expect("valid rocket instance");
+        let state = client.rocket().state::<Box<ServerState>>().expect("ServerState");
+        add_menu_item(state, "Mock Item", true).await;
+        add_table(state, 1, TableStatus::Open).await;
+        insert_order(state, 2).await;
+
+        let orders_input = OrdersInput {
+            orders: vec![
+                OrderInput { menu_item: "Mock Item".into(), seat: None },
+                OrderInput { menu_item: "Mock Item".into(), seat: None },
+            ],
+        };
+        let order_ids = add_orders(1, orders_input, State::from(state)).await.unwrap();
+
+        let mut cooking_durations = Vec::new();
+        for order_id in order_ids {
+            cooking_durations.push(get_order(1, order_id, State::from(state)).await.unwrap().cooking_duration_secs);
+        }
+        assert_eq!(cooking_durations, vec![660, 720]);
+    }
+
+    #[rocket::async_test]
+    async fn test_add_orders_rejects_invalid_payload() {
         let rocket = setup_rocket();
-        let client = Client::tracked(rocket).expect("valid rocket instance");
+        let client = Client::tracked(rocket).await.expect("valid rocket instance");
         let state = client.rocket().state::<Box<ServerState>>().expect("ServerState");
+        add_table(state, 1, TableStatus::Open).await;
+
+        let result = add_orders(1, OrdersInput { orders: vec![] }, State::from(state)).await;
+        assert!(matches!(result, Err(StorageError::Validation(_))));
 
         let orders_input = OrdersInput {
             orders: vec![OrderInput {
-                menu_item: "Mock Item".into(),
+                menu_item: "  ".into(),
+                seat: None,
             }],
         };
-        assert!(add_orders(1, orders_input, &State::from(state)).is_ok());
+        let result = add_orders(1, orders_input, State::from(state)).await;
+        assert!(matches!(result, Err(StorageError::Validation(_))));
+
+        let orders_input = OrdersInput {
+            orders: vec![OrderInput {
+                menu_item: "Pizza".into(),
+                seat: Some(0),
+            }],
+        };
+        let result = add_orders(1, orders_input, State::from(state)).await;
+        assert!(matches!(result, Err(StorageError::Validation(_))));
+        assert!(get_orders(1, State::from(state)).await.unwrap().is_empty());
     }
 
-    #[test]
-    fn test_remove_order() {
+    #[rocket::async_test]
+    async fn test_add_orders_rejects_items_not_orderable() {
         let rocket = setup_rocket();
-        let client = Client::tracked(rocket).expect("valid rocket instance");
+        let client = Client::tracked(rocket).await.expect("valid rocket instance");
         let state = client.rocket().state::<Box<ServerState>>().expect("ServerState");
+        add_menu_item(state, "Pizza", true).await;
+        add_menu_item(state, "Soup", false).await;
+        add_table(state, 1, TableStatus::Open).await;
 
-        let mut conn = get_connection();
-        let table_id = 1;
-        let order_id = Uuid::new_v4();
+        for menu_item in ["Piza", "Soup"] {
+            let orders_input = OrdersInput {
+                orders: vec![
+                    OrderInput { menu_item: "Pizza".into(), seat: None },
+                    OrderInput { menu_item: menu_item.into(), seat: None },
+                ],
+            };
+            let result = add_orders(1, orders_input, State::from(state)).await;
+            assert!(matches!(result, Err(StorageError::Validation(_))), "{} was accepted", menu_item);
+        }
+        assert!(get_orders(1, State::from(state)).await.unwrap().is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn test_add_orders_rejects_unknown_and_closed_tables() {
+        let rocket = setup_rocket();
+        let client = Client::tracked(rocket).await.expect("valid rocket instance");
+        let state = client.rocket().state::<Box<ServerState>>().expect("ServerState");
+        add_menu_item(state, "Pizza", true).await;
+        add_table(state, 2, TableStatus::Closed).await;
+
+        let pizza = || OrdersInput {
+            orders: vec![OrderInput { menu_item: "Pizza".into(), seat: None }],
+        };
+        let result = add_orders(1, pizza(), State::from(state)).await;
+        assert!(matches!(result, Err(StorageError::NotFound(_))));
+        let result = add_orders(2, pizza(), State::from(state)).await;
+        assert!(matches!(result, Err(StorageError::Conflict(_))));
+        assert!(get_orders(2, State::from(state)).await.unwrap().is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn test_orders_stay_with_their_session() {
+        let rocket = setup_rocket();
+        let client = Client::tracked(rocket).await.expect("valid rocket instance");
+        let state = client.rocket().state::<Box<ServerState>>().expect("ServerState");
+        add_menu_item(state, "Pizza", true).await;
+        add_table(state, 1, TableStatus::Open).await;
+        let first_session = state.db.get_table(1).await.unwrap().session_id.unwrap();
+
+        let pizza = || OrdersInput {
+            orders: vec![OrderInput { menu_item: "Pizza".into(), seat: None }],
+        };
+        let first_order = add_orders(1, pizza(), State::from(state)).await.unwrap()[0];
+        let cash = PaymentInput {
+            tender: "cash".to_string(),
+            amount: 1000,
+            tip: 0,
+            reference: None,
+        };
+        crate::domain::billing::record_payment(1, None, cash, State::from(state)).await.unwrap();
+        crate::domain::floor::close_table(1, State::from(state)).await.unwrap();
+        assert!(get_orders(1, State::from(state)).await.unwrap().is_empty());
+
+        crate::domain::floor::open_table(1, State::from(state)).await.unwrap();
+        let second_order = add_orders(1, pizza(), State::from(state)).await.unwrap()[0];
+        let current: Vec<Uuid> = get_orders(1, State::from(state)).await.unwrap().iter().map(|order| order.id).collect();
+        assert_eq!(current, vec![second_order]);
+
+        let history = get_session_orders(1, first_session, Decoding::Strict, State::from(state)).await.unwrap();
+        assert_eq!(history.iter().map(|order| order.id).collect::<Vec<_>>(), vec![first_order]);
+        assert!(matches!(
+            get_session_orders(2, first_session, Decoding::Strict, State::from(state)).await,
+            Err(StorageError::NotFound(_))
+        ));
+    }
+
+    fn status(status: &str) -> OrderStatusInput {
+        OrderStatusInput { status: status.to_string() }
+    }
+
+    #[rocket::async_test]
+    async fn test_change_order_status() {
+        let rocket = setup_rocket();
+        let client = Client::tracked(rocket).await.expect("valid rocket instance");
+        let state = client.rocket().state::<Box<ServerState>>().expect("ServerState");
+        add_table(state, 1, TableStatus::Open).await;
+        let order_id = insert_order(state, 1).await;
+
+        let order = get_order(1, order_id, State::from(state)).await.unwrap();
+        assert_eq!(order.status, OrderStatus::Placed);
+
+        for (next, expected) in [
+            ("cooking", OrderStatus::Cooking),
+            ("ready", OrderStatus::Ready),
+            ("served", OrderStatus::Served),
+        ] {
+            let order = change_order_status(1, order_id, status(next), State::from(state)).await.unwrap();
+            assert_eq!(order.status, expected);
+        }
+
+        let order = get_order(1, order_id, State::from(state)).await.unwrap();
+        assert!(order.started_at.is_some());
+        assert!(order.finished_at.is_some());
+        assert!(order.served_at.is_some());
+        assert!(order.cancelled_at.is_none());
+    }
+
+    #[rocket::async_test]
+    async fn test_change_order_status_rejects_illegal_transitions() {
+        let rocket = setup_rocket();
+        let client = Client::tracked(rocket).await.expect("valid rocket instance");
+        let state = client.rocket().state::<Box<ServerState>>().expect("ServerState");
+        add_table(state, 1, TableStatus::Open).await;
+        let order_id = insert_order(state, 1).await;
+
+        for next in ["ready", "served", "placed"] {
+            let result = change_order_status(1, order_id, status(next), State::from(state)).await;
+            assert!(matches!(result, Err(StorageError::Conflict(_))), "placed -> {} was allowed", next);
+        }
 
-        conn.exec_drop(
-            INSERT_ORDER_SQL,
-            params! {
-                "order_id" => order_id.to_string(),
-                "table_id" => table_id,
-            },
-        ).unwrap();
+        let result = change_order_status(1, order_id, status("eaten"), State::from(state)).await;
+        assert!(matches!(result, Err(StorageError::Validation(_))));
 
-        let result = remove_order(table_id, order_id, &State::from(state));
+        change_order_status(1, order_id, status("cancelled"), State::from(state)).await.unwrap();
+        let result = change_order_status(1, order_id, status("cooking"), State::from(state)).await;
+        assert!(matches!(result, Err(StorageError::Conflict(_))));
+        assert_eq!(get_order(1, order_id, State::from(state)).await.unwrap().status, OrderStatus::Cancelled);
+    }
+
+    #[rocket::async_test]
+    async fn test_ready_at_and_remaining_seconds() {
+        let created_at = Utc::now();
+        let mut order = from_db(DBOrder::placed(Uuid::new_v4(), "Mock Item".to_string(), created_at, 600));
+        assert_eq!(order.ready_at(), created_at + Duration::seconds(600));
+        assert_eq!(order.remaining_seconds(created_at), 600);
+        assert_eq!(order.remaining_seconds(created_at + Duration::seconds(450)), 150);
+        assert_eq!(order.remaining_seconds(created_at + Duration::seconds(900)), 0);
+
+        order.status = OrderStatus::Ready;
+        assert_eq!(order.remaining_seconds(created_at), 0);
+    }
+
+    #[rocket::async_test]
+    async fn test_get_missing_order() {
+        let rocket = setup_rocket();
+        let client = Client::tracked(rocket).await.expect("valid rocket instance");
+        let state = client.rocket().state::<Box<ServerState>>().expect("ServerState");
+
+        let result = get_order(1, Uuid::new_v4(), State::from(state)).await;
+        assert!(matches!(result, Err(StorageError::NotFound(_))));
+    }
+
+    #[rocket::async_test]
+    async fn test_remove_order() {
+        let rocket = setup_rocket();
+        let client = Client::tracked(rocket).await.expect("valid rocket instance");
+        let state = client.rocket().state::<Box<ServerState>>().expect("ServerState");
+
+        let table_id = 1;
+        add_table(state, table_id, TableStatus::Open).await;
+        let order_id = insert_order(state, table_id).await;
+
+        let server = Principal { name: "till-1".to_string(), role: Role::Server };
+        let result = remove_order(table_id, order_id, &server, State::from(state)).await;
         assert!(
             result.is_ok(),
             "Expected Ok but got Err: {:?}",
             result.err()
         );
+        assert!(get_orders(table_id, State::from(state)).await.unwrap().is_empty());
+        let result = remove_order(table_id, order_id, &server, State::from(state)).await;
+        assert!(matches!(result, Err(StorageError::NotFound(_))));
+    }
+
+    #[rocket::async_test]
+    async fn test_only_managers_remove_started_orders() {
+        let rocket = setup_rocket();
+        let client = Client::tracked(rocket).await.expect("valid rocket instance");
+        let state = client.rocket().state::<Box<ServerState>>().expect("ServerState");
+
+        let table_id = 1;
+        add_table(state, table_id, TableStatus::Open).await;
+        let order_id = insert_order(state, table_id).await;
+        let cooking = OrderStatusInput { status: "cooking".to_string() };
+        change_order_status(table_id, order_id, cooking, State::from(state)).await.unwrap();
+
+        let server = Principal { name: "till-1".to_string(), role: Role::Server };
+        let result = remove_order(table_id, order_id, &server, State::from(state)).await;
+        assert_eq!(result, Err(StorageError::Forbidden("Only a manager can delete an order that is cooking".to_string())));
+        assert_eq!(get_orders(table_id, State::from(state)).await.unwrap().len(), 1);
+
+        let manager = Principal { name: "ana".to_string(), role: Role::Manager };
+        remove_order(table_id, order_id, &manager, State::from(state)).await.unwrap();
+        assert!(get_orders(table_id, State::from(state)).await.unwrap().is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn test_orders_from_closed_sessions_are_out_of_reach() {
+        let rocket = setup_rocket();
+        let client = Client::tracked(rocket).await.expect("valid rocket instance");
+        let state = client.rocket().state::<Box<ServerState>>().expect("ServerState");
+
+        add_table(state, 1, TableStatus::Open).await;
+        let order_id = insert_order(state, 1).await;
+        let session_id = state.db.get_table(1).await.unwrap().session_id.unwrap();
+        state.db.close_table_session(1, session_id, Utc::now()).await.unwrap();
+        add_table_session(state, 1).await;
+
+        let manager = Principal { name: "ana".to_string(), role: Role::Manager };
+        let results = [
+            get_order(1, order_id, State::from(state)).await.map(|_| ()),
+            change_order_status(1, order_id, status("cancelled"), State::from(state)).await.map(|_| ()),
+            remove_order(1, order_id, &manager, State::from(state)).await,
+        ];
+        for result in results {
+            assert!(matches!(result, Err(StorageError::NotFound(_))));
+        }
+        let archived = get_session_orders(1, session_id, Decoding::Strict, State::from(state)).await.unwrap();
+        assert_eq!(archived[0].status, OrderStatus::Placed);
+    }
+
+    #[rocket::async_test]
+    async fn test_concurrent_changes_to_a_table_do_not_interleave() {
+        let rocket = setup_rocket();
+        let client = Client::tracked(rocket).await.expect("valid rocket instance");
+        let state = client.rocket().state::<Box<ServerState>>().expect("ServerState");
+        add_menu_item(state, "Pizza", true).await;
+        add_table(state, 1, TableStatus::Open).await;
+        let pizza = || OrdersInput {
+            orders: vec![OrderInput { menu_item: "Pizza".into(), seat: None }],
+        };
+        add_orders(1, pizza(), State::from(state)).await.unwrap();
+
+        // Two guests paying the whole balance at once: only one of them can.
+        let cash = || PaymentInput { tender: "cash".to_string(), amount: 1000, tip: 0, reference: None };
+        let (first, second) = rocket::tokio::join!(
+            crate::domain::billing::record_payment(1, None, cash(), State::from(state)),
+            crate::domain::billing::record_payment(1, None, cash(), State::from(state)),
+        );
+        assert!(first.is_ok() != second.is_ok(), "both payments went through");
+        let bill = crate::domain::billing::get_bill(1, State::from(state)).await.unwrap();
+        assert_eq!((bill.total, bill.paid), (1000, 1000));
+
+        // An order racing the close either lands first, leaving a balance that keeps
+        // the table open, or finds the table closed.
+        let (closed, ordered) = rocket::tokio::join!(
+            crate::domain::floor::close_table(1, State::from(state)),
+            add_orders(1, pizza(), State::from(state)),
+        );
+        assert!(closed.is_ok() != ordered.is_ok(), "an order landed in a closed session");
     }
 }
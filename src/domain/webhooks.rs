@@ -0,0 +1,477 @@
+use crate::db::{AsyncStorage, StorageError};
+use crate::domain::events::{Event, Subscription, EVENT_NAMES};
+use crate::domain::signing::{hmac_sha256, to_hex};
+use crate::protocol::protocol::WebhookInput;
+use crate::ServerState;
+use chrono::Utc;
+use futures::future::join_all;
+use rocket::tokio::{self, select, time::sleep};
+use rocket::State;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
+use uuid::Uuid;
+
+pub use crate::db::{DeadLetter, Webhook};
+
+pub const SIGNATURE_HEADER: &str = "X-Restaurant-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Restaurant-Timestamp";
+pub const EVENT_HEADER: &str = "X-Restaurant-Event";
+pub const EVENT_ID_HEADER: &str = "X-Restaurant-Event-Id";
+
+/// Signs webhook deliveries. Each webhook gets its own secret, derived from the
+/// server's key and the webhook id, so nothing secret has to be stored with it.
+pub struct WebhookSigner {
+    key: Vec<u8>,
+}
+
+impl WebhookSigner {
+    /// Derives the signing key from the server's `SECRET_KEY`, so changing that key
+    /// changes every webhook's secret.
+    pub fn new(secret_key: &str) -> Self {
+        WebhookSigner { key: hmac_sha256(secret_key.as_bytes(), b"restaurant webhooks") }
+    }
+
+    /// A signer with a key of its own, for the state `ServerState::new` builds in tests
+    /// and tools. Secrets it hands out stop matching once the process exits.
+    pub fn random() -> Self {
+        WebhookSigner { key: rand::random::<[u8; 32]>().to_vec() }
+    }
+
+    /// The secret a receiver checks `webhook_id`'s signatures with.
+    pub fn secret(&self, webhook_id: Uuid) -> String {
+        to_hex(&hmac_sha256(&self.key, webhook_id.as_bytes()))
+    }
+
+    /// The `X-Restaurant-Signature` value for a delivery: `sha256=` and the hex
+    /// HMAC-SHA256 of `{timestamp}.{body}`, keyed with the webhook's secret.
+    pub fn sign(&self, webhook_id: Uuid, timestamp: i64, body: &str) -> String {
+        let signed = format!("{}.{}", timestamp, body);
+        format!("sha256={}", to_hex(&hmac_sha256(self.secret(webhook_id).as_bytes(), signed.as_bytes())))
+    }
+}
+
+/// How hard a delivery is tried before the event is set aside as a dead letter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    /// The wait after the first failure, doubled after each one that follows.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// How long one attempt may take.
+    pub timeout: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 6,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The wait after failed attempt number `attempt`, counting from 1.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let doublings = attempt.saturating_sub(1).min(31);
+        self.initial_backoff.saturating_mul(1 << doublings).min(self.max_backoff)
+    }
+}
+
+/// Whether `webhook` wants events named `event`.
+pub fn wants(webhook: &Webhook, event: &str) -> bool {
+    webhook.events.is_empty() || webhook.events.iter().any(|name| name == event)
+}
+
+/// Posts order events to the webhooks that want them, retrying failed deliveries
+/// with exponential backoff and keeping the ones that never got through as dead
+/// letters.
+#[derive(Clone)]
+pub struct WebhookDelivery {
+    db: Arc<dyn AsyncStorage>,
+    signer: Arc<WebhookSigner>,
+    policy: RetryPolicy,
+    client: reqwest::Client,
+}
+
+impl WebhookDelivery {
+    pub fn new(db: Arc<dyn AsyncStorage>, signer: WebhookSigner, policy: RetryPolicy) -> Self {
+        WebhookDelivery { db, signer: Arc::new(signer), policy, client: reqwest::Client::new() }
+    }
+
+    pub fn signer(&self) -> &WebhookSigner {
+        &self.signer
+    }
+
+    /// Delivers every event from `subscription`, rendered by `render`, until the bus
+    /// goes away or `shutdown` completes. Events are handed off as they arrive, so a
+    /// slow receiver holds up nobody else.
+    pub async fn run(
+        self,
+        mut subscription: Subscription,
+        shutdown: impl Future<Output = ()>,
+        render: fn(Event) -> String,
+    ) {
+        tokio::pin!(shutdown);
+        loop {
+            let event = select! {
+                _ = &mut shutdown => break,
+                event = subscription.next() => match event {
+                    Some(event) => event,
+                    None => break,
+                },
+            };
+            let delivery = self.clone();
+            tokio::spawn(async move { delivery.dispatch(event, render).await });
+        }
+    }
+
+    /// Delivers one event to every webhook that wants it, waiting until each has
+    /// succeeded or given up.
+    pub async fn dispatch(&self, event: Event, render: fn(Event) -> String) {
+        let webhooks = match self.db.get_webhooks().await {
+            Ok(webhooks) => webhooks,
+            Err(e) => {
+                rocket::warn!("Dropping event {} for webhooks: {}", event.id, e);
+                return;
+            }
+        };
+        let name = event.kind.name();
+        let webhooks: Vec<Webhook> = webhooks.into_iter().filter(|webhook| wants(webhook, name)).collect();
+        if webhooks.is_empty() {
+            return;
+        }
+        let event_id = event.id;
+        let payload = render(event);
+        join_all(webhooks.iter().map(|webhook| self.deliver(webhook, event_id, name, &payload))).await;
+    }
+
+    /// Posts `payload` to `webhook` until it is accepted, returning how many attempts
+    /// it took. Once the attempts run out the event is stored as a dead letter and
+    /// returned.
+    pub async fn deliver(
+        &self,
+        webhook: &Webhook,
+        event_id: u64,
+        event: &str,
+        payload: &str,
+    ) -> Result<u32, DeadLetter> {
+        let mut attempt = 1;
+        loop {
+            let error = match self.attempt(webhook, event_id, event, payload).await {
+                Ok(()) => return Ok(attempt),
+                Err(error) => error,
+            };
+            if attempt >= self.policy.max_attempts {
+                let dead_letter = DeadLetter {
+                    id: Uuid::new_v4(),
+                    webhook_id: webhook.id,
+                    event_id,
+                    event: event.to_string(),
+                    payload: payload.to_string(),
+                    attempts: attempt,
+                    last_error: error,
+                    failed_at: Utc::now(),
+                };
+                if let Err(e) = self.db.add_dead_letter(dead_letter.clone()).await {
+                    rocket::warn!("Lost dead letter for event {} to webhook {}: {}", event_id, webhook.id, e);
+                }
+                return Err(dead_letter);
+            }
+            sleep(self.policy.backoff(attempt)).await;
+            attempt += 1;
+        }
+    }
+
+    async fn attempt(&self, webhook: &Webhook, event_id: u64, event: &str, payload: &str) -> Result<(), String> {
+        let timestamp = Utc::now().timestamp();
+        let response = self
+            .client
+            .post(&webhook.url)
+            .timeout(self.policy.timeout)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, event)
+            .header(EVENT_ID_HEADER, event_id.to_string())
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(SIGNATURE_HEADER, self.signer.sign(webhook.id, timestamp, payload))
+            .body(payload.to_string())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("HTTP {}", response.status()))
+        }
+    }
+}
+
+fn validate(input: &WebhookInput) -> Result<Vec<String>, StorageError> {
+    let url = Url::parse(input.url.trim())
+        .map_err(|e| StorageError::Validation(format!("url '{}' is not valid: {}", input.url, e)))?;
+    if !matches!(url.scheme(), "http" | "https") || url.host().is_none() {
+        return Err(StorageError::Validation(format!("url '{}' must be an http or https address", input.url)));
+    }
+    let mut events: Vec<String> = Vec::new();
+    for name in &input.events {
+        let name = name.trim().to_lowercase();
+        if !EVENT_NAMES.contains(&name.as_str()) {
+            return Err(StorageError::Validation(format!(
+                "Unknown event '{}', expected one of {}",
+                name,
+                EVENT_NAMES.join(", ")
+            )));
+        }
+        if !events.contains(&name) {
+            events.push(name);
+        }
+    }
+    Ok(events)
+}
+
+pub async fn get_webhooks(state: &State<Box<ServerState>>) -> Result<Vec<Webhook>, StorageError> {
+    state.db.get_webhooks().await
+}
+
+pub async fn get_webhook(webhook_id: Uuid, state: &State<Box<ServerState>>) -> Result<Webhook, StorageError> {
+    state.db.get_webhook(webhook_id).await
+}
+
+pub async fn add_webhook(input: WebhookInput, state: &State<Box<ServerState>>) -> Result<Webhook, StorageError> {
+    let events = validate(&input)?;
+    let webhook = Webhook { id: Uuid::new_v4(), url: input.url.trim().to_string(), events, created_at: Utc::now() };
+    state.db.add_webhook(webhook.clone()).await?;
+    Ok(webhook)
+}
+
+pub async fn remove_webhook(webhook_id: Uuid, state: &State<Box<ServerState>>) -> Result<(), StorageError> {
+    state.db.delete_webhook(webhook_id).await
+}
+
+pub async fn get_dead_letters(state: &State<Box<ServerState>>) -> Result<Vec<DeadLetter>, StorageError> {
+    state.db.get_dead_letters().await
+}
+
+pub async fn remove_dead_letter(dead_letter_id: Uuid, state: &State<Box<ServerState>>) -> Result<(), StorageError> {
+    state.db.delete_dead_letter(dead_letter_id).await
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::db::blocking::BlockingStorage;
+    use crate::db::memory::InMemoryStorage;
+    use crate::db::{Order as DBOrder, Storage};
+    use crate::domain::events::OrderEvent;
+    use crate::domain::tables::from_db;
+    use rocket::tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use rocket::tokio::net::TcpListener;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// A request the stub receiver was sent, with lowercased header names.
+    pub(crate) struct Received {
+        pub headers: HashMap<String, String>,
+        pub body: String,
+    }
+
+    /// A local HTTP server that answers with `statuses` in turn, then with 200, and
+    /// keeps every request it gets.
+    pub(crate) struct StubReceiver {
+        pub url: String,
+        pub received: Arc<Mutex<Vec<Received>>>,
+    }
+
+    impl StubReceiver {
+        pub async fn start(statuses: Vec<u16>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/hook", listener.local_addr().unwrap());
+            let received = Arc::new(Mutex::new(Vec::new()));
+            let kept = received.clone();
+            tokio::spawn(async move {
+                let mut statuses = statuses.into_iter();
+                loop {
+                    let (mut stream, _) = listener.accept().await.unwrap();
+                    let request = read_request(&mut stream).await;
+                    kept.lock().unwrap().push(request);
+                    let status = statuses.next().unwrap_or(200);
+                    let response = format!("HTTP/1.1 {} Stub\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+                    stream.write_all(response.as_bytes()).await.unwrap();
+                }
+            });
+            StubReceiver { url, received }
+        }
+
+        pub fn count(&self) -> usize {
+            self.received.lock().unwrap().len()
+        }
+    }
+
+    async fn read_request(stream: &mut tokio::net::TcpStream) -> Received {
+        let mut buffer = Vec::new();
+        let mut chunk = [0u8; 4096];
+        let head_end = loop {
+            let read = stream.read(&mut chunk).await.unwrap();
+            buffer.extend_from_slice(&chunk[..read]);
+            if let Some(position) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+                break position + 4;
+            }
+        };
+        let head = String::from_utf8_lossy(&buffer[..head_end]).to_string();
+        let headers: HashMap<String, String> = head
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+            .collect();
+        let length: usize = headers.get("content-length").and_then(|value| value.parse().ok()).unwrap_or(0);
+        while buffer.len() < head_end + length {
+            let read = stream.read(&mut chunk).await.unwrap();
+            buffer.extend_from_slice(&chunk[..read]);
+        }
+        Received { headers, body: String::from_utf8_lossy(&buffer[head_end..head_end + length]).to_string() }
+    }
+
+    fn fast_policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    fn delivery(max_attempts: u32) -> (WebhookDelivery, Arc<InMemoryStorage>) {
+        let db = Arc::new(InMemoryStorage::new());
+        let delivery = WebhookDelivery::new(
+            Arc::new(BlockingStorage::new(db.clone())),
+            WebhookSigner::new("test secret"),
+            fast_policy(max_attempts),
+        );
+        (delivery, db)
+    }
+
+    fn webhook(url: &str, events: &[&str]) -> Webhook {
+        Webhook {
+            id: Uuid::new_v4(),
+            url: url.to_string(),
+            events: events.iter().map(|name| name.to_string()).collect(),
+            created_at: Utc::now(),
+        }
+    }
+
+    fn created() -> Event {
+        let order = DBOrder::placed(Uuid::new_v4(), "Pizza".to_string(), Utc::now(), 600);
+        Event { id: 12, at: Utc::now(), table_number: 4, kind: OrderEvent::Created(from_db(order)) }
+    }
+
+    fn render(event: Event) -> String {
+        format!(r#"{{"id":{}}}"#, event.id)
+    }
+
+    #[test]
+    fn test_secrets_differ_by_webhook_and_server_key() {
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        let signer = WebhookSigner::new("server key");
+        assert_eq!(signer.secret(first), WebhookSigner::new("server key").secret(first));
+        assert_ne!(signer.secret(first), signer.secret(second));
+        assert_ne!(signer.secret(first), WebhookSigner::new("other key").secret(first));
+
+        let expected = to_hex(&hmac_sha256(signer.secret(first).as_bytes(), b"1700000000.{}"));
+        assert_eq!(signer.sign(first, 1_700_000_000, "{}"), format!("sha256={}", expected));
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_limit() {
+        let policy = RetryPolicy::default();
+        let waits: Vec<u64> = (1..=8).map(|attempt| policy.backoff(attempt).as_secs()).collect();
+        assert_eq!(waits, vec![1, 2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(policy.backoff(u32::MAX), policy.max_backoff);
+    }
+
+    #[test]
+    fn test_validate_checks_url_and_events() {
+        let input = |url: &str, events: &[&str]| WebhookInput {
+            url: url.to_string(),
+            events: events.iter().map(|name| name.to_string()).collect(),
+        };
+        assert_eq!(
+            validate(&input(" https://inventory.example.com/hook ", &["Order_Created", "order_created"])),
+            Ok(vec!["order_created".to_string()])
+        );
+        assert_eq!(validate(&input("http://10.0.0.5:8080/", &[])), Ok(vec![]));
+        for url in ["inventory.example.com", "ftp://example.com/", "http://"] {
+            assert!(matches!(validate(&input(url, &[])), Err(StorageError::Validation(_))), "{}", url);
+        }
+        assert!(matches!(
+            validate(&input("https://example.com/", &["order_eaten"])),
+            Err(StorageError::Validation(_))
+        ));
+    }
+
+    #[rocket::async_test]
+    async fn test_delivery_is_signed() {
+        let receiver = StubReceiver::start(vec![]).await;
+        let (delivery, _) = delivery(3);
+        let hook = webhook(&receiver.url, &[]);
+
+        assert_eq!(delivery.deliver(&hook, 7, "order_created", r#"{"id":7}"#).await, Ok(1));
+        let received = receiver.received.lock().unwrap();
+        let request = &received[0];
+        assert_eq!(request.body, r#"{"id":7}"#);
+        assert_eq!(request.headers["x-restaurant-event"], "order_created");
+        assert_eq!(request.headers["x-restaurant-event-id"], "7");
+        assert_eq!(request.headers["content-type"], "application/json");
+        let timestamp: i64 = request.headers["x-restaurant-timestamp"].parse().unwrap();
+        assert_eq!(request.headers["x-restaurant-signature"], delivery.signer().sign(hook.id, timestamp, &request.body));
+    }
+
+    #[rocket::async_test]
+    async fn test_delivery_retries_until_accepted() {
+        let receiver = StubReceiver::start(vec![500, 503]).await;
+        let (delivery, db) = delivery(5);
+
+        let hook = webhook(&receiver.url, &[]);
+        assert_eq!(delivery.deliver(&hook, 1, "order_created", "{}").await, Ok(3));
+        assert_eq!(receiver.count(), 3);
+        assert_eq!(db.get_dead_letters(), Ok(vec![]));
+    }
+
+    #[rocket::async_test]
+    async fn test_delivery_gives_up_with_a_dead_letter() {
+        let receiver = StubReceiver::start(vec![500; 10]).await;
+        let (delivery, db) = delivery(3);
+        let hook = webhook(&receiver.url, &[]);
+
+        let dead_letter = delivery.deliver(&hook, 9, "order_deleted", r#"{"id":9}"#).await.unwrap_err();
+        assert_eq!(receiver.count(), 3);
+        assert_eq!(
+            (dead_letter.webhook_id, dead_letter.event_id, dead_letter.attempts),
+            (hook.id, 9, 3)
+        );
+        assert_eq!(dead_letter.last_error, "HTTP 500 Internal Server Error");
+        assert_eq!(db.get_dead_letters(), Ok(vec![dead_letter]));
+
+        // Nothing listening is a failure too.
+        let closed = webhook("http://127.0.0.1:9/hook", &[]);
+        assert!(delivery.deliver(&closed, 10, "order_deleted", "{}").await.is_err());
+    }
+
+    #[rocket::async_test]
+    async fn test_dispatch_follows_event_filters() {
+        let (everything, created_only, deleted_only) =
+            (StubReceiver::start(vec![]).await, StubReceiver::start(vec![]).await, StubReceiver::start(vec![]).await);
+        let (delivery, db) = delivery(1);
+        db.add_webhook(webhook(&everything.url, &[])).unwrap();
+        db.add_webhook(webhook(&created_only.url, &["order_created"])).unwrap();
+        db.add_webhook(webhook(&deleted_only.url, &["order_deleted", "order_status_changed"])).unwrap();
+
+        delivery.dispatch(created(), render).await;
+        assert_eq!((everything.count(), created_only.count(), deleted_only.count()), (1, 1, 0));
+        assert_eq!(created_only.received.lock().unwrap()[0].body, r#"{"id":12}"#);
+    }
+}
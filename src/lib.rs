@@ -3,8 +3,42 @@ pub mod db;
 pub mod domain;
 pub mod protocol;
 
-use crate::db::Storage;
+use crate::db::{blocking::BlockingStorage, AsyncStorage, Storage};
+use crate::domain::auth::Authenticator;
+use crate::domain::billing::Pricing;
+use crate::domain::cooking::{CookingTimeEstimator, MenuEstimator};
+use crate::domain::events::EventBus;
+use crate::domain::floor::TableLocks;
+use crate::domain::webhooks::{RetryPolicy, WebhookDelivery, WebhookSigner};
+use std::sync::Arc;
 
 pub struct ServerState {
-    pub db: Box<dyn Storage>,
+    pub db: Box<dyn AsyncStorage>,
+    pub estimator: Box<dyn CookingTimeEstimator>,
+    pub pricing: Pricing,
+    pub events: Arc<EventBus>,
+    pub webhooks: WebhookDelivery,
+    pub auth: Authenticator,
+    pub table_locks: TableLocks,
+}
+
+impl ServerState {
+    /// Creates state around a synchronous `db` that estimates cooking times from the menu
+    /// and bills in USD without tax or service charge, with an empty event bus, and
+    /// with webhooks and tokens signed with random keys and no API keys.
+    pub fn new(db: Arc<dyn Storage>) -> Self {
+        ServerState {
+            db: Box::new(BlockingStorage::new(db.clone())),
+            estimator: Box::new(MenuEstimator),
+            pricing: Pricing::default(),
+            events: Arc::new(EventBus::default()),
+            webhooks: WebhookDelivery::new(
+                Arc::new(BlockingStorage::new(db)),
+                WebhookSigner::random(),
+                RetryPolicy::default(),
+            ),
+            auth: Authenticator::random(),
+            table_locks: TableLocks::default(),
+        }
+    }
 }
@@ -1,33 +1,123 @@
 #[macro_use] extern crate rocket;
 
-use ryans_restaurant_app::{db::{mysql::MySqlDb, Storage}, api, ServerState};
+use ryans_restaurant_app::{db::{blocking::BlockingStorage, memory::InMemoryStorage, migrations::{self, Migration, Migrator}, mysql::{self, MySqlDb}, postgres::{self, PostgresDb}, sqlite::{self, SqliteDb}, Storage, StorageError}, api, domain::{auth::{api_keys_from_config, Authenticator}, billing::pricing_from_config, cooking::estimator_from_config, events::EventBus, floor::TableLocks, webhooks::{RetryPolicy, WebhookDelivery, WebhookSigner}}, ServerState};
 use dotenv::dotenv;
 use std::env;
+use std::sync::Arc;
+
+/// Connects to a SQL backend, then applies pending migrations or, when `auto_migrate`
+/// is off, checks that none are pending.
+fn open_database<D: Storage + Migrator + 'static>(
+    db: Result<D, StorageError>,
+    known: &[Migration],
+    auto_migrate: bool,
+) -> Arc<dyn Storage> {
+    let db = db.unwrap_or_else(|e| panic!("Failed to connect to the database: {}", e));
+    if auto_migrate {
+        migrations::run(&db, known).expect("Failed to migrate database");
+    } else {
+        migrations::verify(&db, known).expect("Database schema does not match this server");
+    }
+    Arc::new(db)
+}
 
 #[launch]
 fn rocket() -> _ {
     dotenv().ok();
 
-    let database_url = env::var("RESTAURANT_DATABASE_URL").expect("RESTAURANT_DATABASE_URL must be declared");
     let secret_key = env::var("SECRET_KEY").expect("SECRET_KEY must be declared");
+    let webhook_signer = WebhookSigner::new(&secret_key);
+    let api_keys = api_keys_from_config(&env::var("RESTAURANT_API_KEYS").unwrap_or_default())
+        .unwrap_or_else(|e| panic!("RESTAURANT_API_KEYS: {}", e));
+    let authenticator = Authenticator::new(&secret_key, api_keys);
+    let mut figment = rocket::Config::figment().merge(("secret_key", secret_key));
+
+    let auto_migrate = env::var("RESTAURANT_AUTO_MIGRATE").as_deref() != Ok("false");
+    let db = match env::var("RESTAURANT_STORAGE").as_deref() {
+        Ok("memory") => Arc::new(InMemoryStorage::new()) as Arc<dyn Storage>,
+        // The database URL picks the backend; `mysql` is the older name for this setting.
+        Ok("database") | Ok("mysql") | Err(_) => {
+            let database_url = env::var("RESTAURANT_DATABASE_URL").expect("RESTAURANT_DATABASE_URL must be declared");
+            if SqliteDb::handles(&database_url) {
+                open_database(SqliteDb::connect(&database_url), sqlite::MIGRATIONS, auto_migrate)
+            } else if PostgresDb::handles(&database_url) {
+                open_database(PostgresDb::connect(&database_url), postgres::MIGRATIONS, auto_migrate)
+            } else {
+                figment = figment.merge(("databases", rocket::figment::value::Dict::from_iter(
+                    std::iter::once((String::from("mysql"), rocket::figment::value::Value::from(database_url.clone())))
+                )));
+                open_database(MySqlDb::connect(&database_url), mysql::MIGRATIONS, auto_migrate)
+            }
+        }
+        Ok(other) => panic!("Unknown RESTAURANT_STORAGE '{}', expected 'database' or 'memory'", other),
+    };
+    let estimator_name = env::var("RESTAURANT_COOKING_ESTIMATOR").unwrap_or_else(|_| "menu".to_string());
+    let estimator_seed = env::var("RESTAURANT_COOKING_ESTIMATOR_SEED")
+        .ok()
+        .map(|seed| seed.parse().expect("RESTAURANT_COOKING_ESTIMATOR_SEED must be a number"));
+    let estimator = estimator_from_config(&estimator_name, estimator_seed).unwrap_or_else(|e| panic!("{}", e));
+    let pricing = pricing_from_config(
+        env::var("RESTAURANT_CURRENCY").ok().as_deref(),
+        env::var("RESTAURANT_TAX_RATE_BPS").ok().as_deref(),
+        env::var("RESTAURANT_SERVICE_CHARGE_BPS").ok().as_deref(),
+    )
+    .unwrap_or_else(|e| panic!("{}", e));
 
-    let db = MySqlDb::new(&database_url);
     let state = Box::new(ServerState{
-        db:Box::new(db) as Box<dyn Storage>
+        db: Box::new(BlockingStorage::new(db.clone())),
+        estimator,
+        pricing,
+        events: Arc::new(EventBus::default()),
+        webhooks: WebhookDelivery::new(Arc::new(BlockingStorage::new(db)), webhook_signer, RetryPolicy::default()),
+        auth: authenticator,
+        table_locks: TableLocks::default(),
     });
 
-    rocket::custom(
-        rocket::Config::figment()
-            .merge(("databases", rocket::figment::value::Dict::from_iter(
-                std::iter::once((String::from("mysql"), rocket::figment::value::Value::from(database_url)))
-            )))
-            .merge(("secret_key", secret_key))
-    )
+    rocket::custom(figment)
+        .attach(api::error::RequestIdFairing)
+        .attach(api::webhooks::delivery())
+        .register("/", api::catchers())
         .manage(state)
         .mount("/", routes![
+        api::auth::get_caller,
+        api::auth::issue_token,
         api::tables::add_table_orders,
         api::tables::delete_table_order,
         api::tables::get_table_orders,
         api::tables::get_table_order,
+        api::tables::update_table_order_status,
+        api::floor::get_tables,
+        api::floor::get_table,
+        api::floor::add_table,
+        api::floor::open_table,
+        api::floor::close_table,
+        api::floor::get_table_sessions,
+        api::floor::get_table_session,
+        api::billing::get_table_bill,
+        api::billing::get_session_bill,
+        api::billing::split_table_bill,
+        api::billing::remove_table_bill_split,
+        api::billing::get_split_bills,
+        api::billing::get_split_bill,
+        api::billing::close_split_bill,
+        api::billing::pay_table_bill,
+        api::billing::pay_split_bill,
+        api::billing::get_table_payments,
+        api::kitchen::get_kitchen_queue,
+        api::kitchen::bump_ticket,
+        api::kitchen::kitchen_socket,
+        api::events::stream_events,
+        api::events::stream_table_events,
+        api::webhooks::get_webhooks,
+        api::webhooks::get_webhook,
+        api::webhooks::add_webhook,
+        api::webhooks::delete_webhook,
+        api::webhooks::get_dead_letters,
+        api::webhooks::delete_dead_letter,
+        api::menu::get_menu_items,
+        api::menu::get_menu_item,
+        api::menu::add_menu_item,
+        api::menu::update_menu_item,
+        api::menu::delete_menu_item,
     ])
-}
\ No newline at end of file
+}
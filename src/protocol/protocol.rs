@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use rocket::serde::Deserialize;
 use serde::Serialize;
 use uuid::Uuid;
@@ -5,6 +6,8 @@ use uuid::Uuid;
 #[derive(Debug, Serialize, Deserialize)]
 pub struct OrderInput {
     pub menu_item: String,
+    #[serde(default)]
+    pub seat: Option<u32>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -16,5 +19,282 @@ pub struct OrdersInput {
 pub struct OrderResponse {
     pub id: Uuid,
     pub menu_item: String,
-    pub cooking_time: String,
+    pub created_at: DateTime<Utc>,
+    pub cooking_duration_secs: u64,
+    pub ready_at: DateTime<Utc>,
+    pub remaining_seconds: u64,
+    pub status: String,
+    pub price: u64,
+    pub seat: Option<u32>,
+    pub station: String,
+    pub ticket_id: Uuid,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub code: String,
+    pub message: String,
+    pub request_id: Uuid,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<FieldError>,
+}
+
+fn default_available() -> bool {
+    true
+}
+
+fn default_station() -> String {
+    "grill".to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MenuItemInput {
+    pub name: String,
+    pub category: String,
+    pub price: u64,
+    pub prep_time_minutes: u32,
+    #[serde(default = "default_available")]
+    pub available: bool,
+    #[serde(default = "default_station")]
+    pub station: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MenuItemResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub category: String,
+    pub price: u64,
+    pub prep_time_minutes: u32,
+    pub available: bool,
+    pub station: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OrderStatusInput {
+    pub status: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TableInput {
+    pub number: u64,
+    pub seats: u32,
+    pub section: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TableResponse {
+    pub number: u64,
+    pub seats: u32,
+    pub section: String,
+    pub status: String,
+    pub session_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionResponse {
+    pub id: Uuid,
+    pub table_number: u64,
+    pub opened_at: DateTime<Utc>,
+    pub closed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionOrdersResponse {
+    #[serde(flatten)]
+    pub session: SessionResponse,
+    pub orders: Vec<OrderResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BillLineResponse {
+    pub menu_item: String,
+    pub unit_price: u64,
+    pub quantity: u32,
+    pub line_total: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BillResponse {
+    pub table_number: u64,
+    pub session_id: Uuid,
+    pub currency: String,
+    pub lines: Vec<BillLineResponse>,
+    pub subtotal: u64,
+    pub service_charge: u64,
+    pub tax: u64,
+    pub total: u64,
+    pub paid: u64,
+    pub balance: u64,
+    pub status: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SplitInput {
+    /// `even`, `seat` or `items`.
+    pub mode: String,
+    #[serde(default)]
+    pub ways: Option<u32>,
+    #[serde(default)]
+    pub parts: Vec<Vec<Uuid>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SplitBillResponse {
+    pub id: Uuid,
+    pub table_number: u64,
+    pub session_id: Uuid,
+    pub position: u32,
+    pub label: String,
+    pub currency: String,
+    pub order_ids: Vec<Uuid>,
+    pub lines: Vec<BillLineResponse>,
+    pub subtotal: u64,
+    pub service_charge: u64,
+    pub tax: u64,
+    pub total: u64,
+    pub paid: u64,
+    pub balance: u64,
+    pub status: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PaymentInput {
+    /// `cash`, `card` or `gift_card`.
+    pub tender: String,
+    pub amount: u64,
+    #[serde(default)]
+    pub tip: u64,
+    #[serde(default)]
+    pub reference: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PaymentResponse {
+    pub id: Uuid,
+    pub session_id: Uuid,
+    pub bill_id: Option<Uuid>,
+    pub tender: String,
+    pub reference: Option<String>,
+    pub amount: u64,
+    pub tip: u64,
+    pub change: u64,
+    pub paid_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TicketResponse {
+    pub id: Uuid,
+    pub table_number: u64,
+    pub fired_at: DateTime<Utc>,
+    pub orders: Vec<OrderResponse>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OrderEventResponse {
+    OrderCreated { table_number: u64, order: OrderResponse },
+    OrderDeleted { table_number: u64, order_id: Uuid },
+    OrderStatusChanged { table_number: u64, order_id: Uuid, from: String, to: String },
+}
+
+/// One order event, as sent on the event streams.
+#[derive(Debug, Serialize)]
+pub struct EventResponse {
+    pub id: u64,
+    pub at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: OrderEventResponse,
+}
+
+/// A message a kitchen display sends over the kitchen socket.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum KitchenCommand {
+    /// Follow these stations, or the whole kitchen when empty.
+    Subscribe {
+        #[serde(default)]
+        stations: Vec<String>,
+    },
+    Bump { station: String, ticket_id: Uuid },
+    SetStatus { table_number: u64, order_id: Uuid, status: String },
+    Ping,
+}
+
+/// A message the server sends to a kitchen display.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum KitchenMessage {
+    /// The open tickets at the stations just subscribed to.
+    Queue { stations: Vec<String>, tickets: Vec<TicketResponse> },
+    /// A ticket that is new or has changed; it replaces any earlier copy.
+    Ticket { ticket: TicketResponse },
+    /// A ticket with nothing left to make at the subscribed stations.
+    TicketCleared { ticket_id: Uuid },
+    Bumped { ticket: TicketResponse },
+    Order { order: OrderResponse },
+    Heartbeat { at: DateTime<Utc> },
+    Pong,
+    Error { code: String, message: String },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookInput {
+    pub url: String,
+    /// Event names to send, or every event when empty.
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookResponse {
+    pub id: Uuid,
+    pub url: String,
+    pub events: Vec<String>,
+    /// The key the receiver checks `X-Restaurant-Signature` with, only returned when
+    /// the webhook is created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeadLetterResponse {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event_id: u64,
+    pub event: String,
+    pub payload: String,
+    pub attempts: u32,
+    pub last_error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenInput {
+    pub name: String,
+    /// `server`, `kitchen`, `manager` or `admin`.
+    pub role: String,
+    #[serde(default)]
+    pub ttl_minutes: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenResponse {
+    pub token: String,
+    pub name: String,
+    pub role: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PrincipalResponse {
+    pub name: String,
+    pub role: String,
 }